@@ -1,19 +1,30 @@
 use anyhow::{Context, Result};
 use std::io::{Cursor, Read, Write};
+use std::path::Path;
 use zip::{ZipArchive, ZipWriter};
-use zip::write::SimpleFileOptions;
+use pdf_conversion_lib::{cbz_file_options, check_rar_listing, is_comic_info_file, parse_lsar_listing, read_cbz_entry, recover_cbz, sanitize_entry_path, sort_pages, verify_cbz, ArchiveBudget, ArchiveEntry, ArchiveVerifier, ComicInfo, DocumentMetadata, RecoveredCbz, EntryKind, IssueKind, IssueSeverity, PageOrder, VerifyReport};
 
-/// Create CBZ (ZIP) archive from images
+/// Pages of an archive in reading order, read lazily where the format allows it
+pub type ArchivePages = Box<dyn ExactSizeIterator<Item = Result<(String, Vec<u8>)>>>;
+
+/// Pages of an archive held in memory (path and data), in reading order
+pub type PageImages = Vec<(String, Vec<u8>)>;
+
+/// Create CBZ (ZIP) archive from images (and other files, stored under their archive paths)
 /// Uses STORED (no compression) because JPEG images are already optimally compressed
 /// This is 50x+ faster with only ~4% larger files
-pub fn create_cbz(images: Vec<(String, Vec<u8>)>) -> Result<Vec<u8>> {
+/// With a password, every entry is AES-256 encrypted
+/// `comment` is stored as the archive comment (ComicBookInfo JSON)
+pub fn create_cbz(images: Vec<(String, Vec<u8>)>, password: Option<&str>, comment: Option<String>) -> Result<Vec<u8>> {
     let buffer = Cursor::new(Vec::new());
     let mut zip = ZipWriter::new(buffer);
+    if let Some(comment) = comment {
+        zip.set_comment(comment);
+    }
 
     // STORED = no compression - optimal for JPEG images which are already compressed
     // Benchmark: 53x faster, only 3.8% larger files
-    let options = SimpleFileOptions::default()
-        .compression_method(zip::CompressionMethod::Stored);
+    let options = cbz_file_options(password);
 
     for (filename, data) in images {
         zip.start_file(&filename, options)
@@ -26,79 +37,221 @@ pub fn create_cbz(images: Vec<(String, Vec<u8>)>) -> Result<Vec<u8>> {
     Ok(buffer.into_inner())
 }
 
-/// Extract images from CBZ/CBR archive
-pub fn extract_images(archive_data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
-    // Check for RAR magic bytes (supports both RAR 4.x and RAR 5.x)
-    let is_rar = if archive_data.len() >= 8 {
+/// Read every file of a CBZ/CBR archive, with its path relative to the archive root and its kind
+/// Images come first, in natural filename order unless `order` keeps the archive order,
+/// followed by metadata and other files. Directory entries are skipped.
+/// `password` decrypts AES-encrypted CBZ entries or protected CBR archives
+pub fn extract_entries(archive_data: &[u8], password: Option<&str>, order: PageOrder) -> Result<Vec<ArchiveEntry>> {
+    let entries = if is_rar(archive_data) {
+        // Extraction order is not the archive order: always sort naturally
+        let mut entries = extract_from_rar(archive_data, password)?;
+        sort_pages(&mut entries, PageOrder::Natural, |entry| &entry.path);
+        entries
+    } else {
+        extract_from_zip(archive_data, password)?
+    };
+    Ok(order_entries(entries, order))
+}
+
+/// Put images first, sorted by `order`, followed by the other entries in their current order
+fn order_entries(entries: Vec<ArchiveEntry>, order: PageOrder) -> Vec<ArchiveEntry> {
+    let (mut images, others): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .partition(|entry| entry.kind == EntryKind::Image);
+    sort_pages(&mut images, order, |entry| &entry.path);
+    images.extend(others);
+    images
+}
+
+/// Extract the pages of a CBZ/CBR archive in reading order
+/// ComicInfo.xml page hints are applied: its page order, and pages marked Deleted are skipped.
+pub fn extract_images(archive_data: &[u8], password: Option<&str>, order: PageOrder) -> Result<Vec<(String, Vec<u8>)>> {
+    let entries = extract_entries(archive_data, password, order)?;
+    Ok(reading_order(entries))
+}
+
+/// Salvage the complete entries of a damaged CBZ (see `recover_cbz`), printing what was lost
+pub fn recover(archive_data: &[u8]) -> Result<RecoveredCbz> {
+    let recovered = recover_cbz(archive_data)
+        .context("Failed to recover archive")?;
+
+    println!("Recovered {} entries ({} images), lost {}",
+             recovered.entries.len(), recovered.image_count(), recovered.lost.len());
+    for lost in &recovered.lost {
+        println!("  ✗ {}: {}", lost.path, lost.reason);
+    }
+    if !recovered.complete {
+        println!("Archive is truncated: entries stored after the last one found are missing");
+    }
+    Ok(recovered)
+}
+
+/// Salvage the pages of a damaged CBZ file in reading order, with the metadata of a recovered ComicInfo.xml
+pub fn recover_pages(path: &Path, order: PageOrder) -> Result<(PageImages, Option<DocumentMetadata>)> {
+    let archive_data = std::fs::read(path)
+        .context("Failed to read CBZ file")?;
+    let recovered = recover(&archive_data)?;
+
+    let metadata = recovered
+        .entries
+        .iter()
+        .find(|entry| is_comic_info_file(&entry.path))
+        .and_then(|entry| ComicInfo::parse(&String::from_utf8_lossy(&entry.data)).ok())
+        .map(|info| info.to_metadata());
+    Ok((reading_order(order_entries(recovered.entries, order)), metadata))
+}
+
+/// Page images of ordered entries (see `extract_entries`), with ComicInfo.xml page hints applied
+fn reading_order(entries: Vec<ArchiveEntry>) -> Vec<(String, Vec<u8>)> {
+    // An unreadable ComicInfo.xml only loses the page hints
+    let comic_info = entries
+        .iter()
+        .find(|entry| is_comic_info_file(&entry.path))
+        .and_then(|entry| {
+            ComicInfo::parse(&String::from_utf8_lossy(&entry.data))
+                .inspect_err(|e| eprintln!("Warning: ignoring ComicInfo.xml: {:#}", e))
+                .ok()
+        });
+
+    let mut images: Vec<(String, Vec<u8>)> = entries
+        .into_iter()
+        .filter(|entry| entry.kind == EntryKind::Image)
+        .map(|entry| (entry.path, entry.data))
+        .collect();
+
+    if let Some(info) = comic_info {
+        let count = images.len();
+        images = info.apply_page_order(images);
+        if images.len() < count {
+            println!("Skipping {} page(s) marked Deleted in ComicInfo.xml", count - images.len());
+        }
+    }
+
+    images
+}
+
+/// Comment of a ZIP archive (e.g. ComicBookInfo JSON), None for RAR archives or empty comments
+pub fn archive_comment(archive_data: &[u8]) -> Option<String> {
+    if is_rar(archive_data) {
+        return None;
+    }
+    let archive = ZipArchive::new(Cursor::new(archive_data)).ok()?;
+    let comment = String::from_utf8_lossy(archive.comment()).to_string();
+    (!comment.is_empty()).then_some(comment)
+}
+
+/// Verify a CBZ/CBR file (see `verify_cbz`)
+/// CBR archives are extracted with unar, which checks entry CRCs; extraction failures are reported as read errors.
+pub fn verify(path: &Path, password: Option<&str>) -> Result<VerifyReport> {
+    if !is_rar_file(path)? {
+        return verify_cbz(path, password);
+    }
+
+    let archive_data = std::fs::read(path)
+        .context("Failed to read CBR file")?;
+    let mut verifier = ArchiveVerifier::new("cbr");
+    match extract_from_rar(&archive_data, password) {
+        Ok(entries) => {
+            for entry in entries {
+                verifier.check_entry(&entry.path, &entry.data);
+            }
+        }
+        Err(e) => verifier.add_issue(IssueSeverity::Error, IssueKind::ReadError, None, format!("{:#}", e)),
+    }
+    Ok(verifier.finish())
+}
+
+/// Check whether a file on disk is a RAR archive (CBR), from its first bytes
+pub fn is_rar_file(path: &Path) -> Result<bool> {
+    let mut header = Vec::with_capacity(8);
+    std::fs::File::open(path)
+        .context("Failed to open CBZ/CBR file")?
+        .take(8)
+        .read_to_end(&mut header)
+        .context("Failed to read CBZ/CBR file")?;
+    Ok(is_rar(&header))
+}
+
+/// Check for RAR magic bytes (supports both RAR 4.x and RAR 5.x)
+fn is_rar(archive_data: &[u8]) -> bool {
+    if archive_data.len() >= 8 {
         // RAR 5.x: Rar!\x1a\x07\x01\x00
         &archive_data[0..8] == b"Rar!\x1a\x07\x01\x00" ||
         // RAR 4.x: Rar!\x1a\x07\x00
         &archive_data[0..7] == b"Rar!\x1a\x07\x00"
     } else {
         false
-    };
-
-    if is_rar {
-        extract_from_rar(archive_data)
-    } else {
-        extract_from_zip(archive_data)
     }
 }
 
-/// Extract images from ZIP archive (CBZ format)
-fn extract_from_zip(archive_data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+/// Read all files from a ZIP archive (CBZ format), in archive order, within the safety limits
+fn extract_from_zip(archive_data: &[u8], password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
     let cursor = Cursor::new(archive_data);
     let mut archive = ZipArchive::new(cursor)
         .context("Failed to open ZIP archive")?;
+    let mut budget = ArchiveBudget::new();
+    budget.check_entry_count(archive.len())?;
 
-    let mut images = Vec::new();
+    let mut entries = Vec::new();
 
     for i in 0..archive.len() {
-        let mut file = archive
-            .by_index(i)
-            .context(format!("Failed to read file at index {}", i))?;
-
-        let file_name = file.name().to_string();
-
-        // Skip non-image files
-        if !is_image_file(&file_name) {
-            continue;
-        }
-
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)
-            .context("Failed to read file contents")?;
+        let file_name = match archive.name_for_index(i) {
+            Some(name) if !name.ends_with('/') => sanitize_entry_path(name)?,
+            _ => continue,
+        };
 
-        images.push((file_name, buffer));
+        let buffer = read_cbz_entry(&mut archive, i, password, &mut budget)?;
+        entries.push(ArchiveEntry::new(file_name, buffer));
     }
 
-    // Sort by filename to maintain page order
-    images.sort_by(|a, b| a.0.cmp(&b.0));
-
-    Ok(images)
+    Ok(entries)
 }
 
-/// Extract images from RAR archive (CBR format)
-fn extract_from_rar(archive_data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+/// Read all files from a RAR archive (CBR format)
+/// The archive is listed with `lsar -j` and checked against the safety limits (entry count,
+/// sizes, compression ratio, paths) before unar extracts anything. The temporary directory
+/// holding the archive and its extracted files is removed on every path.
+/// lsar and unar only take the password as an argument, so it shows in the process list while they run.
+fn extract_from_rar(archive_data: &[u8], password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
     use std::process::Command;
+    use tempfile::TempDir;
 
     // RAR extraction requires external `unar` tool
-    let temp_dir = std::env::temp_dir();
-    let temp_cbr = temp_dir.join(format!("temp_{}.cbr", uuid::Uuid::new_v4()));
-    let temp_extract_dir = temp_dir.join(format!("cbr_extract_{}", uuid::Uuid::new_v4()));
+    let temp_dir = TempDir::new().context("Failed to create temp directory")?;
+    let temp_cbr = temp_dir.path().join("input.cbr");
+    let temp_extract_dir = temp_dir.path().join("pages");
 
     // Write CBR to temporary file
     std::fs::write(&temp_cbr, archive_data)
         .context("Failed to write temporary CBR file")?;
 
+    // List the archive and refuse it before extraction if it breaks a limit
+    let mut command = Command::new("lsar");
+    command.arg("-j");
+    if let Some(password) = password {
+        command.arg("-p").arg(password);
+    }
+    let output = command
+        .arg(&temp_cbr)
+        .output()
+        .context("Failed to execute lsar command. Install with: brew install unar")?;
+    if !output.status.success() {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("Failed to list RAR archive: {}", error_msg);
+    }
+    check_rar_listing(&parse_lsar_listing(&output.stdout)?)?;
+
     // Create extraction directory
     std::fs::create_dir_all(&temp_extract_dir)
         .context("Failed to create extraction directory")?;
 
-    // Extract using unar
-    let output = Command::new("unar")
-        .arg("-o")
-        .arg(&temp_extract_dir)
+    // Extract using unar; -D keeps unar from wrapping archives with several top-level items in a folder
+    let mut command = Command::new("unar");
+    command.arg("-D").arg("-o").arg(&temp_extract_dir);
+    if let Some(password) = password {
+        command.arg("-p").arg(password);
+    }
+    let output = command
         .arg(&temp_cbr)
         .output()
         .context("Failed to execute unar command. Install with: brew install unar")?;
@@ -108,56 +261,46 @@ fn extract_from_rar(archive_data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
         anyhow::bail!("Failed to extract RAR archive: {}", error_msg);
     }
 
-    // Read extracted images
-    let mut images = Vec::new();
+    // Read extracted files
+    let mut entries = Vec::new();
 
     // Recursively read all files from extraction directory
-    fn read_images_recursive(dir: &std::path::Path, images: &mut Vec<(String, Vec<u8>)>) -> Result<()> {
-        if let Ok(entries) = std::fs::read_dir(dir) {
-            for entry in entries {
-                if let Ok(entry) = entry {
-                    let path = entry.path();
-                    if path.is_dir() {
-                        // Recurse into subdirectories
-                        read_images_recursive(&path, images)?;
-                    } else if path.is_file() {
-                        let file_name = path
-                            .file_name()
-                            .context("Failed to get filename")?
-                            .to_string_lossy()
-                            .to_string();
-
-                        if is_image_file(&file_name) {
-                            let buffer = std::fs::read(&path)
-                                .context(format!("Failed to read extracted file: {}", file_name))?;
-                            images.push((file_name, buffer));
-                        }
-                    }
+    // Paths are kept relative to the archive root, so chapters in folders do not collide.
+    // Symbolic links are never followed: a crafted archive could point them outside the extraction directory.
+    fn read_files_recursive(root: &Path, dir: &Path, entries: &mut Vec<ArchiveEntry>, budget: &mut ArchiveBudget) -> Result<()> {
+        if let Ok(dir_entries) = std::fs::read_dir(dir) {
+            for dir_entry in dir_entries.flatten() {
+                let path = dir_entry.path();
+                let Ok(file_type) = dir_entry.file_type() else { continue };
+                if file_type.is_symlink() {
+                    eprintln!("Warning: skipping symbolic link {:?} in RAR archive", path.strip_prefix(root).unwrap_or(&path));
+                } else if file_type.is_dir() {
+                    // Recurse into subdirectories
+                    read_files_recursive(root, &path, entries, budget)?;
+                } else if file_type.is_file() {
+                    let relative = path
+                        .strip_prefix(root)
+                        .context("Failed to get filename")?
+                        .to_string_lossy()
+                        .to_string();
+                    let file_name = sanitize_entry_path(&relative)?;
+
+                    // Sizes are checked before reading; RAR compressed sizes are not known here
+                    let size = dir_entry.metadata()
+                        .context(format!("Failed to read extracted file: {}", file_name))?
+                        .len();
+                    budget.add_entry(&file_name, size, size)?;
+
+                    let buffer = std::fs::read(&path)
+                        .context(format!("Failed to read extracted file: {}", file_name))?;
+                    entries.push(ArchiveEntry::new(file_name, buffer));
                 }
             }
         }
         Ok(())
     }
 
-    read_images_recursive(&temp_extract_dir, &mut images)?;
-
-    // Cleanup
-    let _ = std::fs::remove_file(&temp_cbr);
-    let _ = std::fs::remove_dir_all(&temp_extract_dir);
-
-    // Sort by filename
-    images.sort_by(|a, b| a.0.cmp(&b.0));
-
-    Ok(images)
-}
-
-/// Check if filename is an image file
-fn is_image_file(filename: &str) -> bool {
-    let lower = filename.to_lowercase();
-    lower.ends_with(".jpg")
-        || lower.ends_with(".jpeg")
-        || lower.ends_with(".png")
-        || lower.ends_with(".webp")
-        || lower.ends_with(".gif")
+    read_files_recursive(&temp_extract_dir, &temp_extract_dir, &mut entries, &mut ArchiveBudget::new())?;
+    Ok(entries)
 }
 
@@ -1,12 +1,15 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
-use pdf_conversion_lib::{bind_pdfium, convert_pdf_to_images_parallel, extract_images_lossless_at_dpi, create_pdf_from_images};
+use pdf_conversion_lib::{archive_password_error, bind_pdfium, convert_pdf_to_images_parallel, extract_images_lossless_at_dpi, extract_page_images_lopdf, limit_error, load_pdf_document, optimize_pdf_file, parse_background_color, read_pdf_chapters, read_pdf_chapters_lopdf, read_cbz_metadata, read_pdf_metadata, read_pdf_metadata_lopdf, set_safety_limits, split_spreads, is_pdf_data, merge_pages, merged_metadata, write_pdf_from_pages, Transparency};
+use pdf_conversion_lib::{ArchiveVerifier, CbzEditor, CbzReader, ComicBookInfo, IssueKind, IssueSeverity, ComicInfo, COMIC_INFO_FILENAME, DocumentMetadata, EntryKind, ImageEncoding, MergeInput, MergeSeparator, PageEdit, PageImageFormat, PageOrder, PageRotation, PdfBackend, PdfConformance, PdfEncryption, PdfMetadataSettings, PdfOutputOptions, PdfPermissions, ReadingDirection, SafetyLimits, SpreadSplit, DEFAULT_SPREAD_RATIO, Volume, VolumeSplit, DEFAULT_VOLUME_TEMPLATE, folder_chapters, plan_volumes, volume_file_name};
 
 mod archive;
 mod benchmark;
-mod image;
+mod password;
+
+use password::{with_password_prompt, with_shared_password};
 
 #[derive(Parser)]
 #[command(
@@ -18,6 +21,52 @@ mod image;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+}
+
+/// Safety limits for untrusted archives and images (apply to every command)
+#[derive(clap::Args)]
+struct LimitArgs {
+    /// Maximum number of entries in an archive
+    #[arg(long, global = true, value_name = "COUNT")]
+    max_entries: Option<usize>,
+
+    /// Maximum uncompressed size of a single archive entry, in MiB
+    #[arg(long, global = true, value_name = "MIB")]
+    max_entry_size: Option<u64>,
+
+    /// Maximum uncompressed size of all entries read from an archive, in MiB
+    #[arg(long, global = true, value_name = "MIB")]
+    max_total_size: Option<u64>,
+
+    /// Maximum image width or height, in pixels
+    #[arg(long, global = true, value_name = "PIXELS")]
+    max_image_dimension: Option<u32>,
+
+    /// Maximum image area, in megapixels
+    #[arg(long, global = true, value_name = "MEGAPIXELS")]
+    max_image_pixels: Option<u64>,
+
+    /// Maximum ratio of uncompressed to compressed size of an archive entry
+    #[arg(long, global = true, value_name = "RATIO")]
+    max_compression_ratio: Option<u64>,
+}
+
+impl LimitArgs {
+    /// Default limits, with the values given on the command line
+    fn to_limits(&self) -> SafetyLimits {
+        let defaults = SafetyLimits::default();
+        SafetyLimits {
+            max_entries: self.max_entries.unwrap_or(defaults.max_entries),
+            max_entry_size: self.max_entry_size.map_or(defaults.max_entry_size, |mib| mib.saturating_mul(1024 * 1024)),
+            max_total_size: self.max_total_size.map_or(defaults.max_total_size, |mib| mib.saturating_mul(1024 * 1024)),
+            max_image_dimension: self.max_image_dimension.unwrap_or(defaults.max_image_dimension),
+            max_image_pixels: self.max_image_pixels.map_or(defaults.max_image_pixels, |megapixels| megapixels.saturating_mul(1_000_000)),
+            max_compression_ratio: self.max_compression_ratio.unwrap_or(defaults.max_compression_ratio),
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -52,6 +101,36 @@ enum Commands {
         /// Number of threads for parallel processing (default: number of CPU cores)
         #[arg(short = 't', long)]
         threads: Option<usize>,
+
+        /// Password for encrypted PDFs (prompted on the terminal if needed and not given)
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Encrypt the CBZ pages with AES-256 using this password
+        #[arg(long)]
+        archive_password: Option<String>,
+
+        /// Read the PDF with the built-in reader instead of PDFium (used automatically when PDFium is missing)
+        /// Only image-only PDFs (one full-page image per page) can be converted this way.
+        #[arg(long)]
+        no_pdfium: bool,
+
+        /// Colour transparent parts of page images are composited on: white, black or #RRGGBB (default: white)
+        #[arg(long, value_name = "COLOR")]
+        background: Option<String>,
+
+        /// Keep the transparency of page images instead: such pages are saved as PNG, even in lossy mode
+        #[arg(long, conflicts_with = "background")]
+        keep_transparency: bool,
+
+        #[command(flatten)]
+        comic_info: ComicInfoArgs,
+
+        #[command(flatten)]
+        spreads: SpreadArgs,
+
+        #[command(flatten)]
+        volumes: VolumeArgs,
     },
 
     /// Convert CBZ/CBR to PDF
@@ -65,13 +144,222 @@ enum Commands {
         #[arg(short, long, value_name = "OUTPUT")]
         output: Option<PathBuf>,
 
-        /// Preserve original image format and quality (lossless mode)
+        /// Embed every page losslessly: JPEG pages as-is, other pages Flate-compressed
         #[arg(short, long)]
         lossless: bool,
 
-        /// JPEG quality for re-compression (1-100, default: 90, only if not lossless)
+        /// JPEG quality for re-encoded pages (1-100, default: 90, only if not lossless)
         #[arg(short = 'q', long, default_value = "90")]
         quality: u8,
+
+        /// Downscale pages whose longest side is larger than this, JPEG pages included (only if not lossless)
+        #[arg(long, value_name = "PIXELS", conflicts_with = "lossless")]
+        max_resolution: Option<u32>,
+
+        /// Password for AES-encrypted CBZ or protected CBR archives (prompted on the terminal if needed and not given).
+        /// CBR passwords are passed to unar on its command line, where other local users can read them in the process list.
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Keep pages in archive (central directory) order instead of natural filename order
+        #[arg(long)]
+        archive_order: bool,
+
+        /// Salvage the complete pages of a damaged or truncated CBZ whose central directory cannot be read
+        #[arg(long)]
+        recover: bool,
+
+        #[command(flatten)]
+        metadata: PdfMetadataArgs,
+
+        #[command(flatten)]
+        protection: PdfProtectionArgs,
+
+        #[command(flatten)]
+        volumes: VolumeArgs,
+    },
+
+    /// Merge PDFs and archives into one book
+    #[command(about = "Merge PDF, CBZ and CBR files into one CBZ or PDF, one chapter per input (folder in CBZ, bookmark in PDF)")]
+    Merge {
+        /// Input PDF/CBZ/CBR file paths, in reading order
+        #[arg(value_name = "INPUT", required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Output file path: .cbz or .pdf
+        #[arg(short, long, value_name = "OUTPUT")]
+        output: PathBuf,
+
+        /// DPI for rendering PDF inputs (default: 300)
+        #[arg(short, long, default_value = "300")]
+        dpi: u32,
+
+        /// Lossless: PDF inputs as PNG, and every page embedded losslessly in PDF output
+        #[arg(short, long)]
+        lossless: bool,
+
+        /// JPEG quality for rendered and re-encoded pages (1-100, default: 90, only in lossy mode)
+        #[arg(short = 'q', long, default_value = "90")]
+        quality: u8,
+
+        /// Password for encrypted inputs (prompted on the terminal if needed and not given).
+        /// CBR passwords are passed to unar on its command line, where other local users can read them in the process list.
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Encrypt the CBZ output with AES-256 using this password
+        #[arg(long)]
+        archive_password: Option<String>,
+
+        /// Read PDF inputs with the built-in reader instead of PDFium (used automatically when PDFium is missing)
+        #[arg(long)]
+        no_pdfium: bool,
+
+        /// Page inserted before each input after the first: "blank" (a white page) or an image file
+        #[arg(long, value_name = "blank|IMAGE")]
+        separator: Option<String>,
+
+        #[command(flatten)]
+        metadata: PdfMetadataArgs,
+
+        #[command(flatten)]
+        protection: PdfProtectionArgs,
+    },
+
+    /// Edit the pages of a CBZ archive
+    #[command(about = "Move, delete, insert, rotate and replace pages of a CBZ; untouched pages are copied without re-encoding")]
+    Edit {
+        /// Input CBZ file path
+        #[arg(value_name = "INPUT")]
+        input: PathBuf,
+
+        /// Output CBZ file path (optional, auto-generated from input if not provided)
+        #[arg(short, long, value_name = "OUTPUT")]
+        output: Option<PathBuf>,
+
+        /// Move a page before another page, or to the end (page numbers of the input, from 1)
+        #[arg(long = "move", value_name = "PAGE:BEFORE|end")]
+        moves: Vec<String>,
+
+        /// Delete a page or a range of pages
+        #[arg(long = "delete", value_name = "PAGE[-PAGE]")]
+        deletes: Vec<String>,
+
+        /// Insert an image, or every page of another CBZ/CBR, before a page or at the end
+        #[arg(long = "insert", value_name = "BEFORE|end:FILE")]
+        inserts: Vec<String>,
+
+        /// Rotate a page or a range of pages clockwise by 90, 180 or 270 degrees (JPEG pages losslessly where possible)
+        #[arg(long = "rotate", value_name = "PAGE[-PAGE]:DEGREES")]
+        rotations: Vec<String>,
+
+        /// Replace the image of a page
+        #[arg(long = "replace", value_name = "PAGE:IMAGE")]
+        replacements: Vec<String>,
+
+        /// JPEG quality for rotated pages that must be re-encoded (1-100, default: 90)
+        #[arg(short = 'q', long, default_value = "90")]
+        quality: u8,
+
+        /// Password for AES-encrypted CBZ archives, input and inserted (prompted on the terminal if needed and not given)
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Encrypt the output with AES-256 using this password
+        #[arg(long)]
+        archive_password: Option<String>,
+    },
+
+    /// Optimise a PDF's page images
+    #[command(about = "Rebuild a PDF as a lightweight image PDF: downsample and recompress page images, keeping page sizes")]
+    PdfOptimize {
+        /// Input PDF file path
+        #[arg(value_name = "INPUT")]
+        input: PathBuf,
+
+        /// Output PDF file path (optional, auto-generated from input if not provided)
+        #[arg(short, long, value_name = "OUTPUT")]
+        output: Option<PathBuf>,
+
+        /// Target resolution of page images; larger images are downsampled (default: 150)
+        #[arg(short, long, default_value = "150")]
+        dpi: u32,
+
+        /// Compress page images losslessly (Flate) instead of JPEG
+        #[arg(short, long)]
+        lossless: bool,
+
+        /// JPEG quality for page images (1-100, default: 80, only if not lossless)
+        #[arg(short = 'q', long, default_value = "80")]
+        quality: u8,
+
+        /// Password for encrypted PDFs (prompted on the terminal if needed and not given)
+        #[arg(long)]
+        password: Option<String>,
+
+        #[command(flatten)]
+        metadata: PdfMetadataArgs,
+
+        #[command(flatten)]
+        protection: PdfProtectionArgs,
+    },
+
+    /// Repair a damaged CBZ archive
+    #[command(about = "Salvage the complete entries of a damaged or truncated CBZ and write them to a clean CBZ")]
+    Repair {
+        /// Input CBZ file path
+        #[arg(value_name = "INPUT")]
+        input: PathBuf,
+
+        /// Output CBZ file path (optional, auto-generated from input if not provided)
+        #[arg(short, long, value_name = "OUTPUT")]
+        output: Option<PathBuf>,
+
+        /// Encrypt the output with AES-256 using this password
+        #[arg(long)]
+        archive_password: Option<String>,
+    },
+
+    /// Re-pack a CBZ/CBR archive as CBZ
+    #[command(about = "Re-pack a CBZ or CBR archive as CBZ, keeping folders, metadata files and the archive comment")]
+    Repack {
+        /// Input CBZ/CBR file path
+        #[arg(value_name = "INPUT")]
+        input: PathBuf,
+
+        /// Output CBZ file path (optional, auto-generated from input if not provided)
+        #[arg(short, long, value_name = "OUTPUT")]
+        output: Option<PathBuf>,
+
+        /// Password for AES-encrypted CBZ or protected CBR archives (prompted on the terminal if needed and not given).
+        /// CBR passwords are passed to unar on its command line, where other local users can read them in the process list.
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Encrypt the output with AES-256 using this password
+        #[arg(long)]
+        archive_password: Option<String>,
+    },
+
+    /// Verify CBZ/CBR archives
+    #[command(about = "Check CBZ/CBR archives: entry CRCs, image decoding and dimensions, duplicate names and missing page numbers")]
+    Verify {
+        /// Input CBZ/CBR file paths
+        #[arg(value_name = "INPUT", required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Print one JSON report per archive (JSON Lines) instead of text
+        #[arg(long)]
+        json: bool,
+
+        /// Fail on warnings too (ambiguous names, missing page numbers, ...)
+        #[arg(long)]
+        strict: bool,
+
+        /// Password for AES-encrypted CBZ or protected CBR archives (prompted on the terminal if needed and not given).
+        /// CBR passwords are passed to unar on its command line, where other local users can read them in the process list.
+        #[arg(long)]
+        password: Option<String>,
     },
 
     /// Smoke test: diagnostic render of single page with regression checks
@@ -100,6 +388,10 @@ enum Commands {
         /// Fail if bbox_coverage < threshold (default: 0.30, 30%)
         #[arg(long, default_value = "0.30")]
         min_bbox_coverage: f64,
+
+        /// Password for encrypted PDFs (prompted on the terminal if needed and not given)
+        #[arg(long)]
+        password: Option<String>,
     },
 
     /// Benchmark compression methods (Stored vs Deflated)
@@ -120,11 +412,271 @@ enum Commands {
         /// Maximum pages to process (0 = all)
         #[arg(long, default_value = "0")]
         max_pages: u32,
+
+        /// Password for encrypted PDFs (prompted on the terminal if needed and not given)
+        #[arg(long)]
+        password: Option<String>,
     },
 }
 
+/// Document metadata and viewer preferences for generated PDFs
+/// Values given here override those read from the archive's ComicInfo.xml
+#[derive(clap::Args)]
+struct PdfMetadataArgs {
+    /// Document title (default: ComicInfo Title, or "Series #Number")
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Document author (default: ComicInfo Writer)
+    #[arg(long)]
+    author: Option<String>,
+
+    /// Document subject (default: ComicInfo Summary)
+    #[arg(long)]
+    subject: Option<String>,
+
+    /// Comma-separated keywords (default: ComicInfo Genre and Tags)
+    #[arg(long)]
+    keywords: Option<String>,
+
+    /// Creation date as YYYY-MM-DD (default: ComicInfo Year/Month/Day, or now)
+    #[arg(long)]
+    date: Option<String>,
+
+    /// Manga: ask viewers to read right-to-left
+    #[arg(long)]
+    manga: bool,
+
+    /// Show two-page spreads with the cover alone (PageLayout TwoPageRight)
+    #[arg(long)]
+    spreads: bool,
+
+    /// Write PDF/A-2b (archival: sRGB output intent, XMP metadata, file identifier)
+    #[arg(long)]
+    pdfa: bool,
+}
+
+/// ComicInfo.xml written into generated CBZ archives
+/// Title, Writer and Summary come from the PDF Info dictionary.
+#[derive(clap::Args)]
+struct ComicInfoArgs {
+    /// Manga: mark the comic as read right-to-left
+    #[arg(long)]
+    manga: bool,
+
+    /// Language of the comic as an ISO code (e.g. en, fr, ja)
+    #[arg(long)]
+    language: Option<String>,
+
+    /// Do not add a ComicInfo.xml to the archive
+    #[arg(long)]
+    no_comic_info: bool,
+
+    /// Also write ComicBookInfo JSON in the ZIP archive comment (for older readers)
+    #[arg(long)]
+    comic_book_info: bool,
+}
+
+impl ComicInfoArgs {
+    /// Build the ComicInfo.xml entry for the converted pages
+    /// `volume` is (number, count) when the book is split into volumes.
+    fn to_xml(&self, pdf_metadata: &DocumentMetadata, images: &[(String, Vec<u8>)], volume: Option<(usize, usize)>) -> Option<String> {
+        if self.no_comic_info {
+            return None;
+        }
+
+        let mut info = ComicInfo::from_metadata(pdf_metadata);
+        if self.manga {
+            info.manga = Some("YesAndRightToLeft".to_string());
+        }
+        if self.language.is_some() {
+            info.language_iso = self.language.clone();
+        }
+        if let Some((number, count)) = volume {
+            info.set_volume(number, count);
+        }
+        info.set_pages(images);
+        Some(info.to_xml())
+    }
+
+    /// Build the ComicBookInfo ZIP comment, if requested
+    fn to_comment(&self, pdf_metadata: &DocumentMetadata) -> Option<String> {
+        if !self.comic_book_info {
+            return None;
+        }
+
+        let mut info = ComicBookInfo::from_metadata(pdf_metadata);
+        if self.language.is_some() {
+            info.language = self.language.clone();
+        }
+        Some(info.to_json())
+    }
+}
+
+/// Splitting double-page spreads into single pages
+#[derive(clap::Args)]
+struct SpreadArgs {
+    /// Split double-page spreads (landscape pages) into two pages, right page first for manga
+    #[arg(long)]
+    split_spreads: bool,
+
+    /// Keep each full spread before its two halves
+    #[arg(long, requires = "split_spreads")]
+    keep_spreads: bool,
+
+    /// Width-to-height ratio from which a landscape page is split even when no gutter is found (default: 1.2)
+    #[arg(long, value_name = "RATIO", default_value_t = DEFAULT_SPREAD_RATIO, requires = "split_spreads")]
+    spread_ratio: f32,
+}
+
+impl SpreadArgs {
+    /// How to split spreads, or None to keep them whole
+    fn to_split(&self, direction: ReadingDirection, jpeg_quality: u8) -> Option<SpreadSplit> {
+        self.split_spreads.then_some(SpreadSplit {
+            direction,
+            keep_spread: self.keep_spreads,
+            min_ratio: self.spread_ratio,
+            jpeg_quality,
+        })
+    }
+}
+
+/// Splitting one book into several output files (volumes)
+/// Volumes are written next to the output path, named from its file name.
+#[derive(clap::Args)]
+struct VolumeArgs {
+    /// Split the output into volumes of at most this many pages
+    #[arg(long, value_name = "PAGES", conflicts_with_all = ["max_volume_size", "split_chapters"])]
+    max_volume_pages: Option<usize>,
+
+    /// Split the output into volumes of at most this size, in MB (estimated from the page sizes for PDF output)
+    #[arg(long, value_name = "MB", conflicts_with = "split_chapters")]
+    max_volume_size: Option<u64>,
+
+    /// One volume per chapter: top-level outline entries of a PDF, folders of an archive
+    #[arg(long)]
+    split_chapters: bool,
+
+    /// Volume file name without extension: {name} (output file name), {volume}, {count}, {title} (chapter title)
+    #[arg(long, value_name = "TEMPLATE", default_value = DEFAULT_VOLUME_TEMPLATE)]
+    volume_name: String,
+}
+
+impl VolumeArgs {
+    /// How to split the output, or None to write a single file
+    fn to_split(&self) -> Option<VolumeSplit> {
+        if let Some(max_pages) = self.max_volume_pages {
+            Some(VolumeSplit::MaxPages(max_pages))
+        } else if let Some(max_size) = self.max_volume_size {
+            Some(VolumeSplit::MaxSize(max_size.saturating_mul(1024 * 1024)))
+        } else if self.split_chapters {
+            Some(VolumeSplit::Chapters)
+        } else {
+            None
+        }
+    }
+
+    /// Path of each volume: the output path's folder and extension, the file name from the template
+    fn volume_paths(&self, output_file: &Path, volumes: &[Volume]) -> Result<Vec<PathBuf>> {
+        let name = output_file.file_stem().context("Invalid output filename")?.to_string_lossy();
+        let extension = output_file.extension().map(|e| e.to_string_lossy()).unwrap_or_default();
+        let paths: Vec<PathBuf> = volumes
+            .iter()
+            .map(|volume| {
+                let file_name = volume_file_name(&self.volume_name, &name, volume, volumes.len());
+                output_file.with_file_name(format!("{}.{}", file_name, extension))
+            })
+            .collect();
+
+        if paths.iter().any(|path| path.file_stem().is_none_or(|stem| stem.is_empty())) {
+            anyhow::bail!("Volume name template {:?} gives an empty file name", self.volume_name);
+        }
+        let unique: std::collections::HashSet<&PathBuf> = paths.iter().collect();
+        if unique.len() < paths.len() {
+            anyhow::bail!("Volume name template {:?} gives several volumes the same file name: use {{volume}}", self.volume_name);
+        }
+        Ok(paths)
+    }
+}
+
+/// Password protection for generated PDFs (AES-256)
+#[derive(clap::Args)]
+struct PdfProtectionArgs {
+    /// Password required to open the PDF
+    #[arg(long)]
+    user_password: Option<String>,
+
+    /// Password granting full access (default: random, so restrictions cannot be lifted)
+    #[arg(long)]
+    owner_password: Option<String>,
+
+    /// Disallow printing
+    #[arg(long)]
+    no_print: bool,
+
+    /// Disallow copying text and images
+    #[arg(long)]
+    no_copy: bool,
+
+    /// Disallow modifying the document and assembling pages
+    #[arg(long)]
+    no_modify: bool,
+
+    /// Disallow adding annotations and filling forms
+    #[arg(long)]
+    no_annotate: bool,
+}
+
+impl PdfProtectionArgs {
+    /// Encryption settings, or None when no protection option is given
+    fn to_encryption(&self) -> Option<PdfEncryption> {
+        let restricted = self.no_print || self.no_copy || self.no_modify || self.no_annotate;
+        if self.user_password.is_none() && self.owner_password.is_none() && !restricted {
+            return None;
+        }
+
+        let permissions = PdfPermissions {
+            print: !self.no_print,
+            print_high_quality: !self.no_print,
+            modify: !self.no_modify,
+            copy: !self.no_copy,
+            annotate: !self.no_annotate,
+            fill_forms: !self.no_annotate,
+            accessibility: true,
+            assemble: !self.no_modify,
+        };
+        Some(PdfEncryption {
+            user_password: self.user_password.clone().unwrap_or_default(),
+            owner_password: self.owner_password.clone().unwrap_or_default(),
+            permissions,
+        })
+    }
+}
+
+impl PdfMetadataArgs {
+    /// Build PDF options, falling back to the archive's ComicInfo.xml / ComicBookInfo metadata
+    /// ComicInfo.xml and ComicBookInfo are only looked up in ZIP archives; RAR archives use flags only.
+    fn to_options(&self, archive_metadata: Option<DocumentMetadata>) -> Result<PdfOutputOptions> {
+        if archive_metadata.is_some() {
+            println!("Found comic metadata in archive");
+        }
+        let settings = PdfMetadataSettings {
+            title: self.title.clone(),
+            author: self.author.clone(),
+            subject: self.subject.clone(),
+            keywords: self.keywords.clone(),
+            date: self.date.clone(),
+            manga: self.manga,
+            spreads: self.spreads,
+            pdfa: self.pdfa,
+        };
+        PdfOutputOptions::from_settings(&settings, archive_metadata)
+    }
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    set_safety_limits(cli.limits.to_limits());
 
     match cli.command {
         Commands::PdfToCbz {
@@ -135,16 +687,61 @@ fn main() -> Result<()> {
             quality,
             max_pages,
             threads,
-        } => convert_pdf_to_cbz(&input, output, dpi, lossless, quality, max_pages, threads),
-        Commands::CbzToPdf { input, output, lossless, quality } => convert_cbz_to_pdf(&input, output, lossless, quality),
-        Commands::SmokeRender { input, page, dpi, output, max_white_ratio, min_bbox_coverage } =>
-            smoke_render(&input, page, dpi, &output, max_white_ratio, min_bbox_coverage),
-        Commands::Benchmark { input, dpi, quality, max_pages } =>
-            run_benchmark(&input, dpi, quality, max_pages),
+            password,
+            archive_password,
+            no_pdfium,
+            background,
+            keep_transparency,
+            comic_info,
+            spreads,
+            volumes,
+        } => {
+            let transparency = match (keep_transparency, background) {
+                (true, _) => Transparency::Keep,
+                (false, Some(color)) => Transparency::Background(parse_background_color(&color)?),
+                (false, None) => Transparency::default(),
+            };
+            convert_pdf_to_cbz(&input, output, dpi, lossless, quality, max_pages, threads, password, archive_password, PdfBackend::select(no_pdfium), transparency, &comic_info, &spreads, &volumes)
+        }
+        Commands::CbzToPdf { input, output, lossless, quality, max_resolution, password, archive_order, recover, metadata, protection, volumes } => {
+            let image_encoding = if lossless {
+                ImageEncoding::Lossless
+            } else {
+                ImageEncoding::Jpeg { quality, max_resolution }
+            };
+            convert_cbz_to_pdf(&input, output, image_encoding, password, PageOrder::from_archive_order(archive_order), recover, &metadata, &protection, &volumes)
+        }
+        Commands::Merge { inputs, output, dpi, lossless, quality, password, archive_password, no_pdfium, separator, metadata, protection } => {
+            let separator = separator.as_deref().map(MergeSeparator::from_arg).transpose()?;
+            merge_files(&inputs, &output, dpi, lossless, quality, password, archive_password, PdfBackend::select(no_pdfium), separator.as_ref(), &metadata, &protection)
+        }
+        Commands::Edit { input, output, moves, deletes, inserts, rotations, replacements, quality, password, archive_password } => {
+            let operations = EditOperations { moves, deletes, inserts, rotations, replacements };
+            edit_archive(&input, output, &operations, quality, password, archive_password)
+        }
+        Commands::PdfOptimize { input, output, dpi, lossless, quality, password, metadata, protection } => {
+            let image_encoding = if lossless {
+                ImageEncoding::Lossless
+            } else {
+                ImageEncoding::Jpeg { quality, max_resolution: None }
+            };
+            optimize_pdf(&input, output, dpi, image_encoding, password, &metadata, &protection)
+        }
+        Commands::Repair { input, output, archive_password } =>
+            repair_archive(&input, output, archive_password),
+        Commands::Verify { inputs, json, strict, password } =>
+            verify_archives(&inputs, json, strict, password),
+        Commands::Repack { input, output, password, archive_password } =>
+            repack_archive(&input, output, password, archive_password),
+        Commands::SmokeRender { input, page, dpi, output, max_white_ratio, min_bbox_coverage, password } =>
+            smoke_render(&input, page, dpi, &output, max_white_ratio, min_bbox_coverage, password),
+        Commands::Benchmark { input, dpi, quality, max_pages, password } =>
+            run_benchmark(&input, dpi, quality, max_pages, password),
     }
 }
 
-fn convert_pdf_to_cbz(input_path: &PathBuf, output_path: Option<PathBuf>, dpi: u32, lossless: bool, quality: u8, max_pages: u32, threads: Option<usize>) -> Result<()> {
+#[allow(clippy::too_many_arguments)]
+fn convert_pdf_to_cbz(input_path: &PathBuf, output_path: Option<PathBuf>, dpi: u32, lossless: bool, quality: u8, max_pages: u32, threads: Option<usize>, password: Option<String>, archive_password: Option<String>, backend: PdfBackend, transparency: Transparency, comic_info_args: &ComicInfoArgs, spread_args: &SpreadArgs, volume_args: &VolumeArgs) -> Result<()> {
     // Validate input
     if !input_path.exists() {
         anyhow::bail!("Input PDF file not found: {:?}", input_path);
@@ -182,6 +779,10 @@ fn convert_pdf_to_cbz(input_path: &PathBuf, output_path: Option<PathBuf>, dpi: u
     println!("Converting PDF to CBZ: {:?}", input_path);
     println!("Output: {:?}", output_file);
 
+    if backend == PdfBackend::Lopdf {
+        println!("PDF reader: {}", backend.name());
+    }
+
     if lossless {
         println!("Mode: PNG Lossless (direct extract or render at {} DPI as PNG)", dpi);
     } else {
@@ -193,39 +794,135 @@ fn convert_pdf_to_cbz(input_path: &PathBuf, output_path: Option<PathBuf>, dpi: u
     }
 
     // Read PDF
-    let pdf_data = std::fs::read(&input_path)
+    let pdf_data = std::fs::read(input_path)
         .context("Failed to read PDF file")?;
 
-    // Convert to images
-    let images = if lossless {
-        // PNG Lossless: direct extract or render as PNG at same DPI
-        extract_images_lossless_at_dpi(&pdf_data, dpi, max_pages)
-            .context("Failed to extract images from PDF")?
-    } else {
-        // JPEG Lossy: render at specified DPI with quality parameter
-        convert_pdf_to_images_parallel(&pdf_data, dpi, quality, max_pages)
-            .context("Failed to convert PDF to images")?
-    };
+    let split = volume_args.to_split();
+    let split_chapters = split == Some(VolumeSplit::Chapters);
+
+    // Convert to images (the Info dictionary and outline are read with the same password)
+    let (mut images, pdf_metadata, mut chapters) = with_password_prompt(password, |password| {
+        if backend == PdfBackend::Lopdf {
+            // Embedded images only: JPEG pages as-is, other pages as PNG or JPEG
+            let pdf_metadata = read_pdf_metadata_lopdf(&pdf_data, password)
+                .context("Failed to read PDF metadata")?;
+            let chapters = if split_chapters {
+                read_pdf_chapters_lopdf(&pdf_data, password).context("Failed to read PDF outline")?
+            } else {
+                Vec::new()
+            };
+            let images = extract_page_images_lopdf(&pdf_data, lossless, quality, max_pages, password, transparency)
+                .context("Failed to extract images from PDF")?;
+            return Ok((images, pdf_metadata, chapters));
+        }
+
+        let pdf_metadata = read_pdf_metadata(&pdf_data, password)
+            .context("Failed to read PDF metadata")?;
+        let chapters = if split_chapters {
+            read_pdf_chapters(&pdf_data, password).context("Failed to read PDF outline")?
+        } else {
+            Vec::new()
+        };
+        let images = if lossless {
+            // PNG Lossless: direct extract or render as PNG at same DPI
+            extract_images_lossless_at_dpi(&pdf_data, dpi, max_pages, password, transparency)
+                .context("Failed to extract images from PDF")
+        } else {
+            // JPEG Lossy: render at specified DPI with quality parameter
+            convert_pdf_to_images_parallel(&pdf_data, dpi, quality, max_pages, password, transparency)
+                .context("Failed to convert PDF to images")
+        }?;
+        Ok((images, pdf_metadata, chapters))
+    })?;
 
     println!("Processed {} pages", images.len());
 
+    let direction = if comic_info_args.manga { ReadingDirection::RightToLeft } else { pdf_metadata.reading_direction };
+    if let Some(spread_split) = spread_args.to_split(direction, quality) {
+        let report = split_spreads(&mut images, &spread_split)?;
+        if report.split_pages.is_empty() {
+            println!("No double-page spreads found");
+        } else {
+            let pages: Vec<String> = report.split_pages.iter().map(usize::to_string).collect();
+            println!("Split {} double-page spreads ({} at a gutter, {} without re-encoding): pages {}", report.split_pages.len(), report.at_gutter, report.lossless, pages.join(", "));
+            // Chapters start at the same page of the new page list
+            for (_, start) in &mut chapters {
+                let split_before = report.split_pages.iter().filter(|&&page| page <= *start).count();
+                *start += split_before * if spread_split.keep_spread { 2 } else { 1 };
+            }
+        }
+    }
+
+    if !comic_info_args.no_comic_info {
+        println!("Adding {}", COMIC_INFO_FILENAME);
+    }
+    if comic_info_args.comic_book_info {
+        println!("Adding ComicBookInfo archive comment");
+    }
+
+    let Some(split) = split else {
+        let size = write_cbz(images, &output_file, &pdf_metadata, None, comic_info_args, archive_password.as_deref())?;
+        println!("✓ Successfully created: {:?} ({:.2} MB)", output_file, size as f64 / (1024.0 * 1024.0));
+        return Ok(());
+    };
+
+    if split_chapters && chapters.is_empty() {
+        anyhow::bail!("The PDF has no outline (bookmarks) to split into chapters");
+    }
+    let page_count = images.len();
+    let page_sizes: Vec<u64> = images.iter().map(|(_, data)| data.len() as u64).collect();
+    let volumes = plan_volumes(&page_sizes, &chapters, split)?;
+    let paths = volume_args.volume_paths(&output_file, &volumes)?;
+    println!("Splitting into {} volumes", volumes.len());
+
+    let mut images = images.into_iter();
+    let mut total_size = 0;
+    for (volume, path) in volumes.iter().zip(&paths) {
+        let volume_images: Vec<(String, Vec<u8>)> = images.by_ref().take(volume.pages.len()).collect();
+        let metadata = volume.metadata(&pdf_metadata);
+        let size = write_cbz(volume_images, path, &metadata, Some((volume.number, volumes.len())), comic_info_args, archive_password.as_deref())?;
+        total_size += size;
+        print_volume(volume, volumes.len(), path, size);
+    }
+
+    println!("✓ Successfully created {} volumes: {} pages ({:.2} MB)", volumes.len(), page_count, total_size as f64 / (1024.0 * 1024.0));
+    Ok(())
+}
+
+/// Write converted pages as a CBZ with its ComicInfo.xml and ComicBookInfo comment, returning its size
+/// `volume` is (number, count) when the book is split into volumes.
+fn write_cbz(
+    mut images: Vec<(String, Vec<u8>)>,
+    output_file: &Path,
+    metadata: &DocumentMetadata,
+    volume: Option<(usize, usize)>,
+    comic_info_args: &ComicInfoArgs,
+    archive_password: Option<&str>,
+) -> Result<u64> {
+    if let Some(xml) = comic_info_args.to_xml(metadata, &images, volume) {
+        images.push((COMIC_INFO_FILENAME.to_string(), xml.into_bytes()));
+    }
+    let comment = comic_info_args.to_comment(metadata);
+
     // Create CBZ archive
-    let cbz_data = archive::create_cbz(images)
+    let cbz_data = archive::create_cbz(images, archive_password, comment)
         .context("Failed to create CBZ archive")?;
 
     // Write output
-    std::fs::write(&output_file, cbz_data)
-        .context("Failed to write CBZ file")?;
-
-    let file_size_mb = std::fs::metadata(&output_file)
-        .map(|m| m.len() as f64 / (1024.0 * 1024.0))
-        .unwrap_or(0.0);
+    std::fs::write(output_file, &cbz_data)
+        .context(format!("Failed to write {:?}", output_file))?;
+    Ok(cbz_data.len() as u64)
+}
 
-    println!("✓ Successfully created: {:?} ({:.2} MB)", output_file, file_size_mb);
-    Ok(())
+/// Progress line for a written volume
+fn print_volume(volume: &Volume, count: usize, path: &Path, size: u64) {
+    let title = volume.title.as_deref().map(|title| format!(" \"{}\"", title)).unwrap_or_default();
+    println!("  ✓ Volume {}/{}{}: {:?} (pages {}-{}, {:.2} MB)",
+             volume.number, count, title, path, volume.pages.start + 1, volume.pages.end, size as f64 / (1024.0 * 1024.0));
 }
 
-fn convert_cbz_to_pdf(input_path: &PathBuf, output_path: Option<PathBuf>, lossless: bool, quality: u8) -> Result<()> {
+#[allow(clippy::too_many_arguments)]
+fn convert_cbz_to_pdf(input_path: &PathBuf, output_path: Option<PathBuf>, image_encoding: ImageEncoding, password: Option<String>, page_order: PageOrder, recover: bool, metadata_args: &PdfMetadataArgs, protection_args: &PdfProtectionArgs, volume_args: &VolumeArgs) -> Result<()> {
     // Validate input
     if !input_path.exists() {
         anyhow::bail!("Input CBZ/CBR file not found: {:?}", input_path);
@@ -235,9 +932,14 @@ fn convert_cbz_to_pdf(input_path: &PathBuf, output_path: Option<PathBuf>, lossle
         anyhow::bail!("Input path is not a file: {:?}", input_path);
     }
 
-    // Validate quality
-    if quality == 0 || quality > 100 {
-        anyhow::bail!("Quality must be between 1 and 100");
+    // Validate quality and resolution
+    if let ImageEncoding::Jpeg { quality, max_resolution } = image_encoding {
+        if quality == 0 || quality > 100 {
+            anyhow::bail!("Quality must be between 1 and 100");
+        }
+        if max_resolution == Some(0) {
+            anyhow::bail!("Max resolution must be at least 1 pixel");
+        }
     }
 
     // Determine output path
@@ -252,43 +954,556 @@ fn convert_cbz_to_pdf(input_path: &PathBuf, output_path: Option<PathBuf>, lossle
     println!("Converting CBZ/CBR to PDF: {:?}", input_path);
     println!("Output: {:?}", output_file);
     
-    if lossless {
-        println!("Mode: Lossless (preserving original image quality)");
-    } else {
-        println!("Mode: Re-compressing with JPEG quality: {}", quality);
+    match image_encoding {
+        ImageEncoding::Lossless => println!("Mode: Lossless (JPEG pages as-is, other pages Flate-compressed)"),
+        ImageEncoding::Jpeg { quality, max_resolution: None } =>
+            println!("Mode: Re-encoding non-JPEG pages with JPEG quality: {}", quality),
+        ImageEncoding::Jpeg { quality, max_resolution: Some(max) } =>
+            println!("Mode: Re-encoding non-JPEG pages with JPEG quality: {}, downscaling pages larger than {} px", quality, max),
     }
 
-    // Read archive
-    let archive_data = std::fs::read(&input_path)
-        .context("Failed to read CBZ/CBR file")?;
-
-    // Extract images
-    let images = archive::extract_images(&archive_data)
-        .context("Failed to extract images from archive")?;
+    // CBZ pages are read from disk one at a time; RAR archives are extracted with unar first.
+    // The path and size of every page are listed up front to plan volumes.
+    let (mut pages, archive_metadata, page_entries): (archive::ArchivePages, _, Vec<(String, u64)>) =
+        if archive::is_rar_file(input_path)? {
+            let archive_data = std::fs::read(input_path)
+                .context("Failed to read CBR file")?;
+            let images = with_password_prompt(password, |password| {
+                archive::extract_images(&archive_data, password, page_order)
+                    .context("Failed to extract images from archive")
+            })?;
+            let page_entries = images.iter().map(|(name, data)| (name.clone(), data.len() as u64)).collect();
+            (Box::new(images.into_iter().map(Ok)), None, page_entries)
+        } else {
+            // Asking for the password if the archive is encrypted
+            let reader = with_password_prompt(password, |password| {
+                CbzReader::open(input_path, password, page_order)
+                    .context("Failed to open CBZ archive")
+            });
+            match reader {
+                Ok(mut reader) => {
+                    if reader.deleted_pages() > 0 {
+                        println!("Skipping {} page(s) marked Deleted in ComicInfo.xml", reader.deleted_pages());
+                    }
+                    let metadata = reader.metadata();
+                    let page_entries = reader.page_entries();
+                    (Box::new(reader.into_pages()), metadata, page_entries)
+                }
+                // Password and safety limit errors are not damage
+                Err(e) if archive_password_error(&e).is_some() || limit_error(&e).is_some() => return Err(e),
+                Err(e) if recover => {
+                    println!("Archive cannot be read ({:#}), recovering pages from local file headers", e);
+                    let (images, metadata) = archive::recover_pages(input_path, page_order)?;
+                    let page_entries = images.iter().map(|(name, data)| (name.clone(), data.len() as u64)).collect();
+                    (Box::new(images.into_iter().map(Ok)), metadata, page_entries)
+                }
+                Err(e) => return Err(e.context("Use --recover to salvage the pages of a damaged archive")),
+            }
+        };
 
-    if images.is_empty() {
+    if pages.len() == 0 {
         anyhow::bail!("No images found in archive");
     }
 
-    println!("Extracted {} images", images.len());
+    println!("Found {} images", pages.len());
 
-    // Create PDF
-    let pdf_data = create_pdf_from_images(images)
-        .context("Failed to create PDF from images")?;
+    let mut pdf_options = metadata_args.to_options(archive_metadata)?;
+    pdf_options.encryption = protection_args.to_encryption();
+    pdf_options.image_encoding = image_encoding;
+    if let Some(title) = pdf_options.metadata.display_title() {
+        println!("Title: {}", title);
+    }
+    if pdf_options.conformance == PdfConformance::PdfA2b {
+        println!("Conformance: PDF/A-2b");
+    }
+    if pdf_options.encryption.is_some() {
+        println!("Encryption: AES-256");
+    }
 
-    // Write output
-    std::fs::write(&output_file, pdf_data)
+    let Some(split) = volume_args.to_split() else {
+        let size = write_pdf(pages, &output_file, &pdf_options)?;
+        println!("✓ Successfully created: {:?} ({:.2} MB)", output_file, size as f64 / (1024.0 * 1024.0));
+        return Ok(());
+    };
+
+    // Volumes are planned from the archive's page sizes: PDF sizes are estimates when pages are re-encoded
+    let chapters = folder_chapters(&page_entries.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>());
+    let page_sizes: Vec<u64> = page_entries.iter().map(|(_, size)| *size).collect();
+    let volumes = plan_volumes(&page_sizes, &chapters, split)?;
+    let paths = volume_args.volume_paths(&output_file, &volumes)?;
+    println!("Splitting into {} volumes", volumes.len());
+
+    let mut total_size = 0;
+    for (volume, path) in volumes.iter().zip(&paths) {
+        let mut volume_options = pdf_options.clone();
+        volume_options.metadata = volume.metadata(&pdf_options.metadata);
+        let size = write_pdf(pages.by_ref().take(volume.pages.len()), path, &volume_options)?;
+        total_size += size;
+        print_volume(volume, volumes.len(), path, size);
+    }
+
+    println!("✓ Successfully created {} volumes: {} pages ({:.2} MB)", volumes.len(), page_entries.len(), total_size as f64 / (1024.0 * 1024.0));
+    Ok(())
+}
+
+/// Stream pages to a PDF file, returning its size
+fn write_pdf<I>(pages: I, output_file: &Path, pdf_options: &PdfOutputOptions) -> Result<u64>
+where
+    I: ExactSizeIterator<Item = Result<(String, Vec<u8>)>>,
+{
+    // Stream PDF pages directly to the output file
+    let file = std::fs::File::create(output_file)
+        .context(format!("Failed to create {:?}", output_file))?;
+    write_pdf_from_pages(pages, std::io::BufWriter::new(file), pdf_options, |_, _| {})
+        .context("Failed to create PDF from images")?
+        .into_inner()
         .context("Failed to write PDF file")?;
 
-    let file_size_mb = std::fs::metadata(&output_file)
-        .map(|m| m.len() as f64 / (1024.0 * 1024.0))
-        .unwrap_or(0.0);
+    Ok(std::fs::metadata(output_file).map(|m| m.len()).unwrap_or(0))
+}
 
-    println!("✓ Successfully created: {:?} ({:.2} MB)", output_file, file_size_mb);
+/// Merge inputs into one CBZ or PDF (chosen by the output extension), one chapter folder per input
+/// PDF inputs are converted like pdf-to-cbz; archive pages are used as-is.
+#[allow(clippy::too_many_arguments)]
+fn merge_files(inputs: &[PathBuf], output_file: &Path, dpi: u32, lossless: bool, quality: u8, mut password: Option<String>, archive_password: Option<String>, backend: PdfBackend, separator: Option<&MergeSeparator>, metadata_args: &PdfMetadataArgs, protection_args: &PdfProtectionArgs) -> Result<()> {
+    if quality == 0 || quality > 100 {
+        anyhow::bail!("Quality must be between 1 and 100");
+    }
+
+    let extension = output_file.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let pdf_output = match extension.as_str() {
+        "pdf" => true,
+        "cbz" => false,
+        _ => anyhow::bail!("Output file must end in .cbz or .pdf: {:?}", output_file),
+    };
+    if pdf_output && archive_password.is_some() {
+        anyhow::bail!("--archive-password only applies to CBZ output");
+    }
+    let encryption = protection_args.to_encryption();
+    if !pdf_output && encryption.is_some() {
+        anyhow::bail!("PDF protection options only apply to PDF output");
+    }
+
+    println!("Merging {} files into: {:?}", inputs.len(), output_file);
+    if backend == PdfBackend::Lopdf {
+        println!("PDF reader: {}", backend.name());
+    }
+
+    let mut merge_inputs = Vec::with_capacity(inputs.len());
+    for (index, input_path) in inputs.iter().enumerate() {
+        if !input_path.is_file() {
+            anyhow::bail!("Input file not found: {:?}", input_path);
+        }
+        let data = std::fs::read(input_path)
+            .context(format!("Failed to read {:?}", input_path))?;
+
+        let (pages, metadata) = if is_pdf_data(&data) {
+            let (pages, metadata) = with_shared_password(&mut password, |password| {
+                convert_pdf_pages(&data, dpi, lossless, quality, password, backend)
+            })
+            .context(format!("Failed to convert {:?}", input_path))?;
+            (pages, Some(metadata))
+        } else {
+            with_shared_password(&mut password, |password| {
+                let pages = archive::extract_images(&data, password, PageOrder::Natural)
+                    .context("Failed to extract images from archive")?;
+                Ok((pages, read_cbz_metadata(&data, password)))
+            })
+            .context(format!("Failed to read {:?}", input_path))?
+        };
+
+        let input = MergeInput::new(input_path, pages, metadata);
+        println!("  {}. {} ({} pages)", index + 1, input.title, input.pages.len());
+        merge_inputs.push(input);
+    }
+
+    let book_metadata = merged_metadata(&merge_inputs);
+    let pages = merge_pages(merge_inputs, separator)?;
+    let page_count = pages.len();
+
+    let mut pdf_options = metadata_args.to_options(None)?;
+    pdf_options.metadata = pdf_options.metadata.merged_with(book_metadata);
+    if let Some(title) = pdf_options.metadata.display_title() {
+        println!("Title: {}", title);
+    }
+
+    let size = if pdf_output {
+        pdf_options.encryption = encryption;
+        pdf_options.image_encoding = if lossless {
+            ImageEncoding::Lossless
+        } else {
+            ImageEncoding::Jpeg { quality, max_resolution: None }
+        };
+        write_pdf(pages.into_iter().map(Ok), output_file, &pdf_options)?
+    } else {
+        let mut pages = pages;
+        let mut info = ComicInfo::from_metadata(&pdf_options.metadata);
+        info.set_pages(&pages);
+        pages.push((COMIC_INFO_FILENAME.to_string(), info.to_xml().into_bytes()));
+        let cbz_data = archive::create_cbz(pages, archive_password.as_deref(), None)
+            .context("Failed to create CBZ archive")?;
+        std::fs::write(output_file, &cbz_data)
+            .context(format!("Failed to write {:?}", output_file))?;
+        cbz_data.len() as u64
+    };
+
+    println!("✓ Successfully created: {:?} ({} pages, {:.2} MB)", output_file, page_count, size as f64 / (1024.0 * 1024.0));
+    Ok(())
+}
+
+/// Convert the pages of a PDF to images, with its Info metadata (see `convert_pdf_to_cbz`)
+fn convert_pdf_pages(pdf_data: &[u8], dpi: u32, lossless: bool, quality: u8, password: Option<&str>, backend: PdfBackend) -> Result<(archive::PageImages, DocumentMetadata)> {
+    let transparency = Transparency::default();
+    if backend == PdfBackend::Lopdf {
+        let metadata = read_pdf_metadata_lopdf(pdf_data, password)
+            .context("Failed to read PDF metadata")?;
+        let images = extract_page_images_lopdf(pdf_data, lossless, quality, 0, password, transparency)
+            .context("Failed to extract images from PDF")?;
+        return Ok((images, metadata));
+    }
+
+    let metadata = read_pdf_metadata(pdf_data, password)
+        .context("Failed to read PDF metadata")?;
+    let images = if lossless {
+        extract_images_lossless_at_dpi(pdf_data, dpi, 0, password, transparency)
+            .context("Failed to extract images from PDF")
+    } else {
+        convert_pdf_to_images_parallel(pdf_data, dpi, quality, 0, password, transparency)
+            .context("Failed to convert PDF to images")
+    }?;
+    Ok((images, metadata))
+}
+
+/// Page edits given on the command line, with page numbers of the input archive
+struct EditOperations {
+    moves: Vec<String>,
+    deletes: Vec<String>,
+    inserts: Vec<String>,
+    rotations: Vec<String>,
+    replacements: Vec<String>,
+}
+
+fn edit_archive(input_path: &PathBuf, output_path: Option<PathBuf>, operations: &EditOperations, quality: u8, password: Option<String>, archive_password: Option<String>) -> Result<()> {
+    if !input_path.is_file() {
+        anyhow::bail!("Input CBZ file not found: {:?}", input_path);
+    }
+    if quality == 0 || quality > 100 {
+        anyhow::bail!("Quality must be between 1 and 100");
+    }
+
+    let output_file = match output_path {
+        Some(p) => p,
+        None => {
+            let stem = input_path.file_stem().context("Invalid input filename")?;
+            input_path.with_file_name(format!("{}_edited.cbz", stem.to_string_lossy()))
+        }
+    };
+    if output_file == *input_path {
+        anyhow::bail!("Output would overwrite the input archive, choose another path with --output");
+    }
+
+    println!("Editing archive: {:?}", input_path);
+    println!("Output: {:?}", output_file);
+
+    let mut editor = with_password_prompt(password.clone(), |password| CbzEditor::open(input_path, password))?;
+    editor.jpeg_quality = quality;
+    println!("Read {} pages", editor.len());
+
+    // Pages are named by their number in the input, so the order of the options does not matter
+    let page_index = |editor: &CbzEditor, page: usize| -> Result<usize> {
+        page.checked_sub(1)
+            .and_then(|page| editor.page_index(page))
+            .context(format!("Page {} is not in the book, or was already deleted", page))
+    };
+    let position = |editor: &CbzEditor, before: &str| -> Result<usize> {
+        if before.eq_ignore_ascii_case("end") {
+            Ok(editor.len())
+        } else {
+            page_index(editor, parse_page_number(before)?)
+        }
+    };
+
+    for replacement in &operations.replacements {
+        let (page, path) = split_edit_arg(replacement, "--replace", "PAGE:IMAGE")?;
+        let page = page_index(&editor, parse_page_number(page)?)?;
+        let data = std::fs::read(path)
+            .context(format!("Failed to read {:?}", path))?;
+        editor.apply(PageEdit::Replace { page, image: (file_name(path), data) })?;
+    }
+
+    for rotation in &operations.rotations {
+        let (pages, degrees) = split_edit_arg(rotation, "--rotate", "PAGE[-PAGE]:DEGREES")?;
+        let degrees = degrees.trim().parse().context(format!("Invalid rotation {:?}", degrees))?;
+        let rotation = PageRotation::from_degrees(degrees)?;
+        for page in parse_page_range(pages)? {
+            let page = page_index(&editor, page)?;
+            editor.apply(PageEdit::Rotate { page, rotation })?;
+        }
+    }
+
+    for insert in &operations.inserts {
+        let (before, path) = split_edit_arg(insert, "--insert", "BEFORE|end:FILE")?;
+        let at = position(&editor, before)?;
+        let data = std::fs::read(path)
+            .context(format!("Failed to read {:?}", path))?;
+        let pages = if PageImageFormat::sniff(&data).is_some() {
+            vec![(file_name(path), data)]
+        } else {
+            with_password_prompt(password.clone(), |password| {
+                archive::extract_images(&data, password, PageOrder::Natural)
+                    .context(format!("Failed to extract images from {:?}", path))
+            })?
+        };
+        println!("Inserting {} pages from {:?}", pages.len(), path);
+        editor.apply(PageEdit::Insert { at, pages })?;
+    }
+
+    for page_move in &operations.moves {
+        let (page, before) = split_edit_arg(page_move, "--move", "PAGE:BEFORE|end")?;
+        let from = page_index(&editor, parse_page_number(page)?)?;
+        let before = position(&editor, before)?;
+        // Positions after the moved page shift down once it is taken out
+        let to = if before > from { before - 1 } else { before };
+        editor.apply(PageEdit::Move { from, to })?;
+    }
+
+    for delete in &operations.deletes {
+        for page in parse_page_range(delete)? {
+            let page = page_index(&editor, page)?;
+            editor.apply(PageEdit::Delete(page))?;
+        }
+    }
+
+    let report = editor.write(&output_file, archive_password.as_deref())?;
+    if report.dropped_pages > 0 {
+        println!("Dropped {} pages marked as deleted in ComicInfo.xml", report.dropped_pages);
+    }
+    if report.lossless_rotations + report.reencoded_rotations > 0 {
+        println!("Rotated {} pages losslessly, {} re-encoded", report.lossless_rotations, report.reencoded_rotations);
+    }
+    println!("✓ Successfully created: {:?} ({} pages, {:.2} MB)", output_file, report.pages, report.output_size as f64 / (1024.0 * 1024.0));
+    Ok(())
+}
+
+/// Split an edit option at the first ':' (so that Windows paths after it are kept whole)
+fn split_edit_arg<'a>(value: &'a str, option: &str, format: &str) -> Result<(&'a str, &'a str)> {
+    value.split_once(':')
+        .filter(|(first, second)| !first.trim().is_empty() && !second.trim().is_empty())
+        .context(format!("Invalid {} value {:?} (expected {})", option, value, format))
+}
+
+/// Page number from 1
+fn parse_page_number(value: &str) -> Result<usize> {
+    match value.trim().parse::<usize>() {
+        Ok(page) if page > 0 => Ok(page),
+        _ => anyhow::bail!("Invalid page number {:?} (pages count from 1)", value),
+    }
+}
+
+/// "N" or "N-M", inclusive
+fn parse_page_range(value: &str) -> Result<std::ops::RangeInclusive<usize>> {
+    let (first, last) = match value.split_once('-') {
+        Some((first, last)) => (parse_page_number(first)?, parse_page_number(last)?),
+        None => {
+            let page = parse_page_number(value)?;
+            (page, page)
+        }
+    };
+    if last < first {
+        anyhow::bail!("Invalid page range {:?}", value);
+    }
+    Ok(first..=last)
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name().map_or_else(|| path.to_string(), |name| name.to_string_lossy().to_string())
+}
+
+fn optimize_pdf(input_path: &PathBuf, output_path: Option<PathBuf>, dpi: u32, image_encoding: ImageEncoding, password: Option<String>, metadata_args: &PdfMetadataArgs, protection_args: &PdfProtectionArgs) -> Result<()> {
+    if !input_path.is_file() {
+        anyhow::bail!("Input PDF file not found: {:?}", input_path);
+    }
+    if dpi == 0 {
+        anyhow::bail!("DPI must be at least 1");
+    }
+    if let ImageEncoding::Jpeg { quality, .. } = image_encoding {
+        if quality == 0 || quality > 100 {
+            anyhow::bail!("Quality must be between 1 and 100");
+        }
+    }
+
+    let output_file = match output_path {
+        Some(p) => p,
+        None => {
+            let stem = input_path.file_stem().context("Invalid input filename")?;
+            input_path.with_file_name(format!("{}_optimized.pdf", stem.to_string_lossy()))
+        }
+    };
+    if output_file == *input_path {
+        anyhow::bail!("Output would overwrite the input PDF, choose another path with --output");
+    }
+
+    println!("Optimizing PDF: {:?}", input_path);
+    println!("Output: {:?}", output_file);
+    match image_encoding {
+        ImageEncoding::Lossless => println!("Mode: {} DPI, lossless (Flate)", dpi),
+        ImageEncoding::Jpeg { quality, .. } => println!("Mode: {} DPI, JPEG quality: {}", dpi, quality),
+    }
+
+    let mut pdf_options = metadata_args.to_options(None)?;
+    pdf_options.encryption = protection_args.to_encryption();
+    pdf_options.image_encoding = image_encoding;
+    if pdf_options.conformance == PdfConformance::PdfA2b {
+        println!("Conformance: PDF/A-2b");
+    }
+    if pdf_options.encryption.is_some() {
+        println!("Encryption: AES-256");
+    }
+
+    let report = with_password_prompt(password, |password| {
+        optimize_pdf_file(input_path, &output_file, password, dpi, pdf_options.clone(), |current, total| {
+            if current % 10 == 0 || current == total {
+                println!("  {}/{} pages", current, total);
+            }
+        })
+    })?;
+
+    let to_mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    println!("✓ Successfully created: {:?} ({:.2} MB)", output_file, to_mb(report.output_size));
+    println!("Pages: {} ({} from embedded images, {} rendered)", report.pages, report.extracted_pages, report.rendered_pages);
+    if report.saved_bytes() >= 0 {
+        println!("Saved: {:.2} MB ({:.1}% of {:.2} MB)", to_mb(report.saved_bytes() as u64), report.saved_ratio() * 100.0, to_mb(report.input_size));
+    } else {
+        println!("Warning: output is {:.2} MB larger than the input ({:.2} MB)", to_mb(report.saved_bytes().unsigned_abs()), to_mb(report.input_size));
+    }
+    Ok(())
+}
+
+fn repair_archive(input_path: &PathBuf, output_path: Option<PathBuf>, archive_password: Option<String>) -> Result<()> {
+    if !input_path.is_file() {
+        anyhow::bail!("Input CBZ file not found: {:?}", input_path);
+    }
+
+    let output_file = match output_path {
+        Some(p) => p,
+        None => {
+            let stem = input_path.file_stem().context("Invalid input filename")?;
+            input_path.with_file_name(format!("{}_repaired.cbz", stem.to_string_lossy()))
+        }
+    };
+    if output_file == *input_path {
+        anyhow::bail!("Output would overwrite the input archive, choose another path with --output");
+    }
+
+    println!("Repairing archive: {:?}", input_path);
+    println!("Output: {:?}", output_file);
+
+    let archive_data = std::fs::read(input_path)
+        .context("Failed to read CBZ file")?;
+    let recovered = archive::recover(&archive_data)?;
+    if recovered.image_count() == 0 {
+        anyhow::bail!("No complete page could be recovered");
+    }
+
+    // Entries keep their archive order and paths; the archive comment is lost with the central directory
+    let files = recovered.entries.into_iter().map(|entry| (entry.path, entry.data)).collect();
+    let cbz_data = archive::create_cbz(files, archive_password.as_deref(), None)
+        .context("Failed to create CBZ archive")?;
+    std::fs::write(&output_file, cbz_data)
+        .context("Failed to write CBZ file")?;
+
+    println!("✓ Successfully created: {:?}", output_file);
+    Ok(())
+}
+
+fn repack_archive(input_path: &PathBuf, output_path: Option<PathBuf>, password: Option<String>, archive_password: Option<String>) -> Result<()> {
+    if !input_path.is_file() {
+        anyhow::bail!("Input CBZ/CBR file not found: {:?}", input_path);
+    }
+
+    // Determine output path (never overwrite the input)
+    let output_file = match output_path {
+        Some(p) => p,
+        None => {
+            let stem = input_path.file_stem().context("Invalid input filename")?;
+            let is_cbz = input_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cbz"));
+            let suffix = if is_cbz { "_repacked" } else { "" };
+            input_path.with_file_name(format!("{}{}.cbz", stem.to_string_lossy(), suffix))
+        }
+    };
+    if output_file == *input_path {
+        anyhow::bail!("Output would overwrite the input archive, choose another path with --output");
+    }
+
+    println!("Re-packing archive: {:?}", input_path);
+    println!("Output: {:?}", output_file);
+
+    let archive_data = std::fs::read(input_path)
+        .context("Failed to read CBZ/CBR file")?;
+
+    // Every entry is kept under its original path: chapter folders, ComicInfo.xml and other files
+    let entries = with_password_prompt(password, |password| {
+        archive::extract_entries(&archive_data, password, PageOrder::Archive)
+            .context("Failed to read archive")
+    })?;
+
+    let images = entries.iter().filter(|entry| entry.kind == EntryKind::Image).count();
+    let metadata = entries.iter().filter(|entry| entry.kind == EntryKind::Metadata).count();
+    println!("Read {} images, {} metadata files, {} other files", images, metadata, entries.len() - images - metadata);
+
+    let files = entries.into_iter().map(|entry| (entry.path, entry.data)).collect();
+    let cbz_data = archive::create_cbz(files, archive_password.as_deref(), archive::archive_comment(&archive_data))
+        .context("Failed to create CBZ archive")?;
+    std::fs::write(&output_file, cbz_data)
+        .context("Failed to write CBZ file")?;
+
+    println!("✓ Successfully created: {:?}", output_file);
+    Ok(())
+}
+
+fn verify_archives(inputs: &[PathBuf], json: bool, strict: bool, mut password: Option<String>) -> Result<()> {
+    let mut failed = 0;
+
+    for input_path in inputs {
+        // An archive that cannot be opened at all is reported like any other failure
+        let report = with_shared_password(&mut password, |password| archive::verify(input_path, password))
+            .unwrap_or_else(|e| {
+                let format = if input_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cbr")) { "cbr" } else { "cbz" };
+                let mut verifier = ArchiveVerifier::new(format);
+                verifier.add_issue(IssueSeverity::Error, IssueKind::ReadError, None, format!("{:#}", e));
+                verifier.finish()
+            });
+
+        let passed = report.is_valid() && !(strict && report.warnings() > 0);
+        if !passed {
+            failed += 1;
+        }
+
+        if json {
+            println!("{}", report.to_json(&input_path.to_string_lossy()));
+            continue;
+        }
+
+        if report.issues.is_empty() {
+            println!("✓ {}: {} pages, {} entries, no issues", input_path.display(), report.pages, report.entries);
+        } else {
+            println!("{} {}: {} pages, {} entries, {} error(s), {} warning(s)",
+                     if passed { "✓" } else { "✗" }, input_path.display(),
+                     report.pages, report.entries, report.errors(), report.warnings());
+        }
+        for issue in &report.issues {
+            let location = issue.entry.as_deref().map(|entry| format!("{}: ", entry)).unwrap_or_default();
+            println!("    {:<7} {:<22} {}{}", issue.severity.as_str(), issue.kind.code(), location, issue.message);
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} archive(s) failed verification", failed, inputs.len());
+    }
     Ok(())
 }
 
-fn smoke_render(input_path: &PathBuf, page_num: u32, dpi: u32, output_path: &PathBuf, max_white_ratio: f64, min_bbox_coverage: f64) -> Result<()> {
+fn smoke_render(input_path: &PathBuf, page_num: u32, dpi: u32, output_path: &PathBuf, max_white_ratio: f64, min_bbox_coverage: f64, password: Option<String>) -> Result<()> {
     use pdfium_render::prelude::*;
 
     // Validate input
@@ -313,14 +1528,14 @@ fn smoke_render(input_path: &PathBuf, page_num: u32, dpi: u32, output_path: &Pat
     println!();
 
     // Read PDF
-    let pdf_data = std::fs::read(&input_path)
+    let pdf_data = std::fs::read(input_path)
         .context("Failed to read PDF file")?;
 
     let pdfium = bind_pdfium()
         .context("Failed to initialize Pdfium")?;
-    let document = pdfium
-        .load_pdf_from_byte_vec(pdf_data, None)
-        .context("Failed to load PDF")?;
+    let document = with_password_prompt(password, |password| {
+        load_pdf_document(&pdfium, pdf_data.clone(), password)
+    })?;
 
     let page_count = document.pages().len() as u32;
     println!("Total pages: {}", page_count);
@@ -466,7 +1681,7 @@ fn smoke_render(input_path: &PathBuf, page_num: u32, dpi: u32, output_path: &Pat
 
     // Display content bounding box and compute bbox_coverage
     println!();
-    let mut bbox_coverage = 0.0_f64;
+    let bbox_coverage;
     if let Some((min_x, min_y, max_x, max_y)) = content_bbox {
         let content_w = max_x - min_x;
         let content_h = max_y - min_y;
@@ -626,7 +1841,7 @@ fn smoke_render(input_path: &PathBuf, page_num: u32, dpi: u32, output_path: &Pat
     }
 }
 
-fn run_benchmark(input_path: &PathBuf, dpi: u32, quality: u8, max_pages: u32) -> Result<()> {
+fn run_benchmark(input_path: &PathBuf, dpi: u32, quality: u8, max_pages: u32, password: Option<String>) -> Result<()> {
     // Validate input
     if !input_path.exists() {
         anyhow::bail!("Input PDF file not found: {:?}", input_path);
@@ -644,15 +1859,17 @@ fn run_benchmark(input_path: &PathBuf, dpi: u32, quality: u8, max_pages: u32) ->
     println!();
 
     // Read PDF
-    let pdf_data = std::fs::read(&input_path)
+    let pdf_data = std::fs::read(input_path)
         .context("Failed to read PDF file")?;
 
     println!("Step 1: Converting PDF to images...");
     let step1_start = Instant::now();
 
     // Convert to JPEG images (lossy mode - the common case)
-    let images = convert_pdf_to_images_parallel(&pdf_data, dpi, quality, max_pages)
-        .context("Failed to convert PDF to images")?;
+    let images = with_password_prompt(password, |password| {
+        convert_pdf_to_images_parallel(&pdf_data, dpi, quality, max_pages, password, Transparency::default())
+            .context("Failed to convert PDF to images")
+    })?;
 
     let step1_time = step1_start.elapsed();
     println!("  ✓ Converted {} pages in {:.2}s", images.len(), step1_time.as_secs_f64());
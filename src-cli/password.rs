@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use pdf_conversion_lib::{archive_password_error, password_error, ArchivePasswordError, PdfPasswordError};
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Number of times the user is asked for a password before giving up
+const MAX_PASSWORD_PROMPTS: u32 = 3;
+
+/// Run an operation on an encrypted PDF or archive, asking for the password on the terminal when needed
+/// `password` (from --password) is tried first; without a terminal the password error is returned as is.
+pub fn with_password_prompt<T, F>(password: Option<String>, operation: F) -> Result<T>
+where
+    F: FnMut(Option<&str>) -> Result<T>,
+{
+    let mut password = password;
+    with_shared_password(&mut password, operation)
+}
+
+/// Like `with_password_prompt`, but keeps the password that worked in `password`
+/// Used when several inputs are opened in a row, so a password typed once is tried on the next input first.
+pub fn with_shared_password<T, F>(password: &mut Option<String>, mut operation: F) -> Result<T>
+where
+    F: FnMut(Option<&str>) -> Result<T>,
+{
+    let mut prompts = 0;
+
+    loop {
+        let error = match operation(password.as_deref()) {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+
+        let (prompt, incorrect) = if let Some(kind) = password_error(&error) {
+            ("PDF password: ", kind == PdfPasswordError::Incorrect)
+        } else if let Some(kind) = archive_password_error(&error) {
+            ("Archive password: ", kind == ArchivePasswordError::Incorrect)
+        } else {
+            return Err(error);
+        };
+        if prompts >= MAX_PASSWORD_PROMPTS || !std::io::stdin().is_terminal() {
+            if incorrect {
+                return Err(error);
+            }
+            return Err(error.context("Use --password to open encrypted files"));
+        }
+
+        if incorrect {
+            eprintln!("Incorrect password, please try again.");
+        }
+        *password = Some(prompt_password(prompt)?);
+        prompts += 1;
+    }
+}
+
+/// Read a password from the terminal without echoing it
+fn prompt_password(prompt: &str) -> Result<String> {
+    eprint!("{}", prompt);
+    std::io::stderr().flush().ok();
+
+    set_terminal_echo(false);
+    let mut line = String::new();
+    let result = std::io::stdin().lock().read_line(&mut line);
+    set_terminal_echo(true);
+    eprintln!();
+
+    result.context("Failed to read password")?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Toggle terminal echo (best effort, via stty on Unix)
+#[cfg(unix)]
+fn set_terminal_echo(enabled: bool) {
+    let _ = std::process::Command::new("stty")
+        .arg(if enabled { "echo" } else { "-echo" })
+        .stdin(std::process::Stdio::inherit())
+        .status();
+}
+
+#[cfg(not(unix))]
+fn set_terminal_echo(_enabled: bool) {}
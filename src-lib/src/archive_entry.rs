@@ -0,0 +1,72 @@
+use crate::comic_info::is_comic_info_file;
+use crate::image_format::PageImageFormat;
+
+/// Extensions of book metadata files kept alongside the pages (besides ComicInfo.xml)
+const METADATA_EXTENSIONS: &[&str] = &["opf", "nfo", "json"];
+
+/// What an archive entry holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    /// A page image
+    Image,
+    /// Book metadata (ComicInfo.xml, OPF, ...)
+    Metadata,
+    /// Anything else (text files, thumbnails databases, ...)
+    Other,
+}
+
+/// A file read from a comic archive, with its path relative to the archive root
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Path inside the archive, with `/` separators
+    pub path: String,
+    pub kind: EntryKind,
+    pub data: Vec<u8>,
+}
+
+impl ArchiveEntry {
+    pub fn new(path: String, data: Vec<u8>) -> ArchiveEntry {
+        let path = path.replace('\\', "/");
+        ArchiveEntry { kind: classify_entry(&path, &data), path, data }
+    }
+
+    /// Folder holding the entry (e.g. a chapter), None at the archive root
+    pub fn folder(&self) -> Option<&str> {
+        entry_folder(&self.path)
+    }
+}
+
+/// Classify an archive entry by its path
+pub fn entry_kind(path: &str) -> EntryKind {
+    if is_comic_info_file(path) {
+        return EntryKind::Metadata;
+    }
+    if PageImageFormat::from_extension(path).is_some() {
+        return EntryKind::Image;
+    }
+    let extension = path
+        .rsplit(['/', '\\'])
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some(extension) if METADATA_EXTENSIONS.contains(&extension) => EntryKind::Metadata,
+        _ => EntryKind::Other,
+    }
+}
+
+/// Classify an archive entry by its content, with its path as a hint
+/// Recognized image data is a page whatever its extension (e.g. `page01` or a mislabelled `.dat`).
+pub fn classify_entry(path: &str, data: &[u8]) -> EntryKind {
+    if !is_comic_info_file(path) && PageImageFormat::sniff(data).is_some() {
+        return EntryKind::Image;
+    }
+    entry_kind(path)
+}
+
+/// Folder part of an entry path, None at the archive root
+pub fn entry_folder(path: &str) -> Option<&str> {
+    path.rsplit_once(['/', '\\'])
+        .map(|(folder, _)| folder)
+        .filter(|folder| !folder.is_empty())
+}
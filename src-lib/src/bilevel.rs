@@ -0,0 +1,431 @@
+use anyhow::{Context, Result};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::{DynamicImage, GrayImage};
+use std::io::Write;
+
+/// Black-and-white image, one bit per pixel
+/// Rows are packed most significant bit first and padded to whole bytes; a set bit is white,
+/// as in 1-bit DeviceGray and grayscale PNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BilevelImage {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl BilevelImage {
+    /// Image from packed rows; None if `data` does not hold `height` rows of `width` pixels
+    pub fn from_packed(width: u32, height: u32, data: Vec<u8>) -> Option<BilevelImage> {
+        if width == 0 || height == 0 || data.len() != row_length(width) * height as usize {
+            return None;
+        }
+        let mut image = BilevelImage { width, height, data };
+        image.clear_padding();
+        Some(image)
+    }
+
+    /// All-black image
+    fn black(width: u32, height: u32) -> BilevelImage {
+        BilevelImage { width, height, data: vec![0; row_length(width) * height as usize] }
+    }
+
+    /// The image as bilevel, if it only has pure black and pure white (opaque) pixels
+    pub fn from_image(image: &DynamicImage) -> Option<BilevelImage> {
+        let is_bilevel = |value: u8| value == 0 || value == 255;
+        let bilevel_pixels = match image {
+            DynamicImage::ImageLuma8(gray) => gray.pixels().all(|p| is_bilevel(p[0])),
+            DynamicImage::ImageRgb8(rgb) => rgb.pixels().all(|p| is_bilevel(p[0]) && p[0] == p[1] && p[1] == p[2]),
+            DynamicImage::ImageRgba8(rgba) => rgba.pixels().all(|p| is_bilevel(p[0]) && p[0] == p[1] && p[1] == p[2] && p[3] == 255),
+            _ => false,
+        };
+        if !bilevel_pixels {
+            return None;
+        }
+
+        let gray = image.to_luma8();
+        let mut bilevel = BilevelImage::black(gray.width(), gray.height());
+        for (x, y, pixel) in gray.enumerate_pixels() {
+            if pixel[0] == 255 {
+                bilevel.set_white(x, y);
+            }
+        }
+        Some(bilevel)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Packed rows (see `BilevelImage`)
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn set_white(&mut self, x: u32, y: u32) {
+        let index = y as usize * row_length(self.width) + x as usize / 8;
+        self.data[index] |= 0x80 >> (x % 8);
+    }
+
+    fn is_white(&self, x: u32, y: u32) -> bool {
+        let index = y as usize * row_length(self.width) + x as usize / 8;
+        self.data[index] & (0x80 >> (x % 8)) != 0
+    }
+
+    /// Swap black and white
+    pub fn invert(&mut self) {
+        for byte in &mut self.data {
+            *byte = !*byte;
+        }
+        self.clear_padding();
+    }
+
+    /// Padding bits at the end of each row are kept at zero so equal images compare equal
+    fn clear_padding(&mut self) {
+        let padding = (8 - self.width % 8) % 8;
+        if padding == 0 {
+            return;
+        }
+        let mask = 0xFFu8 << padding;
+        let row_length = row_length(self.width);
+        for row in self.data.chunks_mut(row_length) {
+            row[row_length - 1] &= mask;
+        }
+    }
+
+    /// 8-bit grayscale copy (0 or 255)
+    pub fn to_luma(&self) -> GrayImage {
+        GrayImage::from_fn(self.width, self.height, |x, y| image::Luma([if self.is_white(x, y) { 255 } else { 0 }]))
+    }
+
+    /// 1-bit grayscale PNG
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, self.width, self.height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::One);
+        let mut writer = encoder.write_header().context("PNG encoding failed")?;
+        writer.write_image_data(&self.data).context("PNG encoding failed")?;
+        writer.finish().context("PNG encoding failed")?;
+        Ok(data)
+    }
+
+    /// Compressed data for a PDF image XObject: CCITT Group 4, or Flate if Group 4 encoding fails
+    pub fn to_stream(&self) -> Result<BilevelStream> {
+        if let Some(data) = self.to_ccitt_g4() {
+            return Ok(BilevelStream {
+                width: self.width,
+                height: self.height,
+                filter: BilevelFilter::Ccitt(CcittParams::group4(self.width, self.height)),
+                data,
+            });
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&self.data).context("Failed to compress image")?;
+        Ok(BilevelStream {
+            width: self.width,
+            height: self.height,
+            filter: BilevelFilter::Flate,
+            data: encoder.finish().context("Failed to compress image")?,
+        })
+    }
+
+    /// CCITT Group 4 encoding, checked by decoding it again
+    /// None if the image is too wide or the encoding does not round-trip.
+    fn to_ccitt_g4(&self) -> Option<Vec<u8>> {
+        let width = u16::try_from(self.width).ok()?;
+        let mut encoder = fax::encoder::Encoder::new(fax::VecWriter::new());
+        for y in 0..self.height {
+            let pels = (0..self.width).map(|x| if self.is_white(x, y) { fax::Color::White } else { fax::Color::Black });
+            encoder.encode_line(pels, width).ok()?;
+        }
+        let data = encoder.finish().ok()?.finish();
+
+        match decode_ccitt(&data, &CcittParams::group4(self.width, self.height)) {
+            Ok(decoded) if decoded == *self => Some(data),
+            _ => {
+                eprintln!("[BILEVEL] CCITT Group 4 encoding did not round-trip, using Flate");
+                None
+            }
+        }
+    }
+}
+
+/// Bytes per packed row
+fn row_length(width: u32) -> usize {
+    width.div_ceil(8) as usize
+}
+
+/// CCITTFaxDecode parameters (the filter's DecodeParms)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CcittParams {
+    /// Negative: Group 4; 0: Group 3 one-dimensional; positive: Group 3 two-dimensional
+    pub k: i64,
+    pub columns: u32,
+    pub rows: u32,
+    pub black_is_1: bool,
+    pub encoded_byte_align: bool,
+    pub end_of_line: bool,
+    pub end_of_block: bool,
+}
+
+impl CcittParams {
+    /// Group 4 with PDF's default parameters
+    pub fn group4(columns: u32, rows: u32) -> CcittParams {
+        CcittParams {
+            k: -1,
+            columns,
+            rows,
+            black_is_1: false,
+            encoded_byte_align: false,
+            end_of_line: false,
+            end_of_block: true,
+        }
+    }
+
+    /// DecodeParms dictionary, leaving out entries at their default value
+    pub fn decode_parms(&self) -> String {
+        let mut dict = format!("<< /K {} /Columns {} /Rows {}", self.k, self.columns, self.rows);
+        if self.black_is_1 {
+            dict.push_str(" /BlackIs1 true");
+        }
+        if self.encoded_byte_align {
+            dict.push_str(" /EncodedByteAlign true");
+        }
+        if self.end_of_line {
+            dict.push_str(" /EndOfLine true");
+        }
+        if !self.end_of_block {
+            dict.push_str(" /EndOfBlock false");
+        }
+        dict.push_str(" >>");
+        dict
+    }
+}
+
+/// How a `BilevelStream` is compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BilevelFilter {
+    Ccitt(CcittParams),
+    /// JBIG2 without global segments
+    Jbig2,
+    Flate,
+}
+
+/// 1-bit image data as stored in a PDF image XObject
+#[derive(Debug, Clone)]
+pub struct BilevelStream {
+    pub width: u32,
+    pub height: u32,
+    pub filter: BilevelFilter,
+    pub data: Vec<u8>,
+}
+
+/// Collects decoded pixels into a `BilevelImage`, ignoring anything past its size
+struct BilevelSink {
+    image: BilevelImage,
+    x: u32,
+    y: u32,
+}
+
+impl BilevelSink {
+    fn new(width: u32, height: u32) -> BilevelSink {
+        BilevelSink { image: BilevelImage::black(width, height), x: 0, y: 0 }
+    }
+
+    fn push(&mut self, white: bool, count: u32) {
+        if white && self.y < self.image.height {
+            let end = self.x.saturating_add(count).min(self.image.width);
+            for x in self.x..end {
+                self.image.set_white(x, self.y);
+            }
+        }
+        self.x = self.x.saturating_add(count);
+    }
+
+    fn end_row(&mut self) {
+        self.x = 0;
+        self.y += 1;
+    }
+}
+
+impl hayro_ccitt::Decoder for BilevelSink {
+    fn push_pixels(&mut self, white: bool, count: u32) {
+        self.push(white, count);
+    }
+
+    fn next_line(&mut self) {
+        self.end_row();
+    }
+}
+
+impl hayro_jbig2::Decoder for BilevelSink {
+    fn push_pixel(&mut self, black: bool) {
+        self.push(!black, 1);
+    }
+
+    fn push_pixel_chunk(&mut self, black: bool, chunk_count: u32) {
+        self.push(!black, chunk_count.saturating_mul(8));
+    }
+
+    fn next_line(&mut self) {
+        self.end_row();
+    }
+}
+
+/// Decode CCITTFaxDecode data as displayed with a DeviceGray colour space
+/// `params.rows` must be set (e.g. to the image height).
+pub fn decode_ccitt(data: &[u8], params: &CcittParams) -> Result<BilevelImage> {
+    if params.columns == 0 || params.rows == 0 {
+        anyhow::bail!("CCITT image has no size");
+    }
+
+    let settings = hayro_ccitt::DecodeSettings {
+        columns: params.columns,
+        rows: params.rows,
+        end_of_block: params.end_of_block,
+        end_of_line: params.end_of_line,
+        rows_are_byte_aligned: params.encoded_byte_align,
+        encoding: match params.k {
+            k if k < 0 => hayro_ccitt::EncodingMode::Group4,
+            0 => hayro_ccitt::EncodingMode::Group3_1D,
+            k => hayro_ccitt::EncodingMode::Group3_2D { k: k.try_into().unwrap_or(u32::MAX) },
+        },
+        invert_black: false,
+    };
+    let mut sink = BilevelSink::new(params.columns, params.rows);
+    let mut context = hayro_ccitt::DecoderContext::new(settings);
+    let result = hayro_ccitt::decode(data, &mut sink, &mut context);
+    // Damaged streams are kept when every row was decoded before the error
+    if let Err(e) = result {
+        if sink.y < params.rows {
+            anyhow::bail!("Invalid CCITT data: {}", e);
+        }
+    }
+
+    let mut image = sink.image;
+    // With BlackIs1, 1 bits (white in DeviceGray) are black in the fax data
+    if params.black_is_1 {
+        image.invert();
+    }
+    Ok(image)
+}
+
+/// Decode JBIG2Decode data (an embedded stream, with its optional JBIG2Globals)
+pub fn decode_jbig2(data: &[u8], globals: Option<&[u8]>) -> Result<BilevelImage> {
+    let image = hayro_jbig2::Image::new_embedded(data, globals)
+        .map_err(|e| anyhow::anyhow!("Invalid JBIG2 data: {}", e))?;
+    if image.width() == 0 || image.height() == 0 {
+        anyhow::bail!("JBIG2 image has no size");
+    }
+
+    let mut sink = BilevelSink::new(image.width(), image.height());
+    image.decode(&mut sink).map_err(|e| anyhow::anyhow!("Invalid JBIG2 data: {}", e))?;
+    Ok(sink.image)
+}
+
+/// Find the CCITTFaxDecode parameters under which `data` decodes to `expected`
+/// For sources whose parameters are not available (PDFium does not expose DecodeParms).
+pub fn infer_ccitt_params(data: &[u8], expected: &BilevelImage) -> Option<CcittParams> {
+    let mut inverted = expected.clone();
+    inverted.invert();
+
+    for k in [-1, 0, 1] {
+        for end_of_line in [false, true] {
+            // Group 4 has no end-of-line codes
+            if k < 0 && end_of_line {
+                continue;
+            }
+            for encoded_byte_align in [false, true] {
+                let params = CcittParams {
+                    k,
+                    end_of_line,
+                    encoded_byte_align,
+                    ..CcittParams::group4(expected.width, expected.height)
+                };
+                let Ok(decoded) = decode_ccitt(data, &params) else {
+                    continue;
+                };
+                if decoded == *expected {
+                    return Some(params);
+                }
+                if decoded == inverted {
+                    return Some(CcittParams { black_is_1: true, ..params });
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 13 x 6 checker pattern: width not a multiple of 8, so rows have padding bits
+    fn pattern() -> BilevelImage {
+        let gray = GrayImage::from_fn(13, 6, |x, y| image::Luma([if (x / 2 + y) % 2 == 0 { 255 } else { 0 }]));
+        BilevelImage::from_image(&DynamicImage::ImageLuma8(gray)).unwrap()
+    }
+
+    fn inverted(image: &BilevelImage) -> BilevelImage {
+        let mut image = image.clone();
+        image.invert();
+        image
+    }
+
+    fn group4(image: &BilevelImage) -> Vec<u8> {
+        let stream = image.to_stream().unwrap();
+        assert!(matches!(stream.filter, BilevelFilter::Ccitt(params) if params == CcittParams::group4(13, 6)));
+        stream.data
+    }
+
+    #[test]
+    fn invert_keeps_padding_clear() {
+        let image = pattern();
+        let once = inverted(&image);
+        assert_ne!(once, image);
+        assert!(once.data().chunks(2).all(|row| row[1] & 0b0000_0111 == 0));
+        assert_eq!(inverted(&once), image);
+        assert_eq!(once.to_luma().get_pixel(0, 0)[0], 0);
+    }
+
+    #[test]
+    fn only_pure_black_and_white_is_bilevel() {
+        let gray = GrayImage::from_fn(4, 4, |x, _| image::Luma([if x == 0 { 128 } else { 255 }]));
+        assert_eq!(BilevelImage::from_image(&DynamicImage::ImageLuma8(gray)), None);
+        assert_eq!(BilevelImage::from_packed(13, 6, vec![0; 11]), None);
+        assert_eq!(BilevelImage::from_image(&DynamicImage::ImageLuma8(pattern().to_luma())), Some(pattern()));
+    }
+
+    #[test]
+    fn group4_round_trip() {
+        let image = pattern();
+        assert_eq!(decode_ccitt(&group4(&image), &CcittParams::group4(13, 6)).unwrap(), image);
+    }
+
+    #[test]
+    fn black_is_1_inverts() {
+        let image = pattern();
+        let params = CcittParams { black_is_1: true, ..CcittParams::group4(13, 6) };
+        assert_eq!(decode_ccitt(&group4(&image), &params).unwrap(), inverted(&image));
+        assert!(params.decode_parms().contains("/BlackIs1 true"));
+        assert!(!CcittParams::group4(13, 6).decode_parms().contains("BlackIs1"));
+    }
+
+    #[test]
+    fn infers_black_is_1() {
+        let image = pattern();
+        let data = group4(&image);
+        assert_eq!(infer_ccitt_params(&data, &image), Some(CcittParams::group4(13, 6)));
+        assert_eq!(
+            infer_ccitt_params(&data, &inverted(&image)),
+            Some(CcittParams { black_is_1: true, ..CcittParams::group4(13, 6) })
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::Result;
+use std::fmt;
+use std::io::{Cursor, Read, Seek};
+use zip::read::ZipFile;
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{AesMode, CompressionMethod, ZipArchive};
+
+use crate::comic_book_info::comic_book_info_from_comment;
+use crate::archive_entry::{entry_kind, EntryKind};
+use crate::comic_info::find_comic_info;
+use crate::image_format::{PageImageFormat, SNIFF_LENGTH};
+use crate::limits::ArchiveBudget;
+use crate::metadata::DocumentMetadata;
+
+/// Error raised when a CBZ has encrypted entries and the password is missing or wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchivePasswordError {
+    /// The archive is encrypted and no password was given
+    Required,
+    /// The given password does not decrypt the archive
+    Incorrect,
+}
+
+impl ArchivePasswordError {
+    /// Stable code for frontends (e.g. Tauri command errors)
+    pub fn code(&self) -> &'static str {
+        match self {
+            ArchivePasswordError::Required => "ARCHIVE_PASSWORD_REQUIRED",
+            ArchivePasswordError::Incorrect => "ARCHIVE_PASSWORD_INCORRECT",
+        }
+    }
+}
+
+impl fmt::Display for ArchivePasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchivePasswordError::Required => write!(f, "Archive is password-protected, a password is required"),
+            ArchivePasswordError::Incorrect => write!(f, "Incorrect password for archive"),
+        }
+    }
+}
+
+impl std::error::Error for ArchivePasswordError {}
+
+/// ZIP entry options for CBZ pages
+/// Uses STORED (no compression) because JPEG images are already optimally compressed.
+/// With a password, entries are AES-256 encrypted (WinZip AE-2, readable by 7-Zip and WinZip).
+pub fn cbz_file_options(password: Option<&str>) -> FileOptions<'_, ()> {
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    match password.filter(|p| !p.is_empty()) {
+        Some(password) => options.with_aes_encryption(AesMode::Aes256, password),
+        None => options,
+    }
+}
+
+/// Open a CBZ entry by index, decrypting it when a password is given
+/// Encryption failures are reported as `ArchivePasswordError`.
+pub fn open_cbz_entry<'a, R: Read + Seek>(
+    archive: &'a mut ZipArchive<R>,
+    index: usize,
+    password: Option<&str>,
+) -> Result<ZipFile<'a>> {
+    let password = password.filter(|p| !p.is_empty());
+    let entry = match password {
+        Some(password) => archive.by_index_decrypt(index, password.as_bytes()),
+        None => archive.by_index(index),
+    };
+
+    match entry {
+        Ok(file) => Ok(file),
+        Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)) => Err(ArchivePasswordError::Required.into()),
+        Err(ZipError::InvalidPassword) => Err(ArchivePasswordError::Incorrect.into()),
+        Err(e) => Err(anyhow::Error::new(e).context(format!("Failed to read file at index {}", index))),
+    }
+}
+
+/// Read a CBZ entry in full, within the size limits tracked by `budget`
+pub fn read_cbz_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    password: Option<&str>,
+    budget: &mut ArchiveBudget,
+) -> Result<Vec<u8>> {
+    let file = open_cbz_entry(archive, index, password)?;
+    let name = file.name().to_string();
+    let compressed_size = file.compressed_size();
+    budget.read_entry(&name, compressed_size, file)
+}
+
+/// Recognize the image format of a CBZ entry from its first bytes, without reading all of it
+pub fn sniff_cbz_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, index: usize, password: Option<&str>) -> Option<PageImageFormat> {
+    sniff_entry(open_cbz_entry(archive, index, password).ok()?)
+}
+
+fn sniff_entry(entry: ZipFile) -> Option<PageImageFormat> {
+    let mut header = Vec::with_capacity(SNIFF_LENGTH);
+    entry.take(SNIFF_LENGTH as u64).read_to_end(&mut header).ok()?;
+    PageImageFormat::sniff(&header)
+}
+
+/// Entry index and path of the page images of a CBZ, in archive order
+/// Images are found by extension; entries with an unknown extension are checked by their content.
+/// Encrypted entries with an unknown extension are listed when the password is missing or wrong,
+/// so that reading the pages asks for the password instead of silently skipping them.
+pub fn list_cbz_pages<R: Read + Seek>(archive: &mut ZipArchive<R>, password: Option<&str>) -> Vec<(usize, String)> {
+    let mut pages = Vec::new();
+    for index in 0..archive.len() {
+        let name = match archive.name_for_index(index) {
+            Some(name) if !name.ends_with('/') => name.to_string(),
+            _ => continue,
+        };
+        let is_page = match entry_kind(&name) {
+            EntryKind::Image => true,
+            EntryKind::Other => match open_cbz_entry(archive, index, password) {
+                Ok(entry) => sniff_entry(entry).is_some(),
+                Err(e) => archive_password_error(&e).is_some(),
+            },
+            EntryKind::Metadata => false,
+        };
+        if is_page {
+            pages.push((index, name));
+        }
+    }
+    pages
+}
+
+/// Find an `ArchivePasswordError` in an error chain
+pub fn archive_password_error(error: &anyhow::Error) -> Option<ArchivePasswordError> {
+    error.chain().find_map(|cause| cause.downcast_ref::<ArchivePasswordError>().copied())
+}
+
+/// Read book metadata from a CBZ: ComicInfo.xml first, completed by ComicBookInfo from the ZIP comment
+/// Returns None when the archive has neither (or is not a ZIP, e.g. CBR).
+pub fn read_cbz_metadata(archive_data: &[u8], password: Option<&str>) -> Option<DocumentMetadata> {
+    let mut archive = ZipArchive::new(Cursor::new(archive_data)).ok()?;
+    read_archive_metadata(&mut archive, password)
+}
+
+/// Read book metadata from an open CBZ archive (see `read_cbz_metadata`)
+pub fn read_archive_metadata<R: Read + Seek>(archive: &mut ZipArchive<R>, password: Option<&str>) -> Option<DocumentMetadata> {
+    let comic_info = find_comic_info(archive, password).ok().flatten();
+    let comic_book_info = comic_book_info_from_comment(archive.comment());
+
+    match (comic_info, comic_book_info) {
+        (Some(info), Some(book_info)) => Some(info.to_metadata().merged_with(book_info.to_metadata())),
+        (Some(info), None) => Some(info.to_metadata()),
+        (None, Some(book_info)) => Some(book_info.to_metadata()),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageFormat};
+    use std::io::Write;
+    use zip::ZipWriter;
+
+    fn png() -> Vec<u8> {
+        let mut data = Vec::new();
+        DynamicImage::new_rgb8(4, 4).write_to(&mut Cursor::new(&mut data), ImageFormat::Png).unwrap();
+        data
+    }
+
+    /// AES-encrypted CBZ with a page without extension, a named page and a text file
+    fn encrypted_cbz() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in [("001", png()), ("002.png", png()), ("notes.dat", b"not a page".to_vec())] {
+            zip.start_file(name, cbz_file_options(Some("secret"))).unwrap();
+            zip.write_all(&data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn page_names(data: &[u8], password: Option<&str>) -> Vec<String> {
+        let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        list_cbz_pages(&mut archive, password).into_iter().map(|(_, name)| name).collect()
+    }
+
+    #[test]
+    fn lists_pages_by_content_with_the_password() {
+        assert_eq!(page_names(&encrypted_cbz(), Some("secret")), ["001", "002.png"]);
+    }
+
+    #[test]
+    fn lists_locked_entries_without_the_password() {
+        let data = encrypted_cbz();
+        // Unknown entries cannot be checked: they are kept, and reading them asks for the password
+        assert_eq!(page_names(&data, None), ["001", "002.png", "notes.dat"]);
+        assert_eq!(page_names(&data, Some("wrong")), ["001", "002.png", "notes.dat"]);
+
+        let mut archive = ZipArchive::new(Cursor::new(&data)).unwrap();
+        let error = read_cbz_entry(&mut archive, 0, None, &mut ArchiveBudget::new()).unwrap_err();
+        assert_eq!(archive_password_error(&error), Some(ArchivePasswordError::Required));
+        let error = read_cbz_entry(&mut archive, 0, Some("wrong"), &mut ArchiveBudget::new()).unwrap_err();
+        assert_eq!(archive_password_error(&error), Some(ArchivePasswordError::Incorrect));
+    }
+}
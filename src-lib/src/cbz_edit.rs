@@ -0,0 +1,428 @@
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Write};
+use std::path::Path;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::archive_entry::entry_folder;
+use crate::cbz::{archive_password_error, cbz_file_options, list_cbz_pages, read_cbz_entry};
+use crate::comic_info::{is_comic_info_file, read_comic_info_entry, ComicInfo, ComicPageInfo, COMIC_INFO_FILENAME};
+use crate::image_format::PageImageFormat;
+use crate::jpeg_transform::rotate_jpeg_lossless;
+use crate::limits::{check_image_header, decode_image, ArchiveBudget};
+use crate::page_order::{sort_pages, PageOrder};
+use crate::pdf_options::DEFAULT_JPEG_QUALITY;
+
+/// Clockwise rotation of a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageRotation {
+    /// 90° clockwise
+    Clockwise,
+    /// 180°
+    UpsideDown,
+    /// 90° counter-clockwise
+    CounterClockwise,
+}
+
+impl PageRotation {
+    /// Rotation from clockwise degrees: 90, 180 or 270 (-90)
+    pub fn from_degrees(degrees: i32) -> Result<PageRotation> {
+        match degrees.rem_euclid(360) {
+            90 => Ok(PageRotation::Clockwise),
+            180 => Ok(PageRotation::UpsideDown),
+            270 => Ok(PageRotation::CounterClockwise),
+            _ => anyhow::bail!("Invalid rotation {}°: expected 90, 180 or 270", degrees),
+        }
+    }
+
+    fn apply(self, image: DynamicImage) -> DynamicImage {
+        match self {
+            PageRotation::Clockwise => image.rotate90(),
+            PageRotation::UpsideDown => image.rotate180(),
+            PageRotation::CounterClockwise => image.rotate270(),
+        }
+    }
+}
+
+/// One change to the pages of a CBZ
+/// Page indices count from 0 and refer to the pages as they are when the edit is applied.
+#[derive(Debug, Clone)]
+pub enum PageEdit {
+    /// Move page `from` so that it becomes page `to`
+    Move { from: usize, to: usize },
+    Delete(usize),
+    /// Insert images (name, data) before page `at`; the page count appends them
+    Insert { at: usize, pages: Vec<(String, Vec<u8>)> },
+    Rotate { page: usize, rotation: PageRotation },
+    /// Replace the image of a page, keeping its position and ComicInfo page type
+    Replace { page: usize, image: (String, Vec<u8>) },
+}
+
+/// Outcome of a CBZ edit
+#[derive(Debug, Clone, Default)]
+pub struct EditReport {
+    pub pages: usize,
+    /// Pages marked Deleted in ComicInfo.xml, dropped from the new archive
+    pub dropped_pages: usize,
+    /// JPEG pages rotated without re-encoding
+    pub lossless_rotations: usize,
+    /// Pages decoded and re-encoded to be rotated
+    pub reencoded_rotations: usize,
+    pub output_size: u64,
+}
+
+/// A page of the edited book
+struct EditedPage {
+    /// Index of the page in the reading order of the archive as opened, from 0 (None for inserted pages)
+    /// This is the order after ComicInfo.xml page hints, not the entry order of the archive.
+    original: Option<usize>,
+    /// Folder (chapter) of the page in the archive
+    folder: Option<String>,
+    /// Name the image came with, used when its format cannot be recognized from its content
+    name: String,
+    data: Vec<u8>,
+    /// ComicInfo.xml page entry of an original page
+    info: Option<ComicPageInfo>,
+}
+
+/// Edits the pages of a CBZ in memory and writes a new archive
+/// Untouched pages are copied as they are; pages are renamed in reading order (keeping their folder)
+/// and the ComicInfo.xml page list is rewritten to match. Other files and the archive comment are kept.
+pub struct CbzEditor {
+    pages: Vec<EditedPage>,
+    /// ComicInfo.xml path and content
+    comic_info: Option<(String, ComicInfo)>,
+    other_files: Vec<(String, Vec<u8>)>,
+    comment: Vec<u8>,
+    /// Quality of rotated JPEG pages that cannot be rotated losslessly
+    pub jpeg_quality: u8,
+    report: EditReport,
+}
+
+impl CbzEditor {
+    /// Read every page of a CBZ, in reading order (ComicInfo.xml page order, pages marked Deleted are dropped)
+    pub fn open(path: &Path, password: Option<&str>) -> Result<CbzEditor> {
+        let file = File::open(path)
+            .context(format!("Failed to open {:?}", path))?;
+        let mut archive = ZipArchive::new(BufReader::new(file))
+            .context("Failed to open ZIP archive")?;
+        let mut budget = ArchiveBudget::new();
+        budget.check_entry_count(archive.len())?;
+
+        let mut pages = list_cbz_pages(&mut archive, password);
+        sort_pages(&mut pages, PageOrder::Natural, |(_, name)| name);
+        let page_indices: HashSet<usize> = pages.iter().map(|(index, _)| *index).collect();
+
+        // An unreadable ComicInfo.xml is kept as it is, without page hints
+        let mut comic_info_index = (0..archive.len())
+            .find(|&i| archive.name_for_index(i).is_some_and(is_comic_info_file));
+        let comic_info = match comic_info_index {
+            Some(index) => match read_comic_info_entry(&mut archive, index, password) {
+                Ok(info) => Some((archive.name_for_index(index).unwrap_or(COMIC_INFO_FILENAME).to_string(), info)),
+                Err(e) if archive_password_error(&e).is_some() => return Err(e),
+                Err(e) => {
+                    eprintln!("[WARNING] Ignoring ComicInfo.xml: {:#}", e);
+                    comic_info_index = None;
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // ComicInfo.xml page entries refer to name-sorted images
+        let count = pages.len();
+        let mut entries: Vec<(usize, String, Option<ComicPageInfo>)> = pages
+            .into_iter()
+            .enumerate()
+            .map(|(image, (index, name))| {
+                let info = comic_info.as_ref().and_then(|(_, info)| info.page(image)).cloned();
+                (index, name, info)
+            })
+            .collect();
+        if let Some((_, info)) = &comic_info {
+            entries = info.apply_page_order(entries);
+        }
+        let dropped_pages = count - entries.len();
+
+        let mut edited = Vec::with_capacity(entries.len());
+        for (page, (index, name, info)) in entries.into_iter().enumerate() {
+            let data = read_cbz_entry(&mut archive, index, password, &mut budget)?;
+            let folder = entry_folder(&name).map(str::to_string);
+            edited.push(EditedPage { original: Some(page), folder, name, data, info });
+        }
+
+        let mut other_files = Vec::new();
+        for index in 0..archive.len() {
+            if page_indices.contains(&index) || Some(index) == comic_info_index {
+                continue;
+            }
+            let name = match archive.name_for_index(index) {
+                Some(name) if !name.ends_with('/') => name.to_string(),
+                _ => continue,
+            };
+            let data = read_cbz_entry(&mut archive, index, password, &mut budget)?;
+            other_files.push((name, data));
+        }
+
+        Ok(CbzEditor {
+            pages: edited,
+            comic_info,
+            other_files,
+            comment: archive.comment().to_vec(),
+            jpeg_quality: DEFAULT_JPEG_QUALITY,
+            report: EditReport { dropped_pages, ..EditReport::default() },
+        })
+    }
+
+    /// Number of pages
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Current index of the page that was at index `original` in the reading order when the archive
+    /// was opened (ComicInfo.xml order, Deleted pages dropped), if it is still in the book
+    pub fn page_index(&self, original: usize) -> Option<usize> {
+        self.pages.iter().position(|page| page.original == Some(original))
+    }
+
+    pub fn apply(&mut self, edit: PageEdit) -> Result<()> {
+        match edit {
+            PageEdit::Move { from, to } => {
+                self.check_page(from)?;
+                self.check_page(to)?;
+                let mut page = self.pages.remove(from);
+                // Like inserted pages, a moved page joins the chapter of the page before it
+                let neighbour = to.checked_sub(1).or((!self.pages.is_empty()).then_some(0));
+                if let Some(index) = neighbour {
+                    page.folder = self.pages[index].folder.clone();
+                }
+                self.pages.insert(to, page);
+            }
+            PageEdit::Delete(page) => {
+                self.check_page(page)?;
+                self.pages.remove(page);
+            }
+            PageEdit::Insert { at, pages } => {
+                if at > self.pages.len() {
+                    anyhow::bail!("Cannot insert before page {}: the book has {} pages", at + 1, self.pages.len());
+                }
+                // Inserted pages join the chapter of the page before them
+                let neighbour = at.checked_sub(1).or((!self.pages.is_empty()).then_some(0));
+                let folder = neighbour.and_then(|index| self.pages[index].folder.clone());
+                let mut inserted = Vec::with_capacity(pages.len());
+                for (name, data) in pages {
+                    check_page_image(&name, &data)?;
+                    inserted.push(EditedPage { original: None, folder: folder.clone(), name, data, info: None });
+                }
+                self.pages.splice(at..at, inserted);
+            }
+            PageEdit::Rotate { page, rotation } => {
+                self.check_page(page)?;
+                self.pages[page].data = self.rotated(page, rotation)?;
+            }
+            PageEdit::Replace { page, image: (name, data) } => {
+                self.check_page(page)?;
+                check_page_image(&name, &data)?;
+                let edited = &mut self.pages[page];
+                edited.name = name;
+                edited.data = data;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_page(&self, page: usize) -> Result<()> {
+        if page >= self.pages.len() {
+            anyhow::bail!("Page {} out of range: the book has {} pages", page + 1, self.pages.len());
+        }
+        Ok(())
+    }
+
+    /// Rotated image of a page: JPEG pages losslessly where possible, others re-encoded (JPEG stays JPEG, the rest becomes PNG)
+    fn rotated(&mut self, page: usize, rotation: PageRotation) -> Result<Vec<u8>> {
+        let EditedPage { name, data, .. } = &self.pages[page];
+        let format = PageImageFormat::detect(name, data);
+        if format == Some(PageImageFormat::Jpeg) {
+            match rotate_jpeg_lossless(data, rotation) {
+                Ok(rotated) => {
+                    self.report.lossless_rotations += 1;
+                    return Ok(rotated);
+                }
+                Err(e) => eprintln!("[EDIT] Re-encoding page {} to rotate it: {:#}", page + 1, e),
+            }
+        }
+
+        let image = rotation.apply(decode_image(data).context(format!("Failed to decode page {}", page + 1))?);
+        let mut encoded = Vec::new();
+        if format == Some(PageImageFormat::Jpeg) {
+            image.to_rgb8().write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, self.jpeg_quality))
+                .context("Failed to encode JPEG")?;
+        } else {
+            image.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png)
+                .context("Failed to encode PNG")?;
+        }
+        self.report.reencoded_rotations += 1;
+        Ok(encoded)
+    }
+
+    /// Write the edited book to a new CBZ (AES-256 encrypted with a password)
+    pub fn write(self, output: &Path, password: Option<&str>) -> Result<EditReport> {
+        if self.pages.is_empty() {
+            anyhow::bail!("No pages left to write");
+        }
+
+        let width = self.pages.len().to_string().len().max(3);
+        let pages: Vec<(String, Vec<u8>, Option<ComicPageInfo>)> = self
+            .pages
+            .into_iter()
+            .enumerate()
+            .map(|(index, page)| {
+                let extension = PageImageFormat::detect(&page.name, &page.data).map_or("jpg", PageImageFormat::extension);
+                let file_name = format!("{:0width$}.{}", index + 1, extension, width = width);
+                let path = match &page.folder {
+                    Some(folder) => format!("{}/{}", folder, file_name),
+                    None => file_name,
+                };
+                (path, page.data, page.info)
+            })
+            .collect();
+
+        let comic_info = self.comic_info.map(|(path, mut info)| {
+            info.pages = pages
+                .iter()
+                .enumerate()
+                .map(|(index, (_, data, page_info))| {
+                    let size = imagesize::blob_size(data).ok();
+                    ComicPageInfo {
+                        image: index as u32,
+                        page_type: page_info.as_ref().and_then(|info| info.page_type.clone()),
+                        double_page: size.is_some_and(|s| s.width > s.height),
+                        image_size: Some(data.len() as u64),
+                        image_width: size.map(|s| s.width as u32),
+                        image_height: size.map(|s| s.height as u32),
+                    }
+                })
+                .collect();
+            if !info.pages.iter().any(ComicPageInfo::is_front_cover) {
+                info.pages[0].page_type.get_or_insert_with(|| "FrontCover".to_string());
+            }
+            info.page_count = Some(pages.len() as u32);
+            (path, info.to_xml())
+        });
+
+        let file = File::create(output)
+            .context(format!("Failed to create {:?}", output))?;
+        let mut zip = ZipWriter::new(BufWriter::new(file));
+        if !self.comment.is_empty() {
+            zip.set_raw_comment(self.comment.into_boxed_slice());
+        }
+        let options = cbz_file_options(password);
+        let page_count = pages.len();
+        let files = pages
+            .into_iter()
+            .map(|(path, data, _)| (path, data))
+            .chain(self.other_files)
+            .chain(comic_info.map(|(path, xml)| (path, xml.into_bytes())));
+        for (path, data) in files {
+            zip.start_file(path.as_str(), options)
+                .context(format!("Failed to add file {}", path))?;
+            zip.write_all(&data)
+                .context("Failed to write file data")?;
+        }
+        zip.finish()
+            .context("Failed to finalize ZIP archive")?
+            .flush()
+            .context("Failed to write CBZ file")?;
+
+        Ok(EditReport {
+            pages: page_count,
+            output_size: std::fs::metadata(output).map(|m| m.len()).unwrap_or(0),
+            ..self.report
+        })
+    }
+}
+
+/// Check that inserted or replacement data is a readable page image
+fn check_page_image(name: &str, data: &[u8]) -> Result<()> {
+    if PageImageFormat::sniff(data).is_none() {
+        anyhow::bail!("{} is not a supported image", name);
+    }
+    check_image_header(data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use zip::write::SimpleFileOptions;
+
+    /// PNG page whose width identifies it
+    fn page(width: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        DynamicImage::new_rgb8(width, 8).write_to(&mut Cursor::new(&mut data), ImageFormat::Png).unwrap();
+        data
+    }
+
+    fn write_cbz(path: &Path, files: &[(&str, Vec<u8>)]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("cbz-edit-{}-{}.cbz", name, std::process::id()))
+    }
+
+    fn page_widths(editor: &CbzEditor) -> Vec<u32> {
+        editor.pages.iter().map(|page| imagesize::blob_size(&page.data).unwrap().width as u32).collect()
+    }
+
+    #[test]
+    fn moved_page_joins_its_new_folder() {
+        let input = temp_path("move-input");
+        let output = temp_path("move-output");
+        write_cbz(&input, &[("a/001.png", page(11)), ("a/002.png", page(12)), ("b/001.png", page(21)), ("b/002.png", page(22))]);
+
+        let mut editor = CbzEditor::open(&input, None).unwrap();
+        assert_eq!(page_widths(&editor), [11, 12, 21, 22]);
+        editor.apply(PageEdit::Move { from: 0, to: 3 }).unwrap();
+        editor.apply(PageEdit::Move { from: 2, to: 0 }).unwrap();
+        let report = editor.write(&output, None).unwrap();
+        assert_eq!(report.pages, 4);
+
+        let reopened = CbzEditor::open(&output, None);
+        let mut names: Vec<String> = ZipArchive::new(File::open(&output).unwrap()).unwrap().file_names().map(str::to_string).collect();
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        // The page moved to the front joins the first folder, the one moved to the end the last folder
+        assert_eq!(page_widths(&reopened.unwrap()), [22, 12, 21, 11]);
+        names.sort();
+        assert_eq!(names, ["a/001.png", "a/002.png", "b/003.png", "b/004.png"]);
+    }
+
+    #[test]
+    fn move_checks_page_range() {
+        let input = temp_path("move-range");
+        write_cbz(&input, &[("001.png", page(11)), ("002.png", page(12))]);
+        let editor = CbzEditor::open(&input, None);
+        std::fs::remove_file(&input).unwrap();
+
+        let mut editor = editor.unwrap();
+        assert!(editor.apply(PageEdit::Move { from: 0, to: 2 }).is_err());
+        editor.apply(PageEdit::Move { from: 1, to: 0 }).unwrap();
+        assert_eq!(page_widths(&editor), [12, 11]);
+        assert!(editor.pages.iter().all(|page| page.folder.is_none()));
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+use crate::cbz::{list_cbz_pages, open_cbz_entry, read_archive_metadata, read_cbz_entry};
+use crate::comic_info::find_comic_info;
+use crate::limits::ArchiveBudget;
+use crate::metadata::DocumentMetadata;
+use crate::page_order::{sort_pages, PageOrder};
+
+/// Reads the pages of a CBZ one at a time from a seekable source
+/// Only the central directory is held in memory (ZIP64 archives larger than 4 GB are supported);
+/// each page is decompressed when it is requested, within the current `SafetyLimits`.
+pub struct CbzReader<R: Read + Seek> {
+    archive: ZipArchive<R>,
+    /// Entry index and path of each page, in reading order
+    pages: Vec<(usize, String)>,
+    password: Option<String>,
+    deleted_pages: usize,
+    budget: ArchiveBudget,
+}
+
+impl CbzReader<BufReader<File>> {
+    /// Open a CBZ file from disk
+    pub fn open(path: &Path, password: Option<&str>, order: PageOrder) -> Result<Self> {
+        let file = File::open(path)
+            .context(format!("Failed to open {:?}", path))?;
+        CbzReader::new(BufReader::new(file), password, order)
+    }
+}
+
+impl<R: Read + Seek> CbzReader<R> {
+    /// List the pages of a CBZ in reading order
+    /// Images are recognized by extension, or by content for unknown extensions.
+    /// Pages are sorted by `order`, then ComicInfo.xml page hints are applied (its page order,
+    /// pages marked Deleted are skipped). A wrong or missing password fails here, before any page is read.
+    pub fn new(reader: R, password: Option<&str>, order: PageOrder) -> Result<Self> {
+        let mut archive = ZipArchive::new(reader)
+            .context("Failed to open ZIP archive")?;
+        let budget = ArchiveBudget::new();
+        budget.check_entry_count(archive.len())?;
+
+        let mut pages = list_cbz_pages(&mut archive, password);
+        sort_pages(&mut pages, order, |(_, name)| name);
+
+        // Check the password on the first page rather than on the first read
+        if let Some(&(index, _)) = pages.first() {
+            open_cbz_entry(&mut archive, index, password)?;
+        }
+
+        // An unreadable ComicInfo.xml only loses the page hints
+        let comic_info = find_comic_info(&mut archive, password)
+            .inspect_err(|e| eprintln!("[WARNING] Ignoring ComicInfo.xml: {:#}", e))
+            .ok()
+            .flatten();
+        let count = pages.len();
+        if let Some(info) = comic_info {
+            pages = info.apply_page_order(pages);
+        }
+
+        Ok(CbzReader {
+            archive,
+            deleted_pages: count - pages.len(),
+            pages,
+            password: password.map(str::to_string),
+            budget,
+        })
+    }
+
+    /// Number of pages to read
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Number of pages skipped because ComicInfo.xml marks them Deleted
+    pub fn deleted_pages(&self) -> usize {
+        self.deleted_pages
+    }
+
+    /// Path and uncompressed size of each page, in reading order (from the central directory, nothing is read)
+    pub fn page_entries(&mut self) -> Vec<(String, u64)> {
+        self.pages
+            .iter()
+            .map(|(index, name)| {
+                let size = self.archive.by_index_raw(*index).map(|file| file.size()).unwrap_or(0);
+                (name.clone(), size)
+            })
+            .collect()
+    }
+
+    /// Book metadata from ComicInfo.xml and the ComicBookInfo comment
+    pub fn metadata(&mut self) -> Option<DocumentMetadata> {
+        read_archive_metadata(&mut self.archive, self.password.as_deref())
+    }
+
+    /// Comment of the archive (e.g. ComicBookInfo JSON)
+    pub fn comment(&self) -> &[u8] {
+        self.archive.comment()
+    }
+
+    /// Read page `page` (in reading order): its archive path and image data
+    pub fn read_page(&mut self, page: usize) -> Result<(String, Vec<u8>)> {
+        let (index, name) = self.pages.get(page)
+            .context(format!("Page {} out of range", page))?
+            .clone();
+
+        let data = read_cbz_entry(&mut self.archive, index, self.password.as_deref(), &mut self.budget)?;
+        Ok((name, data))
+    }
+
+    /// Iterate over the pages, reading each one when it is reached
+    pub fn into_pages(self) -> CbzPages<R> {
+        CbzPages { reader: self, next: 0 }
+    }
+}
+
+/// Iterator over the pages of a `CbzReader`, yielding `(path, data)` in reading order
+pub struct CbzPages<R: Read + Seek> {
+    reader: CbzReader<R>,
+    next: usize,
+}
+
+impl<R: Read + Seek> Iterator for CbzPages<R> {
+    type Item = Result<(String, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.reader.len() {
+            return None;
+        }
+        let page = self.reader.read_page(self.next);
+        self.next += 1;
+        Some(page)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.reader.len() - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<R: Read + Seek> ExactSizeIterator for CbzPages<R> {}
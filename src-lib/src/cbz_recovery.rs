@@ -0,0 +1,354 @@
+use anyhow::Result;
+use flate2::read::DeflateDecoder;
+use std::io::Read;
+
+use crate::archive_entry::{ArchiveEntry, EntryKind};
+use crate::limits::{sanitize_entry_path, ArchiveBudget, SafetyLimits};
+
+const LOCAL_HEADER_SIGNATURE: &[u8; 4] = b"PK\x03\x04";
+const DATA_DESCRIPTOR_SIGNATURE: &[u8; 4] = b"PK\x07\x08";
+const CENTRAL_DIRECTORY_SIGNATURE: &[u8; 4] = b"PK\x01\x02";
+
+/// Size of a local file header before the file name
+const LOCAL_HEADER_SIZE: usize = 30;
+
+/// ZIP compression methods that can be recovered
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// General purpose flags
+const FLAG_ENCRYPTED: u16 = 1;
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+
+/// An entry found in a damaged archive that could not be salvaged
+#[derive(Debug, Clone)]
+pub struct LostEntry {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Entries salvaged from a damaged CBZ, in archive order
+#[derive(Debug, Clone, Default)]
+pub struct RecoveredCbz {
+    /// Complete entries whose data matches their CRC-32
+    pub entries: Vec<ArchiveEntry>,
+    /// Entries whose header was found but whose data is incomplete or corrupt
+    pub lost: Vec<LostEntry>,
+    /// The central directory was reached, so no entry is missing past the last one found
+    pub complete: bool,
+}
+
+impl RecoveredCbz {
+    /// Number of salvaged page images
+    pub fn image_count(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.kind == EntryKind::Image).count()
+    }
+}
+
+/// Salvage the entries of a CBZ whose central directory is missing or damaged
+/// (interrupted downloads, half-copied files), by scanning local file headers from the start.
+/// Damaged regions are skipped up to the next local header. Encrypted entries cannot be recovered.
+/// Entries with unsafe paths are reported as lost; exceeding the `SafetyLimits` is an error.
+pub fn recover_cbz(data: &[u8]) -> Result<RecoveredCbz> {
+    let mut recovered = RecoveredCbz::default();
+    let mut budget = ArchiveBudget::new();
+    let max_entry_size = SafetyLimits::current().max_entry_size;
+    let mut offset = 0;
+
+    while let Some(found) = find(data, LOCAL_HEADER_SIGNATURE, offset) {
+        // Everything after the first central directory entry is the archive index
+        if find(&data[offset..found], CENTRAL_DIRECTORY_SIGNATURE, 0).is_some() {
+            recovered.complete = true;
+            break;
+        }
+        offset = found;
+
+        let Some(header) = LocalHeader::parse(&data[offset..]) else {
+            break;
+        };
+        let data_start = offset + header.header_size;
+        let name = header.name.replace('\\', "/");
+        let is_directory = name.ends_with('/');
+
+        match header.read_data(&data[data_start..], max_entry_size) {
+            Ok((content, consumed)) => {
+                offset = data_start + consumed;
+                if is_directory {
+                    continue;
+                }
+                budget.add_entry(&name, consumed as u64, content.len() as u64)?;
+                match sanitize_entry_path(&name) {
+                    Ok(path) => recovered.entries.push(ArchiveEntry::new(path, content)),
+                    Err(e) => recovered.lost.push(LostEntry { path: name, reason: e.to_string() }),
+                }
+            }
+            Err(reason) => {
+                if !is_directory {
+                    recovered.lost.push(LostEntry { path: name, reason });
+                }
+                // Resynchronize on the next local header
+                offset += LOCAL_HEADER_SIGNATURE.len();
+            }
+        }
+    }
+
+    if !recovered.complete {
+        recovered.complete = find(&data[offset..], CENTRAL_DIRECTORY_SIGNATURE, 0).is_some();
+    }
+
+    if recovered.entries.is_empty() && recovered.lost.is_empty() {
+        anyhow::bail!("No ZIP entries found, the file is not a CBZ archive");
+    }
+    Ok(recovered)
+}
+
+/// Fields of a local file header needed to read its data
+struct LocalHeader {
+    flags: u16,
+    method: u16,
+    crc32: u32,
+    compressed_size: u64,
+    name: String,
+    header_size: usize,
+}
+
+impl LocalHeader {
+    fn parse(data: &[u8]) -> Option<LocalHeader> {
+        if data.len() < LOCAL_HEADER_SIZE {
+            return None;
+        }
+        let name_length = read_u16(data, 26) as usize;
+        let extra_length = read_u16(data, 28) as usize;
+        let header_size = LOCAL_HEADER_SIZE + name_length + extra_length;
+        if data.len() < header_size {
+            return None;
+        }
+
+        let mut header = LocalHeader {
+            flags: read_u16(data, 6),
+            method: read_u16(data, 8),
+            crc32: read_u32(data, 14),
+            compressed_size: read_u32(data, 18) as u64,
+            name: String::from_utf8_lossy(&data[LOCAL_HEADER_SIZE..LOCAL_HEADER_SIZE + name_length]).to_string(),
+            header_size,
+        };
+
+        // ZIP64 extended information: sizes above 4 GiB
+        let mut extra = &data[LOCAL_HEADER_SIZE + name_length..header_size];
+        while extra.len() >= 4 {
+            let (id, size) = (read_u16(extra, 0), read_u16(extra, 2) as usize);
+            let field = &extra[4..extra.len().min(4 + size)];
+            if id == 0x0001 && field.len() >= 16 {
+                header.compressed_size = read_u64(field, 8);
+            }
+            extra = &extra[(4 + size).min(extra.len())..];
+        }
+        Some(header)
+    }
+
+    /// Decompress the entry data that follows the header
+    /// Returns the content and the number of bytes used (data and data descriptor), or why it is lost.
+    fn read_data(&self, data: &[u8], max_size: u64) -> std::result::Result<(Vec<u8>, usize), String> {
+        if self.flags & FLAG_ENCRYPTED != 0 {
+            return Err("Entry is encrypted".to_string());
+        }
+        let has_descriptor = self.flags & FLAG_DATA_DESCRIPTOR != 0;
+        let mut crc32 = self.crc32;
+
+        let (content, mut consumed) = match self.method {
+            METHOD_STORED if has_descriptor && self.compressed_size == 0 => {
+                // Size only known from the descriptor: find one that matches the data before it
+                let end = find_stored_end(data).ok_or("Entry data is truncated")?;
+                (data[..end].to_vec(), end)
+            }
+            METHOD_STORED => {
+                let size = self.compressed_size as usize;
+                if data.len() < size {
+                    return Err(format!("Entry data is truncated ({} of {} bytes)", data.len(), size));
+                }
+                (data[..size].to_vec(), size)
+            }
+            METHOD_DEFLATED => {
+                // The deflate stream marks its own end, so the sizes are not needed
+                let mut decoder = DeflateDecoder::new(data);
+                let mut content = Vec::new();
+                (&mut decoder).take(max_size + 1).read_to_end(&mut content)
+                    .map_err(|e| format!("Entry data is truncated or corrupt: {}", e))?;
+                (content, decoder.total_in() as usize)
+            }
+            method => return Err(format!("Unsupported compression method {}", method)),
+        };
+
+        if has_descriptor {
+            // Optional signature, CRC-32, then 32-bit or ZIP64 sizes
+            let descriptor = &data[consumed..];
+            let skip = if descriptor.starts_with(DATA_DESCRIPTOR_SIGNATURE) { 4 } else { 0 };
+            if descriptor.len() < skip + 12 {
+                return Err("Entry data descriptor is truncated".to_string());
+            }
+            crc32 = read_u32(descriptor, skip);
+            let zip64 = descriptor.len() >= skip + 20 && read_u64(descriptor, skip + 4) == consumed as u64;
+            consumed += skip + if zip64 { 20 } else { 12 };
+        }
+
+        if crc32fast::hash(&content) != crc32 {
+            return Err("Entry data does not match its CRC-32".to_string());
+        }
+        Ok((content, consumed))
+    }
+}
+
+/// End of stored data followed by a data descriptor whose compressed size matches
+fn find_stored_end(data: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    while let Some(end) = find(data, DATA_DESCRIPTOR_SIGNATURE, start) {
+        if data.len() >= end + 12 && read_u32(data, end + 8) as usize == end {
+            return Some(end);
+        }
+        start = end + 1;
+    }
+    None
+}
+
+/// Position of `pattern` in `data`, starting at `from`
+fn find(data: &[u8], pattern: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|position| from + position)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap_or_default())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_zip::{build_zip, RawEntry};
+
+    /// Page-like content long enough for deflate to matter
+    fn content(seed: u8) -> Vec<u8> {
+        (0..2000u32).map(|i| (i % 251) as u8 ^ seed).collect()
+    }
+
+    fn paths(recovered: &RecoveredCbz) -> Vec<&str> {
+        recovered.entries.iter().map(|entry| entry.path.as_str()).collect()
+    }
+
+    #[test]
+    fn intact_archive_is_complete() {
+        let zip = build_zip(&[
+            RawEntry::stored("001.txt", &content(1)),
+            RawEntry::stored("pages/", b""),
+            RawEntry::deflated("pages/002.txt", &content(2)),
+        ]);
+        let recovered = recover_cbz(&zip).unwrap();
+        assert_eq!(paths(&recovered), ["001.txt", "pages/002.txt"]);
+        assert_eq!(recovered.entries[1].data, content(2));
+        assert!(recovered.lost.is_empty());
+        assert!(recovered.complete);
+    }
+
+    #[test]
+    fn truncated_tail() {
+        let zip = build_zip(&[
+            RawEntry::stored("001.txt", &content(1)),
+            RawEntry::deflated("002.txt", &content(2)),
+            RawEntry::stored("003.txt", &content(3)),
+        ]);
+        let first_two = RawEntry::stored("001.txt", &content(1)).local().len()
+            + RawEntry::deflated("002.txt", &content(2)).local().len();
+        let recovered = recover_cbz(&zip[..first_two + 100]).unwrap();
+        assert_eq!(paths(&recovered), ["001.txt", "002.txt"]);
+        assert_eq!(recovered.lost.len(), 1);
+        assert_eq!(recovered.lost[0].path, "003.txt");
+        assert!(recovered.lost[0].reason.contains("truncated"), "{}", recovered.lost[0].reason);
+        assert!(!recovered.complete);
+    }
+
+    #[test]
+    fn data_descriptor_entries() {
+        let stored = RawEntry { descriptor: true, ..RawEntry::stored("001.txt", &content(1)) };
+        let deflated = RawEntry { descriptor: true, ..RawEntry::deflated("002.txt", &content(2)) };
+        // Stored data containing a descriptor signature that does not match its size
+        let mut tricky = b"PK\x07\x08 not a descriptor ".to_vec();
+        tricky.extend_from_slice(&content(3));
+        let tricky = RawEntry { descriptor: true, ..RawEntry::stored("003.txt", &tricky) };
+        let expected = tricky.data.clone();
+
+        let recovered = recover_cbz(&build_zip(&[stored, deflated, tricky])).unwrap();
+        assert_eq!(paths(&recovered), ["001.txt", "002.txt", "003.txt"]);
+        assert_eq!(recovered.entries[0].data, content(1));
+        assert_eq!(recovered.entries[1].data, content(2));
+        assert_eq!(recovered.entries[2].data, expected);
+        assert!(recovered.lost.is_empty());
+        assert!(recovered.complete);
+    }
+
+    #[test]
+    fn zip64_extra_fields() {
+        let sizes_in_header = RawEntry { zip64: true, ..RawEntry::stored("001.txt", &content(1)) };
+        let stored_descriptor = RawEntry { zip64: true, descriptor: true, ..RawEntry::stored("002.txt", &content(2)) };
+        let deflated_descriptor = RawEntry { zip64: true, descriptor: true, ..RawEntry::deflated("003.txt", &content(3)) };
+
+        let recovered = recover_cbz(&build_zip(&[sizes_in_header, stored_descriptor, deflated_descriptor])).unwrap();
+        assert_eq!(paths(&recovered), ["001.txt", "002.txt", "003.txt"]);
+        for (entry, seed) in recovered.entries.iter().zip(1..) {
+            assert_eq!(entry.data, content(seed));
+        }
+        assert!(recovered.lost.is_empty());
+        assert!(recovered.complete);
+    }
+
+    #[test]
+    fn resyncs_after_corrupt_entry() {
+        let entries = [
+            RawEntry::stored("001.txt", &content(1)),
+            RawEntry::stored("002.txt", &content(2)),
+            RawEntry::deflated("003.txt", &content(3)),
+        ];
+        let mut zip = build_zip(&entries);
+        // Damage the data of the middle entry
+        let middle = entries[0].local().len() + LOCAL_HEADER_SIZE + "002.txt".len() + 10;
+        zip[middle] ^= 0xFF;
+
+        let recovered = recover_cbz(&zip).unwrap();
+        assert_eq!(paths(&recovered), ["001.txt", "003.txt"]);
+        assert_eq!(recovered.entries[1].data, content(3));
+        assert_eq!(recovered.lost.len(), 1);
+        assert_eq!(recovered.lost[0].path, "002.txt");
+        assert_eq!(recovered.lost[0].reason, "Entry data does not match its CRC-32");
+        assert!(recovered.complete);
+    }
+
+    #[test]
+    fn encrypted_entry_is_lost() {
+        let encrypted = RawEntry { encrypted: true, ..RawEntry::stored("001.txt", &content(1)) };
+        let recovered = recover_cbz(&build_zip(&[encrypted, RawEntry::stored("002.txt", &content(2))])).unwrap();
+        assert_eq!(paths(&recovered), ["002.txt"]);
+        assert_eq!(recovered.lost.len(), 1);
+        assert_eq!(recovered.lost[0].path, "001.txt");
+        assert_eq!(recovered.lost[0].reason, "Entry is encrypted");
+    }
+
+    #[test]
+    fn unsafe_path_is_lost() {
+        let recovered = recover_cbz(&build_zip(&[RawEntry::stored("../001.txt", &content(1))])).unwrap();
+        assert!(recovered.entries.is_empty());
+        assert_eq!(recovered.lost[0].path, "../001.txt");
+    }
+
+    #[test]
+    fn not_a_zip() {
+        assert!(recover_cbz(b"%PDF-1.7 not an archive").is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, Utc};
+use serde_json::{json, Map, Value};
+use std::io::Cursor;
+use zip::ZipArchive;
+
+use crate::metadata::{date_from_parts, split_list, Credit, DocumentMetadata};
+
+/// Key of the ComicBookInfo object in the ZIP comment JSON
+const COMIC_BOOK_INFO_KEY: &str = "ComicBookInfo/1.0";
+
+/// Application name written in the `appID` field
+const APP_ID: &str = "pdf-to-cbz";
+
+/// Metadata read from a ComicBookInfo JSON document (stored in the ZIP archive comment)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ComicBookInfo {
+    pub series: Option<String>,
+    pub title: Option<String>,
+    pub publisher: Option<String>,
+    pub issue: Option<String>,
+    pub volume: Option<u32>,
+    pub publication_year: Option<i32>,
+    pub publication_month: Option<u32>,
+    pub genre: Option<String>,
+    pub language: Option<String>,
+    pub comments: Option<String>,
+    pub credits: Vec<Credit>,
+    pub tags: Vec<String>,
+}
+
+impl ComicBookInfo {
+    /// Parse a ZIP comment holding ComicBookInfo JSON
+    /// Numbers and strings are accepted interchangeably, as taggers disagree on field types.
+    pub fn parse(comment: &str) -> Result<ComicBookInfo> {
+        let document: Value = serde_json::from_str(comment.trim())
+            .context("Failed to parse ComicBookInfo JSON")?;
+        let info = document
+            .get(COMIC_BOOK_INFO_KEY)
+            .and_then(Value::as_object)
+            .context("ZIP comment has no ComicBookInfo/1.0 object")?;
+
+        let credits = info
+            .get("credits")
+            .and_then(Value::as_array)
+            .map(|credits| {
+                credits
+                    .iter()
+                    .filter_map(|credit| {
+                        Some(Credit {
+                            person: text_field(credit.get("person"))?,
+                            role: text_field(credit.get("role")).unwrap_or_default(),
+                            primary: credit.get("primary").and_then(Value::as_bool).unwrap_or(false),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let tags = info
+            .get("tags")
+            .and_then(Value::as_array)
+            .map(|tags| tags.iter().filter_map(|tag| text_field(Some(tag))).collect())
+            .unwrap_or_default();
+
+        Ok(ComicBookInfo {
+            series: text_field(info.get("series")),
+            title: text_field(info.get("title")),
+            publisher: text_field(info.get("publisher")),
+            issue: text_field(info.get("issue")),
+            volume: number_field(info.get("volume")),
+            publication_year: number_field(info.get("publicationYear")),
+            publication_month: number_field(info.get("publicationMonth")),
+            genre: text_field(info.get("genre")),
+            language: text_field(info.get("language")),
+            comments: text_field(info.get("comments")),
+            credits,
+            tags,
+        })
+    }
+
+    /// Serialize to the JSON document stored in the ZIP comment
+    pub fn to_json(&self) -> String {
+        let mut info = Map::new();
+        let mut insert = |key: &str, value: Value| {
+            if !value.is_null() {
+                info.insert(key.to_string(), value);
+            }
+        };
+
+        insert("series", json!(self.series));
+        insert("title", json!(self.title));
+        insert("publisher", json!(self.publisher));
+        insert("issue", json!(self.issue));
+        insert("volume", json!(self.volume));
+        insert("publicationYear", json!(self.publication_year));
+        insert("publicationMonth", json!(self.publication_month));
+        insert("genre", json!(self.genre));
+        insert("language", json!(self.language));
+        insert("comments", json!(self.comments));
+        if !self.credits.is_empty() {
+            let credits = self
+                .credits
+                .iter()
+                .map(|credit| {
+                    let mut entry = json!({ "person": credit.person, "role": credit.role });
+                    if credit.primary {
+                        entry["primary"] = json!(true);
+                    }
+                    entry
+                })
+                .collect();
+            insert("credits", Value::Array(credits));
+        }
+        if !self.tags.is_empty() {
+            insert("tags", json!(self.tags));
+        }
+
+        json!({
+            "appID": APP_ID,
+            "lastModified": Utc::now().format("%Y-%m-%d %H:%M:%S %z").to_string(),
+            COMIC_BOOK_INFO_KEY: info,
+        })
+        .to_string()
+    }
+
+    /// Map ComicBookInfo fields to the shared document metadata model
+    pub fn to_metadata(&self) -> DocumentMetadata {
+        let mut metadata = DocumentMetadata {
+            title: self.title.clone(),
+            subject: self.comments.clone(),
+            keywords: self.tags.clone(),
+            genres: self.genre.as_deref().map(split_list).unwrap_or_default(),
+            creation_date: self
+                .publication_year
+                .and_then(|year| date_from_parts(year, self.publication_month.unwrap_or(1), 1)),
+            series: self.series.clone(),
+            number: self.issue.clone(),
+            volume: self.volume,
+            publisher: self.publisher.clone(),
+            language: self.language.clone(),
+            credits: self.credits.clone(),
+            ..DocumentMetadata::default()
+        };
+        metadata.author = metadata.writers();
+        metadata
+    }
+
+    /// Build ComicBookInfo fields from document metadata
+    /// Without credits, the author is written as the Writer.
+    pub fn from_metadata(metadata: &DocumentMetadata) -> ComicBookInfo {
+        let credits = if metadata.credits.is_empty() {
+            metadata
+                .author
+                .iter()
+                .map(|author| Credit { person: author.clone(), role: "Writer".to_string(), primary: true })
+                .collect()
+        } else {
+            metadata.credits.clone()
+        };
+
+        ComicBookInfo {
+            series: metadata.series.clone(),
+            title: metadata.title.clone(),
+            publisher: metadata.publisher.clone(),
+            issue: metadata.number.clone(),
+            volume: metadata.volume,
+            publication_year: metadata.creation_date.map(|date| date.year()),
+            publication_month: metadata.creation_date.map(|date| date.month()),
+            genre: (!metadata.genres.is_empty()).then(|| metadata.genres.join(", ")),
+            language: metadata.language.clone(),
+            comments: metadata.subject.clone(),
+            credits,
+            tags: metadata.keywords.clone(),
+        }
+    }
+}
+
+/// Read ComicBookInfo from the comment of a CBZ (ZIP) archive, if present
+/// Comments that are not ComicBookInfo JSON (e.g. plain text) are ignored.
+pub fn read_comic_book_info_from_zip(archive_data: &[u8]) -> Result<Option<ComicBookInfo>> {
+    let archive = ZipArchive::new(Cursor::new(archive_data))
+        .context("Failed to open ZIP archive")?;
+    Ok(comic_book_info_from_comment(archive.comment()))
+}
+
+/// Parse a raw ZIP comment as ComicBookInfo, if it is one
+pub fn comic_book_info_from_comment(comment: &[u8]) -> Option<ComicBookInfo> {
+    let comment = std::str::from_utf8(comment).ok()?;
+    if !comment.contains(COMIC_BOOK_INFO_KEY) {
+        return None;
+    }
+    ComicBookInfo::parse(comment)
+        .inspect_err(|e| eprintln!("[WARNING] Ignoring ZIP comment: {:#}", e))
+        .ok()
+}
+
+/// String field, accepting numbers (e.g. `"issue": 12`)
+fn text_field(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(text) => Some(text.trim().to_string()).filter(|text| !text.is_empty()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// Numeric field, accepting numeric strings (e.g. `"publicationYear": "1986"`)
+fn number_field<T: std::str::FromStr>(value: Option<&Value>) -> Option<T> {
+    text_field(value)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMENT: &str = r#"{
+        "appID": "ComicTagger/1.0",
+        "lastModified": "2024-03-01 10:00:00 +0000",
+        "ComicBookInfo/1.0": {
+            "series": "Watchmen",
+            "title": "At Midnight, All the Agents...",
+            "publisher": "DC Comics",
+            "issue": 1,
+            "volume": "1986",
+            "publicationYear": 1986,
+            "publicationMonth": 9,
+            "genre": "Superhero, Mystery",
+            "language": "en",
+            "comments": "Rorschach investigates.",
+            "credits": [
+                { "person": "Alan Moore", "role": "Writer", "primary": true },
+                { "person": "Dave Gibbons", "role": "Artist" }
+            ],
+            "tags": ["classic", "limited series"]
+        }
+    }"#;
+
+    #[test]
+    fn parses_fields() {
+        let info = ComicBookInfo::parse(COMMENT).unwrap();
+        assert_eq!(info.series.as_deref(), Some("Watchmen"));
+        assert_eq!(info.issue.as_deref(), Some("1"));
+        assert_eq!(info.volume, Some(1986));
+        assert_eq!(info.publication_month, Some(9));
+        assert_eq!(info.credits.len(), 2);
+        assert!(info.credits[0].primary);
+        assert!(!info.credits[1].primary);
+    }
+
+    #[test]
+    fn metadata_round_trip_keeps_every_field() {
+        let info = ComicBookInfo::parse(COMMENT).unwrap();
+        let metadata = info.to_metadata();
+        assert_eq!(metadata.genres, ["Superhero", "Mystery"]);
+        assert_eq!(metadata.keywords, ["classic", "limited series"]);
+        assert_eq!(metadata.author.as_deref(), Some("Alan Moore"));
+
+        let rewritten = ComicBookInfo::parse(&ComicBookInfo::from_metadata(&metadata).to_json()).unwrap();
+        assert_eq!(rewritten, info);
+    }
+
+    #[test]
+    fn ignores_other_comments() {
+        assert!(comic_book_info_from_comment(b"Scanned by someone").is_none());
+        assert!(ComicBookInfo::parse(r#"{"appID": "x"}"#).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::{Context, Result};
+use chrono::Datelike;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::io::{Cursor, Read, Seek};
+use zip::ZipArchive;
+
+use crate::cbz::read_cbz_entry;
+use crate::limits::ArchiveBudget;
+use crate::metadata::{date_from_parts, split_list, Credit, DocumentMetadata, ReadingDirection};
+
+/// File name of the ComicRack metadata entry inside CBZ archives
+pub const COMIC_INFO_FILENAME: &str = "ComicInfo.xml";
+
+/// Metadata read from a ComicInfo.xml document (ComicRack schema)
+#[derive(Debug, Clone, Default)]
+pub struct ComicInfo {
+    pub title: Option<String>,
+    pub series: Option<String>,
+    pub number: Option<String>,
+    /// Number of issues (or volumes) in the series
+    pub count: Option<u32>,
+    pub volume: Option<u32>,
+    pub summary: Option<String>,
+    pub writer: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Option<String>,
+    pub year: Option<i32>,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+    pub manga: Option<String>,
+    pub page_count: Option<u32>,
+    pub language_iso: Option<String>,
+    pub pages: Vec<ComicPageInfo>,
+}
+
+/// One `<Page>` entry of the ComicInfo.xml `Pages` list
+#[derive(Debug, Clone, Default)]
+pub struct ComicPageInfo {
+    /// Zero-based index of the image in the archive (images sorted by name)
+    pub image: u32,
+    /// Page type hint: FrontCover, Story, Deleted, ...
+    pub page_type: Option<String>,
+    pub double_page: bool,
+    pub image_size: Option<u64>,
+    pub image_width: Option<u32>,
+    pub image_height: Option<u32>,
+}
+
+impl ComicPageInfo {
+    /// Page marked as removed from the book (kept in the archive, skipped when reading)
+    pub fn is_deleted(&self) -> bool {
+        self.page_type.as_deref().is_some_and(|t| t.eq_ignore_ascii_case("Deleted"))
+    }
+
+    pub fn is_front_cover(&self) -> bool {
+        self.page_type.as_deref().is_some_and(|t| t.eq_ignore_ascii_case("FrontCover"))
+    }
+
+    /// Read a `<Page>` element's attributes
+    fn from_element(element: &BytesStart) -> Result<Option<ComicPageInfo>> {
+        let mut page = ComicPageInfo::default();
+        let mut image = None;
+        for attribute in element.attributes() {
+            let attribute = attribute.context("Invalid Page attribute in ComicInfo.xml")?;
+            let value = attribute.unescape_value()
+                .context("Invalid Page attribute in ComicInfo.xml")?;
+            match attribute.key.as_ref() {
+                b"Image" => image = value.trim().parse().ok(),
+                b"Type" => page.page_type = Some(value.trim().to_string()).filter(|t| !t.is_empty()),
+                b"DoublePage" => page.double_page = value.trim().eq_ignore_ascii_case("true"),
+                b"ImageSize" => page.image_size = value.trim().parse().ok(),
+                b"ImageWidth" => page.image_width = value.trim().parse().ok(),
+                b"ImageHeight" => page.image_height = value.trim().parse().ok(),
+                _ => {}
+            }
+        }
+
+        // Entries without a valid image index cannot be matched to a file
+        Ok(image.map(|image| ComicPageInfo { image, ..page }))
+    }
+}
+
+impl ComicInfo {
+    /// Parse a ComicInfo.xml document
+    pub fn parse(xml: &str) -> Result<ComicInfo> {
+        let mut reader = Reader::from_str(xml);
+        reader.config_mut().trim_text(true);
+
+        let mut info = ComicInfo::default();
+        let mut current: Option<String> = None;
+
+        loop {
+            match reader.read_event().context("Failed to parse ComicInfo.xml")? {
+                Event::Start(element) | Event::Empty(element) if element.name().as_ref() == b"Page" => {
+                    info.pages.extend(ComicPageInfo::from_element(&element)?);
+                    current = None;
+                }
+                Event::Start(element) => {
+                    current = Some(String::from_utf8_lossy(element.name().as_ref()).to_string());
+                }
+                Event::Text(text) => {
+                    if let Some(field) = &current {
+                        let value = text.unescape()
+                            .context("Invalid text in ComicInfo.xml")?
+                            .to_string();
+                        info.set_field(field, value);
+                    }
+                }
+                Event::End(_) => current = None,
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(info)
+    }
+
+    fn set_field(&mut self, field: &str, value: String) {
+        if value.is_empty() {
+            return;
+        }
+        match field {
+            "Title" => self.title = Some(value),
+            "Series" => self.series = Some(value),
+            "Number" => self.number = Some(value),
+            "Count" => self.count = value.parse().ok(),
+            "Volume" => self.volume = value.parse().ok(),
+            "Summary" => self.summary = Some(value),
+            "Writer" => self.writer = Some(value),
+            "Genre" => self.genre = Some(value),
+            "Tags" => self.tags = Some(value),
+            "Year" => self.year = value.parse().ok(),
+            "Month" => self.month = value.parse().ok(),
+            "Day" => self.day = value.parse().ok(),
+            "Manga" => self.manga = Some(value),
+            "PageCount" => self.page_count = value.parse().ok(),
+            "LanguageISO" => self.language_iso = Some(value),
+            _ => {}
+        }
+    }
+
+    /// Map ComicInfo fields to the shared document metadata model
+    pub fn to_metadata(&self) -> DocumentMetadata {
+        let keywords = self.tags.as_deref().map(split_list).unwrap_or_default();
+        let genres = self.genre.as_deref().map(split_list).unwrap_or_default();
+
+        let creation_date = self
+            .year
+            .and_then(|year| date_from_parts(year, self.month.unwrap_or(1), self.day.unwrap_or(1)));
+
+        let reading_direction = match self.manga.as_deref() {
+            Some("YesAndRightToLeft") => ReadingDirection::RightToLeft,
+            _ => ReadingDirection::LeftToRight,
+        };
+
+        let credits = self.writer.as_deref().map(split_list).unwrap_or_default()
+            .into_iter()
+            .map(|person| Credit { person, role: "Writer".to_string(), primary: false })
+            .collect();
+
+        DocumentMetadata {
+            title: self.title.clone(),
+            author: self.writer.clone(),
+            subject: self.summary.clone(),
+            keywords,
+            genres,
+            creation_date,
+            reading_direction,
+            series: self.series.clone(),
+            number: self.number.clone(),
+            volume: self.volume,
+            language: self.language_iso.clone(),
+            credits,
+            ..DocumentMetadata::default()
+        }
+    }
+
+    /// Build ComicInfo fields from document metadata (e.g. a PDF Info dictionary)
+    pub fn from_metadata(metadata: &DocumentMetadata) -> ComicInfo {
+        let date = metadata.creation_date.map(|date| date.date_naive());
+        ComicInfo {
+            title: metadata.title.clone(),
+            series: metadata.series.clone(),
+            number: metadata.number.clone(),
+            writer: metadata.author.clone().or_else(|| metadata.writers()),
+            summary: metadata.subject.clone(),
+            volume: metadata.volume,
+            genre: (!metadata.genres.is_empty()).then(|| metadata.genres.join(", ")),
+            tags: (!metadata.keywords.is_empty()).then(|| metadata.keywords.join(", ")),
+            language_iso: metadata.language.clone(),
+            year: date.map(|d| d.year()),
+            month: date.map(|d| d.month()),
+            day: date.map(|d| d.day()),
+            manga: (metadata.reading_direction == ReadingDirection::RightToLeft)
+                .then(|| "YesAndRightToLeft".to_string()),
+            ..ComicInfo::default()
+        }
+    }
+
+    /// Mark the archive as volume `number` of `count`: Number, Volume and Count
+    pub fn set_volume(&mut self, number: usize, count: usize) {
+        self.number = Some(number.to_string());
+        self.volume = Some(number as u32);
+        self.count = Some(count as u32);
+    }
+
+    /// Fill PageCount and the `Pages` list from the archive images (in reading order)
+    /// Landscape pages are flagged as double-page spreads.
+    pub fn set_pages(&mut self, images: &[(String, Vec<u8>)]) {
+        self.page_count = Some(images.len() as u32);
+        self.pages = images
+            .iter()
+            .enumerate()
+            .map(|(index, (_, data))| {
+                let size = imagesize::blob_size(data).ok();
+                ComicPageInfo {
+                    image: index as u32,
+                    page_type: (index == 0).then(|| "FrontCover".to_string()),
+                    double_page: size.is_some_and(|s| s.width > s.height),
+                    image_size: Some(data.len() as u64),
+                    image_width: size.map(|s| s.width as u32),
+                    image_height: size.map(|s| s.height as u32),
+                }
+            })
+            .collect();
+    }
+
+    /// Hints for the image at `index` (position in the name-sorted image list)
+    pub fn page(&self, index: usize) -> Option<&ComicPageInfo> {
+        self.pages.iter().find(|page| page.image as usize == index)
+    }
+
+    /// Reading order of `image_count` name-sorted images, as indices
+    /// Follows the order of the `Pages` list and skips pages marked Deleted;
+    /// images without a `Page` entry keep their position after the listed ones.
+    pub fn page_order(&self, image_count: usize) -> Vec<usize> {
+        let mut seen = vec![false; image_count];
+        let mut order = Vec::with_capacity(image_count);
+
+        for page in &self.pages {
+            let index = page.image as usize;
+            if index >= image_count || seen[index] {
+                continue;
+            }
+            seen[index] = true;
+            if !page.is_deleted() {
+                order.push(index);
+            }
+        }
+        order.extend((0..image_count).filter(|&index| !seen[index]));
+        order
+    }
+
+    /// Reorder name-sorted items (images, page infos) following `page_order`
+    pub fn apply_page_order<T>(&self, items: Vec<T>) -> Vec<T> {
+        if self.pages.is_empty() {
+            return items;
+        }
+        let order = self.page_order(items.len());
+        let mut slots: Vec<Option<T>> = items.into_iter().map(Some).collect();
+        order.into_iter().filter_map(|index| slots[index].take()).collect()
+    }
+
+    /// Serialize to a ComicInfo.xml document (ComicRack schema v2.0 element order)
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n");
+
+        let fields = [
+            ("Title", self.title.clone()),
+            ("Series", self.series.clone()),
+            ("Number", self.number.clone()),
+            ("Count", self.count.map(|v| v.to_string())),
+            ("Volume", self.volume.map(|v| v.to_string())),
+            ("Summary", self.summary.clone()),
+            ("Year", self.year.map(|v| v.to_string())),
+            ("Month", self.month.map(|v| v.to_string())),
+            ("Day", self.day.map(|v| v.to_string())),
+            ("Writer", self.writer.clone()),
+            ("Genre", self.genre.clone()),
+            ("Tags", self.tags.clone()),
+            ("PageCount", self.page_count.map(|v| v.to_string())),
+            ("LanguageISO", self.language_iso.clone()),
+            ("Manga", self.manga.clone()),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                xml.push_str(&format!("  <{0}>{1}</{0}>\n", name, escape(value.as_str())));
+            }
+        }
+
+        if !self.pages.is_empty() {
+            xml.push_str("  <Pages>\n");
+            for page in &self.pages {
+                let mut attributes = format!("Image=\"{}\"", page.image);
+                if let Some(page_type) = &page.page_type {
+                    attributes.push_str(&format!(" Type=\"{}\"", escape(page_type.as_str())));
+                }
+                if page.double_page {
+                    attributes.push_str(" DoublePage=\"true\"");
+                }
+                if let Some(size) = page.image_size {
+                    attributes.push_str(&format!(" ImageSize=\"{}\"", size));
+                }
+                if let (Some(width), Some(height)) = (page.image_width, page.image_height) {
+                    attributes.push_str(&format!(" ImageWidth=\"{}\" ImageHeight=\"{}\"", width, height));
+                }
+                xml.push_str(&format!("    <Page {} />\n", attributes));
+            }
+            xml.push_str("  </Pages>\n");
+        }
+
+        xml.push_str("</ComicInfo>\n");
+        xml
+    }
+}
+
+/// Read ComicInfo.xml from a CBZ (ZIP) archive, if present
+/// The entry name is matched case-insensitively, in any folder.
+/// `password` decrypts AES-encrypted archives.
+pub fn read_comic_info_from_zip(archive_data: &[u8], password: Option<&str>) -> Result<Option<ComicInfo>> {
+    let mut archive = ZipArchive::new(Cursor::new(archive_data))
+        .context("Failed to open ZIP archive")?;
+    find_comic_info(&mut archive, password)
+}
+
+/// Find and read ComicInfo.xml in an open archive, if present
+pub fn find_comic_info<R: Read + Seek>(archive: &mut ZipArchive<R>, password: Option<&str>) -> Result<Option<ComicInfo>> {
+    let index = (0..archive.len())
+        .find(|&i| archive.name_for_index(i).is_some_and(is_comic_info_file));
+    let Some(index) = index else {
+        return Ok(None);
+    };
+
+    read_comic_info_entry(archive, index, password).map(Some)
+}
+
+/// Read and parse the ComicInfo.xml entry at `index` of an open archive
+pub fn read_comic_info_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    password: Option<&str>,
+) -> Result<ComicInfo> {
+    let data = read_cbz_entry(archive, index, password, &mut ArchiveBudget::new())
+        .context("Failed to read ComicInfo.xml")?;
+    ComicInfo::parse(&String::from_utf8_lossy(&data))
+}
+
+/// Check if an archive entry is a ComicInfo.xml file
+pub fn is_comic_info_file(filename: &str) -> bool {
+    let base = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+    base.eq_ignore_ascii_case(COMIC_INFO_FILENAME)
+}
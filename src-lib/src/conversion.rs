@@ -1,10 +1,14 @@
 use anyhow::{Context, Result};
 use pdfium_render::prelude::*;
 use rayon::prelude::*;
-use image::{ImageEncoder, GenericImageView};
+use image::ImageEncoder;
 use std::io::Write;
 use std::time::Instant;
-use crate::{bind_pdfium, find_best_image_candidate, extract_image_bytes_as_jpeg};
+use crate::{bind_pdfium, find_best_image_candidate, extract_bilevel_image, extract_image};
+use crate::pdf_document::load_pdf_document;
+use crate::pdf_options::PdfOutputOptions;
+use crate::pdf_writer::write_pdf_from_images;
+use crate::transparency::Transparency;
 
 // Helper function to log with timestamps
 fn log_with_time(msg: &str, start: &Instant) {
@@ -22,11 +26,15 @@ fn log_with_time(msg: &str, start: &Instant) {
 /// 3. Parallel JPEG encoding
 ///
 /// For 270-page PDFs, this takes ~2m10s vs 1h36m with naive sequential rendering.
+/// Masked images are composited or keep their alpha (then saved as PNG) as set by `transparency`.
+/// `password` opens encrypted PDFs (fails with `PdfPasswordError` if missing or wrong).
 pub fn convert_pdf_to_images_parallel(
     pdf_data: &[u8],
     dpi: u32,
     quality: u8,
     max_pages: u32,
+    password: Option<&str>,
+    transparency: Transparency,
 ) -> Result<Vec<(String, Vec<u8>)>> {
     let start_global = Instant::now();
     let effective_dpi = if dpi == 0 { 300 } else { dpi };
@@ -38,9 +46,7 @@ pub fn convert_pdf_to_images_parallel(
     log_with_time("[LIB] PDFium bound successfully", &start_global);
 
     log_with_time(&format!("[LIB] Loading PDF from {} bytes...", pdf_data.len()), &start_global);
-    let document = pdfium
-        .load_pdf_from_byte_vec(pdf_data.to_vec(), None)
-        .context("Failed to load PDF")?;
+    let document = load_pdf_document(&pdfium, pdf_data.to_vec(), password)?;
 
     log_with_time("[LIB] PDF loaded, counting pages...", &start_global);
     let page_count = document.pages().len();
@@ -79,12 +85,18 @@ pub fn convert_pdf_to_images_parallel(
         let (best_candidate, _crop_bounds) = find_best_image_candidate(&page)?;
 
         let extraction_success = if let Some(candidate) = best_candidate {
-            // Try to extract the image DIRECTLY as JPEG (OPTIMIZED - no PNG intermediate!)
-            match extract_image_bytes_as_jpeg(&page, candidate.object_index, quality) {
-                Ok(jpeg_bytes) => {
-                    // Successfully extracted as JPEG - add to fast path
-                    let filename = format!("page_{:04}.jpg", page_num);
-                    extracted_pages.push((filename, jpeg_bytes));
+            // Black-and-white scans stay 1-bit PNG, images with kept transparency become PNG,
+            // other images are extracted DIRECTLY as JPEG (OPTIMIZED - no PNG intermediate!)
+            let extracted = match extract_bilevel_image(&page, candidate.object_index) {
+                Ok(Some(bilevel)) => bilevel.to_png().map(|png| ("png", png)),
+                _ => extract_image(&document, &page, candidate.object_index, transparency)
+                    .and_then(|image| encode_extracted_image(&image, quality)),
+            };
+            match extracted {
+                Ok((extension, bytes)) => {
+                    // Successfully extracted - add to fast path
+                    let filename = format!("page_{:04}.{}", page_num, extension);
+                    extracted_pages.push((filename, bytes));
                     true
                 }
                 Err(_) => false // Fall through to render
@@ -199,21 +211,41 @@ pub fn convert_pdf_to_images_parallel(
     Ok(images)
 }
 
+/// Encode an extracted image as JPEG at `quality`, or as PNG when it kept transparent pixels
+fn encode_extracted_image(image: &image::DynamicImage, quality: u8) -> Result<(&'static str, Vec<u8>)> {
+    let mut data = Vec::new();
+    if image.color().has_alpha() {
+        let rgba = image.to_rgba8();
+        image::codecs::png::PngEncoder::new(&mut data)
+            .write_image(rgba.as_raw(), rgba.width(), rgba.height(), image::ExtendedColorType::Rgba8)
+            .context("Failed to encode image as PNG")?;
+        return Ok(("png", data));
+    }
+
+    let rgb = image.to_rgb8();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, quality)
+        .encode(rgb.as_raw(), rgb.width(), rgb.height(), image::ExtendedColorType::Rgb8)
+        .context("Failed to encode image as JPEG")?;
+    Ok(("jpg", data))
+}
+
 /// Extract images from PDF with PNG lossless encoding at specified DPI
 /// Uses Direct Extract pipeline: high-quality image extraction if available,
 /// otherwise falls back to full-page rendering at the specified DPI as PNG
+/// Masked images are composited or keep their alpha as set by `transparency`.
+/// `password` opens encrypted PDFs (fails with `PdfPasswordError` if missing or wrong).
 pub fn extract_images_lossless_at_dpi(
     pdf_data: &[u8],
     dpi: u32,
     max_pages: u32,
+    password: Option<&str>,
+    transparency: Transparency,
 ) -> Result<Vec<(String, Vec<u8>)>> {
     let effective_dpi = if dpi == 0 { 300 } else { dpi };
 
     let pdfium = bind_pdfium()
         .context("Failed to initialize Pdfium")?;
-    let document = pdfium
-        .load_pdf_from_byte_vec(pdf_data.to_vec(), None)
-        .context("Failed to load PDF")?;
+    let document = load_pdf_document(&pdfium, pdf_data.to_vec(), password)?;
 
     let page_count = document.pages().len() as u32;
     if page_count == 0 {
@@ -240,7 +272,7 @@ pub fn extract_images_lossless_at_dpi(
 
         if let Some(candidate) = &best_candidate {
             // Try to extract the image
-            match crate::extract_image_bytes(&page, candidate.object_index) {
+            match crate::extract_image_bytes(&document, &page, candidate.object_index, transparency) {
                 Ok(image_bytes) => {
                     crate::log_page_diagnostic(page_num, &best_candidate, crop_bounds, false);
                     let filename = format!("page_{:04}.png", page_num);
@@ -300,142 +332,8 @@ pub fn extract_images_lossless_at_dpi(
 /// Create PDF from image bytes
 /// Converts a collection of images into a PDF document with one image per page.
 /// Optimized for JPEG images with direct insertion without re-encoding.
-pub fn create_pdf_from_images(images: Vec<(String, Vec<u8>)>) -> Result<Vec<u8>> {
-    use printpdf::{Mm, PdfDocument};
-
-    if images.is_empty() {
-        anyhow::bail!("No images to convert");
-    }
-
-    let (document, page1, layer1) =
-        PdfDocument::new("CBZ to PDF", Mm(210.0), Mm(297.0), "Layer 1");
-
-    // Add first image
-    if let Some((_, image_data)) = images.first() {
-        add_image_to_page(&document, page1, layer1, image_data)?;
-    }
-
-    // Add remaining images
-    for (_, image_data) in images.iter().skip(1) {
-        let (page, layer) = document.add_page(Mm(210.0), Mm(297.0), "Page");
-        add_image_to_page(&document, page, layer, image_data)?;
-    }
-
-    // Serialize to bytes
-    let pdf_bytes = document
-        .save_to_bytes()
-        .map_err(|e| anyhow::anyhow!("Failed to serialize PDF: {:?}", e))?;
-
-    Ok(pdf_bytes)
-}
-
-/// Add image to PDF page (A4: 210×297mm)
-fn add_image_to_page(
-    document: &printpdf::PdfDocumentReference,
-    page_id: printpdf::PdfPageIndex,
-    layer_id: printpdf::PdfLayerIndex,
-    image_data: &[u8],
-) -> Result<()> {
-    use image::ImageFormat;
-    use printpdf::{Image, ImageTransform, Mm};
-    use std::io::Cursor;
-
-    // Fast dimension reading
-    let (img_width, img_height) = if let Ok(size) = imagesize::blob_size(image_data) {
-        (size.width as u32, size.height as u32)
-    } else {
-        // Fallback: decode image to get dimensions
-        let reader = image::ImageReader::new(Cursor::new(image_data));
-        let reader_with_format = reader.with_guessed_format()
-            .context("Failed to detect image format")?;
-        let img = reader_with_format.decode()
-            .context("Failed to decode image")?;
-        img.dimensions()
-    };
-
-    // Get layer
-    let layer = document.get_page(page_id).get_layer(layer_id);
-
-    // Try direct JPEG insertion (no decode)
-    if let Ok(size) = imagesize::blob_size(image_data) {
-        let reader = image::ImageReader::new(Cursor::new(image_data));
-        if let Ok(reader_with_format) = reader.with_guessed_format() {
-            if matches!(reader_with_format.format(), Some(ImageFormat::Jpeg)) {
-                // Direct JPEG insertion (fast!)
-                let image_xobject = printpdf::ImageXObject {
-                    width: printpdf::Px(size.width),
-                    height: printpdf::Px(size.height),
-                    color_space: printpdf::ColorSpace::Rgb,
-                    bits_per_component: printpdf::ColorBits::Bit8,
-                    image_data: image_data.to_vec(),
-                    image_filter: Some(printpdf::ImageFilter::DCT),
-                    interpolate: true,
-                    smask: None,
-                    clipping_bbox: None,
-                };
-
-                let image = Image::from(image_xobject);
-                let dpi = calculate_dpi(size.width as f32, size.height as f32);
-
-                let transform = ImageTransform {
-                    translate_x: Some(Mm(0.0)),
-                    translate_y: Some(Mm(0.0)),
-                    dpi: Some(dpi),
-                    rotate: None,
-                    ..Default::default()
-                };
-
-                image.add_to_layer(layer, transform);
-                return Ok(());
-            }
-        }
-    }
-
-    // Fallback: decode and convert
-    let reader = image::ImageReader::new(Cursor::new(image_data));
-    let reader_with_format = reader
-        .with_guessed_format()
-        .context("Failed to detect image format")?;
-    let img = reader_with_format
-        .decode()
-        .context("Failed to decode image")?;
-
-    let rgb_img = img.to_rgb8();
-    let image_xobject = printpdf::ImageXObject {
-        width: printpdf::Px(img_width as usize),
-        height: printpdf::Px(img_height as usize),
-        color_space: printpdf::ColorSpace::Rgb,
-        bits_per_component: printpdf::ColorBits::Bit8,
-        image_data: rgb_img.to_vec(),
-        image_filter: None,
-        interpolate: true,
-        smask: None,
-        clipping_bbox: None,
-    };
-
-    let image = Image::from(image_xobject);
-    let dpi = calculate_dpi(img_width as f32, img_height as f32);
-
-    let transform = ImageTransform {
-        translate_x: Some(Mm(0.0)),
-        translate_y: Some(Mm(0.0)),
-        dpi: Some(dpi),
-        rotate: None,
-        ..Default::default()
-    };
-
-    image.add_to_layer(layer, transform);
-    Ok(())
-}
-
-/// Calculate optimal DPI to fit image on A4 page (210×297mm)
-fn calculate_dpi(img_width: f32, img_height: f32) -> f32 {
-    // A4 dimensions: 210mm × 297mm = 8.27" × 11.69"
-    let page_width_inch = 210.0 / 25.4;
-    let page_height_inch = 297.0 / 25.4;
-
-    let dpi_w = img_width / page_width_inch;
-    let dpi_h = img_height / page_height_inch;
-
-    dpi_w.max(dpi_h)
+/// Metadata and viewer preferences are taken from `options`.
+/// The document is built in memory; use `write_pdf_from_images` to stream to a file.
+pub fn create_pdf_from_images(images: Vec<(String, Vec<u8>)>, options: &PdfOutputOptions) -> Result<Vec<u8>> {
+    write_pdf_from_images(images, Vec::new(), options, |_, _| {})
 }
@@ -1,6 +1,10 @@
 use anyhow::{Context, Result};
 use pdfium_render::prelude::*;
-use image::ImageEncoder;
+use image::{DynamicImage, ImageEncoder};
+
+use crate::bilevel::BilevelImage;
+use crate::jpx::{decode_jpx, JpxAlpha};
+use crate::transparency::{has_transparency, Transparency};
 
 // Threshold for considering an image suitable for direct extraction.
 // Comics can have significant margins and white space.
@@ -9,6 +13,9 @@ use image::ImageEncoder;
 // Even a small extracted image is preferable to rendering white space.
 pub const MIN_COVERAGE_FOR_DIRECT_EXTRACT: f64 = 0.005; // 0.5% (very low threshold)
 
+/// Rectangle on a page in PDF points: (left, bottom, right, top)
+pub type PageBounds = (f32, f32, f32, f32);
+
 /// Information about a candidate image for extraction
 #[derive(Debug, Clone)]
 pub struct ImageCandidate {
@@ -28,7 +35,7 @@ pub struct ImageCandidate {
 /// Returns (None, bounds_of_cropbox) if no suitable image, fallback to render
 pub fn find_best_image_candidate(
     page: &PdfPage,
-) -> Result<(Option<ImageCandidate>, (f32, f32, f32, f32))> {
+) -> Result<(Option<ImageCandidate>, PageBounds)> {
     // Get effective page bounds (CropBox or MediaBox)
     let crop_box = page.boundaries().crop()
         .or_else(|_| page.boundaries().media())
@@ -98,7 +105,7 @@ fn process_object(
             let obj_area = obj_w * obj_h;
             let coverage = (obj_area / crop_area) as f64;
 
-            let can_extract_raw = image_obj.get_raw_bitmap().is_ok();
+            let can_extract_raw = is_jpx_image_object(image_obj) || image_obj.get_raw_bitmap().is_ok();
 
             candidates.push(ImageCandidate {
                 object_index,
@@ -117,98 +124,172 @@ fn process_object(
     }
 }
 
+/// Extract an image object with its soft mask or colour-key mask handled as set by `transparency`
+/// Fully opaque images have no alpha channel.
+pub fn extract_image(
+    document: &PdfDocument,
+    page: &PdfPage,
+    object_index: usize,
+    transparency: Transparency,
+) -> Result<DynamicImage> {
+    let object = page
+        .objects()
+        .iter()
+        .nth(object_index)
+        .context("Object not found at index")?;
+    let image_obj = object.as_image_object().context("Object is not an image")?;
+    let image = masked_image(document, image_obj).context("Image cannot be extracted")?;
+    Ok(transparency.apply(image))
+}
+
 /// Extract an image object as PNG bytes (lossless)
+/// Black-and-white images are saved as 1-bit PNG; images that keep transparent pixels as RGBA.
 pub fn extract_image_bytes(
+    document: &PdfDocument,
     page: &PdfPage,
     object_index: usize,
+    transparency: Transparency,
 ) -> Result<Vec<u8>> {
-    let page_objects = page.objects();
-
-    if object_index >= page_objects.len() {
-        anyhow::bail!("Object index out of bounds");
-    }
-
-    // Get object by iterating (pdfium_render API limitation)
-    let mut target_object = None;
-    for (idx, obj) in page_objects.iter().enumerate() {
-        if idx == object_index {
-            target_object = Some(obj);
-            break;
-        }
+    // Black-and-white scans are kept 1-bit
+    if let Ok(Some(bilevel)) = extract_bilevel_image(page, object_index) {
+        return bilevel.to_png();
     }
 
-    let object = target_object.context("Object not found at index")?;
-
-    if let Some(image_obj) = object.as_image_object() {
-        // Get bitmap and encode as PNG (lossless)
-        if let Ok(bitmap) = image_obj.get_raw_bitmap() {
-            let dynamic_image = bitmap.as_image();
-            let rgb_image = dynamic_image.to_rgb8();
-
-            // Encode as PNG (lossless)
-            let mut png_data = Vec::new();
-            let encoder = image::codecs::png::PngEncoder::new(&mut png_data);
-            encoder.write_image(
-                rgb_image.as_raw(),
-                rgb_image.width(),
-                rgb_image.height(),
-                image::ExtendedColorType::Rgb8,
-            ).context("Failed to encode image as PNG")?;
-
-            return Ok(png_data);
-        }
-    }
-
-    anyhow::bail!("Object is not an image or cannot be extracted")
+    let image = extract_image(document, page, object_index, transparency)?;
+    let (pixels, color_type) = if image.color().has_alpha() {
+        (image.to_rgba8().into_raw(), image::ExtendedColorType::Rgba8)
+    } else {
+        (image.to_rgb8().into_raw(), image::ExtendedColorType::Rgb8)
+    };
+
+    // Encode as PNG (lossless)
+    let mut png_data = Vec::new();
+    let encoder = image::codecs::png::PngEncoder::new(&mut png_data);
+    encoder.write_image(&pixels, image.width(), image.height(), color_type)
+        .context("Failed to encode image as PNG")?;
+    Ok(png_data)
 }
 
 /// Extract an image object as JPEG bytes with quality control (NO intermediate PNG encoding)
+/// JPEG has no alpha channel: transparent pixels are composited on white with `Transparency::Keep`.
 pub fn extract_image_bytes_as_jpeg(
+    document: &PdfDocument,
     page: &PdfPage,
     object_index: usize,
     quality: u8,
+    transparency: Transparency,
 ) -> Result<Vec<u8>> {
-    let page_objects = page.objects();
+    let image = extract_image(document, page, object_index, transparency)?;
+    let rgb_image = Transparency::default().apply(image).to_rgb8();
+
+    // Encode directly as JPEG (SKIP PNG encoding!)
+    let mut jpeg_data = Vec::new();
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(
+        &mut jpeg_data,
+        quality
+    );
+    encoder.encode(
+        rgb_image.as_raw(),
+        rgb_image.width(),
+        rgb_image.height(),
+        image::ExtendedColorType::Rgb8,
+    ).context("Failed to encode image as JPEG")?;
+    Ok(jpeg_data)
+}
 
-    if object_index >= page_objects.len() {
-        anyhow::bail!("Object index out of bounds");
+/// Pixels of an image object with its soft mask or colour-key mask as alpha channel
+/// PDFium's raw bitmap ignores masks: when a small render of the object shows transparency,
+/// the object is rendered at its native size instead (upright images only, so pixels line up).
+fn masked_image(document: &PdfDocument, image_obj: &PdfPageImageObject) -> Option<DynamicImage> {
+    let image = raw_image(image_obj)?;
+    if has_transparency(&image) || !is_upright(image_obj) {
+        return Some(image);
     }
 
-    // Get object by iterating (pdfium_render API limitation)
-    let mut target_object = None;
-    for (idx, obj) in page_objects.iter().enumerate() {
-        if idx == object_index {
-            target_object = Some(obj);
-            break;
+    let (width, height) = (image.width(), image.height());
+    let probe = image_obj.get_processed_image_with_size(document, width.min(64) as Pixels, height.min(64) as Pixels);
+    if !probe.is_ok_and(|probe| has_interior_transparency(&probe)) {
+        return Some(image);
+    }
+    match image_obj.get_processed_image_with_size(document, width as Pixels, height as Pixels) {
+        Ok(masked) if masked.width() == width && masked.height() == height => Some(masked),
+        _ => {
+            eprintln!("[EXTRACT] Could not render the image with its mask, keeping it opaque");
+            Some(image)
         }
     }
+}
 
-    let object = target_object.context("Object not found at index")?;
+/// Whether an image object is drawn without rotation or skew
+fn is_upright(image_obj: &PdfPageImageObject) -> bool {
+    image_obj.matrix().is_ok_and(|matrix| matrix.b() == 0.0 && matrix.c() == 0.0)
+}
 
-    if let Some(image_obj) = object.as_image_object() {
-        // Get bitmap and encode as JPEG directly (OPTIMIZED - no PNG intermediate!)
-        if let Ok(bitmap) = image_obj.get_raw_bitmap() {
-            let dynamic_image = bitmap.as_image();
-            let rgb_image = dynamic_image.to_rgb8();
-
-            // Encode directly as JPEG (SKIP PNG encoding!)
-            let mut jpeg_data = Vec::new();
-            let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(
-                &mut jpeg_data,
-                quality
-            );
-            encoder.encode(
-                rgb_image.as_raw(),
-                rgb_image.width(),
-                rgb_image.height(),
-                image::ExtendedColorType::Rgb8,
-            ).context("Failed to encode image as JPEG")?;
-
-            return Ok(jpeg_data);
+/// Whether a render has transparent pixels away from its edges (edges may be antialiased)
+fn has_interior_transparency(image: &DynamicImage) -> bool {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    rgba.enumerate_pixels()
+        .any(|(x, y, pixel)| x > 0 && y > 0 && x + 1 < width && y + 1 < height && pixel[3] < 255)
+}
+
+/// Pixels of an image object at its native resolution
+/// JPEG 2000 images are decoded in-process so their ICC colours and alpha channel are handled
+/// (PDFium's raw bitmap drops them); PDFium's bitmap is used for everything else, or if that fails.
+fn raw_image(image_obj: &PdfPageImageObject) -> Option<DynamicImage> {
+    if is_jpx_image_object(image_obj) {
+        // PDFium does not expose SMaskInData: an alpha channel in the data is assumed to be used
+        let decoded = image_obj
+            .get_raw_image_data()
+            .map_err(anyhow::Error::from)
+            .and_then(|data| decode_jpx(&data, JpxAlpha::Mask));
+        match decoded {
+            Ok(image) => return Some(image),
+            Err(e) => eprintln!("[EXTRACT] JPEG 2000 decoding failed, using PDFium's bitmap: {:#}", e),
         }
     }
+    // The raw bitmap has no mask applied: its fourth channel (BGRx) is not opacity
+    let image = image_obj.get_raw_bitmap().ok()?.as_image();
+    Some(match image {
+        DynamicImage::ImageRgba8(_) => DynamicImage::ImageRgb8(image.to_rgb8()),
+        image => image,
+    })
+}
+
+/// Whether an image object is stored as JPEG 2000 (JPXDecode)
+fn is_jpx_image_object(image_obj: &PdfPageImageObject) -> bool {
+    image_obj.filters().iter().any(|filter| filter.name() == "JPXDecode")
+}
 
-    anyhow::bail!("Object is not an image or cannot be extracted")
+/// Extract a black-and-white image object (CCITT, JBIG2 or other 1-bit image) without widening it to RGB
+/// Returns None when the image is not bilevel
+pub fn extract_bilevel_image(
+    page: &PdfPage,
+    object_index: usize,
+) -> Result<Option<BilevelImage>> {
+    let object = page
+        .objects()
+        .iter()
+        .nth(object_index)
+        .context("Object not found at index")?;
+    let image_obj = object.as_image_object().context("Object is not an image")?;
+    Ok(bilevel_image(image_obj))
+}
+
+/// Whether an image object is stored with one bit per pixel
+pub fn is_bilevel_image_object(image_obj: &PdfPageImageObject) -> bool {
+    image_obj.filters().iter().any(|filter| matches!(filter.name(), "CCITTFaxDecode" | "JBIG2Decode"))
+        || image_obj.bits_per_pixel().is_ok_and(|bits| bits == 1)
+}
+
+/// Decoded pixels of a 1-bit image object, if they are black and white
+/// (a 1-bit Indexed image may use other colours)
+fn bilevel_image(image_obj: &PdfPageImageObject) -> Option<BilevelImage> {
+    if !is_bilevel_image_object(image_obj) {
+        return None;
+    }
+    let bitmap = image_obj.get_raw_bitmap().ok()?;
+    BilevelImage::from_image(&bitmap.as_image())
 }
 
 /// Log diagnostic info about a page's best image candidate
@@ -0,0 +1,123 @@
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+/// Number of leading bytes needed to recognize an image format
+pub const SNIFF_LENGTH: usize = 32;
+
+/// Image formats read as comic pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    WebP,
+    Tiff,
+    Bmp,
+    Avif,
+    JpegXl,
+}
+
+impl PageImageFormat {
+    /// Format suggested by a file name's extension
+    pub fn from_extension(path: &str) -> Option<PageImageFormat> {
+        let extension = path
+            .rsplit(['/', '\\'])
+            .next()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, extension)| extension.to_ascii_lowercase())?;
+        match extension.as_str() {
+            "jpg" | "jpeg" | "jpe" | "jfif" => Some(PageImageFormat::Jpeg),
+            "png" => Some(PageImageFormat::Png),
+            "gif" => Some(PageImageFormat::Gif),
+            "webp" => Some(PageImageFormat::WebP),
+            "tif" | "tiff" => Some(PageImageFormat::Tiff),
+            "bmp" => Some(PageImageFormat::Bmp),
+            "avif" => Some(PageImageFormat::Avif),
+            "jxl" => Some(PageImageFormat::JpegXl),
+            _ => None,
+        }
+    }
+
+    /// Format recognized from the magic bytes at the start of the data
+    pub fn sniff(data: &[u8]) -> Option<PageImageFormat> {
+        let format = match data {
+            [0xFF, 0xD8, 0xFF, ..] => PageImageFormat::Jpeg,
+            [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => PageImageFormat::Png,
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => PageImageFormat::Gif,
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => PageImageFormat::WebP,
+            [b'I', b'I', 0x2A, 0x00, ..] | [b'M', b'M', 0x00, 0x2A, ..] => PageImageFormat::Tiff,
+            [b'B', b'M', _, _, _, _, 0, 0, 0, 0, ..] => PageImageFormat::Bmp,
+            // Bare codestream or ISO BMFF container
+            [0xFF, 0x0A, ..] | [0, 0, 0, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A, ..] => PageImageFormat::JpegXl,
+            [_, _, _, _, b'f', b't', b'y', b'p', b'a', b'v', b'i', b'f' | b's', ..] => PageImageFormat::Avif,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// Format recognized from the first bytes of a file on disk
+    pub fn sniff_file(path: &Path) -> Option<PageImageFormat> {
+        let mut header = Vec::with_capacity(SNIFF_LENGTH);
+        File::open(path).ok()?.take(SNIFF_LENGTH as u64).read_to_end(&mut header).ok()?;
+        PageImageFormat::sniff(&header)
+    }
+
+    /// Format of an image, from its content, or its name when the content is not recognized
+    pub fn detect(path: &str, data: &[u8]) -> Option<PageImageFormat> {
+        PageImageFormat::sniff(data).or_else(|| PageImageFormat::from_extension(path))
+    }
+
+    /// Usual file extension of the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            PageImageFormat::Jpeg => "jpg",
+            PageImageFormat::Png => "png",
+            PageImageFormat::Gif => "gif",
+            PageImageFormat::WebP => "webp",
+            PageImageFormat::Tiff => "tiff",
+            PageImageFormat::Bmp => "bmp",
+            PageImageFormat::Avif => "avif",
+            PageImageFormat::JpegXl => "jxl",
+        }
+    }
+
+    /// Short upper-case name (e.g. for page listings)
+    pub fn name(self) -> &'static str {
+        match self {
+            PageImageFormat::Jpeg => "JPEG",
+            PageImageFormat::Png => "PNG",
+            PageImageFormat::Gif => "GIF",
+            PageImageFormat::WebP => "WEBP",
+            PageImageFormat::Tiff => "TIFF",
+            PageImageFormat::Bmp => "BMP",
+            PageImageFormat::Avif => "AVIF",
+            PageImageFormat::JpegXl => "JXL",
+        }
+    }
+
+    /// Whether this build can decode the format
+    pub fn is_supported(self) -> bool {
+        self != PageImageFormat::Avif || cfg!(feature = "avif")
+    }
+
+    /// Whether the image holds several frames (animated GIF or WebP)
+    /// Only the first frame of an animation is used as a page.
+    pub fn is_animated(self, data: &[u8]) -> bool {
+        match self {
+            PageImageFormat::Gif => gif_frame_count(data) > 1,
+            PageImageFormat::WebP => image::codecs::webp::WebPDecoder::new(Cursor::new(data))
+                .map(|decoder| decoder.has_animation())
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+/// Number of image descriptors in a GIF, stopping at two
+fn gif_frame_count(data: &[u8]) -> usize {
+    use image::AnimationDecoder;
+    image::codecs::gif::GifDecoder::new(Cursor::new(data))
+        .map(|decoder| decoder.into_frames().take(2).count())
+        .unwrap_or(0)
+}
@@ -0,0 +1,940 @@
+use anyhow::{Context, Result};
+
+use crate::cbz_edit::PageRotation;
+use crate::limits::SafetyLimits;
+
+/// Natural (row-major) index of each coefficient in zigzag order
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5,
+    12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// Largest DC difference category of 8-bit baseline JPEG
+const MAX_DC_CATEGORY: u32 = 11;
+
+/// Rotate a JPEG without decoding it to pixels, so no quality is lost
+/// The DCT blocks are moved and transposed and the entropy coding is redone with optimal Huffman tables;
+/// markers (EXIF, ICC profile, ...) are kept. Only baseline and extended Huffman-coded 8-bit JPEGs with a
+/// single scan are supported, and the side that gets mirrored must be a whole number of MCUs (otherwise
+/// the padding blocks would become visible): an error is returned in the other cases, for the caller to
+/// re-encode the image instead.
+pub fn rotate_jpeg_lossless(data: &[u8], rotation: PageRotation) -> Result<Vec<u8>> {
+    let mut jpeg = Jpeg::parse(data)?;
+    if !jpeg.can_rotate(rotation) {
+        anyhow::bail!("JPEG dimensions are not a multiple of its block size");
+    }
+    jpeg.decode_scan()?;
+    jpeg.rotate(rotation);
+    jpeg.encode()
+}
+
+/// Cut a JPEG into a left and a right image without decoding it to pixels (see `rotate_jpeg_lossless`)
+/// The cut is moved to the MCU column boundary nearest to `x` (every 8 or 16 pixels); the images and
+/// the column actually used are returned.
+pub fn split_jpeg_lossless(data: &[u8], x: usize) -> Result<(Vec<u8>, Vec<u8>, usize)> {
+    let mut jpeg = Jpeg::parse(data)?;
+    let (mcu_width, _) = jpeg.mcu_size();
+    let (mcus_wide, _) = jpeg.mcu_grid();
+    if mcus_wide < 2 {
+        anyhow::bail!("JPEG is too narrow to be split");
+    }
+    let column = ((x + mcu_width / 2) / mcu_width).clamp(1, mcus_wide - 1);
+    jpeg.decode_scan()?;
+    let left = jpeg.columns(0, column).encode()?;
+    let right = jpeg.columns(column, mcus_wide).encode()?;
+    Ok((left, right, column * mcu_width))
+}
+
+#[derive(Clone)]
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant_table: u8,
+    dc_table: usize,
+    ac_table: usize,
+    /// Size of the block grid, padding blocks included
+    blocks_wide: usize,
+    blocks_high: usize,
+    /// Quantized coefficients of each block (row-major grid), in natural order
+    blocks: Vec<[i16; 64]>,
+}
+
+/// Huffman table as stored in a DHT segment
+#[derive(Clone, Default)]
+struct HuffmanTable {
+    /// Number of codes of each length, 1 to 16
+    counts: [u8; 16],
+    symbols: Vec<u8>,
+}
+
+impl HuffmanTable {
+    fn parse(data: &[u8]) -> Result<(HuffmanTable, usize)> {
+        let counts: [u8; 16] = data.get(..16).context("Truncated DHT segment")?.try_into()?;
+        let total: usize = counts.iter().map(|&count| count as usize).sum();
+        let symbols = data.get(16..16 + total).context("Truncated DHT segment")?.to_vec();
+        Ok((HuffmanTable { counts, symbols }, 16 + total))
+    }
+
+    /// Code and length of each symbol (canonical Huffman codes)
+    fn codes(&self) -> [(u16, u8); 256] {
+        let mut codes = [(0u16, 0u8); 256];
+        let mut code = 0u16;
+        let mut symbols = self.symbols.iter();
+        for (length, &count) in self.counts.iter().enumerate() {
+            for symbol in symbols.by_ref().take(count as usize) {
+                codes[*symbol as usize] = (code, length as u8 + 1);
+                code = code.wrapping_add(1);
+            }
+            code = code.wrapping_shl(1);
+        }
+        codes
+    }
+
+    /// Optimal table for symbol frequencies, with code lengths limited to 16 bits (JPEG Annex K.2)
+    fn optimal(frequencies: &[u64; 256]) -> HuffmanTable {
+        // One reserved symbol (256) keeps any code from being all ones
+        let mut freq = [0u64; 257];
+        freq[..256].copy_from_slice(frequencies);
+        freq[256] = 1;
+        let mut code_size = [0usize; 257];
+        let mut others = [usize::MAX; 257];
+
+        // Least frequent symbol, the largest one on ties
+        let least = |freq: &[u64; 257], except: Option<usize>| {
+            (0..257).filter(|&i| freq[i] > 0 && Some(i) != except).min_by_key(|&i| (freq[i], std::cmp::Reverse(i)))
+        };
+        while let Some(mut c1) = least(&freq, None) {
+            let Some(mut c2) = least(&freq, Some(c1)) else { break };
+            freq[c1] += freq[c2];
+            freq[c2] = 0;
+            code_size[c1] += 1;
+            while others[c1] != usize::MAX {
+                c1 = others[c1];
+                code_size[c1] += 1;
+            }
+            others[c1] = c2;
+            code_size[c2] += 1;
+            while others[c2] != usize::MAX {
+                c2 = others[c2];
+                code_size[c2] += 1;
+            }
+        }
+
+        let mut bits = [0usize; 258];
+        for &size in code_size.iter().filter(|&&size| size > 0) {
+            bits[size] += 1;
+        }
+        for i in (17..bits.len()).rev() {
+            while bits[i] > 0 {
+                let mut j = i - 2;
+                while bits[j] == 0 {
+                    j -= 1;
+                }
+                bits[i] -= 2;
+                bits[i - 1] += 1;
+                bits[j + 1] += 2;
+                bits[j] -= 1;
+            }
+        }
+        if let Some(longest) = (1..=16).rev().find(|&i| bits[i] > 0) {
+            bits[longest] -= 1;
+        }
+
+        let mut counts = [0u8; 16];
+        for (count, &bit) in counts.iter_mut().zip(&bits[1..=16]) {
+            *count = bit as u8;
+        }
+        let mut symbols = Vec::new();
+        for size in 1..257 {
+            symbols.extend((0..256).filter(|&symbol| code_size[symbol] == size).map(|symbol| symbol as u8));
+        }
+        symbols.truncate(counts.iter().map(|&count| count as usize).sum());
+        HuffmanTable { counts, symbols }
+    }
+}
+
+/// Canonical Huffman decoder
+struct HuffmanDecoder {
+    max_code: [i32; 17],
+    min_code: [i32; 17],
+    first_symbol: [usize; 17],
+    symbols: Vec<u8>,
+}
+
+impl HuffmanDecoder {
+    fn new(table: &HuffmanTable) -> HuffmanDecoder {
+        let mut decoder = HuffmanDecoder { max_code: [-1; 17], min_code: [0; 17], first_symbol: [0; 17], symbols: table.symbols.clone() };
+        let mut code = 0i32;
+        let mut index = 0usize;
+        for length in 1..=16 {
+            let count = table.counts[length - 1] as usize;
+            if count > 0 {
+                decoder.first_symbol[length] = index;
+                decoder.min_code[length] = code;
+                code += count as i32;
+                index += count;
+                decoder.max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        decoder
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> Result<u8> {
+        let mut code = 0i32;
+        for length in 1..=16 {
+            code = (code << 1) | bits.bit()? as i32;
+            if code <= self.max_code[length] {
+                let index = self.first_symbol[length] + (code - self.min_code[length]) as usize;
+                return self.symbols.get(index).copied().context("Invalid Huffman code");
+            }
+        }
+        anyhow::bail!("Invalid Huffman code")
+    }
+}
+
+/// Reads the bits of one restart interval (byte stuffing already removed)
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader { data, position: 0, bit: 0 }
+    }
+
+    fn bit(&mut self) -> Result<u8> {
+        let byte = *self.data.get(self.position).context("Truncated JPEG data")?;
+        let value = (byte >> (7 - self.bit)) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    /// Read a `size`-bit magnitude and extend its sign (JPEG F.2.2.1)
+    fn value(&mut self, size: u8) -> Result<i16> {
+        let mut value = 0i32;
+        for _ in 0..size {
+            value = (value << 1) | self.bit()? as i32;
+        }
+        if size > 0 && value < 1 << (size - 1) {
+            value -= (1 << size) - 1;
+        }
+        Ok(value as i16)
+    }
+}
+
+/// Writes entropy-coded data with byte stuffing
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, size: u32) {
+        for i in (0..size).rev() {
+            self.buffer = (self.buffer << 1) | ((value >> i) & 1);
+            self.bits += 1;
+            if self.bits == 8 {
+                let byte = self.buffer as u8;
+                self.out.push(byte);
+                if byte == 0xFF {
+                    self.out.push(0x00);
+                }
+                self.buffer = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    /// Pad the last byte with one bits
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.put(0xFF, 8 - self.bits);
+        }
+    }
+}
+
+/// One coded item of the scan, as produced by `Jpeg::code_scan`
+enum Coded {
+    /// Huffman symbol of table `table` (DC tables 0-3, AC tables 4-7) followed by `size` extra bits
+    Symbol { table: usize, symbol: u8, extra: u32, size: u32 },
+    /// Restart marker RSTn
+    Restart(u8),
+}
+
+struct Jpeg<'a> {
+    /// Segments before the scan, in file order (marker, payload)
+    segments: Vec<(u8, &'a [u8])>,
+    width: usize,
+    height: usize,
+    precision: u8,
+    /// Components in frame order
+    components: Vec<Component>,
+    /// Component indices in scan order
+    scan_order: Vec<usize>,
+    restart_interval: usize,
+    dc_tables: [Option<HuffmanTable>; 4],
+    ac_tables: [Option<HuffmanTable>; 4],
+    /// Entropy-coded data of each restart interval, unstuffed
+    intervals: Vec<Vec<u8>>,
+    /// Quantization tables are transposed with the blocks by 90° rotations
+    transposed: bool,
+}
+
+impl<'a> Jpeg<'a> {
+    fn parse(data: &'a [u8]) -> Result<Jpeg<'a>> {
+        if !data.starts_with(&[0xFF, 0xD8]) {
+            anyhow::bail!("Not a JPEG image");
+        }
+        let mut jpeg = Jpeg {
+            segments: Vec::new(),
+            width: 0,
+            height: 0,
+            precision: 0,
+            components: Vec::new(),
+            scan_order: Vec::new(),
+            restart_interval: 0,
+            dc_tables: Default::default(),
+            ac_tables: Default::default(),
+            intervals: Vec::new(),
+            transposed: false,
+        };
+
+        let mut position = 2;
+        loop {
+            if data.get(position) != Some(&0xFF) {
+                anyhow::bail!("Invalid JPEG marker");
+            }
+            while data.get(position) == Some(&0xFF) {
+                position += 1;
+            }
+            let marker = *data.get(position).context("Truncated JPEG")?;
+            position += 1;
+            if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+                continue;
+            }
+            if marker == 0xD9 {
+                anyhow::bail!("JPEG has no image data");
+            }
+
+            let length = data.get(position..position + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize).context("Truncated JPEG")?;
+            let payload = data.get(position + 2..position + length.max(2)).context("Truncated JPEG segment")?;
+            position += length.max(2);
+
+            match marker {
+                0xC4 => jpeg.parse_huffman_tables(payload)?,
+                0xC0 | 0xC1 => jpeg.parse_frame(payload)?,
+                0xC2 => anyhow::bail!("Progressive JPEG"),
+                0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => anyhow::bail!("Unsupported JPEG coding (lossless, hierarchical or arithmetic)"),
+                0xDD => jpeg.restart_interval = payload.get(..2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize).context("Invalid DRI segment")?,
+                0xDA => {
+                    jpeg.parse_scan(payload)?;
+                    jpeg.read_intervals(&data[position..])?;
+                    return Ok(jpeg);
+                }
+                _ => {}
+            }
+            jpeg.segments.push((marker, payload));
+        }
+    }
+
+    fn parse_huffman_tables(&mut self, mut payload: &[u8]) -> Result<()> {
+        while let Some((&class_id, rest)) = payload.split_first() {
+            let (table, length) = HuffmanTable::parse(rest)?;
+            let id = (class_id & 0x0F) as usize;
+            if id > 3 {
+                anyhow::bail!("Invalid Huffman table id");
+            }
+            match class_id >> 4 {
+                0 => self.dc_tables[id] = Some(table),
+                1 => self.ac_tables[id] = Some(table),
+                _ => anyhow::bail!("Invalid Huffman table class"),
+            }
+            payload = &rest[length..];
+        }
+        Ok(())
+    }
+
+    fn parse_frame(&mut self, payload: &[u8]) -> Result<()> {
+        let header = payload.get(..6).context("Truncated SOF segment")?;
+        self.precision = header[0];
+        self.height = u16::from_be_bytes([header[1], header[2]]) as usize;
+        self.width = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let count = header[5] as usize;
+        if self.precision != 8 {
+            anyhow::bail!("{}-bit JPEG", self.precision);
+        }
+        if self.width == 0 || self.height == 0 {
+            anyhow::bail!("JPEG height is defined later in the file (DNL)");
+        }
+        SafetyLimits::current().check_image_size(self.width as u64, self.height as u64)?;
+
+        let specs = payload.get(6..6 + 3 * count).context("Truncated SOF segment")?;
+        for spec in specs.chunks(3) {
+            let (h, v) = ((spec[1] >> 4) as usize, (spec[1] & 0x0F) as usize);
+            if !(1..=4).contains(&h) || !(1..=4).contains(&v) {
+                anyhow::bail!("Invalid JPEG sampling factors");
+            }
+            self.components.push(Component {
+                id: spec[0],
+                h,
+                v,
+                quant_table: spec[2],
+                dc_table: 0,
+                ac_table: 0,
+                blocks_wide: 0,
+                blocks_high: 0,
+                blocks: Vec::new(),
+            });
+        }
+        if self.components.is_empty() {
+            anyhow::bail!("JPEG has no components");
+        }
+        Ok(())
+    }
+
+    fn parse_scan(&mut self, payload: &[u8]) -> Result<()> {
+        let count = *payload.first().context("Truncated SOS segment")? as usize;
+        let specs = payload.get(1..1 + 2 * count).context("Truncated SOS segment")?;
+        if count != self.components.len() {
+            anyhow::bail!("JPEG with several scans");
+        }
+        for spec in specs.chunks(2) {
+            let index = self.components.iter().position(|c| c.id == spec[0]).context("Unknown component in scan")?;
+            let component = &mut self.components[index];
+            component.dc_table = (spec[1] >> 4) as usize;
+            component.ac_table = (spec[1] & 0x0F) as usize;
+            if component.dc_table > 3 || component.ac_table > 3 {
+                anyhow::bail!("Invalid Huffman table id");
+            }
+            self.scan_order.push(index);
+        }
+        if payload.get(1 + 2 * count..4 + 2 * count) != Some(&[0, 63, 0]) {
+            anyhow::bail!("Unsupported JPEG scan");
+        }
+        Ok(())
+    }
+
+    /// Split the entropy-coded data at restart markers, removing byte stuffing
+    fn read_intervals(&mut self, data: &[u8]) -> Result<()> {
+        let mut intervals = vec![Vec::new()];
+        let mut i = 0;
+        loop {
+            let byte = *data.get(i).context("Truncated JPEG data")?;
+            if byte != 0xFF {
+                intervals.last_mut().expect("one interval").push(byte);
+                i += 1;
+                continue;
+            }
+            let mut j = i + 1;
+            while data.get(j) == Some(&0xFF) {
+                j += 1;
+            }
+            match *data.get(j).context("Truncated JPEG data")? {
+                0x00 => intervals.last_mut().expect("one interval").push(0xFF),
+                0xD0..=0xD7 => intervals.push(Vec::new()),
+                0xD9 => break,
+                _ => anyhow::bail!("JPEG with several scans"),
+            }
+            i = j + 1;
+        }
+        self.intervals = intervals;
+        Ok(())
+    }
+
+    /// Size of an MCU in pixels (a single-component scan is coded block by block)
+    fn mcu_size(&self) -> (usize, usize) {
+        if self.components.len() == 1 {
+            return (8, 8);
+        }
+        let h_max = self.components.iter().map(|c| c.h).max().unwrap_or(1);
+        let v_max = self.components.iter().map(|c| c.v).max().unwrap_or(1);
+        (8 * h_max, 8 * v_max)
+    }
+
+    /// Whether the edges that get mirrored end on a block boundary
+    fn can_rotate(&self, rotation: PageRotation) -> bool {
+        let (mcu_width, mcu_height) = self.mcu_size();
+        let whole_width = self.width.is_multiple_of(mcu_width);
+        let whole_height = self.height.is_multiple_of(mcu_height);
+        match rotation {
+            PageRotation::Clockwise => whole_height,
+            PageRotation::UpsideDown => whole_width && whole_height,
+            PageRotation::CounterClockwise => whole_width,
+        }
+    }
+
+    /// Number of MCUs across and down
+    fn mcu_grid(&self) -> (usize, usize) {
+        let (mcu_width, mcu_height) = self.mcu_size();
+        (self.width.div_ceil(mcu_width), self.height.div_ceil(mcu_height))
+    }
+
+    /// Blocks of component `index` in MCU `mcu`, in coding order
+    fn mcu_blocks(&self, index: usize, mcu: usize) -> Vec<usize> {
+        let component = &self.components[index];
+        if self.components.len() == 1 {
+            return vec![mcu];
+        }
+        let (mcus_wide, _) = self.mcu_grid();
+        let (mx, my) = (mcu % mcus_wide, mcu / mcus_wide);
+        let mut blocks = Vec::with_capacity(component.h * component.v);
+        for v in 0..component.v {
+            for h in 0..component.h {
+                blocks.push((my * component.v + v) * component.blocks_wide + mx * component.h + h);
+            }
+        }
+        blocks
+    }
+
+    fn set_block_grids(&mut self) {
+        let (mcus_wide, mcus_high) = self.mcu_grid();
+        let single = self.components.len() == 1;
+        for component in &mut self.components {
+            (component.blocks_wide, component.blocks_high) = if single {
+                (mcus_wide, mcus_high)
+            } else {
+                (mcus_wide * component.h, mcus_high * component.v)
+            };
+        }
+    }
+
+    fn mcu_count(&self) -> usize {
+        let (mcus_wide, mcus_high) = self.mcu_grid();
+        mcus_wide * mcus_high
+    }
+
+    fn decode_scan(&mut self) -> Result<()> {
+        self.set_block_grids();
+        for component in &mut self.components {
+            component.blocks = vec![[0i16; 64]; component.blocks_wide * component.blocks_high];
+        }
+        let decoder = |table: &Option<HuffmanTable>| table.as_ref().map(HuffmanDecoder::new).context("Missing Huffman table");
+        let dc: Vec<HuffmanDecoder> = self.scan_order.iter().map(|&i| decoder(&self.dc_tables[self.components[i].dc_table])).collect::<Result<_>>()?;
+        let ac: Vec<HuffmanDecoder> = self.scan_order.iter().map(|&i| decoder(&self.ac_tables[self.components[i].ac_table])).collect::<Result<_>>()?;
+
+        let intervals = std::mem::take(&mut self.intervals);
+        let mcus = self.mcu_count();
+        let per_interval = if self.restart_interval > 0 { self.restart_interval } else { mcus };
+        if intervals.len() < mcus.div_ceil(per_interval) {
+            anyhow::bail!("Truncated JPEG data");
+        }
+
+        for (interval, data) in intervals.iter().enumerate().take(mcus.div_ceil(per_interval)) {
+            let mut bits = BitReader::new(data);
+            let mut predictions = vec![0i16; self.scan_order.len()];
+            for mcu in interval * per_interval..((interval + 1) * per_interval).min(mcus) {
+                for (scan_index, &index) in self.scan_order.iter().enumerate() {
+                    for block in self.mcu_blocks(index, mcu) {
+                        let coefficients = &mut self.components[index].blocks[block];
+                        let size = dc[scan_index].decode(&mut bits)?;
+                        if size as u32 > MAX_DC_CATEGORY {
+                            anyhow::bail!("Invalid DC difference");
+                        }
+                        predictions[scan_index] = predictions[scan_index].wrapping_add(bits.value(size)?);
+                        coefficients[0] = predictions[scan_index];
+
+                        let mut k = 1;
+                        while k < 64 {
+                            let symbol = ac[scan_index].decode(&mut bits)?;
+                            let (run, size) = ((symbol >> 4) as usize, symbol & 0x0F);
+                            if size == 0 {
+                                if run != 15 {
+                                    break;
+                                }
+                                k += 16;
+                                continue;
+                            }
+                            k += run;
+                            if k > 63 {
+                                anyhow::bail!("Invalid AC coefficient index");
+                            }
+                            coefficients[ZIGZAG[k]] = bits.value(size)?;
+                            k += 1;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The image made of MCU columns `start..end`
+    fn columns(&self, start: usize, end: usize) -> Jpeg<'a> {
+        let (mcu_width, _) = self.mcu_size();
+        let single = self.components.len() == 1;
+        let components = self
+            .components
+            .iter()
+            .map(|component| {
+                let blocks_per_column = if single { 1 } else { component.h };
+                let (first, last) = (start * blocks_per_column, end * blocks_per_column);
+                Component {
+                    blocks_wide: last - first,
+                    blocks: component.blocks.chunks(component.blocks_wide).flat_map(|row| row[first..last].iter().copied()).collect(),
+                    ..*component
+                }
+            })
+            .collect();
+        Jpeg {
+            segments: self.segments.clone(),
+            width: (end * mcu_width).min(self.width) - start * mcu_width,
+            height: self.height,
+            precision: self.precision,
+            components,
+            scan_order: self.scan_order.clone(),
+            restart_interval: self.restart_interval,
+            dc_tables: Default::default(),
+            ac_tables: Default::default(),
+            intervals: Vec::new(),
+            transposed: self.transposed,
+        }
+    }
+
+    /// Move and transform the DCT blocks; sampling factors and dimensions are swapped by 90° rotations
+    fn rotate(&mut self, rotation: PageRotation) {
+        for component in &mut self.components {
+            let (wide, high) = (component.blocks_wide, component.blocks_high);
+            let (new_wide, new_high) = match rotation {
+                PageRotation::UpsideDown => (wide, high),
+                _ => (high, wide),
+            };
+            let mut blocks = vec![[0i16; 64]; wide * high];
+            for y in 0..new_high {
+                for x in 0..new_wide {
+                    let (source_x, source_y) = match rotation {
+                        PageRotation::Clockwise => (y, high - 1 - x),
+                        PageRotation::UpsideDown => (wide - 1 - x, high - 1 - y),
+                        PageRotation::CounterClockwise => (wide - 1 - y, x),
+                    };
+                    let source = &component.blocks[source_y * wide + source_x];
+                    let target = &mut blocks[y * new_wide + x];
+                    for v in 0..8 {
+                        for u in 0..8 {
+                            target[v * 8 + u] = match rotation {
+                                PageRotation::Clockwise if u % 2 == 1 => -source[u * 8 + v],
+                                PageRotation::Clockwise => source[u * 8 + v],
+                                PageRotation::UpsideDown if (u + v) % 2 == 1 => -source[v * 8 + u],
+                                PageRotation::UpsideDown => source[v * 8 + u],
+                                PageRotation::CounterClockwise if v % 2 == 1 => -source[u * 8 + v],
+                                PageRotation::CounterClockwise => source[u * 8 + v],
+                            };
+                        }
+                    }
+                }
+            }
+            component.blocks = blocks;
+            component.blocks_wide = new_wide;
+            component.blocks_high = new_high;
+            if rotation != PageRotation::UpsideDown {
+                std::mem::swap(&mut component.h, &mut component.v);
+            }
+        }
+        if rotation != PageRotation::UpsideDown {
+            std::mem::swap(&mut self.width, &mut self.height);
+            self.transposed = true;
+        }
+    }
+
+    /// Huffman symbols and extra bits of the scan, in order
+    fn code_scan(&self, mut emit: impl FnMut(Coded)) -> Result<()> {
+        let mcus = self.mcu_count();
+        let mut predictions = vec![0i16; self.scan_order.len()];
+        for mcu in 0..mcus {
+            if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval == 0 {
+                emit(Coded::Restart(((mcu / self.restart_interval - 1) % 8) as u8));
+                predictions.iter_mut().for_each(|p| *p = 0);
+            }
+            for (scan_index, &index) in self.scan_order.iter().enumerate() {
+                let component = &self.components[index];
+                for block in self.mcu_blocks(index, mcu) {
+                    let coefficients = &component.blocks[block];
+                    let (dc_table, ac_table) = (component.dc_table, 4 + component.ac_table);
+
+                    let difference = coefficients[0] as i32 - predictions[scan_index] as i32;
+                    predictions[scan_index] = coefficients[0];
+                    let (size, extra) = magnitude(difference);
+                    if size > MAX_DC_CATEGORY {
+                        anyhow::bail!("DC difference too large for baseline JPEG");
+                    }
+                    emit(Coded::Symbol { table: dc_table, symbol: size as u8, extra, size });
+
+                    let mut run = 0;
+                    for &k in &ZIGZAG[1..] {
+                        let value = coefficients[k] as i32;
+                        if value == 0 {
+                            run += 1;
+                            continue;
+                        }
+                        while run > 15 {
+                            emit(Coded::Symbol { table: ac_table, symbol: 0xF0, extra: 0, size: 0 });
+                            run -= 16;
+                        }
+                        let (size, extra) = magnitude(value);
+                        if size > 10 {
+                            anyhow::bail!("AC coefficient too large for baseline JPEG");
+                        }
+                        emit(Coded::Symbol { table: ac_table, symbol: ((run << 4) | size) as u8, extra, size });
+                        run = 0;
+                    }
+                    if run > 0 {
+                        emit(Coded::Symbol { table: ac_table, symbol: 0x00, extra: 0, size: 0 });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        // First pass: symbol frequencies of each table
+        let mut frequencies = [[0u64; 256]; 8];
+        self.code_scan(|coded| {
+            if let Coded::Symbol { table, symbol, .. } = coded {
+                frequencies[table][symbol as usize] += 1;
+            }
+        })?;
+        let tables: Vec<Option<HuffmanTable>> = frequencies
+            .iter()
+            .map(|frequencies| frequencies.iter().any(|&f| f > 0).then(|| HuffmanTable::optimal(frequencies)))
+            .collect();
+        let codes: Vec<[(u16, u8); 256]> = tables.iter().map(|table| table.as_ref().map(HuffmanTable::codes).unwrap_or([(0, 0); 256])).collect();
+
+        let mut out = vec![0xFF, 0xD8];
+        for &(marker, payload) in &self.segments {
+            match marker {
+                0xC4 => continue,
+                0xDB => write_segment(&mut out, marker, &self.quantization_tables(payload)?),
+                0xC0 | 0xC1 => write_segment(&mut out, marker, &self.frame_header()),
+                _ => write_segment(&mut out, marker, payload),
+            }
+        }
+
+        let mut dht = Vec::new();
+        for (index, table) in tables.iter().enumerate() {
+            if let Some(table) = table {
+                let class = if index < 4 { 0 } else { 1 };
+                dht.push((class << 4) | (index % 4) as u8);
+                dht.extend_from_slice(&table.counts);
+                dht.extend_from_slice(&table.symbols);
+            }
+        }
+        write_segment(&mut out, 0xC4, &dht);
+
+        let mut sos = vec![self.scan_order.len() as u8];
+        for &index in &self.scan_order {
+            let component = &self.components[index];
+            sos.push(component.id);
+            sos.push(((component.dc_table << 4) | component.ac_table) as u8);
+        }
+        sos.extend_from_slice(&[0, 63, 0]);
+        write_segment(&mut out, 0xDA, &sos);
+
+        // Second pass: entropy-coded data
+        let mut bits = BitWriter::default();
+        self.code_scan(|coded| match coded {
+            Coded::Symbol { table, symbol, extra, size } => {
+                let (code, length) = codes[table][symbol as usize];
+                bits.put(code as u32, length as u32);
+                bits.put(extra, size);
+            }
+            Coded::Restart(n) => {
+                bits.align();
+                bits.out.extend_from_slice(&[0xFF, 0xD0 + n]);
+            }
+        })?;
+        bits.align();
+        out.extend_from_slice(&bits.out);
+        out.extend_from_slice(&[0xFF, 0xD9]);
+        Ok(out)
+    }
+
+    fn frame_header(&self) -> Vec<u8> {
+        let mut header = vec![self.precision];
+        header.extend_from_slice(&(self.height as u16).to_be_bytes());
+        header.extend_from_slice(&(self.width as u16).to_be_bytes());
+        header.push(self.components.len() as u8);
+        for component in &self.components {
+            header.extend_from_slice(&[component.id, ((component.h << 4) | component.v) as u8, component.quant_table]);
+        }
+        header
+    }
+
+    /// DQT payload, with the tables transposed after a 90° rotation
+    fn quantization_tables(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        if !self.transposed {
+            return Ok(payload.to_vec());
+        }
+        let mut out = Vec::with_capacity(payload.len());
+        let mut rest = payload;
+        while let Some((&precision_id, tail)) = rest.split_first() {
+            let width = if precision_id >> 4 == 0 { 1 } else { 2 };
+            let values = tail.get(..64 * width).context("Truncated DQT segment")?;
+            let mut natural = [0usize; 64];
+            for (k, &index) in ZIGZAG.iter().enumerate() {
+                natural[index] = k;
+            }
+            out.push(precision_id);
+            for &index in &ZIGZAG {
+                // Coefficient (v, u) of the rotated image was (u, v)
+                let source = natural[(index % 8) * 8 + index / 8];
+                out.extend_from_slice(&values[source * width..(source + 1) * width]);
+            }
+            rest = &tail[64 * width..];
+        }
+        Ok(out)
+    }
+}
+
+/// Category (bit size) and extra bits of a coefficient or DC difference
+fn magnitude(value: i32) -> (u32, u32) {
+    let size = 32 - value.unsigned_abs().leading_zeros();
+    let extra = if value < 0 { (value - 1) as u32 & ((1 << size) - 1) } else { value as u32 };
+    (size, extra)
+}
+
+fn write_segment(out: &mut Vec<u8>, marker: u8, payload: &[u8]) {
+    out.extend_from_slice(&[0xFF, marker]);
+    out.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{DynamicImage, GenericImageView, GrayImage, RgbImage};
+
+    const ROTATIONS: [PageRotation; 3] = [PageRotation::Clockwise, PageRotation::UpsideDown, PageRotation::CounterClockwise];
+
+    fn rgb(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x * 9) as u8, (y * 13) as u8, ((x * y) % 256) as u8])
+        }))
+    }
+
+    fn gray(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| image::Luma([((x * 7) ^ (y * 11)) as u8])))
+    }
+
+    fn encode(image: &DynamicImage) -> Vec<u8> {
+        let mut data = Vec::new();
+        image.write_with_encoder(JpegEncoder::new_with_quality(&mut data, 90)).unwrap();
+        data
+    }
+
+    /// The same JPEG with a restart marker every `interval` MCUs
+    fn with_restart_interval(data: &[u8], interval: u16) -> Vec<u8> {
+        let dri = interval.to_be_bytes();
+        let mut jpeg = Jpeg::parse(data).unwrap();
+        jpeg.decode_scan().unwrap();
+        jpeg.restart_interval = interval as usize;
+        jpeg.segments.push((0xDD, &dri));
+        jpeg.encode().unwrap()
+    }
+
+    fn decode(data: &[u8]) -> DynamicImage {
+        image::load_from_memory_with_format(data, image::ImageFormat::Jpeg).unwrap()
+    }
+
+    /// Largest difference between two images of the same size, per channel
+    /// Rotated blocks go through the decoder's IDCT transposed, which rounds slightly differently.
+    fn max_difference(a: &DynamicImage, b: &DynamicImage) -> u8 {
+        assert_eq!(a.dimensions(), b.dimensions());
+        a.to_rgb8().as_raw().iter().zip(b.to_rgb8().as_raw()).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0)
+    }
+
+    fn rotate_pixels(image: &DynamicImage, rotation: PageRotation) -> DynamicImage {
+        match rotation {
+            PageRotation::Clockwise => image.rotate90(),
+            PageRotation::UpsideDown => image.rotate180(),
+            PageRotation::CounterClockwise => image.rotate270(),
+        }
+    }
+
+    /// Images covering RGB and gray, with and without restart intervals
+    fn samples(width: u32, height: u32) -> Vec<Vec<u8>> {
+        let rgb = encode(&rgb(width, height));
+        let gray = encode(&gray(width, height));
+        vec![with_restart_interval(&rgb, 2), with_restart_interval(&gray, 3), rgb, gray]
+    }
+
+    #[test]
+    fn restart_intervals_keep_the_image() {
+        for image in [rgb(40, 24), gray(20, 12)] {
+            let data = encode(&image);
+            let restarted = with_restart_interval(&data, 2);
+            assert!(restarted.windows(2).any(|marker| marker == [0xFF, 0xDD]));
+            assert!(restarted.windows(2).any(|marker| marker == [0xFF, 0xD1]));
+            assert_eq!(decode(&restarted), decode(&data));
+        }
+    }
+
+    #[test]
+    fn rotates_mcu_aligned_images() {
+        for data in samples(32, 24) {
+            let decoded = decode(&data);
+            for rotation in ROTATIONS {
+                let rotated = decode(&rotate_jpeg_lossless(&data, rotation).unwrap());
+                assert!(max_difference(&rotated, &rotate_pixels(&decoded, rotation)) <= 2, "{:?}", rotation);
+            }
+        }
+    }
+
+    #[test]
+    fn rotates_when_only_the_kept_edge_is_partial() {
+        // 20 pixels wide (partial last MCU column), 16 high: the right edge becomes the bottom edge
+        for data in samples(20, 16) {
+            let rotated = decode(&rotate_jpeg_lossless(&data, PageRotation::Clockwise).unwrap());
+            assert!(max_difference(&rotated, &decode(&data).rotate90()) <= 2);
+            assert!(rotate_jpeg_lossless(&data, PageRotation::UpsideDown).is_err());
+            assert!(rotate_jpeg_lossless(&data, PageRotation::CounterClockwise).is_err());
+        }
+        // 16 wide, 20 high: only a counter-clockwise rotation keeps the partial bottom edge out of sight
+        for data in samples(16, 20) {
+            let rotated = decode(&rotate_jpeg_lossless(&data, PageRotation::CounterClockwise).unwrap());
+            assert!(max_difference(&rotated, &decode(&data).rotate270()) <= 2);
+            assert!(rotate_jpeg_lossless(&data, PageRotation::Clockwise).is_err());
+            assert!(rotate_jpeg_lossless(&data, PageRotation::UpsideDown).is_err());
+        }
+    }
+
+    #[test]
+    fn splits_on_mcu_columns() {
+        for (width, height) in [(32, 24), (30, 13)] {
+            for data in samples(width, height) {
+                let decoded = decode(&data);
+                let (left, right, column) = split_jpeg_lossless(&data, 13).unwrap();
+                assert_eq!(column, 16);
+                assert_eq!(decode(&left), decoded.crop_imm(0, 0, 16, height));
+                assert_eq!(decode(&right), decoded.crop_imm(16, 0, width - 16, height));
+            }
+        }
+    }
+
+    #[test]
+    fn split_column_stays_inside_the_image() {
+        let data = encode(&rgb(40, 8));
+        assert_eq!(split_jpeg_lossless(&data, 0).unwrap().2, 8);
+        assert_eq!(split_jpeg_lossless(&data, 39).unwrap().2, 32);
+        assert!(split_jpeg_lossless(&encode(&gray(8, 16)), 4).is_err());
+    }
+
+    #[test]
+    fn refuses_other_data() {
+        assert!(rotate_jpeg_lossless(b"\x89PNG\r\n\x1a\n", PageRotation::Clockwise).is_err());
+        let data = encode(&rgb(16, 16));
+        assert!(rotate_jpeg_lossless(&data[..data.len() / 2], PageRotation::Clockwise).is_err());
+    }
+}
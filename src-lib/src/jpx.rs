@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use image::DynamicImage;
+
+use crate::limits::SafetyLimits;
+
+/// How the alpha channel of a JPEG 2000 image is used (the image dictionary's SMaskInData)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JpxAlpha {
+    /// 0: the alpha channel is ignored
+    Ignore,
+    /// 1: the alpha channel is the image's soft mask
+    Mask,
+    /// 2: same, with colour samples premultiplied by alpha
+    Premultiplied,
+}
+
+impl JpxAlpha {
+    /// Value of an SMaskInData entry (0 when absent)
+    pub fn from_smask_in_data(value: i64) -> JpxAlpha {
+        match value {
+            1 => JpxAlpha::Mask,
+            2 => JpxAlpha::Premultiplied,
+            _ => JpxAlpha::Ignore,
+        }
+    }
+}
+
+/// Decode a JPXDecode image (JP2 file or raw codestream) to 8-bit grayscale or RGB
+/// ICC-tagged and CMYK images are converted to sRGB. A used alpha channel is kept (straight,
+/// not premultiplied) so it can be handled like a soft mask; an ignored one is dropped.
+pub fn decode_jpx(data: &[u8], alpha: JpxAlpha) -> Result<DynamicImage> {
+    let image = hayro_jpeg2000::Image::new(data, &hayro_jpeg2000::DecodeSettings::default())
+        .map_err(|e| anyhow::anyhow!("Invalid JPEG 2000 image: {}", e))?;
+    SafetyLimits::current().check_image_size(image.width() as u64, image.height() as u64)?;
+
+    let image = DynamicImage::from_decoder(image).context("Failed to decode JPEG 2000 image")?;
+    Ok(match (image, alpha) {
+        (image @ DynamicImage::ImageLumaA8(_), JpxAlpha::Ignore) => DynamicImage::ImageLuma8(image.to_luma8()),
+        (image @ DynamicImage::ImageRgba8(_), JpxAlpha::Ignore) => DynamicImage::ImageRgb8(image.to_rgb8()),
+        (DynamicImage::ImageLumaA8(mut gray), JpxAlpha::Premultiplied) => {
+            gray.pixels_mut().for_each(|p| unpremultiply(&mut p.0));
+            DynamicImage::ImageLumaA8(gray)
+        }
+        (DynamicImage::ImageRgba8(mut rgba), JpxAlpha::Premultiplied) => {
+            rgba.pixels_mut().for_each(|p| unpremultiply(&mut p.0));
+            DynamicImage::ImageRgba8(rgba)
+        }
+        (image, _) => image,
+    })
+}
+
+/// Divide the colour samples of a pixel (alpha last) by its alpha
+fn unpremultiply(pixel: &mut [u8]) {
+    let (alpha, colors) = pixel.split_last_mut().expect("pixel has an alpha sample");
+    if *alpha == 0 {
+        return;
+    }
+    for c in colors {
+        *c = ((*c as u32 * 255 + *alpha as u32 / 2) / *alpha as u32).min(255) as u8;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Raw codestream of an 8 x 8 image of 8-bit `components` whose packets are all empty:
+    /// every coefficient is zero, so every sample decodes to the DC level (128)
+    pub(crate) fn flat_codestream(components: u8) -> Vec<u8> {
+        let mut data = vec![0xFF, 0x4F]; // SOC
+        data.extend_from_slice(&[0xFF, 0x51, 0, 38 + 3 * components, 0, 0]); // SIZ, no capabilities
+        for value in [8u32, 8, 0, 0, 8, 8, 0, 0] {
+            data.extend_from_slice(&value.to_be_bytes()); // image and tile size and offsets
+        }
+        data.extend_from_slice(&[0, components]);
+        for _ in 0..components {
+            data.extend_from_slice(&[7, 1, 1]); // unsigned 8-bit, no subsampling
+        }
+        data.extend_from_slice(&[0xFF, 0x52, 0, 12, 0, 0, 0, 1, 0, 0, 4, 4, 0, 1]); // COD: 1 layer, no DWT levels, 5/3
+        data.extend_from_slice(&[0xFF, 0x5C, 0, 4, 0x40, 0x40]); // QCD: no quantization
+        let tile_length = 14 + components as u32; // SOT and SOD markers, one empty packet per component
+        data.extend_from_slice(&[0xFF, 0x90, 0, 10, 0, 0]); // SOT: tile 0
+        data.extend_from_slice(&tile_length.to_be_bytes());
+        data.extend_from_slice(&[0, 1]);
+        data.extend_from_slice(&[0xFF, 0x93]); // SOD
+        data.extend(std::iter::repeat_n(0u8, components as usize));
+        data.extend_from_slice(&[0xFF, 0xD9]); // EOC
+        data
+    }
+
+    #[test]
+    fn decodes_codestream() {
+        let image = decode_jpx(&flat_codestream(1), JpxAlpha::Ignore).unwrap();
+        assert_eq!(image.color(), image::ColorType::L8);
+        assert_eq!((image.width(), image.height()), (8, 8));
+        assert!(image.to_luma8().pixels().all(|p| p[0] == 128));
+
+        assert!(decode_jpx(b"not a JPEG 2000 image", JpxAlpha::Ignore).is_err());
+    }
+
+    #[test]
+    fn alpha_channel_by_smask_in_data() {
+        let data = flat_codestream(2);
+        let ignored = decode_jpx(&data, JpxAlpha::from_smask_in_data(0)).unwrap();
+        assert_eq!(ignored.color(), image::ColorType::L8);
+
+        let mask = decode_jpx(&data, JpxAlpha::from_smask_in_data(1)).unwrap();
+        assert_eq!(mask.color(), image::ColorType::La8);
+        assert!(mask.to_luma_alpha8().pixels().all(|p| p.0 == [128, 128]));
+
+        // Premultiplied: 128 at half opacity is white
+        let premultiplied = decode_jpx(&data, JpxAlpha::from_smask_in_data(2)).unwrap();
+        assert!(premultiplied.to_luma_alpha8().pixels().all(|p| p.0 == [255, 128]));
+    }
+
+    #[test]
+    fn unpremultiplies_pixels() {
+        let mut pixel = [64, 32, 0, 128];
+        unpremultiply(&mut pixel);
+        assert_eq!(pixel, [128, 64, 0, 128]);
+
+        // Transparent pixels are left alone, rounding never passes 255
+        let mut pixel = [10, 0];
+        unpremultiply(&mut pixel);
+        assert_eq!(pixel, [10, 0]);
+        let mut pixel = [200, 100];
+        unpremultiply(&mut pixel);
+        assert_eq!(pixel, [255, 100]);
+    }
+}
@@ -2,23 +2,82 @@
 
 pub mod pdfium_loader;
 pub mod direct_extract;
+pub mod lopdf_extract;
 pub mod conversion;
+pub mod pdf_document;
+pub mod metadata;
+pub mod comic_info;
+pub mod comic_book_info;
+pub mod cbz;
+pub mod cbz_reader;
+pub mod cbz_recovery;
+pub mod archive_entry;
+pub mod page_order;
+pub mod pdf_options;
+pub mod pdf_writer;
+pub mod pdf_optimize;
+pub mod pdf_encryption;
+pub mod pdfa;
+pub mod verify;
+pub mod limits;
+pub mod rar_listing;
+pub mod image_format;
+pub mod bilevel;
+pub mod jpx;
+pub mod transparency;
+pub mod volumes;
+pub mod merge;
+pub mod cbz_edit;
+pub mod jpeg_transform;
+pub mod spreads;
+
+#[cfg(test)]
+mod test_zip;
 
 // Re-export main types and functions for convenience
 pub use pdfium_loader::bind_pdfium;
 pub use direct_extract::{
     ImageCandidate,
     find_best_image_candidate,
+    extract_image,
     extract_image_bytes,
     extract_image_bytes_as_jpeg,
+    extract_bilevel_image,
+    is_bilevel_image_object,
     log_page_diagnostic,
     MIN_COVERAGE_FOR_DIRECT_EXTRACT,
 };
+pub use lopdf_extract::{PdfBackend, UnsupportedPageError, extract_page_images_lopdf, load_lopdf_document, lopdf_chapters, lopdf_metadata, lopdf_page_sizes, read_pdf_chapters_lopdf, read_pdf_metadata_lopdf, unsupported_page_error};
 pub use conversion::{
     convert_pdf_to_images_parallel,
     extract_images_lossless_at_dpi,
     create_pdf_from_images,
 };
+pub use pdf_document::{PdfPasswordError, document_chapters, document_metadata, load_pdf_document, load_pdf_file, password_error, read_pdf_chapters, read_pdf_metadata};
+pub use metadata::{Credit, DocumentMetadata, ReadingDirection};
+pub use cbz::{ArchivePasswordError, archive_password_error, cbz_file_options, list_cbz_pages, open_cbz_entry, read_archive_metadata, read_cbz_entry, read_cbz_metadata, sniff_cbz_entry};
+pub use cbz_reader::{CbzPages, CbzReader};
+pub use cbz_recovery::{LostEntry, RecoveredCbz, recover_cbz};
+pub use comic_info::{ComicInfo, ComicPageInfo, COMIC_INFO_FILENAME, find_comic_info, is_comic_info_file, read_comic_info_entry, read_comic_info_from_zip};
+pub use comic_book_info::{ComicBookInfo, comic_book_info_from_comment, read_comic_book_info_from_zip};
+pub use archive_entry::{ArchiveEntry, EntryKind, classify_entry, entry_folder, entry_kind};
+pub use image_format::PageImageFormat;
+pub use jpx::{JpxAlpha, decode_jpx};
+pub use transparency::{Transparency, has_transparency, parse_background_color};
+pub use volumes::{DEFAULT_VOLUME_TEMPLATE, Volume, VolumeSplit, folder_chapters, plan_volumes, volume_file_name};
+pub use cbz_edit::{CbzEditor, EditReport, PageEdit, PageRotation};
+pub use jpeg_transform::{rotate_jpeg_lossless, split_jpeg_lossless};
+pub use spreads::{DEFAULT_SPREAD_RATIO, SpreadReport, SpreadSplit, split_spreads};
+pub use merge::{MergeInput, MergeSeparator, is_pdf_data, merge_pages, merged_metadata};
+pub use bilevel::{BilevelFilter, BilevelImage, BilevelStream, CcittParams, decode_ccitt, decode_jbig2, infer_ccitt_params};
+pub use page_order::{PageOrder, natural_cmp, sort_pages};
+pub use pdf_options::{DEFAULT_JPEG_QUALITY, ImageEncoding, PdfMetadataSettings, PdfOutputOptions, PageLayout, PdfConformance};
+pub use pdf_encryption::{PdfEncryption, PdfPermissions};
+pub use limits::{ArchiveBudget, LimitError, SafetyLimits, check_image_header, decode_image, limit_error, sanitize_entry_path, set_safety_limits};
+pub use rar_listing::{RarListEntry, check_rar_listing, parse_lsar_listing};
+pub use verify::{ArchiveVerifier, IssueKind, IssueSeverity, VerifyIssue, VerifyReport, verify_cbz};
+pub use pdf_writer::{ImagePlacement, PdfStreamWriter, write_pdf_from_images, write_pdf_from_pages};
+pub use pdf_optimize::{DEFAULT_OPTIMIZE_DPI, OptimizeReport, optimize_pdf_file};
 
 // Re-export pdfium_render types that are part of the public API
 pub use pdfium_render::prelude::Pdfium;
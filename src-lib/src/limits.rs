@@ -0,0 +1,314 @@
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageDecoder, ImageError, ImageReader};
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::sync::RwLock;
+
+use crate::image_format::PageImageFormat;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Entries smaller than this are not checked against the compression ratio
+/// (small text files legitimately compress very well)
+const RATIO_MIN_SIZE: u64 = MIB;
+
+/// Safety limits applied when reading untrusted archives and images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SafetyLimits {
+    /// Maximum number of entries in an archive
+    pub max_entries: usize,
+    /// Maximum uncompressed size of a single entry, in bytes
+    pub max_entry_size: u64,
+    /// Maximum uncompressed size of all entries read from an archive, in bytes
+    pub max_total_size: u64,
+    /// Maximum image width or height, in pixels
+    pub max_image_dimension: u32,
+    /// Maximum image area, in pixels (bounds decoder allocations)
+    pub max_image_pixels: u64,
+    /// Maximum ratio of uncompressed to compressed size of an entry (zip bombs)
+    /// Checked on entries of 1 MiB or more. Deflated BMP, TIFF or PPM pages and mostly white scans
+    /// reach a few hundred to one, so the default of 1000 only stops archives built to explode.
+    pub max_compression_ratio: u64,
+}
+
+const DEFAULT_LIMITS: SafetyLimits = SafetyLimits {
+    max_entries: 10_000,
+    max_entry_size: 512 * MIB,
+    max_total_size: 32 * 1024 * MIB,
+    max_image_dimension: 65_535,
+    max_image_pixels: 256 * 1024 * 1024,
+    max_compression_ratio: 1000,
+};
+
+/// Limits used by every reader of the library (process-wide)
+static LIMITS: RwLock<SafetyLimits> = RwLock::new(DEFAULT_LIMITS);
+
+impl Default for SafetyLimits {
+    fn default() -> Self {
+        DEFAULT_LIMITS
+    }
+}
+
+impl SafetyLimits {
+    /// Limits currently in effect
+    pub fn current() -> SafetyLimits {
+        *LIMITS.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Check image dimensions read from a header, before decoding
+    pub fn check_image_size(&self, width: u64, height: u64) -> Result<(), LimitError> {
+        if width > self.max_image_dimension as u64
+            || height > self.max_image_dimension as u64
+            || width.saturating_mul(height) > self.max_image_pixels
+        {
+            return Err(LimitError::ImageTooLarge { width, height });
+        }
+        Ok(())
+    }
+
+    /// Decoder limits for the `image` crate
+    fn image_limits(&self) -> image::Limits {
+        let mut limits = image::Limits::default();
+        limits.max_image_width = Some(self.max_image_dimension);
+        limits.max_image_height = Some(self.max_image_dimension);
+        // Up to 16-bit RGBA output
+        limits.max_alloc = Some(self.max_image_pixels.saturating_mul(8));
+        limits
+    }
+}
+
+/// Replace the limits used by every reader of the library
+pub fn set_safety_limits(limits: SafetyLimits) {
+    *LIMITS.write().unwrap_or_else(|e| e.into_inner()) = limits;
+}
+
+/// Error raised when an archive or image exceeds a `SafetyLimits` limit, or an entry path is unsafe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitError {
+    TooManyEntries { count: usize, limit: usize },
+    EntryTooLarge { name: String, limit: u64 },
+    ArchiveTooLarge { limit: u64 },
+    CompressionRatio { name: String, ratio: u64, limit: u64 },
+    ImageTooLarge { width: u64, height: u64 },
+    ImageMemory,
+    UnsafePath { path: String },
+}
+
+impl LimitError {
+    /// Stable code for frontends (e.g. Tauri command errors)
+    pub fn code(&self) -> &'static str {
+        match self {
+            LimitError::TooManyEntries { .. } => "LIMIT_TOO_MANY_ENTRIES",
+            LimitError::EntryTooLarge { .. } => "LIMIT_ENTRY_TOO_LARGE",
+            LimitError::ArchiveTooLarge { .. } => "LIMIT_ARCHIVE_TOO_LARGE",
+            LimitError::CompressionRatio { .. } => "LIMIT_COMPRESSION_RATIO",
+            LimitError::ImageTooLarge { .. } => "LIMIT_IMAGE_TOO_LARGE",
+            LimitError::ImageMemory => "LIMIT_IMAGE_MEMORY",
+            LimitError::UnsafePath { .. } => "UNSAFE_ENTRY_PATH",
+        }
+    }
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::TooManyEntries { count, limit } =>
+                write!(f, "Archive has {} entries, the limit is {}", count, limit),
+            LimitError::EntryTooLarge { name, limit } =>
+                write!(f, "Entry {} is larger than the {} MiB limit", name, limit / MIB),
+            LimitError::ArchiveTooLarge { limit } =>
+                write!(f, "Archive contents are larger than the {} MiB limit", limit / MIB),
+            LimitError::CompressionRatio { name, ratio, limit } =>
+                write!(f, "Entry {} expands {}x, the limit is {}x (possible zip bomb)", name, ratio, limit),
+            LimitError::ImageTooLarge { width, height } =>
+                write!(f, "Image is {}x{} pixels, larger than the allowed size", width, height),
+            LimitError::ImageMemory =>
+                write!(f, "Image needs more memory to decode than allowed"),
+            LimitError::UnsafePath { path } =>
+                write!(f, "Unsafe entry path {:?} (absolute or leaving the archive root)", path),
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+/// Find a `LimitError` in an error chain
+pub fn limit_error(error: &anyhow::Error) -> Option<&LimitError> {
+    error.chain().find_map(|cause| cause.downcast_ref::<LimitError>())
+}
+
+/// Tracks the entries and bytes read from one archive against the current limits
+#[derive(Debug)]
+pub struct ArchiveBudget {
+    limits: SafetyLimits,
+    entries: usize,
+    total_size: u64,
+}
+
+impl Default for ArchiveBudget {
+    fn default() -> Self {
+        ArchiveBudget::new()
+    }
+}
+
+impl ArchiveBudget {
+    pub fn new() -> ArchiveBudget {
+        ArchiveBudget { limits: SafetyLimits::current(), entries: 0, total_size: 0 }
+    }
+
+    /// Check the number of entries listed by an archive, before reading them
+    pub fn check_entry_count(&self, count: usize) -> Result<(), LimitError> {
+        if count > self.limits.max_entries {
+            return Err(LimitError::TooManyEntries { count, limit: self.limits.max_entries });
+        }
+        Ok(())
+    }
+
+    /// Read an entry, stopping as soon as it exceeds the entry or archive size limits
+    /// `compressed_size` is the stored size of the entry, used for the compression ratio check.
+    pub fn read_entry<R: Read>(&mut self, name: &str, compressed_size: u64, reader: R) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        reader
+            .take(self.limits.max_entry_size + 1)
+            .read_to_end(&mut data)
+            .context(format!("Failed to read {}", name))?;
+        self.add_entry(name, compressed_size, data.len() as u64)?;
+        Ok(data)
+    }
+
+    /// Account for an entry of known sizes (e.g. a file extracted to disk)
+    pub fn add_entry(&mut self, name: &str, compressed_size: u64, size: u64) -> Result<(), LimitError> {
+        self.entries += 1;
+        self.check_entry_count(self.entries)?;
+
+        if size > self.limits.max_entry_size {
+            return Err(LimitError::EntryTooLarge { name: name.to_string(), limit: self.limits.max_entry_size });
+        }
+        if size >= RATIO_MIN_SIZE {
+            let ratio = size / compressed_size.max(1);
+            if ratio > self.limits.max_compression_ratio {
+                return Err(LimitError::CompressionRatio {
+                    name: name.to_string(),
+                    ratio,
+                    limit: self.limits.max_compression_ratio,
+                });
+            }
+        }
+
+        self.total_size += size;
+        if self.total_size > self.limits.max_total_size {
+            return Err(LimitError::ArchiveTooLarge { limit: self.limits.max_total_size });
+        }
+        Ok(())
+    }
+}
+
+/// Normalize an archive entry path to a relative path with `/` separators
+/// Absolute paths (including UNC and `\\?\` paths), drive prefixes (`C:`) and `..` components are rejected.
+/// Colons elsewhere are kept (`Vol. 1: The Beginning/01.jpg`).
+pub fn sanitize_entry_path(path: &str) -> Result<String, LimitError> {
+    let unsafe_path = || LimitError::UnsafePath { path: path.to_string() };
+    let normalized = path.replace('\\', "/");
+    if normalized.starts_with('/') || has_drive_prefix(&normalized) {
+        return Err(unsafe_path());
+    }
+
+    let mut parts = Vec::new();
+    for part in normalized.split('/') {
+        match part {
+            "" | "." => continue,
+            ".." => return Err(unsafe_path()),
+            _ => parts.push(part),
+        }
+    }
+    if parts.is_empty() {
+        return Err(unsafe_path());
+    }
+    Ok(parts.join("/"))
+}
+
+/// Windows drive prefix: `C:/x` (absolute) or `C:x` (relative to the drive's current directory)
+/// A colon followed by a space is title punctuation (`A: Prologue/01.jpg`), not a drive.
+fn has_drive_prefix(path: &str) -> bool {
+    match path.as_bytes() {
+        [letter, b':', rest @ ..] => letter.is_ascii_alphabetic() && rest.first() != Some(&b' '),
+        _ => false,
+    }
+}
+
+/// Check an image's dimensions from its header, without decoding it
+/// Images whose header cannot be read are left to the decoder limits.
+pub fn check_image_header(data: &[u8]) -> Result<(), LimitError> {
+    match imagesize::blob_size(data) {
+        Ok(size) => SafetyLimits::current().check_image_size(size.width as u64, size.height as u64),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Decode an image within the current dimension and allocation limits
+/// The format is recognized from the content. Animated GIF and WebP images decode to their first frame.
+pub fn decode_image(data: &[u8]) -> Result<DynamicImage> {
+    let limits = SafetyLimits::current();
+    check_image_header(data)?;
+
+    let format = PageImageFormat::sniff(data);
+    if let Some(format) = format.filter(|format| !format.is_supported()) {
+        anyhow::bail!("{} images are not supported by this build (enable the `avif` feature)", format.name());
+    }
+    if let Some(format) = format.filter(|format| format.is_animated(data)) {
+        eprintln!("[WARNING] Animated {} image: only its first frame is used", format.name());
+    }
+
+    let decoded = if format == Some(PageImageFormat::JpegXl) {
+        decode_jpeg_xl(data, limits.image_limits())
+    } else {
+        let mut reader = ImageReader::new(Cursor::new(data))
+            .with_guessed_format()
+            .context("Failed to detect image format")?;
+        reader.limits(limits.image_limits());
+        reader.decode()
+    };
+    match decoded {
+        Ok(image) => Ok(image),
+        Err(ImageError::Limits(_)) => Err(LimitError::ImageMemory.into()),
+        Err(e) => Err(anyhow::Error::new(e).context("Failed to decode image")),
+    }
+}
+
+/// Decode a JPEG XL image (pure-Rust decoder)
+fn decode_jpeg_xl(data: &[u8], limits: image::Limits) -> image::ImageResult<DynamicImage> {
+    let mut decoder = jxl_oxide::integration::JxlDecoder::new(Cursor::new(data))?;
+    decoder.set_limits(limits)?;
+    DynamicImage::from_decoder(decoder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression_ratio() {
+        let mut budget = ArchiveBudget::new();
+        // Small entries compress as they like
+        assert_eq!(budget.add_entry("ComicInfo.xml", 1, MIB - 1), Ok(()));
+        // A deflated bitmap page far above 100:1
+        assert_eq!(budget.add_entry("001.bmp", 40 * 1024, 24 * MIB), Ok(()));
+        assert!(matches!(budget.add_entry("bomb.bmp", 1024, 2 * MIB), Err(LimitError::CompressionRatio { ratio: 2048, limit: 1000, .. })));
+    }
+
+    #[test]
+    fn keeps_relative_paths() {
+        assert_eq!(sanitize_entry_path("a: b/01.jpg"), Ok("a: b/01.jpg".to_string()));
+        assert_eq!(sanitize_entry_path("Vol. 1: The Beginning/01.jpg"), Ok("Vol. 1: The Beginning/01.jpg".to_string()));
+        assert_eq!(sanitize_entry_path("ch:1/p:2.jpg"), Ok("ch:1/p:2.jpg".to_string()));
+        assert_eq!(sanitize_entry_path("A: Prologue/01.jpg"), Ok("A: Prologue/01.jpg".to_string()));
+        assert_eq!(sanitize_entry_path("./pages\\\\01.jpg"), Ok("pages/01.jpg".to_string()));
+    }
+
+    #[test]
+    fn rejects_escaping_paths() {
+        for path in ["C:/x", "C:x", "C:", "c:\\x", "/x", "\\x", "//server/share/x", "\\\\?\\C:\\x", "../x", "a/../../x", "", "./"] {
+            assert_eq!(sanitize_entry_path(path), Err(LimitError::UnsafePath { path: path.to_string() }), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,1337 @@
+use anyhow::{Context, Result};
+use image::{DynamicImage, GrayImage, ImageBuffer, ImageFormat, RgbImage};
+use lopdf::content::Content;
+use lopdf::encryption::DecryptionError;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{Cursor, Read};
+
+use crate::bilevel::{decode_ccitt, decode_jbig2, BilevelImage, CcittParams};
+use crate::image_format::PageImageFormat;
+use crate::jpx::{decode_jpx, JpxAlpha};
+use crate::limits::{decode_image, SafetyLimits};
+use crate::metadata::{parse_pdf_date, split_list, DocumentMetadata};
+use crate::pdf_document::PdfPasswordError;
+use crate::pdfium_loader::bind_pdfium;
+use crate::transparency::Transparency;
+
+/// Distance, in points, an image may extend past the page edge (rounding in PDF writers)
+const EDGE_TOLERANCE: f32 = 1.0;
+
+/// Same tolerance relative to the page size, for large pages
+const EDGE_TOLERANCE_RATIO: f32 = 0.005;
+
+/// Form XObjects nested deeper than this are not followed
+const MAX_FORM_DEPTH: usize = 8;
+
+/// Content stream operators that change state without painting anything
+const NON_PAINTING_OPERATORS: &[&str] = &[
+    "q", "Q", "cm", "gs", "w", "J", "j", "M", "d", "ri", "i",
+    "g", "G", "rg", "RG", "k", "K", "cs", "CS", "sc", "scn", "SC", "SCN",
+    "BMC", "BDC", "EMC", "MP", "DP", "BX", "EX",
+];
+
+/// PDF engine used to read PDF pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdfBackend {
+    /// PDFium: renders any page, extracts embedded images
+    Pdfium,
+    /// Built-in lopdf reader: image-only pages (one full-page image each), no rendering
+    Lopdf,
+}
+
+impl PdfBackend {
+    /// PDFium when the library can be loaded, the built-in reader otherwise or when `force_lopdf` is set
+    pub fn select(force_lopdf: bool) -> PdfBackend {
+        if force_lopdf {
+            return PdfBackend::Lopdf;
+        }
+        match bind_pdfium() {
+            Ok(_) => PdfBackend::Pdfium,
+            Err(e) => {
+                eprintln!("[LOPDF] PDFium is not available ({:?}), using the built-in reader for image-only PDFs", e);
+                PdfBackend::Lopdf
+            }
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PdfBackend::Pdfium => "PDFium",
+            PdfBackend::Lopdf => "built-in (image-only PDFs)",
+        }
+    }
+}
+
+/// Error raised when the built-in reader cannot reproduce a page faithfully
+/// The page needs PDFium (e.g. it has text, drawings, or several images); the reason is the error's cause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedPageError {
+    /// 1-based page number
+    pub page: u32,
+}
+
+impl fmt::Display for UnsupportedPageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Page {} cannot be converted without PDFium", self.page)
+    }
+}
+
+impl std::error::Error for UnsupportedPageError {}
+
+/// Find an `UnsupportedPageError` in an error chain
+/// It is attached as context, which only `anyhow::Error::downcast_ref` finds (not the error chain).
+pub fn unsupported_page_error(error: &anyhow::Error) -> Option<UnsupportedPageError> {
+    error.downcast_ref::<UnsupportedPageError>().copied()
+        .or_else(|| error.chain().find_map(|cause| cause.downcast_ref::<UnsupportedPageError>().copied()))
+}
+
+/// Load a PDF with lopdf, decrypting it with `password` (RC4-encrypted PDFs only)
+/// Password failures are reported as `PdfPasswordError`.
+pub fn load_lopdf_document(pdf_data: &[u8], password: Option<&str>) -> Result<Document> {
+    let mut document = Document::load_mem(pdf_data).context("Failed to load PDF")?;
+    if !document.is_encrypted() {
+        return Ok(document);
+    }
+
+    let password = password.filter(|p| !p.is_empty());
+    match document.decrypt(password.unwrap_or("")) {
+        Ok(()) => Ok(document),
+        Err(lopdf::Error::Decryption(DecryptionError::IncorrectPassword)) => Err(if password.is_some() {
+            PdfPasswordError::Incorrect
+        } else {
+            PdfPasswordError::Required
+        }
+        .into()),
+        Err(e) => Err(anyhow::Error::new(e).context("This PDF's encryption is not supported without PDFium")),
+    }
+}
+
+/// Read the Info dictionary of a PDF without PDFium (see `read_pdf_metadata`)
+pub fn read_pdf_metadata_lopdf(pdf_data: &[u8], password: Option<&str>) -> Result<DocumentMetadata> {
+    let document = load_lopdf_document(pdf_data, password)?;
+    Ok(lopdf_metadata(&document))
+}
+
+/// Map the Info dictionary of a lopdf document to the shared metadata model
+pub fn lopdf_metadata(document: &Document) -> DocumentMetadata {
+    let info = document
+        .trailer
+        .get(b"Info")
+        .and_then(|info| resolve(document, info))
+        .and_then(Object::as_dict)
+        .ok();
+    let text = |key: &[u8]| {
+        info.and_then(|info| info.get(key).ok())
+            .and_then(|value| resolve(document, value).ok())
+            .and_then(|value| value.as_str().ok())
+            .map(|bytes| decode_text_string(bytes).trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    DocumentMetadata {
+        title: text(b"Title"),
+        author: text(b"Author"),
+        subject: text(b"Subject"),
+        keywords: text(b"Keywords")
+            .map(|keywords| split_list(&keywords))
+            .unwrap_or_default(),
+        creation_date: text(b"CreationDate")
+            .and_then(|date| parse_pdf_date(&date)),
+        ..DocumentMetadata::default()
+    }
+}
+
+/// Size of every page in points (width, height), as displayed (rotation applied)
+pub fn lopdf_page_sizes(document: &Document) -> Result<Vec<(f32, f32)>> {
+    document
+        .get_pages()
+        .into_values()
+        .map(|page_id| {
+            let (left, bottom, right, top) = page_box(document, page_id)?;
+            let rotation = page_rotation(document, page_id)?;
+            let (width, height) = (right - left, top - bottom);
+            Ok(if rotation % 180 == 0 { (width, height) } else { (height, width) })
+        })
+        .collect()
+}
+
+/// Read the top-level outline entries of a PDF without PDFium (see `read_pdf_chapters`)
+pub fn read_pdf_chapters_lopdf(pdf_data: &[u8], password: Option<&str>) -> Result<Vec<(String, usize)>> {
+    let document = load_lopdf_document(pdf_data, password)?;
+    Ok(lopdf_chapters(&document))
+}
+
+/// Top-level outline entries of a lopdf document: title and index of the page they point to
+/// Entries that do not point to a page of the document are skipped.
+pub fn lopdf_chapters(document: &Document) -> Vec<(String, usize)> {
+    let page_indices: HashMap<ObjectId, usize> = document
+        .get_pages()
+        .into_values()
+        .enumerate()
+        .map(|(index, page_id)| (page_id, index))
+        .collect();
+    let first = document
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Outlines"))
+        .and_then(|outlines| resolve(document, outlines))
+        .and_then(Object::as_dict)
+        .and_then(|outlines| outlines.get(b"First"))
+        .and_then(Object::as_reference);
+
+    let mut chapters = Vec::new();
+    let mut next = first.ok();
+    let mut seen = HashSet::new();
+    // Following Next links, stopping at a cycle
+    while let Some(item_id) = next.filter(|&id| seen.insert(id)) {
+        let Ok(item) = document.get_dictionary(item_id) else {
+            break;
+        };
+        let destination = item.get(b"Dest").ok().or_else(|| {
+            let action = resolve(document, item.get(b"A").ok()?).ok()?.as_dict().ok()?;
+            (action.get(b"S").and_then(Object::as_name).ok()? == b"GoTo").then(|| action.get(b"D").ok())?
+        });
+        if let Some(page) = destination.and_then(|destination| destination_page(document, destination, &page_indices, 0)) {
+            let title = item.get(b"Title")
+                .and_then(|title| resolve(document, title))
+                .and_then(Object::as_str)
+                .map(|bytes| decode_text_string(bytes).trim().to_string())
+                .unwrap_or_default();
+            chapters.push((title, page));
+        }
+        next = item.get(b"Next").and_then(Object::as_reference).ok();
+    }
+    chapters
+}
+
+/// Page index of an explicit destination ([page /Fit ...]), a named destination or a destination dictionary
+fn destination_page(document: &Document, destination: &Object, page_indices: &HashMap<ObjectId, usize>, depth: usize) -> Option<usize> {
+    if depth > 4 {
+        return None;
+    }
+    match resolve(document, destination).ok()? {
+        Object::Array(array) => match array.first()? {
+            Object::Reference(page_id) => page_indices.get(page_id).copied(),
+            _ => None,
+        },
+        Object::Dictionary(dict) => destination_page(document, dict.get(b"D").ok()?, page_indices, depth + 1),
+        Object::Name(name) | Object::String(name, _) => {
+            let named = named_destination(document, name)?;
+            destination_page(document, named, page_indices, depth + 1)
+        }
+        _ => None,
+    }
+}
+
+/// Look up a named destination in the catalog's Dests dictionary or Dests name tree
+fn named_destination<'a>(document: &'a Document, name: &[u8]) -> Option<&'a Object> {
+    let catalog = document.catalog().ok()?;
+    if let Some(destination) = catalog.get(b"Dests").ok()
+        .and_then(|dests| resolve(document, dests).ok())
+        .and_then(|dests| dests.as_dict().ok())
+        .and_then(|dests| dests.get(name).ok())
+    {
+        return Some(destination);
+    }
+
+    let names = resolve(document, catalog.get(b"Names").ok()?).ok()?.as_dict().ok()?;
+    let mut nodes = vec![resolve(document, names.get(b"Dests").ok()?).ok()?.as_dict().ok()?];
+    // Bounded walk of the name tree, in case of a Kids cycle
+    for _ in 0..1024 {
+        let node = nodes.pop()?;
+        if let Ok(entries) = node.get(b"Names").and_then(|entries| resolve(document, entries)).and_then(Object::as_array) {
+            let found = entries.chunks_exact(2).find(|pair| {
+                resolve(document, &pair[0]).and_then(Object::as_str).is_ok_and(|key| key == name)
+            });
+            if let Some(pair) = found {
+                return Some(&pair[1]);
+            }
+        }
+        if let Ok(kids) = node.get(b"Kids").and_then(|kids| resolve(document, kids)).and_then(Object::as_array) {
+            nodes.extend(kids.iter().rev().filter_map(|kid| resolve(document, kid).ok()?.as_dict().ok()));
+        }
+    }
+    None
+}
+
+/// Extract the full-page image of every page without PDFium
+/// Each page must draw exactly one upright image inside the page, with no other visible content;
+/// other pages fail with `UnsupportedPageError` rather than being approximated.
+/// JPEG images are stored as-is; other images are saved as PNG (`lossless`) or JPEG at `quality`.
+/// Masked images are composited or keep their alpha (then saved as PNG) as set by `transparency`.
+/// `password` opens encrypted PDFs (fails with `PdfPasswordError` if missing or wrong).
+pub fn extract_page_images_lopdf(
+    pdf_data: &[u8],
+    lossless: bool,
+    quality: u8,
+    max_pages: u32,
+    password: Option<&str>,
+    transparency: Transparency,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let document = load_lopdf_document(pdf_data, password)?;
+    let pages = document.get_pages();
+    if pages.is_empty() {
+        anyhow::bail!("PDF has no pages");
+    }
+
+    let pages_to_process = if max_pages > 0 && (max_pages as usize) < pages.len() {
+        max_pages as usize
+    } else {
+        pages.len()
+    };
+
+    let mut images = Vec::with_capacity(pages_to_process);
+    for (&page_num, &page_id) in pages.iter().take(pages_to_process) {
+        let (extension, data) = extract_page(&document, page_id, lossless, quality, transparency)
+            .context(UnsupportedPageError { page: page_num })?;
+        images.push((format!("page_{:04}.{}", page_num, extension), data));
+    }
+
+    eprintln!("[LOPDF] Extracted {} page images", images.len());
+    Ok(images)
+}
+
+/// Encoded image of one page, with its file extension
+fn extract_page(
+    document: &Document,
+    page_id: ObjectId,
+    lossless: bool,
+    quality: u8,
+    transparency: Transparency,
+) -> Result<(&'static str, Vec<u8>)> {
+    let rotation = page_rotation(document, page_id)?;
+    if has_visible_annotations(document, page_id) {
+        anyhow::bail!("page has visible annotations");
+    }
+
+    let content = document.get_page_content(page_id).context("Failed to read the page content")?;
+    let resources = page_resources(document, page_id);
+    let mut draws = Vec::new();
+    scan_content(document, &content, &resources, IDENTITY, 0, &mut draws)?;
+
+    let draw = match draws.as_slice() {
+        [draw] => draw,
+        [] => anyhow::bail!("page has no image"),
+        _ => anyhow::bail!("page draws {} images", draws.len()),
+    };
+    check_placement(draw.matrix, page_box(document, page_id)?)?;
+
+    match (decode_image_stream(document, draw.stream)?, rotation) {
+        (PageBitmap::Jpeg(data), 0) => Ok(("jpg", data)),
+        (PageBitmap::Bilevel(bilevel), 0) => Ok(("png", bilevel.to_png()?)),
+        (bitmap, rotation) => {
+            let image = bitmap.into_image()?;
+            let image = match rotation {
+                90 => image.rotate90(),
+                180 => image.rotate180(),
+                270 => image.rotate270(),
+                _ => image,
+            };
+            encode_page(transparency.apply(image), lossless, quality)
+        }
+    }
+}
+
+/// Save a decoded page as PNG (lossless) or JPEG at `quality`, grayscale when the image has no color
+/// Black-and-white pages and pages that kept transparent pixels are always saved as PNG.
+fn encode_page(image: DynamicImage, lossless: bool, quality: u8) -> Result<(&'static str, Vec<u8>)> {
+    if let Some(bilevel) = BilevelImage::from_image(&image) {
+        return Ok(("png", bilevel.to_png()?));
+    }
+
+    let mut data = Vec::new();
+    if lossless || image.color().has_alpha() {
+        image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .context("Failed to encode image as PNG")?;
+        return Ok(("png", data));
+    }
+
+    let image = if image.color().has_color() {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+    } else {
+        DynamicImage::ImageLuma8(image.to_luma8())
+    };
+    image.write_with_encoder(image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, quality))
+        .context("Failed to encode image as JPEG")?;
+    Ok(("jpg", data))
+}
+
+// ============================================================================
+// Content stream
+// ============================================================================
+
+/// Transformation matrix [a b c d e f]
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// `m` concatenated with `ctm` (the effect of `m cm` on the current transformation)
+fn concat(m: Matrix, ctm: Matrix) -> Matrix {
+    [
+        m[0] * ctm[0] + m[1] * ctm[2],
+        m[0] * ctm[1] + m[1] * ctm[3],
+        m[2] * ctm[0] + m[3] * ctm[2],
+        m[2] * ctm[1] + m[3] * ctm[3],
+        m[4] * ctm[0] + m[5] * ctm[2] + ctm[4],
+        m[4] * ctm[1] + m[5] * ctm[3] + ctm[5],
+    ]
+}
+
+/// An image XObject painted by a content stream, with the matrix it is drawn with
+struct ImageDraw<'a> {
+    stream: &'a Stream,
+    matrix: Matrix,
+}
+
+/// Collect the images painted by a content stream, failing on anything else that paints
+/// Form XObjects are followed, with their own resources when they have some.
+fn scan_content<'a>(
+    document: &'a Document,
+    content: &[u8],
+    resources: &[&'a Dictionary],
+    ctm: Matrix,
+    depth: usize,
+    draws: &mut Vec<ImageDraw<'a>>,
+) -> Result<()> {
+    let content = Content::decode(content).context("Failed to parse the page content")?;
+    let mut ctm = ctm;
+    let mut saved = Vec::new();
+
+    for operation in &content.operations {
+        match operation.operator.as_str() {
+            "q" => saved.push(ctm),
+            "Q" => ctm = saved.pop().unwrap_or(ctm),
+            "cm" => {
+                let values: Vec<f32> = operation.operands.iter().filter_map(|o| o.as_float().ok()).collect();
+                let matrix: Matrix = values.try_into()
+                    .map_err(|_| anyhow::anyhow!("malformed `cm` operator"))?;
+                ctm = concat(matrix, ctm);
+            }
+            "gs" => {
+                let name = operation.operands.first().and_then(|o| o.as_name().ok()).unwrap_or_default();
+                if let Some(state) = lookup_resource(document, resources, b"ExtGState", name).and_then(|o| o.as_dict().ok()) {
+                    check_graphics_state(document, state)?;
+                }
+            }
+            "Do" => {
+                let name = operation.operands.first().and_then(|o| o.as_name().ok()).unwrap_or_default();
+                let stream = lookup_resource(document, resources, b"XObject", name)
+                    .and_then(|o| o.as_stream().ok())
+                    .with_context(|| format!("missing XObject /{}", String::from_utf8_lossy(name)))?;
+                match stream.dict.get(b"Subtype").and_then(Object::as_name).unwrap_or_default() {
+                    b"Image" => draws.push(ImageDraw { stream, matrix: ctm }),
+                    b"Form" => {
+                        if depth >= MAX_FORM_DEPTH {
+                            anyhow::bail!("form XObjects are nested too deeply");
+                        }
+                        let matrix = stream.dict.get(b"Matrix")
+                            .and_then(Object::as_array)
+                            .ok()
+                            .and_then(|values| values.iter().map(|v| v.as_float().ok()).collect::<Option<Vec<f32>>>())
+                            .and_then(|values| Matrix::try_from(values).ok())
+                            .unwrap_or(IDENTITY);
+                        let form_resources: Vec<&Dictionary> = match stream.dict.get(b"Resources").and_then(|r| resolve(document, r)).and_then(Object::as_dict) {
+                            Ok(own) => vec![own],
+                            Err(_) => resources.to_vec(),
+                        };
+                        let form_content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                        scan_content(document, &form_content, &form_resources, concat(matrix, ctm), depth + 1, draws)?;
+                    }
+                    other => anyhow::bail!("page uses a /{} XObject", String::from_utf8_lossy(other)),
+                }
+            }
+            "BT" => anyhow::bail!("page has text"),
+            "BI" => anyhow::bail!("page has an inline image"),
+            operator if NON_PAINTING_OPERATORS.contains(&operator) => {}
+            operator => anyhow::bail!("page has vector drawing (`{}` operator)", operator),
+        }
+    }
+    Ok(())
+}
+
+/// Refuse graphics states that make the image blend with what is under it
+fn check_graphics_state(document: &Document, state: &Dictionary) -> Result<()> {
+    let number = |key: &[u8]| state.get(key).ok().and_then(|o| resolve(document, o).ok()).and_then(|o| o.as_float().ok());
+    let opaque = number(b"ca").is_none_or(|alpha| alpha >= 1.0) && number(b"CA").is_none_or(|alpha| alpha >= 1.0);
+    let no_soft_mask = state.get(b"SMask").and_then(Object::as_name).map_or(true, |name| name == b"None");
+    let normal_blend = match state.get(b"BM") {
+        Ok(Object::Name(name)) => name == b"Normal" || name == b"Compatible",
+        Ok(Object::Array(modes)) => modes.first().and_then(|m| m.as_name().ok()).is_none_or(|name| name == b"Normal"),
+        _ => true,
+    };
+    if !(opaque && no_soft_mask && normal_blend) {
+        anyhow::bail!("page uses transparency");
+    }
+    Ok(())
+}
+
+/// Check that an image drawn with `matrix` is upright and inside `page_box`
+/// Blank margins around the image are dropped, as with PDFium direct extraction.
+fn check_placement(matrix: Matrix, page_box: (f32, f32, f32, f32)) -> Result<()> {
+    let [a, b, c, d, e, f] = matrix;
+    if b.abs() > f32::EPSILON || c.abs() > f32::EPSILON || a <= 0.0 || d <= 0.0 {
+        anyhow::bail!("image is rotated, skewed or flipped");
+    }
+
+    let (left, bottom, right, top) = page_box;
+    let tolerance_x = EDGE_TOLERANCE.max((right - left) * EDGE_TOLERANCE_RATIO);
+    let tolerance_y = EDGE_TOLERANCE.max((top - bottom) * EDGE_TOLERANCE_RATIO);
+    let inside = e >= left - tolerance_x
+        && e + a <= right + tolerance_x
+        && f >= bottom - tolerance_y
+        && f + d <= top + tolerance_y;
+    if !inside {
+        anyhow::bail!(
+            "image ({:.0}x{:.0} pt at {:.0},{:.0}) is cropped by the page ({:.0}x{:.0} pt)",
+            a, d, e, f, right - left, top - bottom
+        );
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Page attributes
+// ============================================================================
+
+/// Follow a reference to the object it points to
+fn resolve<'a>(document: &'a Document, object: &'a Object) -> lopdf::Result<&'a Object> {
+    document.dereference(object).map(|(_, object)| object)
+}
+
+/// A page attribute, inherited from the page tree when the page does not set it
+fn inherited_attribute<'a>(document: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node = document.get_dictionary(page_id).ok();
+    // Bounded walk up the page tree, in case of a Parent cycle
+    for _ in 0..64 {
+        let dict = node?;
+        if let Ok(value) = dict.get(key) {
+            return resolve(document, value).ok();
+        }
+        node = dict.get(b"Parent").and_then(Object::as_reference).and_then(|id| document.get_dictionary(id)).ok();
+    }
+    None
+}
+
+/// Visible page area (CropBox, or MediaBox), as (left, bottom, right, top)
+fn page_box(document: &Document, page_id: ObjectId) -> Result<(f32, f32, f32, f32)> {
+    let rect = |key: &[u8]| {
+        let values = inherited_attribute(document, page_id, key)?.as_array().ok()?;
+        let values: Vec<f32> = values.iter().filter_map(|v| resolve(document, v).ok()?.as_float().ok()).collect();
+        match values[..] {
+            [x1, y1, x2, y2] => Some((x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2))),
+            _ => None,
+        }
+    };
+    rect(b"CropBox").or_else(|| rect(b"MediaBox")).context("page has no MediaBox")
+}
+
+/// Clockwise page rotation: 0, 90, 180 or 270
+fn page_rotation(document: &Document, page_id: ObjectId) -> Result<i64> {
+    let rotation = inherited_attribute(document, page_id, b"Rotate")
+        .and_then(|value| value.as_i64().ok())
+        .unwrap_or(0)
+        .rem_euclid(360);
+    if rotation % 90 != 0 {
+        anyhow::bail!("page rotation of {} degrees", rotation);
+    }
+    Ok(rotation)
+}
+
+/// Resource dictionaries of a page, its own first
+fn page_resources(document: &Document, page_id: ObjectId) -> Vec<&Dictionary> {
+    let Ok((own, inherited)) = document.get_page_resources(page_id) else {
+        return Vec::new();
+    };
+    own.into_iter()
+        .chain(inherited.into_iter().filter_map(|id| document.get_dictionary(id).ok()))
+        .collect()
+}
+
+/// A named resource (e.g. an XObject) from the first resource dictionary that has it
+fn lookup_resource<'a>(document: &'a Document, resources: &[&'a Dictionary], category: &[u8], name: &[u8]) -> Option<&'a Object> {
+    resources.iter().find_map(|resources| {
+        let entries = resolve(document, resources.get(category).ok()?).ok()?.as_dict().ok()?;
+        resolve(document, entries.get(name).ok()?).ok()
+    })
+}
+
+/// Whether the page has annotations that PDFium would draw over the image (links and popups are invisible)
+fn has_visible_annotations(document: &Document, page_id: ObjectId) -> bool {
+    let Ok(annotations) = document.get_page_annotations(page_id) else {
+        return false;
+    };
+    annotations.iter().any(|annotation| {
+        let subtype = annotation.get(b"Subtype").and_then(Object::as_name).unwrap_or_default();
+        subtype != b"Link" && subtype != b"Popup" && annotation.has(b"AP")
+    })
+}
+
+/// Decode a PDF text string (UTF-16BE with a byte order mark, or PDFDocEncoding)
+fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+        return String::from_utf16_lossy(&units);
+    }
+    if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8_lossy(utf8).into_owned();
+    }
+    // PDFDocEncoding matches Latin-1 for printable characters
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+// ============================================================================
+// Image XObjects
+// ============================================================================
+
+/// A decoded image XObject: JPEG data kept as-is, black-and-white pixels, or pixels
+enum PageBitmap {
+    Jpeg(Vec<u8>),
+    Bilevel(BilevelImage),
+    Pixels(DynamicImage),
+}
+
+impl PageBitmap {
+    fn into_image(self) -> Result<DynamicImage> {
+        match self {
+            PageBitmap::Jpeg(data) => decode_image(&data),
+            PageBitmap::Bilevel(bilevel) => Ok(DynamicImage::ImageLuma8(bilevel.to_luma())),
+            PageBitmap::Pixels(image) => Ok(image),
+        }
+    }
+}
+
+/// Colour space of an image XObject
+enum ColorSpace {
+    Gray,
+    Rgb,
+    /// Palette of `base_components` (1 or 3) bytes per entry
+    Indexed { base_components: usize, palette: Vec<u8> },
+}
+
+impl ColorSpace {
+    fn parse(document: &Document, object: &Object) -> Result<ColorSpace> {
+        let object = resolve(document, object)?;
+        let (family, params) = match object {
+            Object::Name(name) => (name.as_slice(), &[][..]),
+            Object::Array(array) => match array.split_first() {
+                Some((family, params)) => (family.as_name()?, params),
+                None => anyhow::bail!("empty colour space"),
+            },
+            _ => anyhow::bail!("malformed colour space"),
+        };
+
+        match family {
+            b"DeviceGray" | b"G" | b"CalGray" => Ok(ColorSpace::Gray),
+            b"DeviceRGB" | b"RGB" | b"CalRGB" => Ok(ColorSpace::Rgb),
+            b"ICCBased" => {
+                let profile = params.first().context("ICCBased colour space without a profile")?;
+                let components = resolve(document, profile)?.as_stream()?.dict.get(b"N").and_then(Object::as_i64)?;
+                match components {
+                    1 => Ok(ColorSpace::Gray),
+                    3 => Ok(ColorSpace::Rgb),
+                    n => anyhow::bail!("{}-component ICC colour space", n),
+                }
+            }
+            b"Indexed" | b"I" => {
+                let [base, _hival, lookup] = params else {
+                    anyhow::bail!("malformed Indexed colour space");
+                };
+                let base_components = match ColorSpace::parse(document, base)? {
+                    ColorSpace::Gray => 1,
+                    ColorSpace::Rgb => 3,
+                    ColorSpace::Indexed { .. } => anyhow::bail!("nested Indexed colour space"),
+                };
+                let palette = match resolve(document, lookup)? {
+                    Object::String(bytes, _) => bytes.clone(),
+                    Object::Stream(stream) => stream.decompressed_content().unwrap_or_else(|_| stream.content.clone()),
+                    _ => anyhow::bail!("malformed Indexed colour space"),
+                };
+                Ok(ColorSpace::Indexed { base_components, palette })
+            }
+            other => anyhow::bail!("{} colour space", String::from_utf8_lossy(other)),
+        }
+    }
+
+    fn components(&self) -> usize {
+        match self {
+            ColorSpace::Gray | ColorSpace::Indexed { .. } => 1,
+            ColorSpace::Rgb => 3,
+        }
+    }
+}
+
+/// Decode an image XObject with its soft mask, stencil mask or colour-key mask,
+/// refusing features the built-in reader does not reproduce
+fn decode_image_stream(document: &Document, stream: &Stream) -> Result<PageBitmap> {
+    let dict = &stream.dict;
+    if is_stencil_mask(dict) {
+        anyhow::bail!("image is a stencil mask");
+    }
+
+    let mask = match dict.get(b"Mask") {
+        Ok(mask) => Some(resolve(document, mask)?),
+        Err(_) => None,
+    };
+    let color_key = match mask {
+        Some(Object::Array(ranges)) => Some(ranges.iter()
+            .map(|o| resolve(document, o).and_then(Object::as_i64))
+            .collect::<lopdf::Result<Vec<i64>>>()
+            .context("malformed colour-key mask")?),
+        _ => None,
+    };
+    let bitmap = decode_image_data(document, stream, color_key.as_deref())?;
+
+    // A soft mask takes precedence over a Mask entry
+    let alpha = match dict.get(b"SMask").and_then(|o| resolve(document, o)) {
+        Ok(Object::Stream(smask)) => Some(decode_soft_mask(document, smask)?),
+        Ok(Object::Name(name)) if name == b"None" => None,
+        Ok(_) => anyhow::bail!("malformed soft mask"),
+        Err(_) => match mask {
+            Some(Object::Stream(stencil)) => Some(decode_stencil_mask(document, stencil)?),
+            Some(Object::Array(_)) | None => None,
+            Some(_) => anyhow::bail!("malformed Mask entry"),
+        },
+    };
+    match alpha {
+        Some(alpha) => Ok(PageBitmap::Pixels(with_alpha(bitmap.into_image()?, alpha))),
+        None => Ok(bitmap),
+    }
+}
+
+/// Opacity of a soft mask image (its gray levels)
+fn decode_soft_mask(document: &Document, smask: &Stream) -> Result<GrayImage> {
+    if smask.dict.has(b"Matte") {
+        anyhow::bail!("soft mask with premultiplied colours (Matte)");
+    }
+    let mask = decode_image_data(document, smask, None).context("Failed to decode soft mask")?;
+    Ok(mask.into_image()?.to_luma8())
+}
+
+/// Opacity of a stencil mask image: samples decoding to 0 (black) are painted, others are masked out
+fn decode_stencil_mask(document: &Document, stencil: &Stream) -> Result<GrayImage> {
+    if !is_stencil_mask(&stencil.dict) {
+        anyhow::bail!("Mask stream is not a stencil mask");
+    }
+    let mask = decode_image_data(document, stencil, None).context("Failed to decode stencil mask")?;
+    let mut alpha = mask.into_image()?.to_luma8();
+    image::imageops::invert(&mut alpha);
+    Ok(alpha)
+}
+
+fn is_stencil_mask(dict: &Dictionary) -> bool {
+    dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false)
+}
+
+/// Combine an image with an opacity mask, resized to the image when their sizes differ
+fn with_alpha(image: DynamicImage, alpha: GrayImage) -> DynamicImage {
+    let alpha = if alpha.dimensions() == (image.width(), image.height()) {
+        alpha
+    } else {
+        image::imageops::resize(&alpha, image.width(), image.height(), image::imageops::FilterType::Triangle)
+    };
+    if image.color().has_color() {
+        let mut rgba = image.to_rgba8();
+        for (pixel, a) in rgba.pixels_mut().zip(alpha.pixels()) {
+            pixel[3] = a[0];
+        }
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        let mut gray = image.to_luma_alpha8();
+        for (pixel, a) in gray.pixels_mut().zip(alpha.pixels()) {
+            pixel[1] = a[0];
+        }
+        DynamicImage::ImageLumaA8(gray)
+    }
+}
+
+/// Decode the samples of an image XObject (or of a mask), applying a colour-key mask
+fn decode_image_data(document: &Document, stream: &Stream, color_key: Option<&[i64]>) -> Result<PageBitmap> {
+    let dict = &stream.dict;
+    let integer = |key: &[u8]| dict.get(key).ok().and_then(|o| resolve(document, o).ok()).and_then(|o| o.as_i64().ok());
+
+    let width = integer(b"Width").filter(|&w| w > 0).context("image has no width")? as u32;
+    let height = integer(b"Height").filter(|&h| h > 0).context("image has no height")? as u32;
+    SafetyLimits::current().check_image_size(width as u64, height as u64)?;
+
+    let filters = stream.filters().unwrap_or_default();
+    let params = decode_params(document, dict, filters.len());
+    let filters: Vec<&str> = filters.iter().map(String::as_str).collect();
+
+    // JPEG 2000 images carry their own colour space and alpha channel
+    if filters == ["JPXDecode"] {
+        if dict.has(b"Decode") {
+            anyhow::bail!("image has a Decode array");
+        }
+        if let Ok(color_space) = dict.get(b"ColorSpace") {
+            if let ColorSpace::Indexed { .. } = ColorSpace::parse(document, color_space)? {
+                anyhow::bail!("Indexed JPEG 2000 image");
+            }
+        }
+        if color_key.is_some() {
+            anyhow::bail!("JPEG 2000 image with a colour-key mask");
+        }
+        let alpha = JpxAlpha::from_smask_in_data(integer(b"SMaskInData").unwrap_or(0));
+        let image = decode_jpx(&stream.content, alpha)?;
+        if image.width() != width || image.height() != height {
+            anyhow::bail!("JPEG 2000 image size does not match its dictionary");
+        }
+        return Ok(PageBitmap::Pixels(image));
+    }
+
+    // Stencil masks are 1-bit and have no colour space
+    let color_space = match dict.get(b"ColorSpace") {
+        Ok(color_space) => ColorSpace::parse(document, color_space)?,
+        Err(_) if is_stencil_mask(dict) => ColorSpace::Gray,
+        Err(_) => anyhow::bail!("image has no colour space"),
+    };
+
+    // CCITT and JBIG2 images are always 1-bit
+    let bits = match filters[..] {
+        _ if is_stencil_mask(dict) => 1,
+        ["CCITTFaxDecode"] | ["JBIG2Decode"] => 1,
+        _ => integer(b"BitsPerComponent").unwrap_or(8) as usize,
+    };
+    // Only the inverting Decode array of black-and-white images is supported
+    let inverted = match dict.get(b"Decode") {
+        Err(_) => false,
+        Ok(decode) => {
+            let range: Vec<f32> = resolve(document, decode)?.as_array()?.iter().filter_map(|o| o.as_float().ok()).collect();
+            if range == [0.0, 1.0] {
+                false
+            } else if range == [1.0, 0.0] && bits == 1 && matches!(color_space, ColorSpace::Gray) {
+                true
+            } else {
+                anyhow::bail!("image has a Decode array");
+            }
+        }
+    };
+
+    // Colour keys are compared to the encoded samples, which only Flate images keep
+    if color_key.is_some() && !filters.iter().all(|&filter| filter == "FlateDecode") {
+        anyhow::bail!("{} image with a colour-key mask", filters.join("+"));
+    }
+
+    match filters[..] {
+        ["DCTDecode"] => {
+            if PageImageFormat::sniff(&stream.content) != Some(PageImageFormat::Jpeg) {
+                anyhow::bail!("DCTDecode image is not a JPEG");
+            }
+            match color_space {
+                ColorSpace::Gray | ColorSpace::Rgb => Ok(PageBitmap::Jpeg(stream.content.clone())),
+                ColorSpace::Indexed { .. } => anyhow::bail!("Indexed JPEG image"),
+            }
+        }
+        ["CCITTFaxDecode"] | ["JBIG2Decode"] => {
+            if !matches!(color_space, ColorSpace::Gray) {
+                anyhow::bail!("{} image with a colour palette", filters[0]);
+            }
+            let mut bilevel = if filters[0] == "JBIG2Decode" {
+                let globals = params[0].as_ref()
+                    .and_then(|p| p.get(b"JBIG2Globals").ok())
+                    .and_then(|o| resolve(document, o).ok())
+                    .and_then(|o| o.as_stream().ok())
+                    .map(|globals| globals.decompressed_content().unwrap_or_else(|_| globals.content.clone()));
+                decode_jbig2(&stream.content, globals.as_deref())?
+            } else {
+                decode_ccitt(&stream.content, &ccitt_params(params[0].as_ref(), width, height)?)?
+            };
+            if bilevel.width() != width || bilevel.height() != height {
+                anyhow::bail!("{} image size does not match its dictionary", filters[0]);
+            }
+            if inverted {
+                bilevel.invert();
+            }
+            Ok(PageBitmap::Bilevel(bilevel))
+        }
+        ref filters if filters.iter().all(|&filter| filter == "FlateDecode") => {
+            let row_length = (width as usize * color_space.components() * bits).div_ceil(8);
+            let expected = row_length * height as usize;
+
+            let mut samples = stream.content.clone();
+            for params in &params {
+                samples = inflate(&samples, expected)?;
+                samples = unpredict(samples, params.as_ref())?;
+            }
+            if samples.len() < expected {
+                anyhow::bail!("image data is truncated");
+            }
+            let mut image = to_bitmap(&samples, width, height, bits, &color_space)?;
+            if inverted {
+                image.invert();
+            }
+            match color_key {
+                Some(ranges) => {
+                    let alpha = color_key_alpha(&samples, width, height, bits, color_space.components(), ranges)?;
+                    Ok(PageBitmap::Pixels(with_alpha(image, alpha)))
+                }
+                None => Ok(PageBitmap::Pixels(image)),
+            }
+        }
+        ref filters => anyhow::bail!("{} images", filters.join("+")),
+    }
+}
+
+/// DecodeParms of each filter (a dictionary for a single filter, an array otherwise)
+fn decode_params(document: &Document, dict: &Dictionary, filter_count: usize) -> Vec<Option<Dictionary>> {
+    let as_dict = |object: &Object| resolve(document, object).ok().and_then(|o| o.as_dict().ok()).cloned();
+    let mut params = match dict.get(b"DecodeParms").ok().and_then(|o| resolve(document, o).ok()) {
+        Some(Object::Array(array)) => array.iter().map(as_dict).collect(),
+        Some(object) => vec![as_dict(object)],
+        None => Vec::new(),
+    };
+    params.resize(filter_count, None);
+    params
+}
+
+/// CCITTFaxDecode parameters of an image, with PDF's defaults
+fn ccitt_params(params: Option<&Dictionary>, width: u32, height: u32) -> Result<CcittParams> {
+    let integer = |key: &[u8]| params.and_then(|p| p.get(key).ok()).and_then(|o| o.as_i64().ok());
+    let flag = |key: &[u8], default: bool| params.and_then(|p| p.get(key).ok()).and_then(|o| o.as_bool().ok()).unwrap_or(default);
+
+    let columns = integer(b"Columns").unwrap_or(width as i64);
+    if columns != width as i64 {
+        anyhow::bail!("CCITT image is {} columns wide but {} pixels wide", columns, width);
+    }
+    Ok(CcittParams {
+        k: integer(b"K").unwrap_or(0),
+        columns: width,
+        rows: height,
+        black_is_1: flag(b"BlackIs1", false),
+        encoded_byte_align: flag(b"EncodedByteAlign", false),
+        end_of_line: flag(b"EndOfLine", false),
+        end_of_block: flag(b"EndOfBlock", true),
+    })
+}
+
+/// zlib-decompress at most a little more than `expected` bytes (data past the image is ignored)
+fn inflate(data: &[u8], expected: usize) -> Result<Vec<u8>> {
+    // PNG predictors add one byte per row
+    let limit = expected.saturating_mul(2).saturating_add(1024) as u64;
+    let mut output = Vec::new();
+    let result = flate2::read::ZlibDecoder::new(data).take(limit).read_to_end(&mut output);
+    match result {
+        Ok(_) => Ok(output),
+        // Many writers leave a damaged stream end: keep what was decoded
+        Err(_) if !output.is_empty() => Ok(output),
+        Err(e) => Err(anyhow::Error::new(e).context("Failed to decompress image")),
+    }
+}
+
+/// Undo a PNG predictor (Predictor 10-15); other predictors are refused
+fn unpredict(data: Vec<u8>, params: Option<&Dictionary>) -> Result<Vec<u8>> {
+    let param = |key: &[u8], default: i64| params.and_then(|p| p.get(key).ok()).and_then(|o| o.as_i64().ok()).unwrap_or(default);
+    let predictor = param(b"Predictor", 1);
+    if predictor == 1 {
+        return Ok(data);
+    }
+    if predictor < 10 {
+        anyhow::bail!("TIFF predictor");
+    }
+
+    let colors = param(b"Colors", 1).max(1) as usize;
+    let bits = param(b"BitsPerComponent", 8).max(1) as usize;
+    let columns = param(b"Columns", 1).max(1) as usize;
+    let bytes_per_pixel = (colors * bits).div_ceil(8);
+    let row_length = (columns * colors * bits).div_ceil(8);
+
+    let mut output = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_length];
+    for chunk in data.chunks(row_length + 1) {
+        let (&filter, encoded) = chunk.split_first().context("empty predictor row")?;
+        let mut row = encoded.to_vec();
+        row.resize(row_length, 0);
+        for i in 0..row_length {
+            let left = if i >= bytes_per_pixel { row[i - bytes_per_pixel] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= bytes_per_pixel { previous[i - bytes_per_pixel] } else { 0 };
+            let predicted = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                other => anyhow::bail!("invalid PNG predictor row filter {}", other),
+            };
+            row[i] = row[i].wrapping_add(predicted);
+        }
+        output.extend_from_slice(&row);
+        previous = row;
+    }
+    Ok(output)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (dl, du, dul) = ((estimate - left as i16).abs(), (estimate - up as i16).abs(), (estimate - up_left as i16).abs());
+    if dl <= du && dl <= dul {
+        left
+    } else if du <= dul {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Build an image from decoded samples (rows padded to whole bytes)
+fn to_bitmap(samples: &[u8], width: u32, height: u32, bits: usize, color_space: &ColorSpace) -> Result<DynamicImage> {
+    let components = color_space.components();
+    let row_length = (width as usize * components * bits).div_ceil(8);
+    let rows = samples.chunks_exact(row_length).take(height as usize);
+
+    match (color_space, bits) {
+        (ColorSpace::Gray, 16) | (ColorSpace::Rgb, 16) => {
+            let values: Vec<u16> = rows.flat_map(|row| row.chunks_exact(2).map(|s| u16::from_be_bytes([s[0], s[1]]))).collect();
+            let image = if components == 1 {
+                ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageLuma16)
+            } else {
+                ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageRgb16)
+            };
+            image.context("image data is truncated")
+        }
+        (ColorSpace::Rgb, 8) => {
+            let values: Vec<u8> = rows.flat_map(|row| row.iter().copied()).collect();
+            RgbImage::from_raw(width, height, values).map(DynamicImage::ImageRgb8).context("image data is truncated")
+        }
+        (ColorSpace::Gray, 1 | 2 | 4 | 8) => {
+            let max = (1u16 << bits) - 1;
+            let values: Vec<u8> = rows
+                .flat_map(|row| unpack_row(row, width as usize, bits))
+                .map(|v| (v as u16 * 255 / max) as u8)
+                .collect();
+            GrayImage::from_raw(width, height, values).map(DynamicImage::ImageLuma8).context("image data is truncated")
+        }
+        (ColorSpace::Indexed { base_components, palette }, 1 | 2 | 4 | 8) => {
+            let entry = |index: u8| {
+                let start = index as usize * base_components;
+                palette.get(start..start + base_components).unwrap_or(&[0, 0, 0][..*base_components])
+            };
+            let values: Vec<u8> = rows
+                .flat_map(|row| unpack_row(row, width as usize, bits))
+                .flat_map(|index| entry(index).iter().copied())
+                .collect();
+            let image = if *base_components == 1 {
+                GrayImage::from_raw(width, height, values).map(DynamicImage::ImageLuma8)
+            } else {
+                RgbImage::from_raw(width, height, values).map(DynamicImage::ImageRgb8)
+            };
+            image.context("image data is truncated")
+        }
+        (_, bits) => anyhow::bail!("{}-bit images", bits),
+    }
+}
+
+/// Split a row of 1, 2, 4 or 8-bit samples into one value per sample
+fn unpack_row(row: &[u8], count: usize, bits: usize) -> impl Iterator<Item = u8> + '_ {
+    let mask = ((1u16 << bits) - 1) as u8;
+    (0..count).map(move |i| {
+        let bit = i * bits;
+        let shift = 8 - bits - bit % 8;
+        (row[bit / 8] >> shift) & mask
+    })
+}
+
+/// Opacity from a colour-key mask: pixels whose samples all fall in the [min max] ranges are masked out
+fn color_key_alpha(samples: &[u8], width: u32, height: u32, bits: usize, components: usize, ranges: &[i64]) -> Result<GrayImage> {
+    if ranges.len() != 2 * components {
+        anyhow::bail!("colour-key mask has {} values for {} colour components", ranges.len(), components);
+    }
+    let row_length = (width as usize * components * bits).div_ceil(8);
+    let values_per_row = width as usize * components;
+
+    let mut alpha = Vec::with_capacity(width as usize * height as usize);
+    for row in samples.chunks_exact(row_length).take(height as usize) {
+        let values: Vec<i64> = if bits == 16 {
+            row.chunks_exact(2).map(|s| u16::from_be_bytes([s[0], s[1]]) as i64).collect()
+        } else {
+            unpack_row(row, values_per_row, bits).map(i64::from).collect()
+        };
+        alpha.extend(values.chunks_exact(components).map(|pixel| {
+            let keyed = pixel.iter().zip(ranges.chunks_exact(2)).all(|(&v, range)| range[0] <= v && v <= range[1]);
+            if keyed { 0 } else { 255 }
+        }));
+    }
+    GrayImage::from_raw(width, height, alpha).context("image data is truncated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpx::tests::flat_codestream;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use image::codecs::jpeg::JpegEncoder;
+    use lopdf::content::Operation;
+    use lopdf::dictionary;
+    use std::io::Write;
+
+    /// One 100 x 100 pt page drawing the image returned by `build` over the whole page
+    /// `build` can add the objects the image refers to (masks) to the document.
+    fn image_pdf(build: impl FnOnce(&mut Document) -> Stream) -> Vec<u8> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let image = build(&mut document);
+        let image_id = document.add_object(image);
+        let content = Content {
+            operations: vec![
+                Operation::new("q", vec![]),
+                Operation::new("cm", vec![100.into(), 0.into(), 0.into(), 100.into(), 0.into(), 0.into()]),
+                Operation::new("Do", vec![Object::Name(b"Im0".to_vec())]),
+                Operation::new("Q", vec![]),
+            ],
+        };
+        let content_id = document.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 100.into(), 100.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! { "XObject" => dictionary! { "Im0" => image_id } },
+        });
+        document.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }));
+        let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        document.trailer.set("Root", catalog_id);
+
+        let mut data = Vec::new();
+        document.save_to(&mut data).unwrap();
+        data
+    }
+
+    /// Image XObject dictionary
+    fn image_dict(width: i64, height: i64, color_space: &str, bits: i64, filter: &str) -> Dictionary {
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => width,
+            "Height" => height,
+            "ColorSpace" => color_space,
+            "BitsPerComponent" => bits,
+            "Filter" => filter,
+        }
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn extract(pdf: &[u8], transparency: Transparency) -> Result<(String, Vec<u8>)> {
+        let mut pages = extract_page_images_lopdf(pdf, true, 90, 0, None, transparency)?;
+        assert_eq!(pages.len(), 1);
+        Ok(pages.remove(0))
+    }
+
+    fn gradient(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| image::Luma([(x * 16 + y * 3) as u8]))
+    }
+
+    #[test]
+    fn dct_image_kept_as_is() {
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageLuma8(gradient(16, 16))
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, 90))
+            .unwrap();
+        let pdf = image_pdf(|_| Stream::new(image_dict(16, 16, "DeviceGray", 8, "DCTDecode"), jpeg.clone()));
+
+        let (name, data) = extract(&pdf, Transparency::default()).unwrap();
+        assert_eq!(name, "page_0001.jpg");
+        assert_eq!(data, jpeg);
+    }
+
+    #[test]
+    fn flate_image_decoded() {
+        let image = gradient(12, 10);
+        let pdf = image_pdf(|_| Stream::new(image_dict(12, 10, "DeviceGray", 8, "FlateDecode"), zlib(image.as_raw())));
+
+        let (name, data) = extract(&pdf, Transparency::default()).unwrap();
+        assert_eq!(name, "page_0001.png");
+        assert_eq!(decode_image(&data).unwrap().to_luma8(), image);
+    }
+
+    #[test]
+    fn flate_image_with_png_predictor() {
+        // RGB rows with the Up filter: each row stored as its difference from the previous one
+        let image = RgbImage::from_fn(4, 3, |x, y| image::Rgb([(x * 60) as u8, (y * 80) as u8, 200]));
+        let row_length = 4 * 3;
+        let mut encoded = Vec::new();
+        for (index, row) in image.as_raw().chunks(row_length).enumerate() {
+            encoded.push(2);
+            for (i, &value) in row.iter().enumerate() {
+                let up = if index == 0 { 0 } else { image.as_raw()[(index - 1) * row_length + i] };
+                encoded.push(value.wrapping_sub(up));
+            }
+        }
+        let mut dict = image_dict(4, 3, "DeviceRGB", 8, "FlateDecode");
+        dict.set("DecodeParms", dictionary! { "Predictor" => 15, "Colors" => 3, "Columns" => 4 });
+        let pdf = image_pdf(|_| Stream::new(dict, zlib(&encoded)));
+
+        let (_, data) = extract(&pdf, Transparency::default()).unwrap();
+        assert_eq!(decode_image(&data).unwrap().to_rgb8(), image);
+    }
+
+    #[test]
+    fn indexed_flate_image_uses_palette() {
+        let palette = vec![255, 0, 0, 0, 0, 255];
+        let mut dict = image_dict(8, 1, "DeviceRGB", 1, "FlateDecode");
+        dict.set("ColorSpace", vec!["Indexed".into(), "DeviceRGB".into(), 1.into(), Object::string_literal(palette)]);
+        let pdf = image_pdf(|_| Stream::new(dict, zlib(&[0b1010_0000])));
+
+        let (_, data) = extract(&pdf, Transparency::default()).unwrap();
+        let image = decode_image(&data).unwrap().to_rgb8();
+        let pixels: Vec<[u8; 3]> = image.pixels().map(|p| p.0).collect();
+        assert_eq!(pixels[..4], [[0, 0, 255], [255, 0, 0], [0, 0, 255], [255, 0, 0]]);
+    }
+
+    /// 1-bit page as a CCITT Group 4 image XObject with `decode_parms` and, if set, a Decode array
+    fn ccitt_pdf(image: &BilevelImage, decode_parms: Dictionary, decode: Option<[i64; 2]>) -> Vec<u8> {
+        let stream = image.to_stream().unwrap();
+        let mut dict = image_dict(image.width() as i64, image.height() as i64, "DeviceGray", 1, "CCITTFaxDecode");
+        dict.set("DecodeParms", decode_parms);
+        if let Some(decode) = decode {
+            dict.set("Decode", vec![decode[0].into(), decode[1].into()]);
+        }
+        image_pdf(|_| Stream::new(dict, stream.data))
+    }
+
+    fn bilevel_page() -> BilevelImage {
+        let gray = GrayImage::from_fn(20, 10, |x, y| image::Luma([if x < 5 || y > 7 { 0 } else { 255 }]));
+        BilevelImage::from_image(&DynamicImage::ImageLuma8(gray)).unwrap()
+    }
+
+    fn inverted(image: &BilevelImage) -> BilevelImage {
+        let mut image = image.clone();
+        image.invert();
+        image
+    }
+
+    fn extracted_bilevel(pdf: &[u8]) -> BilevelImage {
+        let (name, data) = extract(pdf, Transparency::default()).unwrap();
+        assert_eq!(name, "page_0001.png");
+        BilevelImage::from_image(&decode_image(&data).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn ccitt_decode_array_and_black_is_1() {
+        let page = bilevel_page();
+        let group4 = || dictionary! { "K" => -1, "Columns" => 20, "Rows" => 10 };
+        assert_eq!(extracted_bilevel(&ccitt_pdf(&page, group4(), None)), page);
+        assert_eq!(extracted_bilevel(&ccitt_pdf(&page, group4(), Some([0, 1]))), page);
+        assert_eq!(extracted_bilevel(&ccitt_pdf(&page, group4(), Some([1, 0]))), inverted(&page));
+
+        let mut black_is_1 = group4();
+        black_is_1.set("BlackIs1", true);
+        assert_eq!(extracted_bilevel(&ccitt_pdf(&page, black_is_1.clone(), None)), inverted(&page));
+        // Both invert: back to the encoded pixels
+        assert_eq!(extracted_bilevel(&ccitt_pdf(&page, black_is_1, Some([1, 0]))), page);
+    }
+
+    #[test]
+    fn flate_decode_array() {
+        let page = bilevel_page();
+        let mut dict = image_dict(20, 10, "DeviceGray", 1, "FlateDecode");
+        dict.set("Decode", vec![1.into(), 0.into()]);
+        let pdf = image_pdf(|_| Stream::new(dict, zlib(page.data())));
+        assert_eq!(extracted_bilevel(&pdf), inverted(&page));
+
+        // Other Decode arrays change colours in ways the reader does not reproduce
+        let mut dict = image_dict(20, 10, "DeviceGray", 8, "FlateDecode");
+        dict.set("Decode", vec![1.into(), 0.into()]);
+        let pdf = image_pdf(|_| Stream::new(dict, zlib(&[0; 200])));
+        let error = extract(&pdf, Transparency::default()).unwrap_err();
+        assert!(format!("{:#}", error).contains("Decode array"));
+    }
+
+    #[test]
+    fn jpx_image_decoded() {
+        let jpx_dict = |width: i64| {
+            let mut dict = image_dict(width, 8, "DeviceGray", 8, "JPXDecode");
+            dict.remove(b"ColorSpace");
+            dict.remove(b"BitsPerComponent");
+            dict
+        };
+        let pdf = image_pdf(|_| Stream::new(jpx_dict(8), flat_codestream(1)));
+        let (_, data) = extract(&pdf, Transparency::default()).unwrap();
+        assert!(decode_image(&data).unwrap().to_luma8().pixels().all(|p| p[0] == 128));
+
+        // Alpha used as the soft mask (SMaskInData 1), composited on the white background
+        let mut dict = jpx_dict(8);
+        dict.set("SMaskInData", 1);
+        let pdf = image_pdf(|_| Stream::new(dict, flat_codestream(2)));
+        let (_, data) = extract(&pdf, Transparency::default()).unwrap();
+        assert!(decode_image(&data).unwrap().to_luma8().pixels().all(|p| p[0] == 191));
+
+        let pdf = image_pdf(|_| Stream::new(jpx_dict(9), flat_codestream(1)));
+        assert!(format!("{:#}", extract(&pdf, Transparency::default()).unwrap_err()).contains("size does not match"));
+
+        let mut dict = jpx_dict(8);
+        dict.set("Decode", vec![1.into(), 0.into()]);
+        let pdf = image_pdf(|_| Stream::new(dict, flat_codestream(1)));
+        assert!(format!("{:#}", extract(&pdf, Transparency::default()).unwrap_err()).contains("Decode array"));
+    }
+
+    /// 2 x 1 RGB image: a red and a green pixel
+    fn red_green() -> Vec<u8> {
+        zlib(&[255, 0, 0, 0, 255, 0])
+    }
+
+    #[test]
+    fn soft_mask_composited_or_kept() {
+        let masked_pdf = || image_pdf(|document| {
+            let smask = document.add_object(Stream::new(image_dict(2, 1, "DeviceGray", 8, "FlateDecode"), zlib(&[255, 0])));
+            let mut dict = image_dict(2, 1, "DeviceRGB", 8, "FlateDecode");
+            dict.set("SMask", smask);
+            // The soft mask takes precedence over a colour-key mask
+            dict.set("Mask", vec![255.into(), 255.into(), 0.into(), 0.into(), 0.into(), 0.into()]);
+            Stream::new(dict, red_green())
+        });
+
+        let (_, data) = extract(&masked_pdf(), Transparency::Background([0, 0, 255])).unwrap();
+        let image = decode_image(&data).unwrap().to_rgb8();
+        assert_eq!((image.get_pixel(0, 0).0, image.get_pixel(1, 0).0), ([255, 0, 0], [0, 0, 255]));
+
+        let (_, data) = extract(&masked_pdf(), Transparency::Keep).unwrap();
+        let image = decode_image(&data).unwrap().to_rgba8();
+        assert_eq!((image.get_pixel(0, 0).0, image.get_pixel(1, 0).0), ([255, 0, 0, 255], [0, 255, 0, 0]));
+    }
+
+    #[test]
+    fn color_key_mask() {
+        // Pixels whose red is 200..=255 and green and blue 0 are masked out
+        let keyed_pdf = |filter: &str, data: Vec<u8>| image_pdf(|_| {
+            let mut dict = image_dict(2, 1, "DeviceRGB", 8, filter);
+            dict.set("Mask", vec![200.into(), 255.into(), 0.into(), 0.into(), 0.into(), 0.into()]);
+            Stream::new(dict, data)
+        });
+
+        let (_, data) = extract(&keyed_pdf("FlateDecode", red_green()), Transparency::Keep).unwrap();
+        let image = decode_image(&data).unwrap().to_rgba8();
+        assert_eq!((image.get_pixel(0, 0)[3], image.get_pixel(1, 0)[3]), (0, 255));
+
+        let (_, data) = extract(&keyed_pdf("FlateDecode", red_green()), Transparency::default()).unwrap();
+        let image = decode_image(&data).unwrap().to_rgb8();
+        assert_eq!((image.get_pixel(0, 0).0, image.get_pixel(1, 0).0), ([255, 255, 255], [0, 255, 0]));
+
+        // Keys are compared to encoded samples, which JPEG does not keep
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(16, 16))
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, 90))
+            .unwrap();
+        let error = extract(&keyed_pdf("DCTDecode", jpeg), Transparency::default()).unwrap_err();
+        assert!(format!("{:#}", error).contains("colour-key mask"));
+    }
+
+    #[test]
+    fn unsupported_filter_needs_pdfium() {
+        let pdf = image_pdf(|_| Stream::new(image_dict(8, 8, "DeviceGray", 8, "LZWDecode"), vec![0; 16]));
+        let error = extract(&pdf, Transparency::default()).unwrap_err();
+        assert_eq!(unsupported_page_error(&error), Some(UnsupportedPageError { page: 1 }));
+        assert!(format!("{:#}", error).contains("LZWDecode images"));
+        // Still found under more context added by callers
+        assert_eq!(unsupported_page_error(&error.context("Failed to convert")), Some(UnsupportedPageError { page: 1 }));
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use image::{DynamicImage, GrayImage, ImageFormat, Luma};
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::image_format::PageImageFormat;
+use crate::metadata::DocumentMetadata;
+
+/// Size of a blank separator page when the next input's first page cannot be measured (A4 at 150 DPI)
+const DEFAULT_SEPARATOR_SIZE: (u32, u32) = (1240, 1754);
+
+/// One input of a merge: its pages in reading order, as converted or extracted
+#[derive(Debug, Clone, Default)]
+pub struct MergeInput {
+    /// Chapter title (bookmark in PDF, folder name in CBZ)
+    pub title: String,
+    pub pages: Vec<(String, Vec<u8>)>,
+    /// Metadata of the input (PDF Info, ComicInfo.xml), if any
+    pub metadata: Option<DocumentMetadata>,
+}
+
+impl MergeInput {
+    /// Input titled from its metadata ("Series #Number" when it has no title), or the file name
+    pub fn new(path: &Path, pages: Vec<(String, Vec<u8>)>, metadata: Option<DocumentMetadata>) -> MergeInput {
+        let title = metadata
+            .as_ref()
+            .and_then(DocumentMetadata::display_title)
+            .filter(|title| !title.trim().is_empty())
+            .unwrap_or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default());
+        MergeInput { title, pages, metadata }
+    }
+}
+
+/// Page inserted before each input after the first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeSeparator {
+    /// A white page the size of the input's first page
+    Blank,
+    /// An image file, used as-is
+    Image(Vec<u8>),
+}
+
+impl MergeSeparator {
+    /// Separator from a command-line or GUI value: "blank", or the path of an image file
+    pub fn from_arg(value: &str) -> Result<MergeSeparator> {
+        if value.eq_ignore_ascii_case("blank") {
+            return Ok(MergeSeparator::Blank);
+        }
+        let data = std::fs::read(value)
+            .context(format!("Failed to read separator image {:?}", value))?;
+        if PageImageFormat::sniff(&data).is_none() {
+            anyhow::bail!("Separator {:?} is not a supported image (expected \"blank\" or an image file)", value);
+        }
+        Ok(MergeSeparator::Image(data))
+    }
+
+    /// Separator page before `next_page`
+    fn page(&self, next_page: Option<&[u8]>) -> Result<Vec<u8>> {
+        match self {
+            MergeSeparator::Image(data) => Ok(data.clone()),
+            MergeSeparator::Blank => {
+                let (width, height) = next_page
+                    .and_then(|data| imagesize::blob_size(data).ok())
+                    .map(|size| (size.width as u32, size.height as u32))
+                    .filter(|&(width, height)| width > 0 && height > 0)
+                    .unwrap_or(DEFAULT_SEPARATOR_SIZE);
+                let page = DynamicImage::ImageLuma8(GrayImage::from_pixel(width, height, Luma([255])));
+                let mut data = Vec::new();
+                page.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+                    .context("Failed to encode blank separator page")?;
+                Ok(data)
+            }
+        }
+    }
+}
+
+/// Whether file data is a PDF (header within the first KB, as PDF readers accept)
+pub fn is_pdf_data(data: &[u8]) -> bool {
+    data[..data.len().min(1024)].windows(5).any(|window| window == b"%PDF-")
+}
+
+/// Combine inputs into the pages of one book, one folder (chapter) per input
+/// Folders are "NN - Title" and pages are renumbered inside them, so natural order keeps the input order
+/// and PDF output gets one bookmark per input (see `write_pdf_from_pages`). Folders inside an input are flattened.
+/// `separator` is inserted at the start of each chapter after the first.
+pub fn merge_pages(inputs: Vec<MergeInput>, separator: Option<&MergeSeparator>) -> Result<Vec<(String, Vec<u8>)>> {
+    if inputs.is_empty() {
+        anyhow::bail!("No inputs to merge");
+    }
+
+    let folder_width = inputs.len().to_string().len().max(2);
+    let mut merged = Vec::new();
+    for (index, input) in inputs.into_iter().enumerate() {
+        if input.pages.is_empty() {
+            anyhow::bail!("Input {} ({}) has no pages", index + 1, input.title);
+        }
+
+        let folder = format!("{:0width$} - {}", index + 1, folder_name(&input.title), width = folder_width);
+        let mut pages = Vec::with_capacity(input.pages.len() + 1);
+        if let Some(separator) = separator.filter(|_| index > 0) {
+            let page = separator.page(input.pages.first().map(|(_, data)| data.as_slice()))?;
+            pages.push((String::from("separator"), page));
+        }
+        pages.extend(input.pages);
+
+        let page_width = pages.len().to_string().len().max(3);
+        for (number, (name, data)) in pages.into_iter().enumerate() {
+            let extension = PageImageFormat::detect(&name, &data).map_or("jpg", PageImageFormat::extension);
+            merged.push((format!("{}/{:0width$}.{}", folder, number + 1, extension, width = page_width), data));
+        }
+    }
+    Ok(merged)
+}
+
+/// Metadata of the merged book: the first value found across inputs for each field
+/// Title, issue and volume numbers describe single inputs, so they are left out; the series is kept.
+pub fn merged_metadata(inputs: &[MergeInput]) -> DocumentMetadata {
+    let metadata = inputs
+        .iter()
+        .filter_map(|input| input.metadata.clone())
+        .fold(DocumentMetadata::default(), DocumentMetadata::merged_with);
+    DocumentMetadata { title: None, number: None, volume: None, ..metadata }
+}
+
+/// Chapter title usable as a folder name: path separators and characters not allowed in file names become `_`
+fn folder_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect();
+    let name = name.trim().trim_end_matches('.');
+    if name.is_empty() { "Untitled".to_string() } else { name.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbz_reader::CbzReader;
+    use crate::lopdf_extract::{extract_page_images_lopdf, lopdf_chapters};
+    use crate::limits::decode_image;
+    use crate::page_order::PageOrder;
+    use crate::pdf_options::PdfOutputOptions;
+    use crate::pdf_writer::write_pdf_from_images;
+    use crate::test_zip::{build_zip, RawEntry};
+    use crate::transparency::Transparency;
+
+    /// Gray PNG page; pages are told apart by their width
+    fn page(width: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, 12, |x, y| Luma([(x * 10 + y) as u8])))
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    fn read_cbz(path: &str, entries: &[RawEntry]) -> MergeInput {
+        let mut reader = CbzReader::new(Cursor::new(build_zip(entries)), None, PageOrder::Natural).unwrap();
+        let metadata = reader.metadata();
+        let pages = reader.into_pages().collect::<Result<Vec<_>>>().unwrap();
+        MergeInput::new(Path::new(path), pages, metadata)
+    }
+
+    #[test]
+    fn merges_two_cbzs_into_chapters() {
+        // Titled by its ComicInfo.xml, pages stored out of order
+        let first = read_cbz("first.cbz", &[
+            RawEntry::stored("02.png", &page(11)),
+            RawEntry::stored("01.png", &page(10)),
+            RawEntry::deflated("ComicInfo.xml", b"<ComicInfo><Title>Alpha</Title></ComicInfo>"),
+        ]);
+        // Titled by its file name, pages in a folder
+        let second = read_cbz("beta.cbz", &[
+            RawEntry::stored("Chapter/01.png", &page(20)),
+            RawEntry::stored("Chapter/02.png", &page(21)),
+        ]);
+
+        let pages = merge_pages(vec![first, second], Some(&MergeSeparator::Blank)).unwrap();
+        let names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["01 - Alpha/001.png", "01 - Alpha/002.png", "02 - beta/001.png", "02 - beta/002.png", "02 - beta/003.png"]);
+
+        let pdf = write_pdf_from_images(pages, Vec::new(), &PdfOutputOptions::default(), |_, _| {}).unwrap();
+        let document = lopdf::Document::load_mem(&pdf).unwrap();
+        assert_eq!(lopdf_chapters(&document), [("01 - Alpha".to_string(), 0), ("02 - beta".to_string(), 2)]);
+
+        // The blank separator takes the size of the page after it
+        let images: Vec<DynamicImage> = extract_page_images_lopdf(&pdf, true, 90, 0, None, Transparency::default())
+            .unwrap()
+            .iter()
+            .map(|(_, data)| decode_image(data).unwrap())
+            .collect();
+        let widths: Vec<u32> = images.iter().map(DynamicImage::width).collect();
+        assert_eq!(widths, [10, 11, 20, 20, 21]);
+        assert!(images[2].to_luma8().pixels().all(|p| p[0] == 255));
+    }
+}
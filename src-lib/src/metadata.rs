@@ -0,0 +1,161 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Reading direction of the book (manga is read right-to-left)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadingDirection {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+}
+
+/// A contributor and their role (Writer, Penciller, Colorist, ...)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credit {
+    pub person: String,
+    pub role: String,
+    /// Main contributor for this role (ComicBookInfo `primary`)
+    pub primary: bool,
+}
+
+/// Document metadata shared by PDF Info dictionaries and comic archive metadata
+#[derive(Debug, Clone, Default)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Vec<String>,
+    /// Genres (ComicInfo Genre, ComicBookInfo genre); written to PDF Keywords ahead of `keywords`
+    pub genres: Vec<String>,
+    pub creation_date: Option<DateTime<Utc>>,
+    pub reading_direction: ReadingDirection,
+    /// Comic series and issue number (no PDF Info equivalent)
+    pub series: Option<String>,
+    pub number: Option<String>,
+    /// Comic archive fields with no PDF Info equivalent
+    pub volume: Option<u32>,
+    pub publisher: Option<String>,
+    /// Language code (ComicInfo LanguageISO, ComicBookInfo language)
+    pub language: Option<String>,
+    /// All contributors; `author` holds the writers for PDF Info
+    pub credits: Vec<Credit>,
+}
+
+impl DocumentMetadata {
+    /// Fill every field missing from `self` with the value from `fallback`
+    /// Used to let CLI/GUI values override metadata found in the source file
+    pub fn merged_with(self, fallback: DocumentMetadata) -> DocumentMetadata {
+        DocumentMetadata {
+            title: self.title.or(fallback.title),
+            author: self.author.or(fallback.author),
+            subject: self.subject.or(fallback.subject),
+            keywords: if self.keywords.is_empty() { fallback.keywords } else { self.keywords },
+            genres: if self.genres.is_empty() { fallback.genres } else { self.genres },
+            creation_date: self.creation_date.or(fallback.creation_date),
+            series: self.series.or(fallback.series),
+            number: self.number.or(fallback.number),
+            volume: self.volume.or(fallback.volume),
+            publisher: self.publisher.or(fallback.publisher),
+            language: self.language.or(fallback.language),
+            credits: if self.credits.is_empty() { fallback.credits } else { self.credits },
+            reading_direction: if self.reading_direction == ReadingDirection::RightToLeft {
+                ReadingDirection::RightToLeft
+            } else {
+                fallback.reading_direction
+            },
+        }
+    }
+
+    /// Title for PDF Info, falling back to "Series #Number" when the issue has no title of its own
+    pub fn display_title(&self) -> Option<String> {
+        self.title.clone().or_else(|| {
+            self.series.as_ref().map(|series| match &self.number {
+                Some(number) => format!("{} #{}", series, number),
+                None => series.clone(),
+            })
+        })
+    }
+
+    /// Genres followed by keywords, for the PDF Keywords entry
+    pub fn pdf_keywords(&self) -> Vec<String> {
+        let mut keywords = self.genres.clone();
+        keywords.extend(self.keywords.iter().filter(|keyword| !self.genres.contains(keyword)).cloned());
+        keywords
+    }
+
+    /// Writers from the credits list, comma separated (used when no author is set)
+    pub fn writers(&self) -> Option<String> {
+        let writers: Vec<&str> = self
+            .credits
+            .iter()
+            .filter(|credit| credit.role.eq_ignore_ascii_case("Writer"))
+            .map(|credit| credit.person.as_str())
+            .collect();
+        (!writers.is_empty()).then(|| writers.join(", "))
+    }
+}
+
+/// Parse a `YYYY-MM-DD` (or `YYYY`, `YYYY-MM`) date into a UTC timestamp at midnight
+pub fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let mut parts = value.trim().splitn(3, '-');
+    let year = parts.next()?.parse::<i32>().ok()?;
+    let month = parts.next().map(|m| m.parse::<u32>().ok()).unwrap_or(Some(1))?;
+    let day = parts.next().map(|d| d.parse::<u32>().ok()).unwrap_or(Some(1))?;
+    date_from_parts(year, month, day)
+}
+
+/// Parse a PDF date string (`D:YYYYMMDDHHmmSS...`), keeping only the calendar date
+pub fn parse_pdf_date(value: &str) -> Option<DateTime<Utc>> {
+    let digits = value.trim().trim_start_matches("D:");
+    let year = digits.get(0..4)?.parse::<i32>().ok()?;
+    let month = digits.get(4..6).map(|m| m.parse::<u32>().ok()).unwrap_or(Some(1))?;
+    let day = digits.get(6..8).map(|d| d.parse::<u32>().ok()).unwrap_or(Some(1))?;
+    date_from_parts(year, month, day)
+}
+
+/// Build a UTC timestamp at midnight from calendar parts
+pub fn date_from_parts(year: i32, month: u32, day: u32) -> Option<DateTime<Utc>> {
+    NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc())
+}
+
+/// Split a comma/semicolon separated list (ComicInfo Genre/Tags, CLI keywords)
+pub fn split_list(value: &str) -> Vec<String> {
+    value
+        .split([',', ';'])
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pdf_keywords_put_genres_first_without_duplicates() {
+        let metadata = DocumentMetadata {
+            genres: vec!["Manga".to_string(), "Action".to_string()],
+            keywords: vec!["Action".to_string(), "shonen".to_string()],
+            ..DocumentMetadata::default()
+        };
+        assert_eq!(metadata.pdf_keywords(), ["Manga", "Action", "shonen"]);
+    }
+
+    #[test]
+    fn merged_with_fills_comic_fields() {
+        let fallback = DocumentMetadata {
+            publisher: Some("Glénat".to_string()),
+            volume: Some(3),
+            language: Some("fr".to_string()),
+            genres: vec!["Aventure".to_string()],
+            ..DocumentMetadata::default()
+        };
+        let merged = DocumentMetadata { language: Some("en".to_string()), ..DocumentMetadata::default() }.merged_with(fallback);
+        assert_eq!(merged.publisher.as_deref(), Some("Glénat"));
+        assert_eq!(merged.volume, Some(3));
+        assert_eq!(merged.language.as_deref(), Some("en"));
+        assert_eq!(merged.genres, ["Aventure"]);
+    }
+}
@@ -0,0 +1,146 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// How pages read from an archive are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageOrder {
+    /// Natural order of entry paths: numbers by value, case-insensitive, folder by folder
+    #[default]
+    Natural,
+    /// Order of the entries in the archive (ZIP central directory)
+    /// RAR archives are extracted with unar, which does not keep entry order: they use natural order.
+    Archive,
+}
+
+impl PageOrder {
+    pub fn from_archive_order(archive_order: bool) -> PageOrder {
+        if archive_order { PageOrder::Archive } else { PageOrder::Natural }
+    }
+}
+
+/// Sort items (listed in archive order) by their entry path according to `order`
+pub fn sort_pages<T, F>(items: &mut [T], order: PageOrder, path: F)
+where
+    F: Fn(&T) -> &str,
+{
+    if order == PageOrder::Natural {
+        items.sort_by(|a, b| natural_cmp(path(a), path(b)));
+    }
+}
+
+/// Compare archive entry paths in natural (human) order
+/// `page2` < `page10`, case is ignored, and paths are compared folder by folder
+/// with the files of a folder before its subfolders.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let a_parts: Vec<&str> = a.split(['/', '\\']).filter(|part| !part.is_empty()).collect();
+    let b_parts: Vec<&str> = b.split(['/', '\\']).filter(|part| !part.is_empty()).collect();
+
+    for (depth, (a_part, b_part)) in a_parts.iter().zip(&b_parts).enumerate() {
+        let a_is_file = depth + 1 == a_parts.len();
+        let b_is_file = depth + 1 == b_parts.len();
+        let ordering = b_is_file
+            .cmp(&a_is_file)
+            .then_with(|| natural_cmp_name(a_part, b_part));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    // Same names ignoring case and zero padding: keep a stable total order
+    a_parts.len().cmp(&b_parts.len()).then_with(|| a.cmp(b))
+}
+
+/// Compare one path component, digit runs by numeric value
+fn natural_cmp_name(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        let (a_char, b_char) = match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(&a_char), Some(&b_char)) => (a_char, b_char),
+        };
+
+        let ordering = if a_char.is_ascii_digit() && b_char.is_ascii_digit() {
+            let a_number = take_digits(&mut a);
+            let b_number = take_digits(&mut b);
+            let a_number = a_number.trim_start_matches('0');
+            let b_number = b_number.trim_start_matches('0');
+            a_number.len().cmp(&b_number.len()).then_with(|| a_number.cmp(b_number))
+        } else {
+            a.next();
+            b.next();
+            a_char.to_lowercase().cmp(b_char.to_lowercase())
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(&c) = chars.peek() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        digits.push(c);
+        chars.next();
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        sort_pages(&mut names, PageOrder::Natural, |name| name);
+        names
+    }
+
+    #[test]
+    fn numbers_by_value() {
+        assert_eq!(sorted(&["10.jpg", "2.jpg", "1.jpg"]), ["1.jpg", "2.jpg", "10.jpg"]);
+        assert_eq!(sorted(&["Page 10.png", "Page 2.png", "Page 1.png"]), ["Page 1.png", "Page 2.png", "Page 10.png"]);
+        assert_eq!(natural_cmp("vol2_p10", "vol2_p9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn ignores_case() {
+        assert_eq!(sorted(&["b.jpg", "A.jpg", "C.jpg"]), ["A.jpg", "b.jpg", "C.jpg"]);
+        assert_eq!(natural_cmp_name("Page", "page"), Ordering::Equal);
+    }
+
+    #[test]
+    fn leading_zeros() {
+        assert_eq!(sorted(&["010.jpg", "9.jpg", "002.jpg"]), ["002.jpg", "9.jpg", "010.jpg"]);
+        // Equal values keep a stable, total order
+        assert_eq!(natural_cmp("007.jpg", "7.jpg"), Ordering::Less);
+        assert_eq!(natural_cmp("7.jpg", "007.jpg"), Ordering::Greater);
+    }
+
+    #[test]
+    fn folders_first() {
+        assert_eq!(natural_cmp("a/10.jpg", "b/1.jpg"), Ordering::Less);
+        assert_eq!(
+            sorted(&["Chapter 10/1.jpg", "Chapter 2/5.jpg", "Chapter 2/10.jpg", "Chapter 1/3.jpg"]),
+            ["Chapter 1/3.jpg", "Chapter 2/5.jpg", "Chapter 2/10.jpg", "Chapter 10/1.jpg"]
+        );
+        // Files of a folder come before its subfolders, whatever their names
+        assert_eq!(sorted(&["a/sub/1.jpg", "a/z.jpg", "cover.jpg"]), ["cover.jpg", "a/z.jpg", "a/sub/1.jpg"]);
+        assert_eq!(natural_cmp("a\\2.jpg", "a/10.jpg"), Ordering::Less);
+    }
+
+    #[test]
+    fn archive_order_is_kept() {
+        let mut names = vec!["10.jpg", "1.jpg"];
+        sort_pages(&mut names, PageOrder::Archive, |name| name);
+        assert_eq!(names, ["10.jpg", "1.jpg"]);
+    }
+}
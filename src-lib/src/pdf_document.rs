@@ -0,0 +1,149 @@
+use anyhow::{Context, Result};
+use pdfium_render::prelude::*;
+use std::fmt;
+use std::path::Path;
+
+use crate::metadata::{parse_pdf_date, split_list, DocumentMetadata};
+use crate::pdfium_loader::bind_pdfium;
+
+/// Outline entries read at most (guards against malformed, looping outlines)
+const MAX_CHAPTERS: usize = 10_000;
+
+/// Error raised when an encrypted PDF is opened without the right password
+/// Callers can detect it with `password_error` to ask the user for a password and retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdfPasswordError {
+    /// The PDF is encrypted and no password was given
+    Required,
+    /// The given password does not open the PDF
+    Incorrect,
+}
+
+impl PdfPasswordError {
+    /// Stable code for frontends (e.g. Tauri command errors)
+    pub fn code(&self) -> &'static str {
+        match self {
+            PdfPasswordError::Required => "PDF_PASSWORD_REQUIRED",
+            PdfPasswordError::Incorrect => "PDF_PASSWORD_INCORRECT",
+        }
+    }
+}
+
+impl fmt::Display for PdfPasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PdfPasswordError::Required => write!(f, "PDF is password-protected, a password is required"),
+            PdfPasswordError::Incorrect => write!(f, "Incorrect password for PDF"),
+        }
+    }
+}
+
+impl std::error::Error for PdfPasswordError {}
+
+/// Load a PDF document, optionally decrypting it with `password`
+/// Encryption failures are reported as `PdfPasswordError`, other failures as "Failed to load PDF".
+pub fn load_pdf_document<'a>(
+    pdfium: &'a Pdfium,
+    pdf_data: Vec<u8>,
+    password: Option<&str>,
+) -> Result<PdfDocument<'a>> {
+    // An empty password is the same as no password for PDFium
+    let password = password.filter(|p| !p.is_empty());
+    check_loaded(pdfium.load_pdf_from_byte_vec(pdf_data, password), password)
+}
+
+/// Load a PDF document from disk without reading it into memory (see `load_pdf_document`)
+pub fn load_pdf_file<'a>(
+    pdfium: &'a Pdfium,
+    path: &Path,
+    password: Option<&'a str>,
+) -> Result<PdfDocument<'a>> {
+    let password = password.filter(|p| !p.is_empty());
+    check_loaded(pdfium.load_pdf_from_file(path, password), password)
+}
+
+/// Map PDFium load failures, reporting encryption failures as `PdfPasswordError`
+fn check_loaded<'a>(result: Result<PdfDocument<'a>, PdfiumError>, password: Option<&str>) -> Result<PdfDocument<'a>> {
+    match result {
+        Ok(document) => Ok(document),
+        Err(PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError)) => {
+            Err(if password.is_some() {
+                PdfPasswordError::Incorrect
+            } else {
+                PdfPasswordError::Required
+            }
+            .into())
+        }
+        Err(e) => Err(e).context("Failed to load PDF"),
+    }
+}
+
+/// Find a `PdfPasswordError` in an error chain
+pub fn password_error(error: &anyhow::Error) -> Option<PdfPasswordError> {
+    error.chain().find_map(|cause| cause.downcast_ref::<PdfPasswordError>().copied())
+}
+
+/// Read the Info dictionary of a PDF (Title, Author, Subject, Keywords, CreationDate)
+pub fn read_pdf_metadata(pdf_data: &[u8], password: Option<&str>) -> Result<DocumentMetadata> {
+    let pdfium = bind_pdfium()
+        .context("Failed to initialize Pdfium")?;
+    let document = load_pdf_document(&pdfium, pdf_data.to_vec(), password)?;
+    Ok(document_metadata(&document))
+}
+
+/// Map the Info dictionary of a loaded document to the shared metadata model
+pub fn document_metadata(document: &PdfDocument) -> DocumentMetadata {
+    let metadata = document.metadata();
+    let text = |tag| {
+        metadata
+            .get(tag)
+            .map(|entry| entry.value().trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    DocumentMetadata {
+        title: text(PdfDocumentMetadataTagType::Title),
+        author: text(PdfDocumentMetadataTagType::Author),
+        subject: text(PdfDocumentMetadataTagType::Subject),
+        keywords: text(PdfDocumentMetadataTagType::Keywords)
+            .map(|keywords| split_list(&keywords))
+            .unwrap_or_default(),
+        creation_date: text(PdfDocumentMetadataTagType::CreationDate)
+            .and_then(|date| parse_pdf_date(&date)),
+        ..DocumentMetadata::default()
+    }
+}
+
+/// Read the top-level outline entries (bookmarks) of a PDF
+pub fn read_pdf_chapters(pdf_data: &[u8], password: Option<&str>) -> Result<Vec<(String, usize)>> {
+    let pdfium = bind_pdfium()
+        .context("Failed to initialize Pdfium")?;
+    let document = load_pdf_document(&pdfium, pdf_data.to_vec(), password)?;
+    Ok(document_chapters(&document))
+}
+
+/// Top-level outline entries of a loaded document: title and index of the page they point to
+/// Entries that do not point to a page of the document are skipped.
+pub fn document_chapters(document: &PdfDocument) -> Vec<(String, usize)> {
+    let bookmarks = document.bookmarks();
+    let mut chapters = Vec::new();
+    let mut next = bookmarks.root();
+    // Bounded, in case of a Next cycle
+    for _ in 0..MAX_CHAPTERS {
+        let Some(bookmark) = next else {
+            break;
+        };
+        let page = bookmark.destination()
+            .and_then(|destination| destination.page_index().ok())
+            .or_else(|| {
+                let action = bookmark.action()?;
+                let destination = action.as_local_destination_action()?.destination().ok()?;
+                destination.page_index().ok()
+            });
+        if let Some(page) = page {
+            chapters.push((bookmark.title().unwrap_or_default().trim().to_string(), page as usize));
+        }
+        next = bookmark.next_sibling();
+    }
+    chapters
+}
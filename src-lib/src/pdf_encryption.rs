@@ -0,0 +1,412 @@
+// Standard security handler, revision 6 (AES-256, ISO 32000-2)
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::{Aes128, Aes256};
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::pdf_options::hex_string;
+
+// Passwords are truncated to 127 bytes of UTF-8 (ISO 32000-2, 7.6.4.3.3)
+const MAX_PASSWORD_BYTES: usize = 127;
+
+/// Operations allowed when an encrypted PDF is opened with the user password
+/// The owner password always grants full access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PdfPermissions {
+    pub print: bool,
+    /// Print at full resolution (otherwise only a low-quality print is allowed)
+    pub print_high_quality: bool,
+    pub modify: bool,
+    /// Copy or extract text and images
+    pub copy: bool,
+    /// Add or modify annotations and fill in form fields
+    pub annotate: bool,
+    pub fill_forms: bool,
+    /// Extract content for accessibility tools
+    pub accessibility: bool,
+    /// Insert, rotate or delete pages
+    pub assemble: bool,
+}
+
+impl Default for PdfPermissions {
+    fn default() -> Self {
+        PdfPermissions {
+            print: true,
+            print_high_quality: true,
+            modify: true,
+            copy: true,
+            annotate: true,
+            fill_forms: true,
+            accessibility: true,
+            assemble: true,
+        }
+    }
+}
+
+impl PdfPermissions {
+    /// Value of the Encrypt dictionary /P entry
+    pub fn p_value(&self) -> i32 {
+        // Bits 7-8 and 13-32 are reserved and must be set
+        let mut p: u32 = 0xFFFF_F0C0;
+        for (allowed, bit) in [
+            (self.print, 3),
+            (self.modify, 4),
+            (self.copy, 5),
+            (self.annotate, 6),
+            (self.fill_forms, 9),
+            (self.accessibility, 10),
+            (self.assemble, 11),
+            (self.print_high_quality, 12),
+        ] {
+            if allowed {
+                p |= 1 << (bit - 1);
+            }
+        }
+        p as i32
+    }
+}
+
+/// Password protection for generated PDFs (AES-256)
+/// An empty user password lets anyone open the file with the permissions below;
+/// an empty owner password is replaced by a random one, so the permissions cannot be lifted.
+/// Passwords are used as UTF-8 without SASLprep normalization (see `truncate_password`).
+#[derive(Debug, Clone, Default)]
+pub struct PdfEncryption {
+    pub user_password: String,
+    pub owner_password: String,
+    pub permissions: PdfPermissions,
+}
+
+/// Encrypts strings and streams of one document and provides its Encrypt dictionary
+pub(crate) struct SecurityHandler {
+    file_key: [u8; 32],
+    dictionary: String,
+}
+
+impl SecurityHandler {
+    pub(crate) fn new(encryption: &PdfEncryption) -> Result<SecurityHandler> {
+        let file_key: [u8; 32] = random_bytes()?;
+        let user_password = truncate_password(&encryption.user_password);
+        let owner_password = if encryption.owner_password.is_empty() {
+            random_bytes::<32>()?.to_vec()
+        } else {
+            truncate_password(&encryption.owner_password).to_vec()
+        };
+
+        // User password: validation hash + salts, and the file key wrapped with the password key
+        let user_salts: [u8; 16] = random_bytes()?;
+        let mut u = hash_r6(user_password, &user_salts[..8], &[]).to_vec();
+        u.extend_from_slice(&user_salts);
+        let ue = aes256_cbc_no_iv(&hash_r6(user_password, &user_salts[8..], &[]), &file_key);
+
+        // Owner password: same, salted with the U string
+        let owner_salts: [u8; 16] = random_bytes()?;
+        let mut o = hash_r6(&owner_password, &owner_salts[..8], &u).to_vec();
+        o.extend_from_slice(&owner_salts);
+        let oe = aes256_cbc_no_iv(&hash_r6(&owner_password, &owner_salts[8..], &u), &file_key);
+
+        // Perms: permissions encrypted with the file key so they cannot be tampered with
+        let p = encryption.permissions.p_value();
+        let mut perms = [0u8; 16];
+        perms[..4].copy_from_slice(&p.to_le_bytes());
+        perms[4..8].copy_from_slice(&[0xFF; 4]);
+        perms[8..12].copy_from_slice(b"Tadb");
+        perms[12..].copy_from_slice(&random_bytes::<4>()?);
+        let perms = aes256_cbc_no_iv(&file_key, &perms);
+
+        let dictionary = format!(
+            "<< /Filter /Standard /V 5 /R 6 /Length 256 /CF << /StdCF << /Type /CryptFilter /CFM /AESV3 /AuthEvent /DocOpen /Length 32 >> >> /StmF /StdCF /StrF /StdCF /O {} /U {} /OE {} /UE {} /P {} /Perms {} /EncryptMetadata true >>",
+            hex_string(&o),
+            hex_string(&u),
+            hex_string(&oe),
+            hex_string(&ue),
+            p,
+            hex_string(&perms)
+        );
+
+        Ok(SecurityHandler { file_key, dictionary })
+    }
+
+    /// Encrypt Dictionary for the trailer /Encrypt entry (written unencrypted)
+    pub(crate) fn dictionary(&self) -> &str {
+        &self.dictionary
+    }
+
+    /// Encrypt a string or stream: random 16-byte IV followed by AES-256-CBC with PKCS#7 padding
+    pub(crate) fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let iv: [u8; 16] = random_bytes()?;
+        let padding = 16 - data.len() % 16;
+        let mut padded = Vec::with_capacity(data.len() + padding);
+        padded.extend_from_slice(data);
+        padded.resize(data.len() + padding, padding as u8);
+
+        let cipher = Aes256::new(GenericArray::from_slice(&self.file_key));
+        let mut encrypted = iv.to_vec();
+        encrypted.extend(cbc_encrypt(|block| cipher.encrypt_block(block), &iv, &padded));
+        Ok(encrypted)
+    }
+}
+
+/// Password hash of revision 6 (ISO 32000-2, algorithm 2.B)
+fn hash_r6(password: &[u8], salt: &[u8], user_key: &[u8]) -> [u8; 32] {
+    let mut k = Sha256::new()
+        .chain_update(password)
+        .chain_update(salt)
+        .chain_update(user_key)
+        .finalize()
+        .to_vec();
+
+    let mut round: u32 = 0;
+    loop {
+        let mut k1 = Vec::with_capacity(64 * (password.len() + k.len() + user_key.len()));
+        for _ in 0..64 {
+            k1.extend_from_slice(password);
+            k1.extend_from_slice(&k);
+            k1.extend_from_slice(user_key);
+        }
+
+        let cipher = Aes128::new(GenericArray::from_slice(&k[..16]));
+        let e = cbc_encrypt(|block| cipher.encrypt_block(block), &k[16..32], &k1);
+
+        // First 16 bytes of E as a big-endian number, modulo 3
+        let selector: u32 = e[..16].iter().map(|&b| b as u32).sum::<u32>() % 3;
+        k = match selector {
+            0 => Sha256::digest(&e).to_vec(),
+            1 => Sha384::digest(&e).to_vec(),
+            _ => Sha512::digest(&e).to_vec(),
+        };
+
+        round += 1;
+        let last = *e.last().unwrap_or(&0) as u32;
+        if round >= 64 && last <= round - 32 {
+            break;
+        }
+    }
+
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&k[..32]);
+    hash
+}
+
+/// AES-256-CBC with a zero IV and no padding (data length must be a multiple of 16)
+fn aes256_cbc_no_iv(key: &[u8; 32], data: &[u8]) -> Vec<u8> {
+    let cipher = Aes256::new(GenericArray::from_slice(key));
+    cbc_encrypt(|block| cipher.encrypt_block(block), &[0; 16], data)
+}
+
+/// CBC mode over a block encryption function (data length must be a multiple of 16)
+fn cbc_encrypt<F>(encrypt_block: F, iv: &[u8], data: &[u8]) -> Vec<u8>
+where
+    F: Fn(&mut GenericArray<u8, aes::cipher::consts::U16>),
+{
+    let mut output = Vec::with_capacity(data.len());
+    let mut previous = GenericArray::clone_from_slice(iv);
+    for chunk in data.chunks(16) {
+        let mut block = GenericArray::clone_from_slice(chunk);
+        for (byte, prev) in block.iter_mut().zip(previous.iter()) {
+            *byte ^= prev;
+        }
+        encrypt_block(&mut block);
+        output.extend_from_slice(&block);
+        previous = block;
+    }
+    output
+}
+
+/// Password bytes for revision 6
+/// The spec asks for SASLprep (RFC 4013) before truncation; it is not applied, so the
+/// raw UTF-8 is used. ASCII passwords are unaffected, but a non-ASCII password typed in a
+/// different Unicode normalization form (e.g. decomposed accents) will not match.
+fn truncate_password(password: &str) -> &[u8] {
+    let bytes = password.as_bytes();
+    &bytes[..bytes.len().min(MAX_PASSWORD_BYTES)]
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow!("Failed to generate random bytes: {}", e))?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::cipher::BlockDecrypt;
+
+    // Revision 6 sample (user password "user", owner password "owner") from pdf.js' crypto tests
+    const USER_HASH: [u8; 32] = [
+        94, 230, 205, 75, 166, 99, 250, 76, 219, 128, 17, 85, 57, 17, 33, 164,
+        150, 46, 103, 176, 160, 156, 187, 233, 166, 223, 163, 253, 147, 235, 95, 184,
+    ];
+    const USER_VALIDATION_SALT: [u8; 8] = [83, 245, 146, 101, 198, 247, 34, 198];
+    const USER_KEY_SALT: [u8; 8] = [191, 11, 16, 94, 237, 216, 20, 175];
+    const USER_ENCRYPTION: [u8; 32] = [
+        121, 208, 2, 181, 230, 89, 156, 60, 253, 143, 212, 28, 84, 180, 196, 177,
+        173, 128, 221, 107, 46, 20, 94, 186, 135, 51, 95, 24, 20, 223, 254, 36,
+    ];
+    const OWNER_HASH: [u8; 32] = [
+        88, 232, 62, 54, 245, 26, 245, 209, 137, 123, 221, 72, 199, 49, 37, 217,
+        31, 74, 115, 167, 127, 158, 176, 77, 45, 163, 87, 47, 39, 90, 217, 141,
+    ];
+    const OWNER_VALIDATION_SALT: [u8; 8] = [142, 232, 169, 208, 202, 214, 5, 185];
+    const OWNER_KEY_SALT: [u8; 8] = [29, 208, 185, 46, 11, 76, 135, 149];
+    const OWNER_ENCRYPTION: [u8; 32] = [
+        209, 73, 224, 77, 103, 155, 201, 181, 190, 68, 223, 20, 62, 90, 56, 210,
+        5, 240, 178, 128, 238, 124, 68, 254, 253, 244, 62, 108, 208, 135, 10, 251,
+    ];
+    const FILE_KEY: [u8; 32] = [
+        42, 218, 213, 39, 73, 91, 72, 79, 67, 38, 248, 133, 18, 189, 61, 34,
+        107, 79, 29, 56, 59, 181, 213, 118, 113, 34, 65, 210, 87, 174, 22, 239,
+    ];
+
+    fn sample_u() -> Vec<u8> {
+        let mut u = USER_HASH.to_vec();
+        u.extend_from_slice(&USER_VALIDATION_SALT);
+        u.extend_from_slice(&USER_KEY_SALT);
+        u
+    }
+
+    fn aes256_cbc_decrypt(key: &[u8], iv: &[u8], data: &[u8]) -> Vec<u8> {
+        let cipher = Aes256::new(GenericArray::from_slice(key));
+        let mut output = Vec::with_capacity(data.len());
+        let mut previous = iv.to_vec();
+        for chunk in data.chunks(16) {
+            let mut block = GenericArray::clone_from_slice(chunk);
+            cipher.decrypt_block(&mut block);
+            output.extend(block.iter().zip(&previous).map(|(byte, prev)| byte ^ prev));
+            previous = chunk.to_vec();
+        }
+        output
+    }
+
+    /// Bytes of a hex string entry such as `/U <...>` in the Encrypt dictionary
+    fn dictionary_entry(dictionary: &str, key: &str) -> Vec<u8> {
+        let start = dictionary.find(&format!("/{} <", key)).unwrap() + key.len() + 3;
+        let end = start + dictionary[start..].find('>').unwrap();
+        let hex = &dictionary[start..end];
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    /// File key recovered from U/UE or O/OE with `password`, after checking the validation hash
+    fn file_key_for(password: &[u8], hash: &[u8], wrapped_key: &[u8], user_key: &[u8]) -> Option<Vec<u8>> {
+        if hash_r6(password, &hash[32..40], user_key) != hash[..32] {
+            return None;
+        }
+        Some(aes256_cbc_decrypt(&hash_r6(password, &hash[40..48], user_key), &[0; 16], wrapped_key))
+    }
+
+    #[test]
+    fn hash_r6_matches_known_user_hash() {
+        assert_eq!(hash_r6(b"user", &USER_VALIDATION_SALT, &[]), USER_HASH);
+    }
+
+    #[test]
+    fn hash_r6_matches_known_owner_hash() {
+        assert_eq!(hash_r6(b"owner", &OWNER_VALIDATION_SALT, &sample_u()), OWNER_HASH);
+    }
+
+    #[test]
+    fn wraps_file_key_into_known_ue_and_oe() {
+        let user_key = hash_r6(b"user", &USER_KEY_SALT, &[]);
+        assert_eq!(aes256_cbc_no_iv(&user_key, &FILE_KEY), USER_ENCRYPTION);
+
+        let owner_key = hash_r6(b"owner", &OWNER_KEY_SALT, &sample_u());
+        assert_eq!(aes256_cbc_no_iv(&owner_key, &FILE_KEY), OWNER_ENCRYPTION);
+    }
+
+    #[test]
+    fn aes256_cbc_no_iv_matches_fips_197() {
+        // FIPS-197 appendix C.3 (a single block, so CBC with a zero IV is plain AES)
+        let key: Vec<u8> = (0u8..32).collect();
+        let plaintext: Vec<u8> = (0u8..16).map(|i| i * 0x11).collect();
+        let expected = [
+            0x8e, 0xa2, 0xb7, 0xca, 0x51, 0x67, 0x45, 0xbf,
+            0xea, 0xfc, 0x49, 0x90, 0x4b, 0x49, 0x60, 0x89,
+        ];
+        assert_eq!(aes256_cbc_no_iv(key.as_slice().try_into().unwrap(), &plaintext), expected);
+    }
+
+    #[test]
+    fn permission_bits() {
+        assert_eq!(PdfPermissions::default().p_value(), -4);
+        let print_only = PdfPermissions {
+            modify: false,
+            copy: false,
+            annotate: false,
+            fill_forms: false,
+            accessibility: false,
+            assemble: false,
+            ..PdfPermissions::default()
+        };
+        assert_eq!(print_only.p_value() as u32, 0xFFFF_F8C4);
+    }
+
+    #[test]
+    fn generated_dictionary_round_trips() {
+        let permissions = PdfPermissions { copy: false, modify: false, ..PdfPermissions::default() };
+        let handler = SecurityHandler::new(&PdfEncryption {
+            user_password: "reader".to_string(),
+            owner_password: "publisher".to_string(),
+            permissions,
+        })
+        .unwrap();
+        let dictionary = handler.dictionary();
+        assert!(dictionary.contains("/V 5 /R 6"));
+        assert!(dictionary.contains(&format!("/P {} ", permissions.p_value())));
+
+        let u = dictionary_entry(dictionary, "U");
+        let ue = dictionary_entry(dictionary, "UE");
+        let o = dictionary_entry(dictionary, "O");
+        let oe = dictionary_entry(dictionary, "OE");
+        assert_eq!((u.len(), ue.len(), o.len(), oe.len()), (48, 32, 48, 32));
+
+        // Both passwords unwrap the same file key; wrong passwords are rejected
+        let user_key = file_key_for(b"reader", &u, &ue, &[]).unwrap();
+        let owner_key = file_key_for(b"publisher", &o, &oe, &u).unwrap();
+        assert_eq!(user_key, handler.file_key);
+        assert_eq!(owner_key, handler.file_key);
+        assert!(file_key_for(b"publisher", &u, &ue, &[]).is_none());
+        assert!(file_key_for(b"reader", &o, &oe, &u).is_none());
+
+        // Perms decrypts to P, the reserved bytes and the "Tadb" marker
+        let perms = aes256_cbc_decrypt(&user_key, &[0; 16], &dictionary_entry(dictionary, "Perms"));
+        assert_eq!(&perms[..4], &permissions.p_value().to_le_bytes());
+        assert_eq!(&perms[4..8], &[0xFF; 4]);
+        assert_eq!(&perms[8..12], b"Tadb");
+    }
+
+    #[test]
+    fn empty_owner_password_is_random() {
+        let handler = SecurityHandler::new(&PdfEncryption::default()).unwrap();
+        let dictionary = handler.dictionary();
+        let u = dictionary_entry(dictionary, "U");
+        let o = dictionary_entry(dictionary, "O");
+        assert!(file_key_for(b"", &u, &dictionary_entry(dictionary, "UE"), &[]).is_some());
+        assert!(file_key_for(b"", &o, &dictionary_entry(dictionary, "OE"), &u).is_none());
+    }
+
+    #[test]
+    fn encrypts_strings_and_streams() {
+        let handler = SecurityHandler::new(&PdfEncryption::default()).unwrap();
+        for data in [&b""[..], b"(Title)", &[7u8; 16], &[0xABu8; 1000]] {
+            let encrypted = handler.encrypt(data).unwrap();
+            // IV + data padded to the next whole block
+            assert_eq!(encrypted.len(), 16 + (data.len() / 16 + 1) * 16);
+
+            let mut decrypted = aes256_cbc_decrypt(&handler.file_key, &encrypted[..16], &encrypted[16..]);
+            let padding = *decrypted.last().unwrap() as usize;
+            assert!((1..=16).contains(&padding));
+            assert!(decrypted[decrypted.len() - padding..].iter().all(|&b| b as usize == padding));
+            decrypted.truncate(decrypted.len() - padding);
+            assert_eq!(decrypted, data);
+        }
+    }
+
+    #[test]
+    fn truncates_long_passwords() {
+        let long = "x".repeat(200);
+        assert_eq!(truncate_password(&long).len(), MAX_PASSWORD_BYTES);
+        assert_eq!(truncate_password("user"), b"user");
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::DynamicImage;
+use pdfium_render::prelude::*;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::bilevel::{decode_jbig2, infer_ccitt_params, BilevelFilter, BilevelImage, BilevelStream};
+use crate::direct_extract::{extract_bilevel_image, extract_image, find_best_image_candidate};
+use crate::pdf_document::{document_metadata, load_pdf_file};
+use crate::pdf_options::PdfOutputOptions;
+use crate::pdf_writer::{ImagePlacement, PdfStreamWriter};
+use crate::pdfium_loader::bind_pdfium;
+use crate::transparency::Transparency;
+
+/// Resolution page images are downsampled to when none is given
+pub const DEFAULT_OPTIMIZE_DPI: u32 = 150;
+
+/// Pages whose largest image covers less of the page are rendered instead,
+/// so text and drawings around the image are kept
+const MIN_COVERAGE_FOR_REUSE: f64 = 0.9;
+
+/// Outcome of a PDF optimisation
+#[derive(Debug, Clone, Default)]
+pub struct OptimizeReport {
+    pub pages: usize,
+    /// Pages rebuilt from their embedded image
+    pub extracted_pages: usize,
+    /// Pages rendered because no single image covers them
+    pub rendered_pages: usize,
+    pub input_size: u64,
+    pub output_size: u64,
+}
+
+impl OptimizeReport {
+    /// Bytes saved (negative when the output is larger)
+    pub fn saved_bytes(&self) -> i64 {
+        self.input_size as i64 - self.output_size as i64
+    }
+
+    /// Fraction of the input size saved (0.0 to 1.0, negative when the output is larger)
+    pub fn saved_ratio(&self) -> f64 {
+        if self.input_size == 0 {
+            return 0.0;
+        }
+        self.saved_bytes() as f64 / self.input_size as f64
+    }
+}
+
+/// Rebuild a PDF as a lightweight image PDF, one image per page, keeping page sizes
+/// Scanned pages reuse their embedded image (see `find_best_image_candidate`), other pages are rendered.
+/// Images above `dpi` are downsampled, then encoded as set by `options.image_encoding`.
+/// Black-and-white scans keep their CCITT or JBIG2 data when possible, otherwise they are re-encoded as CCITT Group 4.
+/// The source document's metadata fills in whatever `options.metadata` leaves empty.
+/// progress_callback: called with (current, total) after each page
+pub fn optimize_pdf_file<F>(
+    input: &Path,
+    output: &Path,
+    password: Option<&str>,
+    dpi: u32,
+    mut options: PdfOutputOptions,
+    mut progress_callback: F,
+) -> Result<OptimizeReport>
+where
+    F: FnMut(usize, usize),
+{
+    let dpi = if dpi == 0 { DEFAULT_OPTIMIZE_DPI } else { dpi };
+    let pdfium = bind_pdfium()
+        .context("Failed to initialize Pdfium")?;
+    let document = load_pdf_file(&pdfium, input, password)?;
+    options.metadata = options.metadata.merged_with(document_metadata(&document));
+
+    let page_count = document.pages().len() as usize;
+    if page_count == 0 {
+        anyhow::bail!("PDF has no pages");
+    }
+
+    let file = File::create(output)
+        .context(format!("Failed to create {:?}", output))?;
+    let mut pdf = PdfStreamWriter::new(BufWriter::new(file), options)?;
+    let mut report = OptimizeReport { pages: page_count, ..OptimizeReport::default() };
+
+    for index in 0..page_count {
+        let page = document
+            .pages()
+            .get(index as u16)
+            .context(format!("Failed to get page {}", index + 1))?;
+        let page_size = (page.width().value, page.height().value);
+
+        match reusable_image(&document, &page)? {
+            Some((ReusedImage::Bilevel(stream), placement)) => {
+                pdf.add_bilevel_page_at(stream, page_size, placement)?;
+                report.extracted_pages += 1;
+            }
+            Some((ReusedImage::Pixels(image), placement)) => {
+                let image = downsample(image, placement.2, placement.3, dpi);
+                pdf.add_bitmap_page_at(&image, page_size, placement)?;
+                report.extracted_pages += 1;
+            }
+            None => {
+                let image = render_page(&page, page_size, dpi)
+                    .context(format!("Failed to render page {}", index + 1))?;
+                pdf.add_bitmap_page_at(&image, page_size, (0.0, 0.0, page_size.0, page_size.1))?;
+                report.rendered_pages += 1;
+            }
+        }
+        progress_callback(index + 1, page_count);
+    }
+    pdf.finish()?;
+
+    report.input_size = std::fs::metadata(input).map(|m| m.len()).unwrap_or(0);
+    report.output_size = std::fs::metadata(output).map(|m| m.len()).unwrap_or(0);
+    Ok(report)
+}
+
+/// Image reused from a scanned page
+enum ReusedImage {
+    /// Black-and-white scan, written as-is (not downsampled, which would add gray levels)
+    Bilevel(BilevelStream),
+    Pixels(DynamicImage),
+}
+
+/// The image covering a scanned page, with its placement
+/// None when the page has to be rendered (no image large enough, rotated page, extraction failure)
+/// Transparent images are composited on white, as the page shows them.
+fn reusable_image(document: &PdfDocument, page: &PdfPage) -> Result<Option<(ReusedImage, ImagePlacement)>> {
+    // Image bounds are in unrotated page space
+    if !matches!(page.rotation(), Ok(PdfPageRenderRotation::None)) {
+        return Ok(None);
+    }
+
+    let (candidate, crop_bounds) = find_best_image_candidate(page)?;
+    let Some(candidate) = candidate.filter(|c| c.can_extract_raw && c.coverage >= MIN_COVERAGE_FOR_REUSE) else {
+        return Ok(None);
+    };
+
+    let image = match extract_bilevel_image(page, candidate.object_index) {
+        Ok(Some(bilevel)) => bilevel_stream(page, candidate.object_index, &bilevel).map(ReusedImage::Bilevel),
+        _ => extract_image(document, page, candidate.object_index, Transparency::default())
+            .map(|image| ReusedImage::Pixels(gray_if_neutral(image))),
+    };
+    let image = match image {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("[OPTIMIZE] Rendering page instead of reusing its image: {:#}", e);
+            return Ok(None);
+        }
+    };
+
+    let (left, bottom, right, top) = candidate.bounds;
+    let placement = (left - crop_bounds.0, bottom - crop_bounds.1, right - left, top - bottom);
+    Ok(Some((image, placement)))
+}
+
+/// The image object's own CCITT or JBIG2 data when it can be copied, otherwise a CCITT Group 4 re-encoding
+/// PDFium does not expose DecodeParms: CCITT parameters are found by decoding the data until it
+/// matches PDFium's pixels, and JBIG2 data is only copied when it needs no global segments.
+fn bilevel_stream(page: &PdfPage, object_index: usize, bilevel: &BilevelImage) -> Result<BilevelStream> {
+    let object = page.objects().iter().nth(object_index).context("Object not found at index")?;
+    let image_obj = object.as_image_object().context("Object is not an image")?;
+    let filters: Vec<String> = image_obj.filters().iter().map(|filter| filter.name().to_string()).collect();
+
+    let original = match image_obj.get_raw_image_data() {
+        Ok(data) if filters == ["CCITTFaxDecode"] =>
+            infer_ccitt_params(&data, bilevel).map(|params| (BilevelFilter::Ccitt(params), data)),
+        Ok(data) if filters == ["JBIG2Decode"] => decode_jbig2(&data, None)
+            .is_ok_and(|decoded| decoded == *bilevel)
+            .then_some((BilevelFilter::Jbig2, data)),
+        _ => None,
+    };
+
+    match original {
+        Some((filter, data)) => Ok(BilevelStream { width: bilevel.width(), height: bilevel.height(), filter, data }),
+        None => bilevel.to_stream(),
+    }
+}
+
+/// Extracted images are always RGB: keep grayscale scans in one channel
+fn gray_if_neutral(image: DynamicImage) -> DynamicImage {
+    match &image {
+        DynamicImage::ImageRgb8(rgb) if rgb.pixels().all(|p| p[0] == p[1] && p[1] == p[2]) =>
+            DynamicImage::ImageLuma8(image.to_luma8()),
+        _ => image,
+    }
+}
+
+/// Downsample an image drawn at `width_pt` × `height_pt` points so it is at most `dpi`
+fn downsample(image: DynamicImage, width_pt: f32, height_pt: f32, dpi: u32) -> DynamicImage {
+    let max_width = (width_pt / 72.0 * dpi as f32).round().max(1.0) as u32;
+    let max_height = (height_pt / 72.0 * dpi as f32).round().max(1.0) as u32;
+    if image.width() <= max_width && image.height() <= max_height {
+        return image;
+    }
+    image.resize(max_width, max_height, FilterType::CatmullRom)
+}
+
+/// Render a whole page at `dpi`
+fn render_page(page: &PdfPage, page_size: (f32, f32), dpi: u32) -> Result<DynamicImage> {
+    let width = (page_size.0 / 72.0 * dpi as f32).round().max(1.0) as i32;
+    let height = (page_size.1 / 72.0 * dpi as f32).round().max(1.0) as i32;
+    let config = PdfRenderConfig::new()
+        .set_target_width(width)
+        .set_target_height(height);
+    let bitmap = page.render_with_config(&config)?;
+    Ok(bitmap.as_image())
+}
@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+
+use crate::metadata::{parse_date, split_list, DocumentMetadata, ReadingDirection};
+use crate::pdf_encryption::PdfEncryption;
+
+/// Title used when neither the source nor the caller provides one
+pub const DEFAULT_PDF_TITLE: &str = "CBZ to PDF";
+
+/// Initial page layout requested from PDF viewers (Catalog /PageLayout)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageLayout {
+    /// One page at a time
+    #[default]
+    SinglePage,
+    /// Pages in one continuous column
+    OneColumn,
+    /// Two pages side by side, odd pages on the left
+    TwoPageLeft,
+    /// Two pages side by side, odd pages on the right (cover shown alone)
+    TwoPageRight,
+}
+
+impl PageLayout {
+    /// PDF name for the Catalog /PageLayout entry
+    pub fn pdf_name(&self) -> &'static str {
+        match self {
+            PageLayout::SinglePage => "SinglePage",
+            PageLayout::OneColumn => "OneColumn",
+            PageLayout::TwoPageLeft => "TwoPageLeft",
+            PageLayout::TwoPageRight => "TwoPageRight",
+        }
+    }
+}
+
+/// Standard the generated PDF conforms to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PdfConformance {
+    /// Plain PDF 1.7
+    #[default]
+    Standard,
+    /// PDF/A-2b (ISO 19005-2, level B) for long-term archival
+    PdfA2b,
+}
+
+/// JPEG quality used when pages are re-encoded and none is given
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// How page images are embedded in generated PDFs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageEncoding {
+    /// Every page is embedded losslessly: JPEG data as-is, other images Flate-compressed
+    #[default]
+    Lossless,
+    /// Non-JPEG pages are re-encoded to JPEG at `quality` (1-100)
+    /// With `max_resolution`, pages whose longest side is larger (JPEG pages included) are downscaled to it.
+    Jpeg { quality: u8, max_resolution: Option<u32> },
+}
+
+/// Document-level options for generated PDFs (Info dictionary and viewer defaults)
+#[derive(Debug, Clone, Default)]
+pub struct PdfOutputOptions {
+    pub metadata: DocumentMetadata,
+    pub page_layout: PageLayout,
+    pub conformance: PdfConformance,
+    /// Password-protect the document (AES-256); not allowed with PDF/A
+    pub encryption: Option<PdfEncryption>,
+    /// How page images are embedded
+    pub image_encoding: ImageEncoding,
+}
+
+/// Document settings chosen by the user (CLI flags, GUI form), before archive metadata is merged in
+#[derive(Debug, Clone, Default)]
+pub struct PdfMetadataSettings {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    /// Comma or semicolon separated keywords, replacing the archive's tags and genres
+    pub keywords: Option<String>,
+    /// Creation date as YYYY-MM-DD
+    pub date: Option<String>,
+    /// Ask viewers to read right-to-left
+    pub manga: bool,
+    /// Show two-page spreads with the cover alone (PageLayout TwoPageRight)
+    pub spreads: bool,
+    /// Write PDF/A-2b
+    pub pdfa: bool,
+}
+
+impl PdfOutputOptions {
+    /// Options from user settings, falling back to the archive's ComicInfo.xml / ComicBookInfo metadata
+    /// Encryption and image encoding are left at their defaults for the caller to set.
+    pub fn from_settings(settings: &PdfMetadataSettings, archive_metadata: Option<DocumentMetadata>) -> Result<PdfOutputOptions> {
+        let creation_date = match &settings.date {
+            Some(date) => Some(parse_date(date)
+                .context(format!("Invalid date '{}', expected YYYY-MM-DD", date))?),
+            None => None,
+        };
+
+        let overrides = DocumentMetadata {
+            title: settings.title.clone(),
+            author: settings.author.clone(),
+            subject: settings.subject.clone(),
+            keywords: settings.keywords.as_deref().map(split_list).unwrap_or_default(),
+            creation_date,
+            reading_direction: if settings.manga {
+                ReadingDirection::RightToLeft
+            } else {
+                ReadingDirection::LeftToRight
+            },
+            ..DocumentMetadata::default()
+        };
+
+        let mut metadata = match archive_metadata {
+            Some(archive_metadata) => overrides.merged_with(archive_metadata),
+            None => overrides,
+        };
+        // Given keywords replace both the archive's tags and genres
+        if settings.keywords.is_some() {
+            metadata.genres.clear();
+        }
+
+        Ok(PdfOutputOptions {
+            metadata,
+            page_layout: if settings.spreads { PageLayout::TwoPageRight } else { PageLayout::SinglePage },
+            conformance: if settings.pdfa { PdfConformance::PdfA2b } else { PdfConformance::Standard },
+            encryption: None,
+            image_encoding: ImageEncoding::default(),
+        })
+    }
+}
+
+/// Encode a PDF text string object
+/// ASCII text is written as a literal string, anything else as UTF-16BE (with BOM) hex string
+pub fn text_string(value: &str) -> String {
+    if value.is_ascii() {
+        return literal_string(value);
+    }
+    hex_string(&text_bytes(value))
+}
+
+/// Bytes of a PDF text string: ASCII as-is, anything else as UTF-16BE with BOM
+pub fn text_bytes(value: &str) -> Vec<u8> {
+    if value.is_ascii() {
+        return value.as_bytes().to_vec();
+    }
+    let mut bytes = vec![0xFE, 0xFF];
+    for unit in value.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+    bytes
+}
+
+/// Encode a PDF date string object (D:YYYYMMDDHHmmSS+00'00')
+pub fn date_string(date: &DateTime<Utc>) -> String {
+    literal_string(&date_text(date))
+}
+
+/// Text of a PDF date string, without delimiters
+pub fn date_text(date: &DateTime<Utc>) -> String {
+    format!("D:{}+00'00'", date.format("%Y%m%d%H%M%S"))
+}
+
+/// Encode bytes as a PDF hex string object
+pub fn hex_string(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2 + 2);
+    hex.push('<');
+    for byte in bytes {
+        hex.push_str(&format!("{:02X}", byte));
+    }
+    hex.push('>');
+    hex
+}
+
+/// Encode an ASCII literal string, escaping delimiters and control characters
+fn literal_string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('(');
+    for c in value.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_ascii_control() => literal.push_str(&format!("\\{:03o}", c as u8)),
+            c => literal.push(c),
+        }
+    }
+    literal.push(')');
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive_metadata() -> DocumentMetadata {
+        DocumentMetadata {
+            title: Some("Archive title".to_string()),
+            author: Some("Archive author".to_string()),
+            keywords: vec!["tag".to_string()],
+            genres: vec!["Fantasy".to_string()],
+            ..DocumentMetadata::default()
+        }
+    }
+
+    #[test]
+    fn settings_override_archive_metadata() {
+        let settings = PdfMetadataSettings {
+            title: Some("Title".to_string()),
+            date: Some("2024-03-05".to_string()),
+            manga: true,
+            spreads: true,
+            pdfa: true,
+            ..PdfMetadataSettings::default()
+        };
+        let options = PdfOutputOptions::from_settings(&settings, Some(archive_metadata())).unwrap();
+        assert_eq!(options.metadata.title.as_deref(), Some("Title"));
+        assert_eq!(options.metadata.author.as_deref(), Some("Archive author"));
+        assert_eq!(options.metadata.genres, ["Fantasy"]);
+        assert_eq!(options.metadata.creation_date, parse_date("2024-03-05"));
+        assert_eq!(options.metadata.reading_direction, ReadingDirection::RightToLeft);
+        assert_eq!(options.page_layout, PageLayout::TwoPageRight);
+        assert_eq!(options.conformance, PdfConformance::PdfA2b);
+        assert!(options.encryption.is_none());
+    }
+
+    #[test]
+    fn keywords_replace_tags_and_genres() {
+        let settings = PdfMetadataSettings { keywords: Some("action; comedy".to_string()), ..PdfMetadataSettings::default() };
+        let options = PdfOutputOptions::from_settings(&settings, Some(archive_metadata())).unwrap();
+        assert_eq!(options.metadata.keywords, ["action", "comedy"]);
+        assert!(options.metadata.genres.is_empty());
+    }
+
+    #[test]
+    fn defaults_without_settings() {
+        let options = PdfOutputOptions::from_settings(&PdfMetadataSettings::default(), None).unwrap();
+        assert_eq!(options.metadata.title, None);
+        assert_eq!(options.page_layout, PageLayout::SinglePage);
+        assert_eq!(options.conformance, PdfConformance::Standard);
+    }
+
+    #[test]
+    fn rejects_invalid_date() {
+        let settings = PdfMetadataSettings { date: Some("05/03/2024".to_string()), ..PdfMetadataSettings::default() };
+        let error = PdfOutputOptions::from_settings(&settings, None).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid date '05/03/2024', expected YYYY-MM-DD");
+    }
+}
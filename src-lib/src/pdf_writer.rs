@@ -0,0 +1,701 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::imageops::FilterType;
+use image::{DynamicImage, ExtendedColorType, ImageEncoder};
+use std::io::Write;
+
+use crate::archive_entry::entry_folder;
+use crate::bilevel::{BilevelFilter, BilevelImage, BilevelStream};
+use crate::limits::{check_image_header, decode_image};
+use crate::metadata::ReadingDirection;
+use crate::pdf_encryption::SecurityHandler;
+use crate::pdf_options::{date_text, hex_string, text_bytes, text_string, ImageEncoding, PdfConformance, PdfOutputOptions, DEFAULT_PDF_TITLE};
+use crate::pdfa::{srgb_icc_profile, xmp_metadata, SRGB_OUTPUT_CONDITION};
+
+// A4 page size in points (210×297mm)
+const A4_WIDTH_PT: f32 = 210.0 / 25.4 * 72.0;
+const A4_HEIGHT_PT: f32 = 297.0 / 25.4 * 72.0;
+
+// Reserved object numbers, written at the end once all pages are known
+const CATALOG_ID: u32 = 1;
+const PAGES_ID: u32 = 2;
+
+// Info /Producer and XMP pdf:Producer
+const PRODUCER: &str = "pdf-to-cbz";
+
+/// Where an image is drawn on a page: (left, bottom, width, height) in points
+pub type ImagePlacement = (f32, f32, f32, f32);
+
+/// Writer that counts bytes so object offsets can be recorded for the xref table
+struct CountingWriter<W: Write> {
+    inner: W,
+    position: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Streaming image-only PDF writer
+/// Each page (image XObject, content stream, page object) is written to the
+/// output as soon as it is added, so memory use does not grow with book size.
+/// The page tree, catalog, Info dictionary, xref table and trailer are written by `finish`.
+/// With `PdfConformance::PdfA2b` the document also gets an sRGB output intent and XMP metadata.
+/// With `encryption` set, all strings and streams are AES-256 encrypted.
+pub struct PdfStreamWriter<W: Write> {
+    out: CountingWriter<W>,
+    /// Byte offset of each object, indexed by object number - 1
+    offsets: Vec<u64>,
+    page_ids: Vec<u32>,
+    /// Outline entries: title and index of the page they point to
+    bookmarks: Vec<(String, usize)>,
+    options: PdfOutputOptions,
+    security: Option<SecurityHandler>,
+}
+
+impl<W: Write> PdfStreamWriter<W> {
+    /// Start a new PDF document on `writer` (writes the file header)
+    pub fn new(writer: W, options: PdfOutputOptions) -> Result<Self> {
+        // PDF/A forbids encryption (ISO 19005-2, 6.1.3)
+        if options.conformance == PdfConformance::PdfA2b && options.encryption.is_some() {
+            anyhow::bail!("PDF/A documents cannot be encrypted");
+        }
+        let security = match &options.encryption {
+            Some(encryption) => Some(SecurityHandler::new(encryption)?),
+            None => None,
+        };
+
+        let mut pdf = PdfStreamWriter {
+            out: CountingWriter { inner: writer, position: 0 },
+            offsets: vec![0; PAGES_ID as usize],
+            page_ids: Vec::new(),
+            bookmarks: Vec::new(),
+            options,
+            security,
+        };
+
+        // Binary comment marks the file as binary for transfer tools
+        pdf.out.write_all(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n")
+            .context("Failed to write PDF header")?;
+
+        Ok(pdf)
+    }
+
+    /// Number of pages written so far
+    pub fn page_count(&self) -> usize {
+        self.page_ids.len()
+    }
+
+    /// Add a bookmark (outline entry) pointing at the next page added
+    pub fn add_bookmark(&mut self, title: &str) {
+        self.bookmarks.push((title.to_string(), self.page_ids.len()));
+    }
+
+    fn is_pdfa(&self) -> bool {
+        self.options.conformance == PdfConformance::PdfA2b
+    }
+
+    /// Add one page holding a single image, fitted on an A4 page
+    /// JPEG data is embedded as-is (DCTDecode); other formats are decoded, then Flate-compressed
+    /// or re-encoded to JPEG depending on `PdfOutputOptions::image_encoding`.
+    pub fn add_image_page(&mut self, image_data: &[u8]) -> Result<()> {
+        // PDF/A with an RGB output intent cannot use DeviceCMYK
+        let image = PageImage::from_bytes(image_data, !self.is_pdfa(), self.options.image_encoding)?;
+
+        // Fit image on the page, anchored bottom-left
+        let dpi = calculate_dpi(image.width as f32, image.height as f32);
+        let draw_width = image.width as f32 / dpi * 72.0;
+        let draw_height = image.height as f32 / dpi * 72.0;
+
+        self.write_page(image, (A4_WIDTH_PT, A4_HEIGHT_PT), (0.0, 0.0, draw_width, draw_height))
+    }
+
+    /// Add one page of `page_size` (width, height in points) with the image drawn in
+    /// `placement` (left, bottom, width, height in points), e.g. to keep the layout of a source PDF page
+    pub fn add_image_page_at(&mut self, image_data: &[u8], page_size: (f32, f32), placement: ImagePlacement) -> Result<()> {
+        let image = PageImage::from_bytes(image_data, !self.is_pdfa(), self.options.image_encoding)?;
+        self.write_page(image, page_size, placement)
+    }
+
+    /// Same as `add_image_page_at` for an already decoded image (e.g. rendered by pdfium)
+    pub fn add_bitmap_page_at(&mut self, image: &DynamicImage, page_size: (f32, f32), placement: ImagePlacement) -> Result<()> {
+        let image = PageImage::from_image(image, self.options.image_encoding)?;
+        self.write_page(image, page_size, placement)
+    }
+
+    /// Same as `add_image_page_at` for 1-bit image data embedded as-is (e.g. a scan's CCITT or JBIG2 stream)
+    pub fn add_bilevel_page_at(&mut self, stream: BilevelStream, page_size: (f32, f32), placement: ImagePlacement) -> Result<()> {
+        self.write_page(PageImage::bilevel(stream), page_size, placement)
+    }
+
+    /// Write the image XObject, content stream and page object of one page
+    fn write_page(&mut self, image: PageImage, page_size: (f32, f32), placement: ImagePlacement) -> Result<()> {
+        let image_id = self.allocate();
+        self.begin_object(image_id)?;
+        let mut dict = format!(
+            "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace {} /BitsPerComponent {}",
+            image.width, image.height, image.color_space, image.bits_per_component
+        );
+        if let Some(filter) = image.filter {
+            dict.push_str(&format!(" /Filter {}", filter));
+        }
+        if let Some(decode_parms) = &image.decode_parms {
+            dict.push_str(&format!(" /DecodeParms {}", decode_parms));
+        }
+        if let Some(decode) = image.decode {
+            dict.push_str(&format!(" /Decode {}", decode));
+        }
+        // PDF/A forbids image interpolation; black-and-white pages are left sharp
+        if !self.is_pdfa() && image.bits_per_component > 1 {
+            dict.push_str(" /Interpolate true");
+        }
+        self.write_stream(&dict, &image.data)?;
+        drop(image.data);
+
+        let (left, bottom, draw_width, draw_height) = placement;
+        let content_id = self.allocate();
+        self.begin_object(content_id)?;
+        let content = format!(
+            "q {} 0 0 {} {} {} cm /Im0 Do Q",
+            format_number(draw_width),
+            format_number(draw_height),
+            format_number(left),
+            format_number(bottom)
+        );
+        self.write_stream("<<", content.as_bytes())?;
+
+        let page_id = self.allocate();
+        self.begin_object(page_id)?;
+        write!(
+            self.out,
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>\nendobj\n",
+            PAGES_ID,
+            format_number(page_size.0),
+            format_number(page_size.1),
+            image_id,
+            content_id
+        )?;
+        self.page_ids.push(page_id);
+
+        Ok(())
+    }
+
+    /// Write the page tree, catalog, Info dictionary, xref table and trailer
+    /// Returns the underlying writer (flushed)
+    pub fn finish(mut self) -> Result<W> {
+        if self.page_ids.is_empty() {
+            anyhow::bail!("No images to convert");
+        }
+
+        // Page tree
+        self.begin_object(PAGES_ID)?;
+        let kids: Vec<String> = self.page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+        write!(
+            self.out,
+            "<< /Type /Pages /Kids [{}] /Count {} >>\nendobj\n",
+            kids.join(" "),
+            self.page_ids.len()
+        )?;
+
+        // Info and XMP dates must match, so take the clock once
+        let now = Utc::now();
+        let creation_date = self.options.metadata.creation_date.unwrap_or(now);
+
+        // PDF/A: XMP metadata and sRGB output intent
+        let pdfa_objects = if self.is_pdfa() {
+            let metadata_id = self.allocate();
+            self.begin_object(metadata_id)?;
+            let xmp = xmp_metadata(&self.options.metadata, &self.title(), PRODUCER, &creation_date, &now);
+            self.write_stream("<< /Type /Metadata /Subtype /XML", xmp.as_bytes())?;
+
+            let profile_id = self.allocate();
+            self.begin_object(profile_id)?;
+            self.write_stream("<< /N 3 /Alternate /DeviceRGB", &srgb_icc_profile())?;
+            Some((metadata_id, profile_id))
+        } else {
+            None
+        };
+
+        let outlines_id = self.write_outlines()?;
+
+        // Catalog with viewer defaults
+        self.begin_object(CATALOG_ID)?;
+        let mut catalog = format!(
+            "<< /Type /Catalog /Pages {} 0 R /PageLayout /{}",
+            PAGES_ID,
+            self.options.page_layout.pdf_name()
+        );
+        if let Some(outlines_id) = outlines_id {
+            catalog.push_str(&format!(" /Outlines {} 0 R /PageMode /UseOutlines", outlines_id));
+        }
+        if self.options.metadata.reading_direction == ReadingDirection::RightToLeft {
+            catalog.push_str(" /ViewerPreferences << /Direction /R2L >>");
+        }
+        // AES-256 (R6) is Adobe extension level 8 on top of the %PDF-1.7 header
+        if self.security.is_some() {
+            catalog.push_str(" /Extensions << /ADBE << /BaseVersion /1.7 /ExtensionLevel 8 >> >>");
+        }
+        if let Some((metadata_id, profile_id)) = pdfa_objects {
+            catalog.push_str(&format!(
+                " /Metadata {} 0 R /OutputIntents [<< /Type /OutputIntent /S /GTS_PDFA1 /OutputConditionIdentifier {} /Info {} /DestinationOutputProfile {} 0 R >>]",
+                metadata_id,
+                text_string(SRGB_OUTPUT_CONDITION),
+                text_string(SRGB_OUTPUT_CONDITION),
+                profile_id
+            ));
+        }
+        catalog.push_str(" >>\nendobj\n");
+        self.out.write_all(catalog.as_bytes())?;
+
+        // Document information
+        let info_id = self.allocate();
+        self.begin_object(info_id)?;
+        let info = self.info_dictionary(&creation_date, &now)?;
+        self.out.write_all(info.as_bytes())?;
+        self.out.write_all(b"\nendobj\n")?;
+
+        // Encryption dictionary (never encrypted itself)
+        let encrypt_entry = match &self.security {
+            Some(security) => {
+                let dictionary = security.dictionary().to_string();
+                let encrypt_id = self.allocate();
+                self.begin_object(encrypt_id)?;
+                write!(self.out, "{}\nendobj\n", dictionary)?;
+                format!(" /Encrypt {} 0 R", encrypt_id)
+            }
+            None => String::new(),
+        };
+
+        // Cross-reference table and trailer
+        let xref_offset = self.out.position;
+        write!(self.out, "xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1)?;
+        for offset in &self.offsets {
+            writeln!(self.out, "{:010} 00000 n ", offset)?;
+        }
+        // File identifier: both halves are equal for a newly created file
+        let file_id: String = uuid::Uuid::new_v4().as_bytes().iter().map(|b| format!("{:02X}", b)).collect();
+        write!(
+            self.out,
+            "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R{} /ID [<{}> <{}>] >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1,
+            CATALOG_ID,
+            info_id,
+            encrypt_entry,
+            file_id,
+            file_id,
+            xref_offset
+        )?;
+
+        self.out.flush().context("Failed to flush PDF output")?;
+        Ok(self.out.inner)
+    }
+
+    /// Write the outline tree (a flat list of bookmarks), returning the Outlines object number
+    fn write_outlines(&mut self) -> Result<Option<u32>> {
+        let bookmarks: Vec<(String, u32)> = std::mem::take(&mut self.bookmarks)
+            .into_iter()
+            .filter_map(|(title, page)| self.page_ids.get(page).map(|&page_id| (title, page_id)))
+            .collect();
+        if bookmarks.is_empty() {
+            return Ok(None);
+        }
+
+        let outlines_id = self.allocate();
+        let item_ids: Vec<u32> = bookmarks.iter().map(|_| self.allocate()).collect();
+
+        self.begin_object(outlines_id)?;
+        write!(
+            self.out,
+            "<< /Type /Outlines /First {} 0 R /Last {} 0 R /Count {} >>\nendobj\n",
+            item_ids[0],
+            item_ids[item_ids.len() - 1],
+            item_ids.len()
+        )?;
+
+        for (index, (title, page_id)) in bookmarks.iter().enumerate() {
+            let mut item = format!("<< /Title {} /Parent {} 0 R", self.text(title)?, outlines_id);
+            if index > 0 {
+                item.push_str(&format!(" /Prev {} 0 R", item_ids[index - 1]));
+            }
+            if let Some(next) = item_ids.get(index + 1) {
+                item.push_str(&format!(" /Next {} 0 R", next));
+            }
+            item.push_str(&format!(" /Dest [{} 0 R /Fit] >>\nendobj\n", page_id));
+
+            self.begin_object(item_ids[index])?;
+            self.out.write_all(item.as_bytes())?;
+        }
+
+        Ok(Some(outlines_id))
+    }
+
+    fn title(&self) -> String {
+        self.options.metadata.display_title().unwrap_or_else(|| DEFAULT_PDF_TITLE.to_string())
+    }
+
+    fn info_dictionary(&self, creation_date: &DateTime<Utc>, modification_date: &DateTime<Utc>) -> Result<String> {
+        let metadata = &self.options.metadata;
+        let mut info = format!("<< /Title {}", self.text(&self.title())?);
+        if let Some(author) = &metadata.author {
+            info.push_str(&format!(" /Author {}", self.text(author)?));
+        }
+        if let Some(subject) = &metadata.subject {
+            info.push_str(&format!(" /Subject {}", self.text(subject)?));
+        }
+        let keywords = metadata.pdf_keywords();
+        if !keywords.is_empty() {
+            info.push_str(&format!(" /Keywords {}", self.text(&keywords.join(", "))?));
+        }
+        info.push_str(&format!(" /CreationDate {}", self.text(&date_text(creation_date))?));
+        info.push_str(&format!(" /ModDate {}", self.text(&date_text(modification_date))?));
+        info.push_str(&format!(" /Producer {} >>", self.text(PRODUCER)?));
+        Ok(info)
+    }
+
+    /// Encode a text string object, encrypted when the document is
+    fn text(&self, value: &str) -> Result<String> {
+        match &self.security {
+            Some(security) => Ok(hex_string(&security.encrypt(&text_bytes(value))?)),
+            None => Ok(text_string(value)),
+        }
+    }
+
+    fn allocate(&mut self) -> u32 {
+        self.offsets.push(0);
+        self.offsets.len() as u32
+    }
+
+    fn begin_object(&mut self, id: u32) -> Result<()> {
+        self.offsets[(id - 1) as usize] = self.out.position;
+        writeln!(self.out, "{} 0 obj", id).context("Failed to write PDF object")
+    }
+
+    /// Write a stream object body; `dict_start` is the dictionary without its closing `>>`
+    fn write_stream(&mut self, dict_start: &str, data: &[u8]) -> Result<()> {
+        let encrypted;
+        let data = match &self.security {
+            Some(security) => {
+                encrypted = security.encrypt(data)?;
+                &encrypted[..]
+            }
+            None => data,
+        };
+        write!(self.out, "{} /Length {} >>\nstream\n", dict_start, data.len())?;
+        self.out.write_all(data).context("Failed to write PDF stream")?;
+        self.out.write_all(b"\nendstream\nendobj\n")?;
+        Ok(())
+    }
+}
+
+/// Image data ready to be embedded as an XObject
+struct PageImage {
+    width: u32,
+    height: u32,
+    color_space: &'static str,
+    bits_per_component: u8,
+    filter: Option<&'static str>,
+    decode_parms: Option<String>,
+    decode: Option<&'static str>,
+    data: Vec<u8>,
+}
+
+impl PageImage {
+    /// `allow_cmyk`: embed CMYK JPEGs as-is; otherwise they are decoded to RGB
+    fn from_bytes(image_data: &[u8], allow_cmyk: bool, encoding: ImageEncoding) -> Result<PageImage> {
+        check_image_header(image_data)?;
+        let max_resolution = match encoding {
+            ImageEncoding::Jpeg { max_resolution, .. } => max_resolution,
+            ImageEncoding::Lossless => None,
+        };
+
+        // Direct JPEG insertion (no decode), unless the page has to be downscaled
+        if let Some(components) = jpeg_components(image_data).filter(|&c| allow_cmyk || c != 4) {
+            let size = imagesize::blob_size(image_data)
+                .ok()
+                .filter(|size| max_resolution.is_none_or(|max| size.width.max(size.height) <= max as usize));
+            if let Some(size) = size {
+                let (color_space, decode) = match components {
+                    1 => ("/DeviceGray", None),
+                    // Adobe CMYK JPEGs are stored inverted
+                    4 => ("/DeviceCMYK", Some("[1 0 1 0 1 0 1 0]")),
+                    _ => ("/DeviceRGB", None),
+                };
+                return Ok(PageImage {
+                    width: size.width as u32,
+                    height: size.height as u32,
+                    color_space,
+                    bits_per_component: 8,
+                    filter: Some("/DCTDecode"),
+                    decode_parms: None,
+                    decode,
+                    data: image_data.to_vec(),
+                });
+            }
+        }
+
+        let img = decode_image(image_data)?;
+        PageImage::from_image(&img, encoding)
+    }
+
+    /// Encode decoded pixels: Flate-compressed when lossless, JPEG otherwise
+    /// Black-and-white pages stay 1-bit (CCITT Group 4) in both modes, unless they have to be downscaled.
+    fn from_image(img: &DynamicImage, encoding: ImageEncoding) -> Result<PageImage> {
+        if let ImageEncoding::Jpeg { quality, max_resolution: Some(max) } = encoding {
+            if img.width().max(img.height()) > max {
+                return PageImage::jpeg(&img.resize(max, max, FilterType::CatmullRom), quality);
+            }
+        }
+        if let Some(bilevel) = BilevelImage::from_image(img) {
+            return Ok(PageImage::bilevel(bilevel.to_stream()?));
+        }
+        match encoding {
+            ImageEncoding::Lossless => PageImage::flate(img),
+            ImageEncoding::Jpeg { quality, .. } => PageImage::jpeg(img, quality),
+        }
+    }
+
+    /// 1-bit DeviceGray image, embedded with its own filter
+    fn bilevel(stream: BilevelStream) -> PageImage {
+        let (filter, decode_parms) = match stream.filter {
+            BilevelFilter::Ccitt(params) => ("/CCITTFaxDecode", Some(params.decode_parms())),
+            BilevelFilter::Jbig2 => ("/JBIG2Decode", None),
+            BilevelFilter::Flate => ("/FlateDecode", None),
+        };
+        PageImage {
+            width: stream.width,
+            height: stream.height,
+            color_space: "/DeviceGray",
+            bits_per_component: 1,
+            filter: Some(filter),
+            decode_parms,
+            decode: None,
+            data: stream.data,
+        }
+    }
+
+    /// Flate-compressed samples, keeping grayscale and 16-bit depth
+    fn flate(img: &DynamicImage) -> Result<PageImage> {
+        let color = img.color();
+        let gray = !color.has_color();
+        let wide = color.bytes_per_pixel() / color.channel_count() > 1;
+        let (color_space, bits_per_component, samples) = match (gray, wide) {
+            (true, false) => ("/DeviceGray", 8, img.to_luma8().into_raw()),
+            (false, false) => ("/DeviceRGB", 8, img.to_rgb8().into_raw()),
+            // 16-bit samples are big-endian in PDF
+            (true, true) => ("/DeviceGray", 16, img.to_luma16().into_raw().iter().flat_map(|s| s.to_be_bytes()).collect()),
+            (false, true) => ("/DeviceRGB", 16, img.to_rgb16().into_raw().iter().flat_map(|s| s.to_be_bytes()).collect()),
+        };
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&samples).context("Failed to compress image")?;
+        Ok(PageImage {
+            width: img.width(),
+            height: img.height(),
+            color_space,
+            bits_per_component,
+            filter: Some("/FlateDecode"),
+            decode_parms: None,
+            decode: None,
+            data: encoder.finish().context("Failed to compress image")?,
+        })
+    }
+
+    /// Baseline JPEG at `quality`, grayscale when the image has no color
+    fn jpeg(img: &DynamicImage, quality: u8) -> Result<PageImage> {
+        let (color_space, samples, color_type) = if img.color().has_color() {
+            ("/DeviceRGB", img.to_rgb8().into_raw(), ExtendedColorType::Rgb8)
+        } else {
+            ("/DeviceGray", img.to_luma8().into_raw(), ExtendedColorType::L8)
+        };
+
+        let mut data = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, quality)
+            .write_image(&samples, img.width(), img.height(), color_type)
+            .context("JPEG encoding failed")?;
+        Ok(PageImage {
+            width: img.width(),
+            height: img.height(),
+            color_space,
+            bits_per_component: 8,
+            filter: Some("/DCTDecode"),
+            decode_parms: None,
+            decode: None,
+            data,
+        })
+    }
+}
+
+/// Number of colour components of a JPEG (from its SOF marker), None if not a JPEG
+fn jpeg_components(data: &[u8]) -> Option<u8> {
+    if data.len() < 4 || data[0] != 0xFF || data[1] != 0xD8 {
+        return None;
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        // Padding bytes and markers without a length field
+        if marker == 0xFF {
+            pos += 1;
+            continue;
+        }
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            pos += 2;
+            continue;
+        }
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        // SOF0-SOF15, excluding DHT (C4), JPG (C8) and DAC (CC)
+        let is_sof = (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_sof {
+            return data.get(pos + 9).copied();
+        }
+        pos += 2 + length;
+    }
+
+    None
+}
+
+/// Calculate optimal DPI to fit image on A4 page (210×297mm)
+fn calculate_dpi(img_width: f32, img_height: f32) -> f32 {
+    let page_width_inch = 210.0 / 25.4;
+    let page_height_inch = 297.0 / 25.4;
+
+    let dpi_w = img_width / page_width_inch;
+    let dpi_h = img_height / page_height_inch;
+
+    dpi_w.max(dpi_h)
+}
+
+/// Format a number for PDF content (no exponent, trailing zeros trimmed)
+fn format_number(value: f32) -> String {
+    let formatted = format!("{:.4}", value);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Write a PDF with one image per page to `writer`, consuming images as they are written
+/// Image names are archive paths: when pages come from several folders (chapters),
+/// each folder gets a bookmark on its first page.
+/// progress_callback: called with (current, total) after each page
+pub fn write_pdf_from_images<W, I, F>(
+    images: I,
+    writer: W,
+    options: &PdfOutputOptions,
+    progress_callback: F,
+) -> Result<W>
+where
+    W: Write,
+    I: IntoIterator<Item = (String, Vec<u8>)>,
+    I::IntoIter: ExactSizeIterator,
+    F: FnMut(usize, usize),
+{
+    write_pdf_from_pages(images.into_iter().map(Ok), writer, options, progress_callback)
+}
+
+/// Same as `write_pdf_from_images`, for pages read lazily (e.g. `CbzPages`)
+/// Only one page is held in memory at a time; the first read error stops the conversion.
+pub fn write_pdf_from_pages<W, I, F>(
+    pages: I,
+    writer: W,
+    options: &PdfOutputOptions,
+    mut progress_callback: F,
+) -> Result<W>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<(String, Vec<u8>)>>,
+    I::IntoIter: ExactSizeIterator,
+    F: FnMut(usize, usize),
+{
+    let pages = pages.into_iter();
+    let total = pages.len();
+    if total == 0 {
+        anyhow::bail!("No images to convert");
+    }
+
+    let mut pdf = PdfStreamWriter::new(writer, options.clone())?;
+    // Last chapter folder seen: root-level pages in between (e.g. an inserted page) do not end a chapter
+    let mut current_folder: Option<String> = None;
+    let mut chapters = 0;
+    for (idx, page) in pages.enumerate() {
+        let (name, image_data) = page?;
+        if let Some(folder) = entry_folder(&name) {
+            if current_folder.as_deref() != Some(folder) {
+                pdf.add_bookmark(folder);
+                chapters += 1;
+                current_folder = Some(folder.to_string());
+            }
+        }
+
+        pdf.add_image_page(&image_data)
+            .context(format!("Failed to add image {}", name))?;
+        progress_callback(idx + 1, total);
+    }
+
+    // A single folder (e.g. the whole book in one directory) is not worth an outline
+    if chapters < 2 {
+        pdf.bookmarks.clear();
+    }
+    pdf.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf_encryption::PdfEncryption;
+
+    /// One-page document holding a small PNG
+    fn write_document(options: PdfOutputOptions) -> String {
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(4, 4)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let mut writer = PdfStreamWriter::new(Vec::new(), options).unwrap();
+        writer.add_image_page(&png).unwrap();
+        String::from_utf8_lossy(&writer.finish().unwrap()).into_owned()
+    }
+
+    #[test]
+    fn encrypted_catalog_declares_extension_level() {
+        let pdf = write_document(PdfOutputOptions {
+            encryption: Some(PdfEncryption { user_password: "user".to_string(), ..PdfEncryption::default() }),
+            ..PdfOutputOptions::default()
+        });
+        assert!(pdf.starts_with("%PDF-1.7\n"));
+        assert!(pdf.contains("/Extensions << /ADBE << /BaseVersion /1.7 /ExtensionLevel 8 >> >>"));
+        assert!(pdf.contains("/Filter /Standard /V 5 /R 6"));
+        assert!(pdf.contains(" /Encrypt "));
+    }
+
+    #[test]
+    fn one_bookmark_per_chapter_folder() {
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(4, 4)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let names = ["A/01.png", "cover.png", "A/02.png", "B/01.png", "B/02.png"];
+        let pages: Vec<(String, Vec<u8>)> = names.iter().map(|name| (name.to_string(), png.clone())).collect();
+        let pdf = write_pdf_from_images(pages, Vec::new(), &PdfOutputOptions::default(), |_, _| {}).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert_eq!(pdf.matches("/Title (A)").count(), 1);
+        assert_eq!(pdf.matches("/Title (B)").count(), 1);
+    }
+
+    #[test]
+    fn plain_catalog_has_no_extensions() {
+        let pdf = write_document(PdfOutputOptions::default());
+        assert!(!pdf.contains("/Extensions"));
+        assert!(!pdf.contains("/Encrypt"));
+    }
+}
@@ -0,0 +1,263 @@
+// PDF/A-2b support: sRGB output intent profile and XMP metadata packet
+use chrono::{DateTime, Utc};
+
+use crate::metadata::DocumentMetadata;
+
+/// OutputConditionIdentifier of the embedded sRGB output intent
+pub const SRGB_OUTPUT_CONDITION: &str = "sRGB IEC61966-2.1";
+
+// D50 PCS illuminant and sRGB primaries adapted to D50 (Bradford)
+const D50_WHITE: (f64, f64, f64) = (0.9642, 1.0, 0.8249);
+const SRGB_RED: (f64, f64, f64) = (0.4361, 0.2225, 0.0139);
+const SRGB_GREEN: (f64, f64, f64) = (0.3851, 0.7169, 0.0971);
+const SRGB_BLUE: (f64, f64, f64) = (0.1431, 0.0606, 0.7141);
+
+// Number of entries in the sampled sRGB tone curve
+const TRC_ENTRIES: usize = 1024;
+
+/// Build an ICC v2 display profile for sRGB (matrix/TRC)
+/// Generated in code so no binary profile has to be shipped with the app.
+pub fn srgb_icc_profile() -> Vec<u8> {
+    let trc = curve_tag();
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", text_description_tag(SRGB_OUTPUT_CONDITION)),
+        (b"cprt", text_tag("No copyright, use freely")),
+        (b"wtpt", xyz_tag(D50_WHITE)),
+        (b"rXYZ", xyz_tag(SRGB_RED)),
+        (b"gXYZ", xyz_tag(SRGB_GREEN)),
+        (b"bXYZ", xyz_tag(SRGB_BLUE)),
+        (b"rTRC", trc.clone()),
+        (b"gTRC", trc.clone()),
+        (b"bTRC", trc),
+    ];
+
+    // Lay out tag data after the header (128 bytes) and tag table
+    let table_size = 4 + tags.len() * 12;
+    let mut offset = 128 + table_size;
+    let mut table = Vec::with_capacity(table_size);
+    let mut data = Vec::new();
+    table.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    for (signature, tag) in &tags {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        data.extend_from_slice(tag);
+        // Tag data is 4-byte aligned
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+        offset = 128 + table_size + data.len();
+    }
+
+    let size = 128 + table.len() + data.len();
+    let mut profile = Vec::with_capacity(size);
+    profile.extend_from_slice(&(size as u32).to_be_bytes());
+    profile.extend_from_slice(&[0; 4]); // preferred CMM
+    profile.extend_from_slice(&0x0210_0000u32.to_be_bytes()); // version 2.1
+    profile.extend_from_slice(b"mntr");
+    profile.extend_from_slice(b"RGB ");
+    profile.extend_from_slice(b"XYZ ");
+    profile.extend_from_slice(&[0; 12]); // creation date/time
+    profile.extend_from_slice(b"acsp");
+    profile.extend_from_slice(&[0; 24]); // platform, flags, manufacturer, model, attributes
+    profile.extend_from_slice(&0u32.to_be_bytes()); // perceptual intent
+    profile.extend_from_slice(&xyz_number(D50_WHITE));
+    profile.extend_from_slice(&[0; 4]); // creator
+    profile.resize(128, 0); // profile ID and reserved bytes
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
+
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz_number((x, y, z): (f64, f64, f64)) -> Vec<u8> {
+    [s15_fixed16(x), s15_fixed16(y), s15_fixed16(z)].concat()
+}
+
+fn xyz_tag(xyz: (f64, f64, f64)) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    tag.extend_from_slice(&xyz_number(xyz));
+    tag
+}
+
+fn text_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"text\0\0\0\0".to_vec();
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag
+}
+
+fn text_description_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag.extend_from_slice(&[0; 8]); // Unicode language code and count
+    tag.extend_from_slice(&[0; 3]); // ScriptCode code and count
+    tag.extend_from_slice(&[0; 67]); // ScriptCode description
+    tag
+}
+
+/// Sampled sRGB transfer function (IEC 61966-2.1)
+fn curve_tag() -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(TRC_ENTRIES as u32).to_be_bytes());
+    for i in 0..TRC_ENTRIES {
+        let v = i as f64 / (TRC_ENTRIES - 1) as f64;
+        let linear = if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        };
+        tag.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+    tag
+}
+
+/// Build the XMP metadata packet for a PDF/A-2b document
+/// Values must match the Info dictionary entries written alongside it.
+pub fn xmp_metadata(
+    metadata: &DocumentMetadata,
+    title: &str,
+    producer: &str,
+    creation_date: &DateTime<Utc>,
+    modification_date: &DateTime<Utc>,
+) -> String {
+    let mut description = String::new();
+    description.push_str(&format!(
+        "   <dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>\n",
+        xml_escape(title)
+    ));
+    if let Some(author) = &metadata.author {
+        description.push_str(&format!(
+            "   <dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>\n",
+            xml_escape(author)
+        ));
+    }
+    if let Some(subject) = &metadata.subject {
+        description.push_str(&format!(
+            "   <dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>\n",
+            xml_escape(subject)
+        ));
+    }
+    let keywords = metadata.pdf_keywords();
+    if !keywords.is_empty() {
+        description.push_str(&format!(
+            "   <pdf:Keywords>{}</pdf:Keywords>\n",
+            xml_escape(&keywords.join(", "))
+        ));
+    }
+    description.push_str(&format!("   <pdf:Producer>{}</pdf:Producer>\n", xml_escape(producer)));
+    description.push_str(&format!("   <xmp:CreateDate>{}</xmp:CreateDate>\n", xmp_date(creation_date)));
+    description.push_str(&format!("   <xmp:ModifyDate>{}</xmp:ModifyDate>\n", xmp_date(modification_date)));
+    description.push_str("   <pdfaid:part>2</pdfaid:part>\n");
+    description.push_str("   <pdfaid:conformance>B</pdfaid:conformance>\n");
+
+    let mut packet = String::new();
+    packet.push_str("<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n");
+    packet.push_str("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n");
+    packet.push_str(" <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n");
+    packet.push_str("  <rdf:Description rdf:about=\"\"\n");
+    packet.push_str("    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n");
+    packet.push_str("    xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\"\n");
+    packet.push_str("    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n");
+    packet.push_str("    xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\">\n");
+    packet.push_str(&description);
+    packet.push_str("  </rdf:Description>\n");
+    packet.push_str(" </rdf:RDF>\n");
+    packet.push_str("</x:xmpmeta>\n");
+    packet.push_str("<?xpacket end=\"w\"?>");
+    packet
+}
+
+/// XMP date (ISO 8601 with explicit UTC offset, matching the Info dictionary dates)
+fn xmp_date(date: &DateTime<Utc>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S+00:00").to_string()
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    fn be_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn icc_profile_header() {
+        let profile = srgb_icc_profile();
+        assert_eq!(be_u32(&profile, 0) as usize, profile.len());
+        assert_eq!(be_u32(&profile, 8), 0x0210_0000);
+        assert_eq!(&profile[12..16], b"mntr");
+        assert_eq!(&profile[16..20], b"RGB ");
+        assert_eq!(&profile[20..24], b"XYZ ");
+        assert_eq!(&profile[36..40], b"acsp");
+
+        // Every tag lies inside the profile, 4-byte aligned, and starts with its type signature
+        let count = be_u32(&profile, 128) as usize;
+        assert_eq!(count, 9);
+        for index in 0..count {
+            let entry = 132 + index * 12;
+            let (offset, size) = (be_u32(&profile, entry + 4) as usize, be_u32(&profile, entry + 8) as usize);
+            assert_eq!(offset % 4, 0);
+            assert!(offset + size <= profile.len());
+            let kind = &profile[offset..offset + 4];
+            assert!([b"desc", b"text", b"XYZ ", b"curv"].iter().any(|k| kind == *k), "tag type {:?}", kind);
+        }
+    }
+
+    /// Text of every element of the packet, by element name; fails on malformed XML
+    fn xmp_texts(packet: &str) -> Vec<(String, String)> {
+        let mut reader = Reader::from_str(packet);
+        reader.config_mut().trim_text(true);
+        let mut texts = Vec::new();
+        let mut current = String::new();
+        loop {
+            match reader.read_event().expect("well-formed XMP") {
+                Event::Start(element) => current = String::from_utf8_lossy(element.name().as_ref()).to_string(),
+                Event::Text(text) => texts.push((current.clone(), text.unescape().unwrap().to_string())),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        texts
+    }
+
+    #[test]
+    fn xmp_packet_well_formed_and_escaped() {
+        let metadata = DocumentMetadata {
+            author: Some("Tom & Jerry".to_string()),
+            subject: Some("a > b".to_string()),
+            keywords: vec!["<tag>".to_string()],
+            ..DocumentMetadata::default()
+        };
+        let date = Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap();
+        let packet = xmp_metadata(&metadata, "Cats & <Dogs>", "Producer", &date, &date);
+
+        assert!(packet.contains("Cats &amp; &lt;Dogs&gt;"));
+        assert!(!packet.contains("<Dogs>") && !packet.contains("<tag>"));
+
+        let texts = xmp_texts(&packet);
+        let text = |name: &str| texts.iter().find(|(element, _)| element == name).map(|(_, text)| text.as_str());
+        assert_eq!(text("pdfaid:part"), Some("2"));
+        assert_eq!(text("pdfaid:conformance"), Some("B"));
+        assert_eq!(text("xmp:CreateDate"), Some("2024-05-01T12:30:00+00:00"));
+        assert_eq!(text("pdf:Keywords"), Some("<tag>"));
+        let items: Vec<&str> = texts.iter().filter(|(element, _)| element == "rdf:li").map(|(_, text)| text.as_str()).collect();
+        assert_eq!(items, ["Cats & <Dogs>", "Tom & Jerry", "a > b"]);
+    }
+}
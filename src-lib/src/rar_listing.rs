@@ -0,0 +1,153 @@
+// Checking RAR archives (CBR) against the safety limits before they are extracted
+// RAR archives are extracted to disk with unar; `lsar -j` lists them first, so an archive
+// that breaks a limit is refused before anything is written.
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::limits::{sanitize_entry_path, ArchiveBudget, LimitError};
+
+/// One entry of a RAR archive, as listed by `lsar -j`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RarListEntry {
+    /// Path inside the archive, as stored
+    pub name: String,
+    /// Uncompressed size, in bytes
+    pub size: u64,
+    /// Stored size, in bytes (the uncompressed size when lsar does not report it)
+    pub compressed_size: u64,
+    pub is_directory: bool,
+    /// Symbolic or hard link
+    pub is_link: bool,
+}
+
+/// Parse the JSON written by `lsar -j`
+pub fn parse_lsar_listing(json: &[u8]) -> Result<Vec<RarListEntry>> {
+    let listing: Value = serde_json::from_slice(json)
+        .context("Failed to parse lsar listing")?;
+    let contents = listing
+        .get("lsarContents")
+        .and_then(Value::as_array)
+        .context("lsar listing has no lsarContents")?;
+
+    contents
+        .iter()
+        .map(|entry| {
+            let name = entry
+                .get("XADFileName")
+                .and_then(Value::as_str)
+                .context("lsar listing entry has no XADFileName")?
+                .to_string();
+            let size = entry.get("XADFileSize").and_then(Value::as_u64).unwrap_or(0);
+            let compressed_size = entry.get("XADCompressedSize").and_then(Value::as_u64).unwrap_or(size);
+            Ok(RarListEntry {
+                name,
+                size,
+                compressed_size,
+                is_directory: flag(entry, "XADIsDirectory"),
+                is_link: flag(entry, "XADIsLink") || flag(entry, "XADIsHardLink"),
+            })
+        })
+        .collect()
+}
+
+/// lsar writes booleans as 0/1 numbers
+fn flag(entry: &Value, key: &str) -> bool {
+    match entry.get(key) {
+        Some(Value::Bool(value)) => *value,
+        Some(value) => value.as_u64().is_some_and(|value| value != 0),
+        None => false,
+    }
+}
+
+/// Check a listed RAR archive against the current safety limits, before extracting it
+/// Entry count, entry and total sizes, compression ratio and entry paths are checked.
+/// Links are refused: once extracted, a link could make a later entry land outside the extraction directory.
+pub fn check_rar_listing(entries: &[RarListEntry]) -> Result<(), LimitError> {
+    let mut budget = ArchiveBudget::new();
+    budget.check_entry_count(entries.len())?;
+
+    for entry in entries {
+        let name = sanitize_entry_path(&entry.name)?;
+        if entry.is_link {
+            return Err(LimitError::UnsafePath { path: entry.name.clone() });
+        }
+        if !entry.is_directory {
+            budget.add_entry(&name, entry.compressed_size, entry.size)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::SafetyLimits;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn file(name: &str, size: u64, compressed_size: u64) -> RarListEntry {
+        RarListEntry { name: name.to_string(), size, compressed_size, is_directory: false, is_link: false }
+    }
+
+    #[test]
+    fn parses_lsar_json() {
+        let json = br#"{
+            "lsarFormatVersion": 2,
+            "lsarContents": [
+                { "XADFileName": "Chapter 1", "XADIsDirectory": 1, "XADIndex": 0 },
+                { "XADFileName": "Chapter 1/001.jpg", "XADFileSize": 2048, "XADCompressedSize": 1900, "XADIndex": 1 },
+                { "XADFileName": "cover", "XADFileSize": 10, "XADIsLink": 1, "XADIndex": 2 }
+            ],
+            "lsarFormatName": "RAR 5"
+        }"#;
+        let entries = parse_lsar_listing(json).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].is_directory);
+        assert_eq!(entries[1], file("Chapter 1/001.jpg", 2048, 1900));
+        assert!(entries[2].is_link);
+        // Missing compressed size falls back to the uncompressed size
+        assert_eq!(entries[2].compressed_size, 10);
+    }
+
+    #[test]
+    fn rejects_invalid_listing() {
+        assert!(parse_lsar_listing(b"not json").is_err());
+        assert!(parse_lsar_listing(br#"{"lsarError": "Archive parsing failed"}"#).is_err());
+    }
+
+    #[test]
+    fn accepts_ordinary_archive() {
+        let entries = [file("001.jpg", 2 * MIB, 2 * MIB - 100), file("002.jpg", 3 * MIB, 3 * MIB)];
+        assert_eq!(check_rar_listing(&entries), Ok(()));
+    }
+
+    #[test]
+    fn refuses_unsafe_paths_and_links() {
+        let escaping = [file("../../etc/passwd", 10, 10)];
+        assert!(matches!(check_rar_listing(&escaping), Err(LimitError::UnsafePath { .. })));
+
+        let absolute = [file("/tmp/page.jpg", 10, 10)];
+        assert!(matches!(check_rar_listing(&absolute), Err(LimitError::UnsafePath { .. })));
+
+        let link = [RarListEntry { is_link: true, ..file("pages", 0, 0) }];
+        assert!(matches!(check_rar_listing(&link), Err(LimitError::UnsafePath { .. })));
+    }
+
+    #[test]
+    fn refuses_bombs_before_extraction() {
+        let limits = SafetyLimits::current();
+        let ratio = [file("bomb.jpg", 500 * MIB, MIB / 4)];
+        assert!(matches!(check_rar_listing(&ratio), Err(LimitError::CompressionRatio { .. })));
+
+        let too_large = [file("huge.jpg", limits.max_entry_size + 1, limits.max_entry_size + 1)];
+        assert!(matches!(check_rar_listing(&too_large), Err(LimitError::EntryTooLarge { .. })));
+
+        let too_many: Vec<_> = (0..=limits.max_entries).map(|i| file(&format!("{}.jpg", i), 1, 1)).collect();
+        assert!(matches!(check_rar_listing(&too_many), Err(LimitError::TooManyEntries { .. })));
+
+        let entry_size = limits.max_entry_size;
+        let count = (limits.max_total_size / entry_size + 1) as usize;
+        let total: Vec<_> = (0..count).map(|i| file(&format!("{}.jpg", i), entry_size, entry_size)).collect();
+        assert!(matches!(check_rar_listing(&total), Err(LimitError::ArchiveTooLarge { .. })));
+    }
+}
@@ -0,0 +1,332 @@
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use rayon::prelude::*;
+use std::io::Cursor;
+
+use crate::image_format::PageImageFormat;
+use crate::jpeg_transform::split_jpeg_lossless;
+use crate::limits::decode_image;
+use crate::metadata::ReadingDirection;
+use crate::pdf_options::DEFAULT_JPEG_QUALITY;
+
+/// Width-to-height ratio from which a page is split even when no gutter is found
+pub const DEFAULT_SPREAD_RATIO: f32 = 1.2;
+
+/// Part of the width on each side of the centre searched for the gutter
+const GUTTER_SEARCH: f32 = 0.1;
+
+/// Largest top-to-bottom standard deviation of a gutter column (blank margin or fold shadow, with scan noise)
+const MAX_GUTTER_DEVIATION: f64 = 12.0;
+
+/// Splitting double-page spreads into two pages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpreadSplit {
+    /// Order of the halves: left page first, or right page first for manga
+    pub direction: ReadingDirection,
+    /// Keep the full spread before its two halves
+    pub keep_spread: bool,
+    /// Landscape pages at least this much wider than high are spreads; narrower landscape pages
+    /// are split only when a gutter is found
+    pub min_ratio: f32,
+    /// Quality of JPEG halves that cannot be cut losslessly
+    pub jpeg_quality: u8,
+}
+
+impl Default for SpreadSplit {
+    fn default() -> Self {
+        SpreadSplit {
+            direction: ReadingDirection::LeftToRight,
+            keep_spread: false,
+            min_ratio: DEFAULT_SPREAD_RATIO,
+            jpeg_quality: DEFAULT_JPEG_QUALITY,
+        }
+    }
+}
+
+/// Spreads found by `split_spreads`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpreadReport {
+    /// Numbers (from 1) of the input pages that were split
+    pub split_pages: Vec<usize>,
+    /// Spreads cut at a gutter found in the image rather than at the centre
+    pub at_gutter: usize,
+    /// JPEG spreads cut without re-encoding
+    pub lossless: usize,
+}
+
+/// Halves of a spread, in reading order
+struct SplitPage {
+    first: (String, Vec<u8>),
+    second: (String, Vec<u8>),
+    at_gutter: bool,
+    lossless: bool,
+}
+
+/// Split the double-page spreads among `pages` into two pages each, in place
+/// A landscape page is a spread when its ratio reaches `min_ratio` or when a gutter (a column with little
+/// variation from top to bottom) stands out near its centre; it is cut at the gutter, or at the centre.
+/// Halves are named after the spread with an `a`/`b` suffix, so they sort right after it. JPEG pages are
+/// cut losslessly where their coding allows, other pages are re-encoded (JPEG stays JPEG, the rest becomes PNG).
+pub fn split_spreads(pages: &mut Vec<(String, Vec<u8>)>, options: &SpreadSplit) -> Result<SpreadReport> {
+    let splits: Vec<Option<SplitPage>> = pages
+        .par_iter()
+        .enumerate()
+        .map(|(index, (name, data))| split_page(name, data, options).context(format!("Failed to split page {}", index + 1)))
+        .collect::<Result<_>>()?;
+
+    let mut report = SpreadReport::default();
+    let mut output = Vec::with_capacity(pages.len());
+    for (index, (page, split)) in std::mem::take(pages).into_iter().zip(splits).enumerate() {
+        let Some(split) = split else {
+            output.push(page);
+            continue;
+        };
+        report.split_pages.push(index + 1);
+        report.at_gutter += split.at_gutter as usize;
+        report.lossless += split.lossless as usize;
+        if options.keep_spread {
+            output.push(page);
+        }
+        output.push(split.first);
+        output.push(split.second);
+    }
+    *pages = output;
+    Ok(report)
+}
+
+/// Halves of a page, or None if it is not a spread
+fn split_page(name: &str, data: &[u8], options: &SpreadSplit) -> Result<Option<SplitPage>> {
+    let Ok(size) = imagesize::blob_size(data) else {
+        return Ok(None);
+    };
+    if size.width <= size.height || size.width < 16 {
+        return Ok(None);
+    }
+
+    let image = decode_image(data)?;
+    let (width, height) = image.dimensions();
+    let gutter = find_gutter(&image);
+    if gutter.is_none() && (width as f32) < height as f32 * options.min_ratio {
+        return Ok(None);
+    }
+    let column = gutter.unwrap_or(width / 2);
+
+    let format = PageImageFormat::detect(name, data);
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let extension = if format == Some(PageImageFormat::Jpeg) { "jpg" } else { "png" };
+    let page_name = |suffix: &str| format!("{}{}.{}", stem, suffix, extension);
+
+    let lossless = match format {
+        Some(PageImageFormat::Jpeg) => match split_jpeg_lossless(data, column as usize) {
+            Ok((left, right, _)) => Some((left, right)),
+            Err(e) => {
+                eprintln!("[SPREADS] Re-encoding {} to split it: {:#}", name, e);
+                None
+            }
+        },
+        _ => None,
+    };
+    let is_lossless = lossless.is_some();
+    let (left, right) = match lossless {
+        Some(halves) => halves,
+        None => (
+            encode_half(&image.crop_imm(0, 0, column, height), format, options.jpeg_quality)?,
+            encode_half(&image.crop_imm(column, 0, width - column, height), format, options.jpeg_quality)?,
+        ),
+    };
+
+    let (first, second) = match options.direction {
+        ReadingDirection::LeftToRight => (left, right),
+        ReadingDirection::RightToLeft => (right, left),
+    };
+    Ok(Some(SplitPage {
+        first: (page_name("a"), first),
+        second: (page_name("b"), second),
+        at_gutter: gutter.is_some(),
+        lossless: is_lossless,
+    }))
+}
+
+/// Column of the gutter between the two pages of a spread, if one stands out near the centre
+/// Gutter columns vary least from top to bottom; of the runs of such columns, the one nearest
+/// the centre wins and the page is cut at its middle. Artwork across the fold has no such column.
+fn find_gutter(image: &DynamicImage) -> Option<u32> {
+    let (width, height) = image.dimensions();
+    let search = ((width as f32 * GUTTER_SEARCH) as u32).max(1);
+    let start = (width / 2).saturating_sub(search);
+    let band = image.crop_imm(start, 0, (2 * search).min(width - start), height).to_luma8();
+    if band.width() == 0 || band.height() == 0 {
+        return None;
+    }
+
+    // Standard deviation of each column, accumulated row by row
+    let mut sums = vec![(0f64, 0f64); band.width() as usize];
+    for row in band.rows() {
+        for (sum, pixel) in sums.iter_mut().zip(row) {
+            let value = pixel[0] as f64;
+            sum.0 += value;
+            sum.1 += value * value;
+        }
+    }
+    let count = band.height() as f64;
+    let deviations: Vec<f64> = sums
+        .iter()
+        .map(|&(sum, squares)| (squares / count - (sum / count).powi(2)).max(0.0).sqrt())
+        .collect();
+
+    let mut sorted = deviations.clone();
+    sorted.sort_by(f64::total_cmp);
+    let (lowest, median) = (sorted[0], sorted[sorted.len() / 2]);
+    if lowest > MAX_GUTTER_DEVIATION || lowest > median * 0.5 {
+        return None;
+    }
+
+    let threshold = lowest + 1.0;
+    let centre = (width / 2 - start) as i64;
+    let mut best: Option<(i64, u32)> = None;
+    let mut x = 0;
+    while x < deviations.len() {
+        if deviations[x] > threshold {
+            x += 1;
+            continue;
+        }
+        let run_start = x;
+        while x < deviations.len() && deviations[x] <= threshold {
+            x += 1;
+        }
+        let middle = ((run_start + x) / 2) as i64;
+        let distance = (middle - centre).abs();
+        if best.is_none_or(|(best_distance, _)| distance < best_distance) {
+            best = Some((distance, middle as u32));
+        }
+    }
+    best.map(|(_, column)| start + column).filter(|&column| column > 0 && column < width)
+}
+
+/// Encode a re-encoded half: JPEG for JPEG spreads, PNG otherwise
+fn encode_half(image: &DynamicImage, format: Option<PageImageFormat>, quality: u8) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    if format == Some(PageImageFormat::Jpeg) {
+        let image = match image {
+            DynamicImage::ImageLuma8(_) => image.clone(),
+            _ => DynamicImage::ImageRgb8(image.to_rgb8()),
+        };
+        image.write_with_encoder(JpegEncoder::new_with_quality(&mut data, quality))
+            .context("Failed to encode JPEG")?;
+    } else {
+        image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .context("Failed to encode PNG")?;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    /// Grey page with a pattern that varies down every column (artwork with no gutter)
+    fn artwork(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| Luma([((x * 7 + y * 31) % 200) as u8]))
+    }
+
+    fn png(image: &GrayImage) -> Vec<u8> {
+        let mut data = Vec::new();
+        image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png).unwrap();
+        data
+    }
+
+    fn width(data: &[u8]) -> u32 {
+        decode_image(data).unwrap().width()
+    }
+
+    fn split(image: &GrayImage, options: &SpreadSplit) -> (Vec<(String, Vec<u8>)>, SpreadReport) {
+        let mut pages = vec![("p01.png".to_string(), png(image))];
+        let report = split_spreads(&mut pages, options).unwrap();
+        (pages, report)
+    }
+
+    #[test]
+    fn splits_at_blank_gutter() {
+        // Blank columns 108..=113 stand out, off the centre (100) but inside the searched band
+        let mut image = artwork(200, 100);
+        for x in 108..114 {
+            for y in 0..100 {
+                image.put_pixel(x, y, Luma([255]));
+            }
+        }
+        let (pages, report) = split(&image, &SpreadSplit::default());
+
+        assert_eq!(report.split_pages, vec![1]);
+        assert_eq!(report.at_gutter, 1);
+        let names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["p01a.png", "p01b.png"]);
+        assert_eq!(width(&pages[0].1), 111);
+        assert_eq!(width(&pages[1].1), 89);
+    }
+
+    #[test]
+    fn artwork_across_fold_has_no_gutter() {
+        assert_eq!(find_gutter(&DynamicImage::ImageLuma8(artwork(200, 100))), None);
+
+        // Wide enough to be a spread anyway: cut at the centre
+        let (pages, report) = split(&artwork(200, 100), &SpreadSplit::default());
+        assert_eq!(report.at_gutter, 0);
+        assert_eq!((width(&pages[0].1), width(&pages[1].1)), (100, 100));
+
+        // Below the spread ratio and no gutter: left alone
+        let (pages, report) = split(&artwork(110, 100), &SpreadSplit::default());
+        assert!(report.split_pages.is_empty());
+        assert_eq!(pages[0].0, "p01.png");
+    }
+
+    #[test]
+    fn portrait_page_untouched() {
+        let image = artwork(100, 200);
+        let (pages, report) = split(&image, &SpreadSplit::default());
+        assert_eq!(report, SpreadReport::default());
+        assert_eq!(pages, vec![("p01.png".to_string(), png(&image))]);
+    }
+
+    #[test]
+    fn right_to_left_swaps_halves() {
+        // Dark left page, light right page
+        let image = GrayImage::from_fn(200, 100, |x, _| Luma([if x < 100 { 0 } else { 255 }]));
+        let first_pixel = |options: &SpreadSplit| {
+            let (pages, _) = split(&image, options);
+            decode_image(&pages[0].1).unwrap().to_luma8().get_pixel(0, 0)[0]
+        };
+        assert_eq!(first_pixel(&SpreadSplit::default()), 0);
+        assert_eq!(first_pixel(&SpreadSplit { direction: ReadingDirection::RightToLeft, ..SpreadSplit::default() }), 255);
+    }
+
+    #[test]
+    fn keep_spread_puts_original_first() {
+        let image = artwork(200, 100);
+        let (pages, report) = split(&image, &SpreadSplit { keep_spread: true, ..SpreadSplit::default() });
+        assert_eq!(report.split_pages, vec![1]);
+        let names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["p01.png", "p01a.png", "p01b.png"]);
+        assert_eq!(pages[0].1, png(&image));
+    }
+
+    #[test]
+    fn jpeg_spread_split_losslessly() {
+        let mut data = Vec::new();
+        DynamicImage::ImageLuma8(artwork(256, 128))
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut data, 90))
+            .unwrap();
+        let original = decode_image(&data).unwrap();
+        let mut pages = vec![("p01.jpg".to_string(), data)];
+        let report = split_spreads(&mut pages, &SpreadSplit::default()).unwrap();
+
+        assert_eq!(report.lossless, 1);
+        let names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["p01a.jpg", "p01b.jpg"]);
+        // Same coefficients as the spread: the halves decode to its exact pixels
+        for ((_, half), x) in pages.iter().zip([0, 128]) {
+            assert_eq!(decode_image(half).unwrap().to_luma8(), original.crop_imm(x, 0, 128, 128).to_luma8());
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// Hand-built ZIP archives for tests (duplicate names, data descriptors, ZIP64 fields, damaged data)
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+/// One entry of a hand-built archive
+pub(crate) struct RawEntry {
+    pub name: String,
+    /// Uncompressed content
+    pub data: Vec<u8>,
+    pub deflate: bool,
+    /// Sizes and CRC-32 left at zero in the local header and written in a data descriptor
+    pub descriptor: bool,
+    /// Sizes in a ZIP64 extended information field of the local header
+    pub zip64: bool,
+    /// Encryption flag set (the data itself is left as is)
+    pub encrypted: bool,
+}
+
+impl RawEntry {
+    pub fn stored(name: &str, data: &[u8]) -> RawEntry {
+        RawEntry { name: name.to_string(), data: data.to_vec(), deflate: false, descriptor: false, zip64: false, encrypted: false }
+    }
+
+    pub fn deflated(name: &str, data: &[u8]) -> RawEntry {
+        RawEntry { deflate: true, ..RawEntry::stored(name, data) }
+    }
+
+    fn stored_data(&self) -> Vec<u8> {
+        if !self.deflate {
+            return self.data.clone();
+        }
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&self.data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn flags(&self) -> u16 {
+        (self.encrypted as u16) | ((self.descriptor as u16) << 3)
+    }
+
+    fn method(&self) -> u16 {
+        if self.deflate { 8 } else { 0 }
+    }
+
+    /// Local file header, data and data descriptor
+    pub fn local(&self) -> Vec<u8> {
+        let stored = self.stored_data();
+        let crc = crc32fast::hash(&self.data);
+        let (header_crc, header_compressed, header_size) = match (self.descriptor, self.zip64) {
+            (true, _) => (0, 0, 0),
+            (false, true) => (crc, u32::MAX, u32::MAX),
+            (false, false) => (crc, stored.len() as u32, self.data.len() as u32),
+        };
+        let mut extra = Vec::new();
+        if self.zip64 {
+            let sizes = if self.descriptor { (0, 0) } else { (self.data.len() as u64, stored.len() as u64) };
+            extra.extend_from_slice(&1u16.to_le_bytes());
+            extra.extend_from_slice(&16u16.to_le_bytes());
+            extra.extend_from_slice(&sizes.0.to_le_bytes());
+            extra.extend_from_slice(&sizes.1.to_le_bytes());
+        }
+
+        let mut out = b"PK\x03\x04".to_vec();
+        out.extend_from_slice(&(if self.zip64 { 45u16 } else { 20u16 }).to_le_bytes());
+        out.extend_from_slice(&self.flags().to_le_bytes());
+        out.extend_from_slice(&self.method().to_le_bytes());
+        out.extend_from_slice(&[0; 4]); // modification time and date
+        out.extend_from_slice(&header_crc.to_le_bytes());
+        out.extend_from_slice(&header_compressed.to_le_bytes());
+        out.extend_from_slice(&header_size.to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        out.extend_from_slice(self.name.as_bytes());
+        out.extend_from_slice(&extra);
+        out.extend_from_slice(&stored);
+
+        if self.descriptor {
+            out.extend_from_slice(b"PK\x07\x08");
+            out.extend_from_slice(&crc.to_le_bytes());
+            if self.zip64 {
+                out.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+                out.extend_from_slice(&(self.data.len() as u64).to_le_bytes());
+            } else {
+                out.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+                out.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+            }
+        }
+        out
+    }
+
+    /// Central directory file header for the entry written at `offset`
+    fn central(&self, offset: u32) -> Vec<u8> {
+        let stored = self.stored_data();
+        let mut out = b"PK\x01\x02".to_vec();
+        out.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        out.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        out.extend_from_slice(&self.flags().to_le_bytes());
+        out.extend_from_slice(&self.method().to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&crc32fast::hash(&self.data).to_le_bytes());
+        out.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&[0; 8]); // extra and comment lengths, disk, internal attributes
+        out.extend_from_slice(&[0; 4]); // external attributes
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(self.name.as_bytes());
+        out
+    }
+}
+
+/// Complete archive: local entries, central directory and end of central directory record
+pub(crate) fn build_zip(entries: &[RawEntry]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut offsets = Vec::new();
+    for entry in entries {
+        offsets.push(out.len() as u32);
+        out.extend_from_slice(&entry.local());
+    }
+
+    let directory_offset = out.len() as u32;
+    for (entry, offset) in entries.iter().zip(offsets) {
+        out.extend_from_slice(&entry.central(offset));
+    }
+    let directory_size = out.len() as u32 - directory_offset;
+
+    out.extend_from_slice(b"PK\x05\x06");
+    out.extend_from_slice(&[0; 4]); // disk numbers
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&directory_size.to_le_bytes());
+    out.extend_from_slice(&directory_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // comment length
+    out
+}
@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use image::{DynamicImage, GrayImage, RgbImage};
+
+/// Default page background: white, as a PDF page is displayed
+pub const WHITE: [u8; 3] = [255, 255, 255];
+
+/// How transparent parts of extracted page images (soft masks, colour-key masks, JPEG 2000 alpha) are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transparency {
+    /// Composite the image over an RGB background colour
+    Background([u8; 3]),
+    /// Keep the alpha channel: pages with transparent pixels are saved as PNG, even in JPEG mode
+    Keep,
+}
+
+impl Default for Transparency {
+    fn default() -> Self {
+        Transparency::Background(WHITE)
+    }
+}
+
+impl Transparency {
+    /// Apply to a decoded image: composite it on the background, or keep its alpha channel
+    /// The alpha channel of fully opaque images is dropped in both cases.
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        if !image.color().has_alpha() {
+            return image;
+        }
+        let gray = !image.color().has_color();
+        if !has_transparency(&image) {
+            return if gray { DynamicImage::ImageLuma8(image.to_luma8()) } else { DynamicImage::ImageRgb8(image.to_rgb8()) };
+        }
+
+        match self {
+            Transparency::Keep => image,
+            Transparency::Background(background) => {
+                let rgba = image.to_rgba8();
+                let blend = |c: u8, b: u8, a: u8| ((c as u32 * a as u32 + b as u32 * (255 - a as u32) + 127) / 255) as u8;
+                let mut rgb = RgbImage::new(rgba.width(), rgba.height());
+                for (out, pixel) in rgb.pixels_mut().zip(rgba.pixels()) {
+                    let [r, g, b, a] = pixel.0;
+                    out.0 = [blend(r, background[0], a), blend(g, background[1], a), blend(b, background[2], a)];
+                }
+                // Grayscale images stay grayscale on a neutral background
+                if gray && background[0] == background[1] && background[1] == background[2] {
+                    DynamicImage::ImageLuma8(GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| image::Luma([rgb.get_pixel(x, y)[0]])))
+                } else {
+                    DynamicImage::ImageRgb8(rgb)
+                }
+            }
+        }
+    }
+}
+
+/// Whether any pixel of the image is not fully opaque
+pub fn has_transparency(image: &DynamicImage) -> bool {
+    match image {
+        DynamicImage::ImageLumaA8(gray) => gray.pixels().any(|p| p[1] < 255),
+        DynamicImage::ImageRgba8(rgba) => rgba.pixels().any(|p| p[3] < 255),
+        image if image.color().has_alpha() => image.to_rgba8().pixels().any(|p| p[3] < 255),
+        _ => false,
+    }
+}
+
+/// Background colour from its name (white, black) or hex value (#RRGGBB)
+pub fn parse_background_color(text: &str) -> Result<[u8; 3]> {
+    let text = text.trim();
+    match text.to_ascii_lowercase().as_str() {
+        "white" => return Ok(WHITE),
+        "black" => return Ok([0, 0, 0]),
+        _ => {}
+    }
+
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.is_ascii() {
+        anyhow::bail!("Invalid colour {:?}: expected white, black or #RRGGBB", text);
+    }
+    let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16)
+        .with_context(|| format!("Invalid colour {:?}: expected white, black or #RRGGBB", text));
+    Ok([component(0)?, component(2)?, component(4)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayAlphaImage, LumaA, Rgba, RgbaImage};
+
+    fn half_transparent_red() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(2, 1, |x, _| Rgba([255, 0, 0, if x == 0 { 255 } else { 128 }])))
+    }
+
+    #[test]
+    fn composites_on_background() {
+        let image = Transparency::default().apply(half_transparent_red());
+        let rgb = image.as_rgb8().expect("RGB without alpha");
+        assert_eq!(rgb.get_pixel(0, 0).0, [255, 0, 0]);
+        assert_eq!(rgb.get_pixel(1, 0).0, [255, 127, 127]);
+
+        let image = Transparency::Background([0, 0, 255]).apply(half_transparent_red());
+        assert_eq!(image.as_rgb8().unwrap().get_pixel(1, 0).0, [128, 0, 127]);
+    }
+
+    #[test]
+    fn gray_stays_gray_on_neutral_background() {
+        let gray = DynamicImage::ImageLumaA8(GrayAlphaImage::from_pixel(1, 1, LumaA([0, 128])));
+        assert_eq!(Transparency::default().apply(gray.clone()).as_luma8().unwrap().get_pixel(0, 0)[0], 127);
+        assert_eq!(Transparency::Background([255, 0, 0]).apply(gray).as_rgb8().unwrap().get_pixel(0, 0).0, [127, 0, 0]);
+    }
+
+    #[test]
+    fn keeps_alpha_or_drops_opaque_alpha() {
+        let image = half_transparent_red();
+        assert_eq!(Transparency::Keep.apply(image.clone()), image);
+
+        let opaque = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([1, 2, 3, 255])));
+        assert!(!has_transparency(&opaque));
+        for transparency in [Transparency::Keep, Transparency::default()] {
+            assert_eq!(transparency.apply(opaque.clone()).color(), image::ColorType::Rgb8);
+        }
+    }
+
+    #[test]
+    fn parses_background_colours() {
+        assert_eq!(parse_background_color(" White ").unwrap(), WHITE);
+        assert_eq!(parse_background_color("black").unwrap(), [0, 0, 0]);
+        assert_eq!(parse_background_color("#FF8000").unwrap(), [255, 128, 0]);
+        assert_eq!(parse_background_color("336699").unwrap(), [0x33, 0x66, 0x99]);
+        for invalid in ["#FFF", "#GG0000", "ÿÿÿ", "red"] {
+            assert!(parse_background_color(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,604 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use zip::ZipArchive;
+
+use crate::archive_entry::{classify_entry, entry_folder, entry_kind, EntryKind};
+use crate::cbz::{archive_password_error, read_cbz_entry};
+use crate::limits::{decode_image, ArchiveBudget};
+use crate::comic_info::{is_comic_info_file, ComicInfo};
+
+/// Pages narrower or shorter than this are reported as implausible
+pub const MIN_PAGE_DIMENSION: u32 = 64;
+
+/// Pages wider or taller than this are reported as implausible
+pub const MAX_PAGE_DIMENSION: u32 = 30_000;
+
+/// Missing page numbers listed per folder before the list is truncated
+const MAX_LISTED_MISSING_PAGES: usize = 20;
+
+/// Bytes searched for the JPEG end of image marker, from the end of the data (allows trailing padding)
+const JPEG_TRAILER_SEARCH: usize = 4096;
+
+/// How serious a verification issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueSeverity {
+    /// The archive is damaged or has unreadable pages
+    Error,
+    /// The archive reads, but something looks wrong (names, numbering, sizes)
+    Warning,
+}
+
+impl IssueSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueSeverity::Error => "error",
+            IssueSeverity::Warning => "warning",
+        }
+    }
+}
+
+/// What a verification issue is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// Entry data does not match its stored CRC-32
+    CrcMismatch,
+    /// Entry could not be read (corrupt data, unsupported compression, ...)
+    ReadError,
+    /// Image data does not decode
+    UndecodableImage,
+    /// Image dimensions are out of range or disagree with the decoded image
+    ImplausibleDimensions,
+    /// Several entries have the same path
+    DuplicateName,
+    /// Entry paths differ only by case or zero padding, so their order is arbitrary
+    AmbiguousName,
+    /// Numbered pages have gaps
+    MissingPages,
+    /// ComicInfo.xml does not parse
+    InvalidComicInfo,
+}
+
+impl IssueKind {
+    /// Stable code for reports
+    pub fn code(&self) -> &'static str {
+        match self {
+            IssueKind::CrcMismatch => "crc_mismatch",
+            IssueKind::ReadError => "read_error",
+            IssueKind::UndecodableImage => "undecodable_image",
+            IssueKind::ImplausibleDimensions => "implausible_dimensions",
+            IssueKind::DuplicateName => "duplicate_name",
+            IssueKind::AmbiguousName => "ambiguous_name",
+            IssueKind::MissingPages => "missing_pages",
+            IssueKind::InvalidComicInfo => "invalid_comic_info",
+        }
+    }
+}
+
+/// A problem found while verifying an archive
+#[derive(Debug, Clone)]
+pub struct VerifyIssue {
+    pub severity: IssueSeverity,
+    pub kind: IssueKind,
+    /// Entry path (or folder, for missing pages), None for archive-wide issues
+    pub entry: Option<String>,
+    pub message: String,
+}
+
+/// Result of verifying a CBZ/CBR archive
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// "cbz" or "cbr"
+    pub format: String,
+    /// Number of files in the archive (directories excluded)
+    pub entries: usize,
+    /// Number of page images
+    pub pages: usize,
+    pub issues: Vec<VerifyIssue>,
+}
+
+impl VerifyReport {
+    pub fn errors(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.severity == IssueSeverity::Error).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.severity == IssueSeverity::Warning).count()
+    }
+
+    /// True when no error was found (warnings allowed)
+    pub fn is_valid(&self) -> bool {
+        self.errors() == 0
+    }
+
+    /// Machine-readable report
+    pub fn to_json(&self, file: &str) -> Value {
+        let issues: Vec<Value> = self
+            .issues
+            .iter()
+            .map(|issue| {
+                json!({
+                    "severity": issue.severity.as_str(),
+                    "kind": issue.kind.code(),
+                    "entry": issue.entry,
+                    "message": issue.message,
+                })
+            })
+            .collect();
+
+        json!({
+            "file": file,
+            "format": self.format,
+            "valid": self.is_valid(),
+            "entries": self.entries,
+            "pages": self.pages,
+            "errors": self.errors(),
+            "warnings": self.warnings(),
+            "issues": issues,
+        })
+    }
+}
+
+/// Collects verification results entry by entry
+/// Entry contents are checked as they are added; name and numbering checks run in `finish`.
+pub struct ArchiveVerifier {
+    report: VerifyReport,
+    names: Vec<String>,
+    /// Paths of the entries holding page images
+    pages: Vec<String>,
+}
+
+impl ArchiveVerifier {
+    pub fn new(format: &str) -> ArchiveVerifier {
+        ArchiveVerifier {
+            report: VerifyReport { format: format.to_string(), ..VerifyReport::default() },
+            names: Vec::new(),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Record an issue
+    pub fn add_issue(&mut self, severity: IssueSeverity, kind: IssueKind, entry: Option<&str>, message: String) {
+        self.report.issues.push(VerifyIssue { severity, kind, entry: entry.map(str::to_string), message });
+    }
+
+    /// Record an entry without checking its contents (e.g. it could not be read)
+    pub fn add_name(&mut self, path: &str) {
+        self.record(path, entry_kind(path));
+    }
+
+    fn record(&mut self, path: &str, kind: EntryKind) {
+        let path = path.replace('\\', "/");
+        self.report.entries += 1;
+        if kind == EntryKind::Image {
+            self.report.pages += 1;
+            self.pages.push(path.clone());
+        }
+        self.names.push(path);
+    }
+
+    /// Record an entry and check its contents: images must decode with plausible dimensions,
+    /// ComicInfo.xml must parse
+    pub fn check_entry(&mut self, path: &str, data: &[u8]) {
+        let kind = classify_entry(path, data);
+        self.record(path, kind);
+
+        if is_comic_info_file(path) {
+            if let Err(e) = ComicInfo::parse(&String::from_utf8_lossy(data)) {
+                self.add_issue(IssueSeverity::Warning, IssueKind::InvalidComicInfo, Some(path), format!("{:#}", e));
+            }
+        } else if kind == EntryKind::Image {
+            self.check_image(path, data);
+        }
+    }
+
+    fn check_image(&mut self, path: &str, data: &[u8]) {
+        let image = match decode_image(data) {
+            Ok(image) => image,
+            Err(e) => {
+                self.add_issue(IssueSeverity::Error, IssueKind::UndecodableImage, Some(path), format!("Image does not decode: {:#}", e));
+                return;
+            }
+        };
+
+        // Decoders fill truncated JPEG data with gray instead of failing
+        let is_jpeg = data.starts_with(&[0xFF, 0xD8]);
+        if is_jpeg && !data.windows(2).rev().take(JPEG_TRAILER_SEARCH).any(|marker| marker == [0xFF, 0xD9]) {
+            self.add_issue(IssueSeverity::Error, IssueKind::UndecodableImage, Some(path), "JPEG data is truncated (no end of image marker)".to_string());
+            return;
+        }
+
+        let (width, height) = (image.width(), image.height());
+        if let Ok(size) = imagesize::blob_size(data) {
+            if (size.width as u32, size.height as u32) != (width, height) {
+                self.add_issue(
+                    IssueSeverity::Error,
+                    IssueKind::ImplausibleDimensions,
+                    Some(path),
+                    format!("Header says {}x{} but the image decodes as {}x{}", size.width, size.height, width, height),
+                );
+                return;
+            }
+        }
+
+        let in_range = |value: u32| (MIN_PAGE_DIMENSION..=MAX_PAGE_DIMENSION).contains(&value);
+        if !in_range(width) || !in_range(height) {
+            self.add_issue(
+                IssueSeverity::Warning,
+                IssueKind::ImplausibleDimensions,
+                Some(path),
+                format!("Page is {}x{}, expected {} to {} pixels per side", width, height, MIN_PAGE_DIMENSION, MAX_PAGE_DIMENSION),
+            );
+        }
+    }
+
+    /// Run the name checks and return the report
+    pub fn finish(mut self) -> VerifyReport {
+        self.check_names();
+        self.check_numbering();
+        self.report
+    }
+
+    /// Flag duplicate paths, and paths that only differ by case or zero padding
+    fn check_names(&mut self) {
+        let mut by_path: HashMap<&str, usize> = HashMap::new();
+        let mut by_key: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+        for name in &self.names {
+            *by_path.entry(name).or_default() += 1;
+            by_key.entry(name_key(name)).or_default().push(name);
+        }
+
+        let mut issues = Vec::new();
+        let mut duplicates: Vec<(&str, usize)> = by_path.into_iter().filter(|&(_, count)| count > 1).collect();
+        duplicates.sort();
+        for (name, count) in duplicates {
+            issues.push(VerifyIssue {
+                severity: IssueSeverity::Error,
+                kind: IssueKind::DuplicateName,
+                entry: Some(name.to_string()),
+                message: format!("{} entries share this path", count),
+            });
+        }
+        for mut names in by_key.into_values() {
+            names.sort();
+            names.dedup();
+            if names.len() > 1 {
+                issues.push(VerifyIssue {
+                    severity: IssueSeverity::Warning,
+                    kind: IssueKind::AmbiguousName,
+                    entry: Some(names[0].to_string()),
+                    message: format!("Page order is ambiguous between {}", names.join(", ")),
+                });
+            }
+        }
+        self.report.issues.extend(issues);
+    }
+
+    /// Report gaps in page numbers, folder by folder
+    /// Only folders where every page name carries a number are checked.
+    fn check_numbering(&mut self) {
+        let mut folders: BTreeMap<&str, Option<Vec<u64>>> = BTreeMap::new();
+        for name in &self.pages {
+            let numbers = folders.entry(entry_folder(name).unwrap_or("")).or_insert(Some(Vec::new()));
+            match (numbers.as_mut(), page_number(name)) {
+                (Some(numbers), Some(number)) => numbers.push(number),
+                _ => *numbers = None,
+            }
+        }
+
+        let mut issues = Vec::new();
+        for (folder, numbers) in folders {
+            let Some(mut numbers) = numbers else { continue };
+            numbers.sort_unstable();
+            numbers.dedup();
+            let (Some(&first), Some(&last)) = (numbers.first(), numbers.last()) else { continue };
+            // Numbers far apart are not a page sequence (e.g. years, scan IDs)
+            if numbers.len() < 2 || last - first > 2 * numbers.len() as u64 {
+                continue;
+            }
+
+            let missing: Vec<u64> = (first..=last).filter(|number| numbers.binary_search(number).is_err()).collect();
+            if missing.is_empty() {
+                continue;
+            }
+            let mut listed: Vec<String> = missing.iter().take(MAX_LISTED_MISSING_PAGES).map(u64::to_string).collect();
+            if missing.len() > MAX_LISTED_MISSING_PAGES {
+                listed.push("...".to_string());
+            }
+            let location = if folder.is_empty() { String::new() } else { format!(" in {}", folder) };
+            issues.push(VerifyIssue {
+                severity: IssueSeverity::Warning,
+                kind: IssueKind::MissingPages,
+                entry: (!folder.is_empty()).then(|| folder.to_string()),
+                message: format!("{} missing page number(s){}: {}", missing.len(), location, listed.join(", ")),
+            });
+        }
+        self.report.issues.extend(issues);
+    }
+}
+
+/// Verify a CBZ file: every entry is read in full (checking its CRC-32, or its HMAC for AES entries)
+/// and checked with `ArchiveVerifier`
+/// Password errors are returned as `ArchivePasswordError` so callers can ask for one.
+pub fn verify_cbz(path: &Path, password: Option<&str>) -> Result<VerifyReport> {
+    let file = File::open(path)
+        .context(format!("Failed to open {:?}", path))?;
+    let mut archive = ZipArchive::new(BufReader::new(file))
+        .context("Failed to open ZIP archive")?;
+    let mut verifier = ArchiveVerifier::new("cbz");
+    let mut budget = ArchiveBudget::new();
+    budget.check_entry_count(archive.len())?;
+
+    for index in 0..archive.len() {
+        let name = match archive.name_for_index(index) {
+            Some(name) if !name.ends_with('/') => name.to_string(),
+            _ => continue,
+        };
+
+        match read_cbz_entry(&mut archive, index, password, &mut budget) {
+            Ok(data) => verifier.check_entry(&name, &data),
+            Err(e) if archive_password_error(&e).is_some() => return Err(e),
+            Err(e) => {
+                let kind = if format!("{:#}", e).contains("checksum") {
+                    IssueKind::CrcMismatch
+                } else {
+                    IssueKind::ReadError
+                };
+                verifier.add_name(&name);
+                verifier.add_issue(IssueSeverity::Error, kind, Some(&name), format!("{:#}", e));
+            }
+        }
+    }
+
+    // The ZIP reader keeps one entry per path: duplicates only show in the central directory
+    let file = File::open(path)
+        .context(format!("Failed to open {:?}", path))?;
+    let names = central_directory_names(&mut BufReader::new(file))
+        .inspect_err(|e| eprintln!("[WARNING] Skipping duplicate name check: {:#}", e))
+        .unwrap_or_default();
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for name in names {
+        *counts.entry(name).or_default() += 1;
+    }
+    for (name, count) in counts.into_iter().filter(|&(_, count)| count > 1) {
+        verifier.add_issue(
+            IssueSeverity::Error,
+            IssueKind::DuplicateName,
+            Some(&name),
+            format!("{} entries share this path, only one can be read", count),
+        );
+    }
+
+    Ok(verifier.finish())
+}
+
+/// Entry names as listed in the ZIP central directory, duplicates included (ZIP64 supported)
+fn central_directory_names<R: Read + Seek>(reader: &mut R) -> Result<Vec<String>> {
+    const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+    const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+    const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4b50;
+    const ENTRY_SIGNATURE: u32 = 0x0201_4b50;
+
+    // End of central directory record: 22 bytes, followed by a comment of up to 64 KiB
+    let file_size = reader.seek(SeekFrom::End(0))?;
+    let tail_size = file_size.min(22 + 0xFFFF);
+    reader.seek(SeekFrom::Start(file_size - tail_size))?;
+    let mut tail = vec![0; tail_size as usize];
+    reader.read_exact(&mut tail)?;
+    let eocd = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&offset| read_u32(&tail, offset) == EOCD_SIGNATURE)
+        .context("Could not find the end of central directory")?;
+
+    let mut entries = read_u16(&tail, eocd + 10) as u64;
+    let mut directory_offset = read_u32(&tail, eocd + 16) as u64;
+    let locator = eocd.checked_sub(20).filter(|&offset| read_u32(&tail, offset) == ZIP64_LOCATOR_SIGNATURE);
+    if let Some(locator) = locator {
+        reader.seek(SeekFrom::Start(read_u64(&tail, locator + 8)))?;
+        let mut record = [0; 56];
+        reader.read_exact(&mut record)?;
+        if read_u32(&record, 0) == ZIP64_EOCD_SIGNATURE {
+            entries = read_u64(&record, 32);
+            directory_offset = read_u64(&record, 48);
+        }
+    }
+
+    reader.seek(SeekFrom::Start(directory_offset))?;
+    let mut names = Vec::new();
+    for _ in 0..entries {
+        let mut header = [0; 46];
+        reader.read_exact(&mut header)?;
+        if read_u32(&header, 0) != ENTRY_SIGNATURE {
+            anyhow::bail!("Corrupt central directory entry");
+        }
+        let mut name = vec![0; read_u16(&header, 28) as usize];
+        reader.read_exact(&mut name)?;
+        let skip = read_u16(&header, 30) as i64 + read_u16(&header, 32) as i64;
+        reader.seek(SeekFrom::Current(skip))?;
+        if !name.ends_with(b"/") {
+            names.push(String::from_utf8_lossy(&name).replace('\\', "/"));
+        }
+    }
+    Ok(names)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap_or_default())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap_or_default())
+}
+
+/// Name used to detect ambiguous ordering: lowercase, with digit runs stripped of leading zeros
+fn name_key(path: &str) -> String {
+    let mut key = String::with_capacity(path.len());
+    let mut digits = String::new();
+    for c in path.chars().chain(std::iter::once('\0')) {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        if !digits.is_empty() {
+            let trimmed = digits.trim_start_matches('0');
+            key.push_str(if trimmed.is_empty() { "0" } else { trimmed });
+            digits.clear();
+        }
+        if c != '\0' {
+            key.extend(c.to_lowercase());
+        }
+    }
+    key
+}
+
+/// Page number of an entry: the last digit run of its file name (without extension)
+fn page_number(path: &str) -> Option<u64> {
+    let name = path.rsplit('/').next()?;
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let end = stem.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = end - stem[..end].chars().rev().take_while(char::is_ascii_digit).count();
+    stem[start..end].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_zip::{build_zip, RawEntry};
+    use std::io::Cursor;
+
+    fn report(names: &[&str]) -> VerifyReport {
+        let mut verifier = ArchiveVerifier::new("cbz");
+        for name in names {
+            verifier.add_name(name);
+        }
+        verifier.finish()
+    }
+
+    fn issues(report: &VerifyReport, kind: IssueKind) -> Vec<&VerifyIssue> {
+        report.issues.iter().filter(|issue| issue.kind == kind).collect()
+    }
+
+    #[test]
+    fn name_key_ignores_case_and_padding() {
+        assert_eq!(name_key("Page_001.JPG"), name_key("page_1.jpg"));
+        assert_eq!(name_key("ch01/p000.png"), "ch1/p0.png");
+        assert_ne!(name_key("page_10.jpg"), name_key("page_1.jpg"));
+    }
+
+    #[test]
+    fn page_number_is_last_digit_run_of_stem() {
+        assert_eq!(page_number("ch1/page_012.jpg"), Some(12));
+        assert_eq!(page_number("vol2_p07.png"), Some(7));
+        assert_eq!(page_number("2024/cover.jpg"), None);
+        assert_eq!(page_number("cover"), None);
+    }
+
+    #[test]
+    fn reports_gaps_per_folder() {
+        let report = report(&["a/01.jpg", "a/02.jpg", "a/04.jpg", "a/05.jpg", "b/1.jpg", "b/2.jpg"]);
+        let missing = issues(&report, IssueKind::MissingPages);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].severity, IssueSeverity::Warning);
+        assert_eq!(missing[0].entry.as_deref(), Some("a"));
+        assert_eq!(missing[0].message, "1 missing page number(s) in a: 3");
+        assert_eq!(report.pages, 6);
+        // Warnings alone keep the archive valid
+        assert!(report.is_valid());
+        assert_eq!(report.to_json("test.cbz")["valid"], true);
+    }
+
+    #[test]
+    fn skips_numbering_that_is_not_a_sequence() {
+        // Numbers far apart: last - first > 2 * len
+        assert!(issues(&report(&["1.jpg", "1999.jpg", "2024.jpg"]), IssueKind::MissingPages).is_empty());
+        // One unnumbered page disables the check for its folder only
+        let report = report(&["cover.jpg", "1.jpg", "3.jpg", "x/1.jpg", "x/3.jpg"]);
+        let missing = issues(&report, IssueKind::MissingPages);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].entry.as_deref(), Some("x"));
+        // A single page has no gaps
+        assert!(issues(&self::report(&["7.jpg"]), IssueKind::MissingPages).is_empty());
+    }
+
+    #[test]
+    fn gap_at_the_limit_is_reported() {
+        // 4 pages spanning 1..=9: 9 - 1 = 8 = 2 * 4
+        let report = report(&["1.jpg", "2.jpg", "3.jpg", "9.jpg"]);
+        let missing = issues(&report, IssueKind::MissingPages);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].entry, None);
+        assert_eq!(missing[0].message, "5 missing page number(s): 4, 5, 6, 7, 8");
+    }
+
+    #[test]
+    fn reports_ambiguous_names() {
+        let report = report(&["Page_1.jpg", "page_001.jpg", "page_2.jpg"]);
+        let ambiguous = issues(&report, IssueKind::AmbiguousName);
+        assert_eq!(ambiguous.len(), 1);
+        assert_eq!(ambiguous[0].severity, IssueSeverity::Warning);
+        assert_eq!(ambiguous[0].message, "Page order is ambiguous between Page_1.jpg, page_001.jpg");
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn duplicate_names_make_the_archive_invalid() {
+        let report = report(&["1.jpg", "2.jpg", "2.jpg"]);
+        let duplicates = issues(&report, IssueKind::DuplicateName);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].entry.as_deref(), Some("2.jpg"));
+        // Same path is a duplicate, not an ambiguity
+        assert!(issues(&report, IssueKind::AmbiguousName).is_empty());
+        assert!(!report.is_valid());
+        assert_eq!(report.to_json("test.cbz")["valid"], false);
+    }
+
+    #[test]
+    fn undecodable_page_is_an_error() {
+        let mut verifier = ArchiveVerifier::new("cbz");
+        verifier.check_entry("1.jpg", b"\xFF\xD8\xFF\xE0 not really a JPEG");
+        let report = verifier.finish();
+        assert_eq!(issues(&report, IssueKind::UndecodableImage).len(), 1);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn central_directory_keeps_duplicates() {
+        let zip = build_zip(&[
+            RawEntry::stored("pages/", b""),
+            RawEntry::stored("pages\\1.txt", b"one"),
+            RawEntry::stored("pages/1.txt", b"two"),
+            RawEntry::deflated("pages/2.txt", b"three"),
+        ]);
+        let names = central_directory_names(&mut Cursor::new(zip)).unwrap();
+        assert_eq!(names, ["pages/1.txt", "pages/1.txt", "pages/2.txt"]);
+
+        assert!(central_directory_names(&mut Cursor::new(b"not a zip".to_vec())).is_err());
+    }
+
+    #[test]
+    fn verify_cbz_reports_duplicate_entries() {
+        let zip = build_zip(&[
+            RawEntry::stored("ComicInfo.xml", b"<ComicInfo><Title>Test</Title></ComicInfo>"),
+            RawEntry::stored("notes.txt", b"first"),
+            RawEntry::stored("notes.txt", b"second"),
+        ]);
+        let path = std::env::temp_dir().join(format!("verify-duplicates-{}.cbz", std::process::id()));
+        std::fs::write(&path, zip).unwrap();
+        let report = verify_cbz(&path, None);
+        std::fs::remove_file(&path).unwrap();
+
+        let report = report.unwrap();
+        let duplicates = issues(&report, IssueKind::DuplicateName);
+        assert!(!duplicates.is_empty());
+        assert!(duplicates.iter().all(|issue| issue.entry.as_deref() == Some("notes.txt")));
+        assert!(duplicates.iter().any(|issue| issue.message == "2 entries share this path, only one can be read"));
+        assert!(!report.is_valid());
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::Result;
+use std::ops::Range;
+
+use crate::archive_entry::entry_folder;
+use crate::metadata::DocumentMetadata;
+
+/// Default file name of a volume, without extension (see `volume_file_name`)
+pub const DEFAULT_VOLUME_TEMPLATE: &str = "{name} v{volume}";
+
+/// How one book is split into several output files (volumes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeSplit {
+    /// At most this many pages per volume
+    MaxPages(usize),
+    /// Volumes of at most this many bytes of page data (a larger page gets a volume of its own)
+    MaxSize(u64),
+    /// One volume per chapter: top-level outline entries of a PDF, or folders of an archive
+    Chapters,
+}
+
+/// One output file of a split book
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Volume {
+    /// Volume number, from 1
+    pub number: usize,
+    /// Chapter title, when split by chapters
+    pub title: Option<String>,
+    /// Indices of the volume's pages in the book
+    pub pages: Range<usize>,
+}
+
+impl Volume {
+    /// Metadata of the volume: the book becomes the series, the volume number its Number,
+    /// and the chapter title (if any) its title
+    pub fn metadata(&self, book: &DocumentMetadata) -> DocumentMetadata {
+        DocumentMetadata {
+            title: self.title.clone(),
+            series: book.series.clone().or_else(|| book.title.clone()),
+            number: Some(self.number.to_string()),
+            ..book.clone()
+        }
+    }
+}
+
+/// Divide a book of `page_sizes.len()` pages into volumes
+/// `page_sizes` are the bytes each page adds to the output, used by `VolumeSplit::MaxSize`;
+/// `chapters` (title, first page index) are used by `VolumeSplit::Chapters`, and pages before
+/// the first chapter join it.
+pub fn plan_volumes(page_sizes: &[u64], chapters: &[(String, usize)], split: VolumeSplit) -> Result<Vec<Volume>> {
+    let page_count = page_sizes.len();
+    if page_count == 0 {
+        anyhow::bail!("No pages to split into volumes");
+    }
+
+    // (start, title) of each volume
+    let starts: Vec<(usize, Option<String>)> = match split {
+        VolumeSplit::MaxPages(0) => anyhow::bail!("A volume must have at least one page"),
+        VolumeSplit::MaxPages(max_pages) => (0..page_count).step_by(max_pages).map(|start| (start, None)).collect(),
+        VolumeSplit::MaxSize(0) => anyhow::bail!("The maximum volume size must be larger than zero"),
+        VolumeSplit::MaxSize(max_size) => {
+            let mut starts = vec![(0, None)];
+            let mut size = 0u64;
+            for (index, &page_size) in page_sizes.iter().enumerate() {
+                if size > 0 && size.saturating_add(page_size) > max_size {
+                    starts.push((index, None));
+                    size = 0;
+                }
+                size = size.saturating_add(page_size);
+            }
+            starts
+        }
+        VolumeSplit::Chapters => {
+            let mut chapters: Vec<&(String, usize)> = chapters.iter().filter(|chapter| chapter.1 < page_count).collect();
+            chapters.sort_by_key(|chapter| chapter.1);
+            chapters.dedup_by_key(|chapter| chapter.1);
+            if chapters.is_empty() {
+                anyhow::bail!("No chapters to split into volumes");
+            }
+            chapters
+                .into_iter()
+                .enumerate()
+                .map(|(index, (title, start))| {
+                    let title = Some(title.trim().to_string()).filter(|title| !title.is_empty());
+                    (if index == 0 { 0 } else { *start }, title)
+                })
+                .collect()
+        }
+    };
+
+    let ends = starts.iter().skip(1).map(|(start, _)| *start).chain([page_count]);
+    Ok(starts
+        .iter()
+        .zip(ends)
+        .enumerate()
+        .map(|(index, ((start, title), end))| Volume { number: index + 1, title: title.clone(), pages: *start..end })
+        .collect())
+}
+
+/// Chapters of an archive's pages: the first page of each folder, named after the folder
+pub fn folder_chapters<S: AsRef<str>>(page_names: &[S]) -> Vec<(String, usize)> {
+    let mut chapters: Vec<(String, usize)> = Vec::new();
+    let mut current_folder = None;
+    for (index, name) in page_names.iter().enumerate() {
+        let folder = entry_folder(name.as_ref());
+        if index == 0 || folder != current_folder {
+            let title = folder.map(|folder| folder.rsplit(['/', '\\']).next().unwrap_or(folder));
+            chapters.push((title.unwrap_or_default().to_string(), index));
+        }
+        current_folder = folder;
+    }
+    chapters
+}
+
+/// File name of a volume (without extension) from a template
+/// Placeholders: `{name}` (the book's file name), `{volume}` (number, zero-padded to the width of
+/// `{count}`, at least two digits), `{count}` (number of volumes) and `{title}` (chapter title,
+/// or "Volume N"). Characters not allowed in file names are replaced by `_`.
+pub fn volume_file_name(template: &str, name: &str, volume: &Volume, count: usize) -> String {
+    let width = count.to_string().len().max(2);
+    let title = volume.title.clone().unwrap_or_else(|| format!("Volume {}", volume.number));
+    let file_name = template
+        .replace("{name}", name)
+        .replace("{volume}", &format!("{:0width$}", volume.number, width = width))
+        .replace("{count}", &count.to_string())
+        .replace("{title}", &title);
+
+    let file_name: String = file_name
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect();
+    file_name.trim().trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(volumes: &[Volume]) -> Vec<Range<usize>> {
+        volumes.iter().map(|volume| volume.pages.clone()).collect()
+    }
+
+    fn chapter(title: &str, start: usize) -> (String, usize) {
+        (title.to_string(), start)
+    }
+
+    #[test]
+    fn max_pages_boundaries() {
+        let sizes = [1u64; 10];
+        assert_eq!(ranges(&plan_volumes(&sizes, &[], VolumeSplit::MaxPages(5)).unwrap()), [0..5, 5..10]);
+        assert_eq!(ranges(&plan_volumes(&sizes, &[], VolumeSplit::MaxPages(4)).unwrap()), [0..4, 4..8, 8..10]);
+        assert_eq!(ranges(&plan_volumes(&sizes, &[], VolumeSplit::MaxPages(10)).unwrap()), vec![(0..10)]);
+        assert_eq!(ranges(&plan_volumes(&sizes, &[], VolumeSplit::MaxPages(11)).unwrap()), vec![(0..10)]);
+        assert_eq!(ranges(&plan_volumes(&sizes, &[], VolumeSplit::MaxPages(1)).unwrap()).len(), 10);
+
+        let volumes = plan_volumes(&sizes, &[], VolumeSplit::MaxPages(4)).unwrap();
+        assert_eq!(volumes.iter().map(|volume| volume.number).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(volumes.iter().all(|volume| volume.title.is_none()));
+
+        assert!(plan_volumes(&sizes, &[], VolumeSplit::MaxPages(0)).is_err());
+        assert!(plan_volumes(&[], &[], VolumeSplit::MaxPages(5)).is_err());
+    }
+
+    #[test]
+    fn max_size_gives_oversized_page_its_own_volume() {
+        // 40 + 50 fits in 100; the 250-byte page is alone; 60 + 40 fits exactly
+        let sizes = [40, 50, 250, 60, 40, 10];
+        let volumes = plan_volumes(&sizes, &[], VolumeSplit::MaxSize(100)).unwrap();
+        assert_eq!(ranges(&volumes), [0..2, 2..3, 3..5, 5..6]);
+
+        // An oversized first page does not leave an empty volume before it
+        let volumes = plan_volumes(&[250, 10], &[], VolumeSplit::MaxSize(100)).unwrap();
+        assert_eq!(ranges(&volumes), [0..1, 1..2]);
+
+        assert!(plan_volumes(&sizes, &[], VolumeSplit::MaxSize(0)).is_err());
+    }
+
+    #[test]
+    fn chapters_split() {
+        let sizes = [1u64; 10];
+        // Pages 0..3 come before the first chapter and join it; the duplicate start keeps the
+        // first title; the out-of-range chapter is dropped; unsorted input is sorted
+        let chapters = [chapter("Two", 6), chapter("One", 3), chapter("Again", 6), chapter("Past the end", 10)];
+        let volumes = plan_volumes(&sizes, &chapters, VolumeSplit::Chapters).unwrap();
+        assert_eq!(ranges(&volumes), [0..6, 6..10]);
+        assert_eq!(volumes[0].title.as_deref(), Some("One"));
+        assert_eq!(volumes[1].title.as_deref(), Some("Two"));
+
+        // Blank titles become None and are named "Volume N" later
+        let volumes = plan_volumes(&sizes, &[chapter("  ", 0), chapter(" Epilogue ", 8)], VolumeSplit::Chapters).unwrap();
+        assert_eq!(volumes[0].title, None);
+        assert_eq!(volumes[1].title.as_deref(), Some("Epilogue"));
+
+        assert!(plan_volumes(&sizes, &[], VolumeSplit::Chapters).is_err());
+        assert!(plan_volumes(&sizes, &[chapter("Late", 12)], VolumeSplit::Chapters).is_err());
+    }
+
+    #[test]
+    fn file_names_from_template() {
+        let volume = |number: usize, title: Option<&str>| Volume { number, title: title.map(str::to_string), pages: 0..1 };
+
+        assert_eq!(volume_file_name(DEFAULT_VOLUME_TEMPLATE, "Book", &volume(3, None), 9), "Book v03");
+        assert_eq!(volume_file_name(DEFAULT_VOLUME_TEMPLATE, "Book", &volume(7, None), 120), "Book v007");
+        assert_eq!(volume_file_name("{name} {volume} of {count}", "Book", &volume(2, None), 12), "Book 02 of 12");
+        assert_eq!(volume_file_name("{volume} - {title}", "Book", &volume(4, None), 5), "04 - Volume 4");
+        assert_eq!(volume_file_name("{volume} - {title}", "Book", &volume(1, Some("Who? Me: <now>")), 2), "01 - Who_ Me_ _now_");
+        assert_eq!(volume_file_name("{name}/{title}.", "A|B", &volume(1, Some("x\ty")), 1), "A_B_x_y");
+    }
+
+    #[test]
+    fn chapters_from_folders() {
+        let names = ["cover.jpg", "Vol 1/Ch 1/01.jpg", "Vol 1/Ch 1/02.jpg", "Vol 1/Ch 2/01.jpg", "Extras\\01.jpg"];
+        assert_eq!(
+            folder_chapters(&names),
+            [chapter("", 0), chapter("Ch 1", 1), chapter("Ch 2", 3), chapter("Extras", 4)]
+        );
+        assert_eq!(folder_chapters(&["A/01.jpg", "A/02.jpg"]), [chapter("A", 0)]);
+        assert!(folder_chapters::<&str>(&[]).is_empty());
+    }
+}
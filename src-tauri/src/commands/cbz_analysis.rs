@@ -1,9 +1,23 @@
-use crate::models::CbzAnalysisResult;
+use crate::models::{ArchiveVerifyReport, CbzAnalysisResult};
+use pdf_conversion_lib::PageOrder;
 
 /// Analyze CBZ file
+/// `archive_order` keeps the archive's entry order instead of natural filename order
 #[tauri::command]
-pub async fn analyze_cbz(path: String) -> Result<CbzAnalysisResult, String> {
-    crate::utils::analyze_cbz(&path)
+pub async fn analyze_cbz(path: String, password: Option<String>, archive_order: Option<bool>) -> Result<CbzAnalysisResult, String> {
+    let order = PageOrder::from_archive_order(archive_order.unwrap_or(false));
+    crate::utils::analyze_cbz(&path, password.as_deref(), order)
         .await
-        .map_err(|e| format!("Failed to analyze CBZ: {}", e))
+        .map_err(|e| crate::utils::describe_error("Failed to analyze CBZ", &e))
+}
+
+/// Verify a CBZ/CBR file: entry CRCs, image decoding and dimensions, duplicate names and missing page numbers
+#[tauri::command]
+pub async fn verify_archive(path: String, password: Option<String>) -> Result<ArchiveVerifyReport, String> {
+    let path = std::path::PathBuf::from(path);
+    tokio::task::spawn_blocking(move || crate::utils::verify_archive(&path, password.as_deref()))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+        .map(ArchiveVerifyReport::from)
+        .map_err(|e| crate::utils::describe_error("Failed to verify archive", &e))
 }
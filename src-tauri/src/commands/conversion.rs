@@ -1,8 +1,9 @@
+use crate::models::{CbzDocumentSettings, PdfDocumentSettings, PdfOptimizeResult};
 use crate::utils;
 use std::fs;
 use std::path::PathBuf;
 use tauri::Emitter;
-use pdf_conversion_lib::convert_pdf_to_images_parallel;
+use pdf_conversion_lib::{convert_pdf_to_images_parallel, extract_page_images_lopdf, is_pdf_data, merge_pages, merged_metadata, read_cbz_metadata, read_pdf_metadata, read_pdf_metadata_lopdf, split_spreads, ComicInfo, MergeInput, MergeSeparator, PdfBackend, SpreadReport, Transparency, COMIC_INFO_FILENAME};
 use once_cell::sync::Lazy;
 use tokio::sync::Mutex;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
@@ -62,6 +63,10 @@ fn validate_output_path(path: &str) -> Result<PathBuf, String> {
 
 /// Convert internal error messages to user-friendly messages
 fn user_friendly_error(internal_error: &str) -> String {
+    // Password error codes are passed through so the GUI can ask for the password
+    if utils::is_password_error_code(internal_error) {
+        return internal_error.to_string();
+    }
     // Map common technical errors to user-friendly messages
     if internal_error.contains("permission denied") || internal_error.contains("Permission denied") {
         return "Access denied. Please check file permissions.".to_string();
@@ -75,11 +80,12 @@ fn user_friendly_error(internal_error: &str) -> String {
     if internal_error.contains("out of memory") || internal_error.contains("OutOfMemory") {
         return "Not enough memory. Try closing other applications or using a smaller file.".to_string();
     }
-    if internal_error.contains("PDFium") || internal_error.contains("pdfium") {
-        return "PDF processing error. The file may be corrupted or password-protected.".to_string();
+    // Pages the built-in reader refuses when PDFium is missing (see `describe_error`)
+    if internal_error.contains("without PDFium") {
+        return internal_error.to_string();
     }
-    if internal_error.contains("password") || internal_error.contains("encrypted") {
-        return "This PDF is password-protected. Please provide an unprotected file.".to_string();
+    if internal_error.contains("PDFium") || internal_error.contains("pdfium") {
+        return "PDF processing error. The file may be corrupted.".to_string();
     }
     if internal_error.contains("corrupted") || internal_error.contains("malformed") {
         return "The file appears to be corrupted or invalid.".to_string();
@@ -98,6 +104,9 @@ pub async fn convert_pdf_to_cbz(
     dpi: u32,
     quality: u32,
     lossless: bool,
+    password: Option<String>,
+    archive_password: Option<String>,
+    settings: Option<CbzDocumentSettings>,
 ) -> Result<Vec<u8>, String> {
     use std::time::Instant;
     let start_time = Instant::now();
@@ -156,15 +165,13 @@ pub async fn convert_pdf_to_cbz(
         }
     });
 
-    let images = tokio::task::spawn_blocking(move || {
-        let result = if lossless {
-            convert_pdf_lossless(&pdf_data, effective_dpi)
-        } else {
-            // Use the optimized parallel conversion from shared library
-            convert_pdf_to_images_parallel(&pdf_data, effective_dpi, effective_quality as u8, 0)
-                .map_err(|e| e.to_string())
-        };
-        result
+    let settings = settings.unwrap_or_default();
+    let (images, comment, spreads) = tokio::task::spawn_blocking(move || {
+        let backend = PdfBackend::select(false);
+        let mut images = convert_pdf_pages(&pdf_data, effective_dpi, effective_quality as u8, lossless, password.as_deref(), backend)?;
+        let spreads = split_page_spreads(&mut images, &settings, effective_quality as u8)?;
+        let comment = add_comic_info(&mut images, &pdf_data, password.as_deref(), backend, &settings);
+        Ok::<_, String>((images, comment, spreads))
     })
     .await
     .map_err(|e| user_friendly_error(&e.to_string()))?
@@ -177,10 +184,10 @@ pub async fn convert_pdf_to_cbz(
         return Err("No pages could be extracted from this PDF. The file may be empty or corrupted.".to_string());
     }
 
-    let page_count = images.len();
+    let page_count = images.iter().filter(|(name, _)| name != COMIC_INFO_FILENAME).count();
     let window_for_zip = window.clone();
 
-    let cbz_data = utils::create_cbz_with_progress(images, move |done, total| {
+    let cbz_data = utils::create_cbz_with_progress(images, archive_password.as_deref(), comment, move |done, total| {
         let percentage = 90 + ((done as f32 / total as f32) * 10.0) as u32;
         let _ = window_for_zip.emit("conversion-progress", serde_json::json!({
             "percentage": percentage,
@@ -191,24 +198,72 @@ pub async fn convert_pdf_to_cbz(
 
     let _ = window.emit("conversion-progress", serde_json::json!({
         "percentage": 100,
-        "message": format!("Done! {} pages → {:.1} MB", page_count, cbz_data.len() as f64 / 1024.0 / 1024.0)
+        "message": format!("Done! {} pages → {:.1} MB{}", page_count, cbz_data.len() as f64 / 1024.0 / 1024.0,
+            spreads.map(|spreads| format!(" ({} spreads split)", spreads.split_pages.len())).unwrap_or_default())
     }));
 
     Ok(cbz_data)
 }
 
+/// Convert every page of a PDF to an image
+/// PDFium extracts or renders pages; without it, the built-in reader converts image-only PDFs.
+fn convert_pdf_pages(pdf_data: &[u8], dpi: u32, quality: u8, lossless: bool, password: Option<&str>, backend: PdfBackend) -> Result<Vec<(String, Vec<u8>)>, String> {
+    match backend {
+        PdfBackend::Lopdf => extract_page_images_lopdf(pdf_data, lossless, quality, 0, password, Transparency::default())
+            .map_err(|e| utils::describe_error("PDF conversion failed", &e)),
+        PdfBackend::Pdfium if lossless => convert_pdf_lossless(pdf_data, dpi, password),
+        // Use the optimized parallel conversion from shared library
+        PdfBackend::Pdfium => convert_pdf_to_images_parallel(pdf_data, dpi, quality, 0, password, Transparency::default())
+            .map_err(|e| utils::describe_error("PDF conversion failed", &e)),
+    }
+}
+
+/// Split the double-page spreads among the converted pages, if requested (right page first for manga)
+fn split_page_spreads(images: &mut Vec<(String, Vec<u8>)>, settings: &CbzDocumentSettings, jpeg_quality: u8) -> Result<Option<SpreadReport>, String> {
+    let Some(options) = settings.spread_split(jpeg_quality) else {
+        return Ok(None);
+    };
+    split_spreads(images, &options)
+        .map(Some)
+        .map_err(|e| utils::describe_error("Spread splitting failed", &e))
+}
+
+/// Append ComicInfo.xml (PDF Info metadata and page list) to the converted pages
+/// and return the ComicBookInfo archive comment, if requested.
+/// Metadata is best effort: a PDF without a readable Info dictionary still gets the page list.
+fn add_comic_info(images: &mut Vec<(String, Vec<u8>)>, pdf_data: &[u8], password: Option<&str>, backend: PdfBackend, settings: &CbzDocumentSettings) -> Option<String> {
+    if images.is_empty() {
+        return None;
+    }
+    let pdf_metadata = match backend {
+        PdfBackend::Pdfium => read_pdf_metadata(pdf_data, password),
+        PdfBackend::Lopdf => read_pdf_metadata_lopdf(pdf_data, password),
+    }
+    .unwrap_or_default();
+    if let Some(xml) = settings.comic_info_xml(&pdf_metadata, images) {
+        images.push((COMIC_INFO_FILENAME.to_string(), xml.into_bytes()));
+    }
+    settings.comic_book_info_comment(&pdf_metadata)
+}
+
 /// Convert PDF lossless mode (PNG at same DPI as lossy)
 /// Uses the optimized pipeline from the shared library
-fn convert_pdf_lossless(pdf_data: &[u8], dpi: u32) -> Result<Vec<(String, Vec<u8>)>, String> {
+fn convert_pdf_lossless(pdf_data: &[u8], dpi: u32, password: Option<&str>) -> Result<Vec<(String, Vec<u8>)>, String> {
     use pdf_conversion_lib::extract_images_lossless_at_dpi;
 
-    extract_images_lossless_at_dpi(pdf_data, dpi, 0)
-        .map_err(|e| format!("Lossless conversion failed: {}", e))
+    extract_images_lossless_at_dpi(pdf_data, dpi, 0, password, Transparency::default())
+        .map_err(|e| utils::describe_error("Lossless conversion failed", &e))
 }
 
 
 #[tauri::command]
-pub async fn convert_cbz_to_pdf(path: String, lossless: bool, quality: u32) -> Result<Vec<u8>, String> {
+pub async fn convert_cbz_to_pdf(
+    path: String,
+    lossless: bool,
+    quality: u32,
+    settings: Option<PdfDocumentSettings>,
+    password: Option<String>,
+) -> Result<Vec<u8>, String> {
     use crate::utils::MemoryMonitor;
 
     // Acquire lock to prevent concurrent PDFium calls
@@ -238,26 +293,25 @@ pub async fn convert_cbz_to_pdf(path: String, lossless: bool, quality: u32) -> R
     // Start memory monitoring
     let mut mem_monitor = MemoryMonitor::new("CBZ to PDF conversion");
 
-    let cbz_data = fs::read(&cbz_path)
-        .map_err(|e| format!("Failed to read CBZ file: {}", e))?;
-
-    mem_monitor.check("After reading CBZ file");
-
-    let images = utils::extract_images_from_cbz(&cbz_data)
+    let settings = settings.unwrap_or_default();
+    let (pages, archive_metadata) = utils::open_archive_pages(&cbz_path, password.as_deref(), settings.page_order())
         .map_err(|e| {
-            eprintln!("[ERROR] Failed to extract CBZ: {}", e);
-            format!("Failed to extract CBZ: {}", e)
+            eprintln!("[ERROR] Failed to open CBZ: {}", e);
+            utils::describe_error("Failed to extract CBZ", &e)
         })?;
 
-    mem_monitor.check(&format!("After extracting {} images", images.len()));
+    mem_monitor.check(&format!("After listing {} images", pages.len()));
 
-    if images.is_empty() {
+    if pages.len() == 0 {
         return Err("No images found in CBZ file".to_string());
     }
 
-    eprintln!("[GUI] Extracted {} images, creating PDF...", images.len());
+    eprintln!("[GUI] Found {} images, creating PDF...", pages.len());
 
-    let pdf_data = utils::create_pdf_from_images(images, |current, total| {
+    let mut pdf_options = settings.to_options(archive_metadata)?;
+    pdf_options.image_encoding = settings.image_encoding(lossless, quality);
+
+    let pdf_data = utils::create_pdf_from_pages(pages, &pdf_options, |current, total| {
         if current % 50 == 0 || current == total {
             eprintln!("[GUI] Creating PDF: {}/{} images processed", current, total);
         }
@@ -354,6 +408,9 @@ pub async fn convert_pdf_to_cbz_direct(
     dpi: u32,
     quality: u32,
     lossless: bool,
+    password: Option<String>,
+    archive_password: Option<String>,
+    settings: Option<CbzDocumentSettings>,
 ) -> Result<u64, String> {
     use std::time::Instant;
     let start_time = Instant::now();
@@ -438,17 +495,16 @@ pub async fn convert_pdf_to_cbz_direct(
     });
 
     // Convert PDF to images
-    let images = tokio::task::spawn_blocking(move || {
-        if lossless {
-            convert_pdf_lossless(&pdf_data, effective_dpi)
-        } else {
-            convert_pdf_to_images_parallel(&pdf_data, effective_dpi, effective_quality as u8, 0)
-                .map_err(|e| e.to_string())
-        }
+    let settings = settings.unwrap_or_default();
+    let (images, comment, spreads) = tokio::task::spawn_blocking(move || {
+        let backend = PdfBackend::select(false);
+        let mut images = convert_pdf_pages(&pdf_data, effective_dpi, effective_quality as u8, lossless, password.as_deref(), backend)?;
+        let spreads = split_page_spreads(&mut images, &settings, effective_quality as u8)?;
+        let comment = add_comic_info(&mut images, &pdf_data, password.as_deref(), backend, &settings);
+        Ok::<_, String>((images, comment, spreads))
     })
     .await
-    .map_err(|e| format!("Task join error: {}", e))?
-    .map_err(|e| format!("PDF conversion failed: {}", e))?;
+    .map_err(|e| format!("Task join error: {}", e))??;
 
     progress_ticker.abort();
 
@@ -456,12 +512,15 @@ pub async fn convert_pdf_to_cbz_direct(
         return Err("No pages were extracted from PDF".to_string());
     }
 
-    let page_count = images.len();
+    let page_count = images.iter().filter(|(name, _)| name != COMIC_INFO_FILENAME).count();
     eprintln!("[RUST CONV#{}] Converted {} pages", conv_id, page_count);
+    if let Some(spreads) = &spreads {
+        eprintln!("[RUST CONV#{}] Split {} spreads (pages {:?}), {} without re-encoding", conv_id, spreads.split_pages.len(), spreads.split_pages, spreads.lossless);
+    }
 
     // Create CBZ archive
     let window_for_zip = window.clone();
-    let cbz_data = utils::create_cbz_with_progress(images, move |done, total| {
+    let cbz_data = utils::create_cbz_with_progress(images, archive_password.as_deref(), comment, move |done, total| {
         let percentage = 90 + ((done as f32 / total as f32) * 10.0) as u32;
         let _ = window_for_zip.emit("conversion-progress", serde_json::json!({
             "percentage": percentage,
@@ -483,9 +542,264 @@ pub async fn convert_pdf_to_cbz_direct(
 
     let _ = window.emit("conversion-progress", serde_json::json!({
         "percentage": 100,
-        "message": format!("Terminé! {} pages → {:.1} MB en {:.1}s", page_count, cbz_size as f64 / 1024.0 / 1024.0, elapsed.as_secs_f64())
+        "message": format!("Terminé! {} pages → {:.1} MB en {:.1}s{}", page_count, cbz_size as f64 / 1024.0 / 1024.0, elapsed.as_secs_f64(),
+            spreads.map(|spreads| format!(" ({} doubles pages découpées)", spreads.split_pages.len())).unwrap_or_default())
     }));
 
     Ok(cbz_size)
 }
 
+
+/// Convert CBZ/CBR to PDF and stream it directly to disk (avoids IPC and in-memory PDF for large archives)
+/// Pages are written one at a time; returns the file size in bytes instead of the file contents
+#[tauri::command]
+pub async fn convert_cbz_to_pdf_direct(
+    window: tauri::Window,
+    path: String,
+    output_path: String,
+    lossless: bool,
+    quality: u32,
+    settings: Option<PdfDocumentSettings>,
+    password: Option<String>,
+) -> Result<u64, String> {
+    use crate::utils::MemoryMonitor;
+    use std::time::Instant;
+    let start_time = Instant::now();
+
+    // Validate input and output paths
+    let validated_input = validate_path(&path)?;
+    let validated_output = validate_output_path(&output_path)?;
+
+    // Acquire lock to prevent concurrent conversions
+    let _lock = CONVERSION_LOCK.lock().await;
+
+    eprintln!("[GUI] Converting CBZ to PDF (direct): {:?} -> {:?} (Lossless: {}, Quality: {})",
+              validated_input, validated_output, lossless, quality);
+
+    let mut mem_monitor = MemoryMonitor::new("CBZ to PDF direct conversion");
+
+    let settings = settings.unwrap_or_default();
+    let (pages, archive_metadata) = utils::open_archive_pages(&validated_input, password.as_deref(), settings.page_order())
+        .map_err(|e| utils::describe_error("Failed to extract CBZ", &e))?;
+    let mut pdf_options = settings.to_options(archive_metadata)?;
+    pdf_options.image_encoding = settings.image_encoding(lossless, quality);
+
+    mem_monitor.check(&format!("After listing {} images", pages.len()));
+
+    if pages.len() == 0 {
+        return Err("No images found in CBZ file".to_string());
+    }
+
+    let page_count = pages.len();
+    let _ = window.emit("conversion-progress", serde_json::json!({
+        "percentage": 5,
+        "message": format!("{} images trouvées, écriture du PDF...", page_count)
+    }));
+
+    let window_for_pdf = window.clone();
+    let file_size = tokio::task::spawn_blocking(move || {
+        utils::write_pdf_file_from_pages(pages, &validated_output, &pdf_options, move |current, total| {
+            let percentage = 5 + ((current as f32 / total as f32) * 95.0) as u32;
+            let _ = window_for_pdf.emit("conversion-progress", serde_json::json!({
+                "percentage": percentage,
+                "message": format!("Écriture PDF page {}/{}...", current, total)
+            }));
+        })
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+    .map_err(|e| {
+        eprintln!("[ERROR] Failed to write PDF: {}", e);
+        utils::describe_error("Failed to write PDF", &e)
+    })?;
+
+    mem_monitor.finish();
+
+    let elapsed = start_time.elapsed();
+    let _ = window.emit("conversion-progress", serde_json::json!({
+        "percentage": 100,
+        "message": format!("Terminé! {} pages → {:.1} MB en {:.1}s", page_count, file_size as f64 / 1024.0 / 1024.0, elapsed.as_secs_f64())
+    }));
+
+    Ok(file_size)
+}
+
+/// Merge PDFs and CBZ/CBR archives into one CBZ or PDF (chosen by the output extension), written to disk
+/// Each input becomes a chapter: a folder in CBZ, a bookmark in PDF. PDF inputs are converted like
+/// PDF → CBZ; `separator` ("blank" or an image path) is inserted before each input after the first.
+/// `archive_password` encrypts CBZ output; PDF output uses the settings' protection. Returns the file size.
+#[tauri::command]
+pub async fn merge_files(
+    window: tauri::Window,
+    paths: Vec<String>,
+    output_path: String,
+    dpi: u32,
+    quality: u32,
+    lossless: bool,
+    separator: Option<String>,
+    password: Option<String>,
+    archive_password: Option<String>,
+    settings: Option<PdfDocumentSettings>,
+) -> Result<u64, String> {
+    use std::time::Instant;
+    let start_time = Instant::now();
+
+    if paths.is_empty() {
+        return Err("No files to merge".to_string());
+    }
+    let inputs = paths.iter().map(|path| validate_path(path)).collect::<Result<Vec<_>, _>>()?;
+    let validated_output = validate_output_path(&output_path)?;
+    let extension = validated_output.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let pdf_output = match extension.as_str() {
+        "pdf" => true,
+        "cbz" => false,
+        _ => return Err("Output file must end in .cbz or .pdf".to_string()),
+    };
+    let separator = separator
+        .filter(|separator| !separator.trim().is_empty())
+        .map(|separator| MergeSeparator::from_arg(&separator))
+        .transpose()
+        .map_err(|e| format!("{:#}", e))?;
+
+    let _lock = CONVERSION_LOCK.lock().await;
+
+    eprintln!("[GUI] Merging {} files -> {:?} (DPI: {}, Lossless: {}, Quality: {})",
+              inputs.len(), validated_output, dpi, lossless, quality);
+
+    let effective_dpi = if dpi == 0 { 200 } else { dpi };
+    let effective_quality = if quality == 0 { 85 } else { quality };
+    let settings = settings.unwrap_or_default();
+    let mut pdf_options = settings.to_options(None)?;
+    pdf_options.image_encoding = settings.image_encoding(lossless, effective_quality);
+
+    let window_for_merge = window.clone();
+    let (page_count, file_size) = tokio::task::spawn_blocking(move || {
+        let total = inputs.len();
+        let mut merge_inputs = Vec::with_capacity(total);
+        for (index, input_path) in inputs.iter().enumerate() {
+            let _ = window_for_merge.emit("conversion-progress", serde_json::json!({
+                "percentage": (index * 80 / total) as u32,
+                "message": format!("Reading file {}/{}...", index + 1, total)
+            }));
+
+            let data = fs::read(input_path)
+                .map_err(|e| user_friendly_error(&e.to_string()))?;
+            let (pages, metadata) = if is_pdf_data(&data) {
+                let backend = PdfBackend::select(false);
+                let pages = convert_pdf_pages(&data, effective_dpi, effective_quality as u8, lossless, password.as_deref(), backend)?;
+                let metadata = match backend {
+                    PdfBackend::Pdfium => read_pdf_metadata(&data, password.as_deref()),
+                    PdfBackend::Lopdf => read_pdf_metadata_lopdf(&data, password.as_deref()),
+                };
+                (pages, metadata.ok())
+            } else {
+                let pages = utils::extract_images_from_cbz(&data, password.as_deref(), settings.page_order())
+                    .map_err(|e| utils::describe_error("Failed to extract archive", &e))?;
+                (pages, read_cbz_metadata(&data, password.as_deref()))
+            };
+            if pages.is_empty() {
+                return Err(format!("No pages found in {}", input_path.display()));
+            }
+            merge_inputs.push(MergeInput::new(input_path, pages, metadata));
+        }
+
+        pdf_options.metadata = pdf_options.metadata.clone().merged_with(merged_metadata(&merge_inputs));
+        let pages = merge_pages(merge_inputs, separator.as_ref()).map_err(|e| format!("{:#}", e))?;
+        let page_count = pages.len();
+
+        let file_size = if pdf_output {
+            utils::write_pdf_file_from_pages(Box::new(pages.into_iter().map(Ok)), &validated_output, &pdf_options, |current, total| {
+                let percentage = 80 + ((current as f32 / total as f32) * 20.0) as u32;
+                let _ = window_for_merge.emit("conversion-progress", serde_json::json!({
+                    "percentage": percentage,
+                    "message": format!("Writing PDF page {}/{}...", current, total)
+                }));
+            })
+            .map_err(|e| user_friendly_error(&e.to_string()))?
+        } else {
+            let mut pages = pages;
+            let mut info = ComicInfo::from_metadata(&pdf_options.metadata);
+            info.set_pages(&pages);
+            pages.push((COMIC_INFO_FILENAME.to_string(), info.to_xml().into_bytes()));
+            let cbz_data = utils::create_cbz_with_progress(pages, archive_password.as_deref(), None, |done, total| {
+                let percentage = 80 + ((done as f32 / total as f32) * 20.0) as u32;
+                let _ = window_for_merge.emit("conversion-progress", serde_json::json!({
+                    "percentage": percentage,
+                    "message": format!("Creating CBZ archive {}/{}...", done, total)
+                }));
+            })
+            .map_err(|e| format!("Failed to create CBZ archive: {}", e))?;
+            fs::write(&validated_output, &cbz_data)
+                .map_err(|e| format!("Failed to write CBZ file: {}", e))?;
+            cbz_data.len() as u64
+        };
+        Ok::<_, String>((page_count, file_size))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))??;
+
+    let elapsed = start_time.elapsed();
+    let _ = window.emit("conversion-progress", serde_json::json!({
+        "percentage": 100,
+        "message": format!("Done! {} pages → {:.1} MB in {:.1}s", page_count, file_size as f64 / 1024.0 / 1024.0, elapsed.as_secs_f64())
+    }));
+
+    Ok(file_size)
+}
+
+/// Shrink a PDF by downsampling and recompressing its page images to `dpi`
+/// Scanned pages reuse their embedded image, other pages are rendered; page sizes are kept
+#[tauri::command]
+pub async fn optimize_pdf(
+    window: tauri::Window,
+    path: String,
+    output_path: String,
+    dpi: u32,
+    lossless: bool,
+    quality: u32,
+    settings: Option<PdfDocumentSettings>,
+    password: Option<String>,
+) -> Result<PdfOptimizeResult, String> {
+    let validated_input = validate_path(&path)?;
+    let validated_output = validate_output_path(&output_path)?;
+    if validated_input == validated_output {
+        return Err("Output file must differ from the input PDF".to_string());
+    }
+
+    let _lock = CONVERSION_LOCK.lock().await;
+
+    eprintln!("[GUI] Optimizing PDF: {:?} -> {:?} (DPI: {}, Lossless: {}, Quality: {})",
+              validated_input, validated_output, dpi, lossless, quality);
+
+    let settings = settings.unwrap_or_default();
+    let mut pdf_options = settings.to_options(None)?;
+    pdf_options.image_encoding = settings.image_encoding(lossless, quality);
+
+    let window_for_pdf = window.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        pdf_conversion_lib::optimize_pdf_file(
+            &validated_input,
+            &validated_output,
+            password.as_deref(),
+            dpi,
+            pdf_options,
+            move |current, total| {
+                let percentage = ((current as f32 / total as f32) * 100.0) as u32;
+                let _ = window_for_pdf.emit("conversion-progress", serde_json::json!({
+                    "percentage": percentage,
+                    "message": format!("Optimizing page {}/{}...", current, total)
+                }));
+            },
+        )
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+    .map_err(|e| utils::describe_error("Failed to optimize PDF", &e))?;
+
+    let _ = window.emit("conversion-progress", serde_json::json!({
+        "percentage": 100,
+        "message": format!("Done! {:.1} MB → {:.1} MB", report.input_size as f64 / 1024.0 / 1024.0, report.output_size as f64 / 1024.0 / 1024.0)
+    }));
+
+    Ok(report.into())
+}
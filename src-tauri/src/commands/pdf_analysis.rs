@@ -2,7 +2,10 @@
 // Note: pdfium-render is used via pdf-conversion-lib for actual PDF rendering
 use std::path::Path;
 
+use pdf_conversion_lib::{load_lopdf_document, load_pdf_document, lopdf_page_sizes};
+
 use crate::models::{PdfAnalysisResult, PageInfo};
+use crate::utils::describe_error;
 
 const TARGET_PIXEL_WIDTH: f64 = 2000.0;
 const MIN_DPI: u32 = 72;
@@ -71,7 +74,8 @@ fn calculate_native_dpi(
 }
 
 /// Internal function to analyze PDF (used by both the command and conversion)
-pub async fn analyze_pdf_internal(path: &str) -> Result<PdfAnalysisResult, String> {
+/// `password` opens encrypted PDFs; without it they fail with a password error code
+pub async fn analyze_pdf_internal(path: &str, password: Option<String>) -> Result<PdfAnalysisResult, String> {
     let path = Path::new(&path);
 
     if !path.exists() {
@@ -88,23 +92,32 @@ pub async fn analyze_pdf_internal(path: &str) -> Result<PdfAnalysisResult, Strin
 
     // Load PDF using pdfium-render (blocking operation)
     let result = tokio::task::spawn_blocking(move || {
-        let pdfium = crate::utils::bind_pdfium(None)
-            .map_err(|e| format!("Failed to initialize Pdfium: {}", e))?;
-
-        let document = pdfium
-            .load_pdf_from_byte_vec(pdf_data, None)
-            .map_err(|e| format!("Failed to load PDF document: {}", e))?;
+        let page_sizes: Vec<(f32, f32)> = match crate::utils::bind_pdfium(None) {
+            Ok(pdfium) => {
+                let document = load_pdf_document(&pdfium, pdf_data, password.as_deref())
+                    .map_err(|e| describe_error("Failed to load PDF document", &e))?;
+                document.pages().iter().map(|page| (page.width().value, page.height().value)).collect()
+            }
+            // Without PDFium, image-only PDFs can still be converted by the built-in reader
+            Err(e) => {
+                eprintln!("[ANALYSIS] PDFium not available ({}), reading page sizes with the built-in reader", e);
+                let document = load_lopdf_document(&pdf_data, password.as_deref())
+                    .map_err(|e| describe_error("Failed to load PDF document", &e))?;
+                lopdf_page_sizes(&document)
+                    .map_err(|e| describe_error("Failed to read PDF pages", &e))?
+            }
+        };
 
-        let page_count = document.pages().len() as u32;
+        let page_count = page_sizes.len() as u32;
         let mut pages = Vec::new();
         let mut total_width_pt = 0.0;
         let mut total_height_pt = 0.0;
         let mut max_width_pt = 0.0;
 
         // Extract page dimensions and calculate per-page native DPI
-        for page in document.pages().iter() {
-            let width_pt = page.width().value as f64;
-            let height_pt = page.height().value as f64;
+        for (width_pt, height_pt) in page_sizes {
+            let width_pt = width_pt as f64;
+            let height_pt = height_pt as f64;
 
             if width_pt > max_width_pt {
                 max_width_pt = width_pt;
@@ -163,6 +176,6 @@ pub async fn analyze_pdf_internal(path: &str) -> Result<PdfAnalysisResult, Strin
 
 /// Analyze PDF structure (Tauri command)
 #[tauri::command]
-pub async fn analyze_pdf(path: String) -> Result<PdfAnalysisResult, String> {
-    analyze_pdf_internal(&path).await
+pub async fn analyze_pdf(path: String, password: Option<String>) -> Result<PdfAnalysisResult, String> {
+    analyze_pdf_internal(&path, password).await
 }
@@ -1,5 +1,6 @@
 use crate::models::ImageFormat;
 use crate::utils;
+use pdf_conversion_lib::{list_cbz_pages, sort_pages, ArchiveBudget, PageImageFormat, PageOrder};
 use std::collections::HashMap;
 use std::sync::Mutex;
 
@@ -16,15 +17,16 @@ pub async fn generate_preview(
     dpi: u32,
     format: ImageFormat,
     quality: u8,
+    password: Option<String>,
 ) -> Result<Vec<u8>, String> {
     let start = std::time::Instant::now();
     eprintln!("[PROFILE] generate_preview start: page={}, dpi={}, format={:?}", page, dpi, format);
 
     // Render the page directly to requested format (JPEG or PNG)
     let render_start = std::time::Instant::now();
-    let image_data = utils::render_pdf_page(&path, page, dpi, format, quality)
+    let image_data = utils::render_pdf_page(&path, page, dpi, format, quality, password)
         .await
-        .map_err(|e| format!("Failed to render page: {}", e))?;
+        .map_err(|e| utils::describe_error("Failed to render page", &e))?;
     eprintln!("[PROFILE] PDF render to {:?} took {}ms, size: {} bytes", format, render_start.elapsed().as_millis(), image_data.len());
     eprintln!("[PROFILE] Total generate_preview time: {}ms", start.elapsed().as_millis());
 
@@ -32,27 +34,32 @@ pub async fn generate_preview(
 }
 
 /// Generate a preview from CBZ file
+/// `archive_order` keeps the archive's entry order instead of natural filename order
 #[tauri::command]
 pub async fn generate_cbz_preview(
     path: String,
     page: u32,
     format: ImageFormat,
     quality: u8,
+    password: Option<String>,
+    archive_order: Option<bool>,
 ) -> Result<Vec<u8>, String> {
     let start = std::time::Instant::now();
     eprintln!("[PROFILE] generate_cbz_preview start: page={}, format={:?}", page, format);
 
-    use std::io::Read;
     use zip::ZipArchive;
 
     // Open file without reading everything into memory
     let open_start = std::time::Instant::now();
     
-    // Check cache first
+    // Check cache first (file lists are cached per path and page order)
+    let order = PageOrder::from_archive_order(archive_order.unwrap_or(false));
+    let cache_key = format!("{}|{:?}", path, order);
     let image_files = {
         let cache = CBZ_FILE_CACHE.lock().unwrap();
-        cache.get(&path).cloned()
+        cache.get(&cache_key).cloned()
     };
+    let is_cached = image_files.is_some();
 
     let image_files = if let Some(cached) = image_files {
         eprintln!("[PROFILE] Using cached file list ({} files)", cached.len());
@@ -66,29 +73,15 @@ pub async fn generate_cbz_preview(
         let mut archive = ZipArchive::new(file)
             .map_err(|e| format!("Failed to open CBZ archive: {}", e))?;
 
-        // Get list of image files (just names, no reading)
-        let mut files: Vec<String> = (0..archive.len())
-            .filter_map(|i| {
-                archive.by_index(i).ok().and_then(|f| {
-                    let name = f.name().to_string();
-                    if is_image_file(&name) {
-                        Some(name)
-                    } else {
-                        None
-                    }
-                })
-            })
+        // Get list of image files (by name; only entries with unknown extensions are sniffed)
+        let mut files: Vec<String> = list_cbz_pages(&mut archive, password.as_deref())
+            .into_iter()
+            .map(|(_, name)| name)
             .collect();
 
-        files.sort();
+        sort_pages(&mut files, order, |name| name);
         eprintln!("[PROFILE] Listing and sorting {} image files took {}ms", files.len(), list_start.elapsed().as_millis());
 
-        // Cache the list
-        {
-            let mut cache = CBZ_FILE_CACHE.lock().unwrap();
-            cache.insert(path.clone(), files.clone());
-        }
-
         files
     };
 
@@ -103,20 +96,24 @@ pub async fn generate_cbz_preview(
     let mut archive = ZipArchive::new(file)
         .map_err(|e| format!("Failed to open CBZ archive: {}", e))?;
     let file_name = &image_files[(page - 1) as usize];
-    let mut file = archive
-        .by_name(file_name)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
-
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)
-        .map_err(|e| format!("Failed to read file contents: {}", e))?;
+    let index = archive
+        .index_for_name(file_name)
+        .ok_or_else(|| format!("Failed to read file: {} not found", file_name))?;
+    let buffer = pdf_conversion_lib::read_cbz_entry(&mut archive, index, password.as_deref(), &mut ArchiveBudget::new())
+        .map_err(|e| utils::describe_error("Failed to read file", &e))?;
+
+    // Cache the list once a page reads: without the password, encrypted entries cannot be told apart from pages
+    if !is_cached {
+        let mut cache = CBZ_FILE_CACHE.lock().unwrap();
+        cache.insert(cache_key, image_files.clone());
+    }
     eprintln!("[PROFILE] Extract image took {}ms, size: {} bytes", extract_start.elapsed().as_millis(), buffer.len());
 
-    // Check if image is already in the requested format - if so, return it directly!
-    let image_already_correct_format = match format {
-        ImageFormat::Jpeg => file_name.to_lowercase().ends_with(".jpg") || file_name.to_lowercase().ends_with(".jpeg"),
-        ImageFormat::Png => file_name.to_lowercase().ends_with(".png"),
-    };
+    // Check if image is already in the requested format (from its content) - if so, return it directly!
+    let image_already_correct_format = matches!(
+        (&format, PageImageFormat::sniff(&buffer)),
+        (ImageFormat::Jpeg, Some(PageImageFormat::Jpeg)) | (ImageFormat::Png, Some(PageImageFormat::Png))
+    );
 
     if image_already_correct_format {
         eprintln!("[PROFILE] Image already in correct format, returning directly. Total time: {}ms", start.elapsed().as_millis());
@@ -132,12 +129,3 @@ pub async fn generate_cbz_preview(
 
     Ok(result)
 }
-
-fn is_image_file(filename: &str) -> bool {
-    let lower = filename.to_lowercase();
-    lower.ends_with(".jpg") ||
-    lower.ends_with(".jpeg") ||
-    lower.ends_with(".png") ||
-    lower.ends_with(".webp") ||
-    lower.ends_with(".gif")
-}
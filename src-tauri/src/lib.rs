@@ -27,11 +27,15 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             analyze_pdf,
             analyze_cbz,
+            verify_archive,
             generate_preview,
             generate_cbz_preview,
             convert_pdf_to_cbz,
             convert_pdf_to_cbz_direct,
             convert_cbz_to_pdf,
+            convert_cbz_to_pdf_direct,
+            merge_files,
+            optimize_pdf,
             save_last_pdf,
             open_file_with_default_app,
             get_file_size,
@@ -1,3 +1,4 @@
+use pdf_conversion_lib::{ComicBookInfo, ComicInfo, VerifyIssue, VerifyReport};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,12 +10,126 @@ pub struct CbzPageInfo {
     pub height: u32,
     pub format: String,
     pub size_kb: f64,
+    /// ComicInfo.xml page type (FrontCover, Story, Deleted, ...)
+    pub page_type: Option<String>,
+    /// ComicInfo.xml marks the page as a two-page spread
+    pub double_page: bool,
+}
+
+impl CbzPageInfo {
+    /// Deleted pages stay in the archive but are skipped by conversion
+    pub fn is_deleted(&self) -> bool {
+        self.page_type.as_deref().is_some_and(|t| t.eq_ignore_ascii_case("Deleted"))
+    }
+}
+
+/// Book metadata from the archive's ComicInfo.xml (or ComicBookInfo ZIP comment)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CbzComicMetadata {
+    pub title: Option<String>,
+    pub series: Option<String>,
+    pub number: Option<String>,
+    pub summary: Option<String>,
+    pub writer: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Option<String>,
+    pub year: Option<i32>,
+    pub language_iso: Option<String>,
+    /// Read right-to-left (ComicInfo Manga = YesAndRightToLeft)
+    pub manga: bool,
+}
+
+impl CbzComicMetadata {
+    pub fn from_comic_info(info: &ComicInfo) -> Self {
+        CbzComicMetadata {
+            title: info.title.clone(),
+            series: info.series.clone(),
+            number: info.number.clone(),
+            summary: info.summary.clone(),
+            writer: info.writer.clone(),
+            genre: info.genre.clone(),
+            tags: info.tags.clone(),
+            year: info.year,
+            language_iso: info.language_iso.clone(),
+            manga: info.manga.as_deref() == Some("YesAndRightToLeft"),
+        }
+    }
+
+    pub fn from_comic_book_info(info: &ComicBookInfo) -> Self {
+        CbzComicMetadata {
+            title: info.title.clone(),
+            series: info.series.clone(),
+            number: info.issue.clone(),
+            summary: info.comments.clone(),
+            writer: info.to_metadata().writers(),
+            genre: info.genre.clone(),
+            tags: (!info.tags.is_empty()).then(|| info.tags.join(", ")),
+            year: info.publication_year,
+            language_iso: None,
+            manga: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CbzAnalysisResult {
+    /// Pages converted to PDF (excludes pages marked Deleted)
     pub page_count: u32,
+    /// Pages in reading order; Deleted pages come last
     pub pages: Vec<CbzPageInfo>,
     pub cbz_size_mb: f64,
+    /// ComicInfo.xml metadata, if the archive has one
+    pub metadata: Option<CbzComicMetadata>,
+}
+
+/// A problem found by `verify_archive`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveVerifyIssue {
+    /// "error" or "warning"
+    pub severity: String,
+    /// Stable issue code (crc_mismatch, undecodable_image, duplicate_name, ...)
+    pub kind: String,
+    pub entry: Option<String>,
+    pub message: String,
+}
+
+impl From<&VerifyIssue> for ArchiveVerifyIssue {
+    fn from(issue: &VerifyIssue) -> Self {
+        ArchiveVerifyIssue {
+            severity: issue.severity.as_str().to_string(),
+            kind: issue.kind.code().to_string(),
+            entry: issue.entry.clone(),
+            message: issue.message.clone(),
+        }
+    }
+}
+
+/// Result of `verify_archive`: valid when no error was found (warnings allowed)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveVerifyReport {
+    pub format: String,
+    pub valid: bool,
+    pub entries: usize,
+    pub pages: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub issues: Vec<ArchiveVerifyIssue>,
+}
+
+impl From<VerifyReport> for ArchiveVerifyReport {
+    fn from(report: VerifyReport) -> Self {
+        ArchiveVerifyReport {
+            valid: report.is_valid(),
+            errors: report.errors(),
+            warnings: report.warnings(),
+            issues: report.issues.iter().map(ArchiveVerifyIssue::from).collect(),
+            format: report.format,
+            entries: report.entries,
+            pages: report.pages,
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use pdf_conversion_lib::{ComicBookInfo, ComicInfo, DocumentMetadata, ImageEncoding, OptimizeReport, DEFAULT_JPEG_QUALITY, PageOrder, PdfEncryption, PdfMetadataSettings, PdfOutputOptions, PdfPermissions, ReadingDirection, SpreadSplit};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -25,3 +26,197 @@ pub struct ConversionProgress {
     pub status: String,
     pub message: Option<String>,
 }
+
+/// PDF metadata and viewer options for CBZ → PDF conversion
+/// Every field is optional; missing values come from the archive's ComicInfo.xml
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfDocumentSettings {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// Creation date as YYYY-MM-DD
+    pub date: Option<String>,
+    /// Ask viewers to read right-to-left
+    #[serde(default)]
+    pub manga: bool,
+    /// Show two-page spreads with the cover alone
+    #[serde(default)]
+    pub spreads: bool,
+    /// Write PDF/A-2b for archival
+    #[serde(default)]
+    pub pdfa: bool,
+    /// Password-protect the PDF (AES-256)
+    pub protection: Option<PdfProtectionSettings>,
+    /// Keep pages in archive order instead of natural filename order
+    #[serde(default)]
+    pub archive_order: bool,
+    /// Downscale pages whose longest side is larger, in pixels (lossy mode only)
+    pub max_resolution: Option<u32>,
+}
+
+/// ComicInfo.xml and page options for PDF → CBZ conversion
+/// Title, Writer and Summary come from the PDF Info dictionary.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CbzDocumentSettings {
+    /// Mark the comic as read right-to-left
+    #[serde(default)]
+    pub manga: bool,
+    /// Language as an ISO code (e.g. "en", "fr", "ja")
+    pub language: Option<String>,
+    /// Do not add a ComicInfo.xml to the archive
+    #[serde(default)]
+    pub skip_comic_info: bool,
+    /// Also write ComicBookInfo JSON in the ZIP archive comment
+    #[serde(default)]
+    pub comic_book_info: bool,
+    /// Split double-page spreads (landscape pages) into two pages
+    #[serde(default)]
+    pub split_spreads: bool,
+    /// Keep each full spread before its two halves
+    #[serde(default)]
+    pub keep_spreads: bool,
+}
+
+impl CbzDocumentSettings {
+    /// Build the ComicInfo.xml entry for the converted pages
+    pub fn comic_info_xml(&self, pdf_metadata: &DocumentMetadata, images: &[(String, Vec<u8>)]) -> Option<String> {
+        if self.skip_comic_info {
+            return None;
+        }
+
+        let mut info = ComicInfo::from_metadata(pdf_metadata);
+        if self.manga {
+            info.manga = Some("YesAndRightToLeft".to_string());
+        }
+        if let Some(language) = self.language.clone().filter(|language| !language.trim().is_empty()) {
+            info.language_iso = Some(language);
+        }
+        info.set_pages(images);
+        Some(info.to_xml())
+    }
+
+    /// How to split spreads, or None to keep them whole
+    pub fn spread_split(&self, jpeg_quality: u8) -> Option<SpreadSplit> {
+        self.split_spreads.then(|| SpreadSplit {
+            direction: if self.manga { ReadingDirection::RightToLeft } else { ReadingDirection::LeftToRight },
+            keep_spread: self.keep_spreads,
+            jpeg_quality,
+            ..SpreadSplit::default()
+        })
+    }
+
+    /// Build the ComicBookInfo ZIP comment, if requested
+    pub fn comic_book_info_comment(&self, pdf_metadata: &DocumentMetadata) -> Option<String> {
+        if !self.comic_book_info {
+            return None;
+        }
+
+        let mut info = ComicBookInfo::from_metadata(pdf_metadata);
+        if let Some(language) = self.language.clone().filter(|language| !language.trim().is_empty()) {
+            info.language = Some(language);
+        }
+        Some(info.to_json())
+    }
+}
+
+/// Password protection for generated PDFs
+/// Without a user password anyone can open the file, but the permissions still apply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfProtectionSettings {
+    pub user_password: Option<String>,
+    /// Password granting full access (random if not set, so restrictions cannot be lifted)
+    pub owner_password: Option<String>,
+    #[serde(default = "allowed")]
+    pub allow_print: bool,
+    #[serde(default = "allowed")]
+    pub allow_copy: bool,
+    #[serde(default = "allowed")]
+    pub allow_modify: bool,
+    #[serde(default = "allowed")]
+    pub allow_annotate: bool,
+}
+
+fn allowed() -> bool {
+    true
+}
+
+impl PdfProtectionSettings {
+    pub fn to_encryption(&self) -> PdfEncryption {
+        PdfEncryption {
+            user_password: self.user_password.clone().unwrap_or_default(),
+            owner_password: self.owner_password.clone().unwrap_or_default(),
+            permissions: PdfPermissions {
+                print: self.allow_print,
+                print_high_quality: self.allow_print,
+                modify: self.allow_modify,
+                copy: self.allow_copy,
+                annotate: self.allow_annotate,
+                fill_forms: self.allow_annotate,
+                accessibility: true,
+                assemble: self.allow_modify,
+            },
+        }
+    }
+}
+
+impl PdfDocumentSettings {
+    pub fn page_order(&self) -> PageOrder {
+        PageOrder::from_archive_order(self.archive_order)
+    }
+
+    /// How pages are embedded: losslessly, or re-encoded to JPEG at `quality` (0 for the default)
+    pub fn image_encoding(&self, lossless: bool, quality: u32) -> ImageEncoding {
+        if lossless {
+            return ImageEncoding::Lossless;
+        }
+        let quality = if quality == 0 { DEFAULT_JPEG_QUALITY } else { quality.min(100) as u8 };
+        ImageEncoding::Jpeg { quality, max_resolution: self.max_resolution.filter(|&max| max > 0) }
+    }
+
+    /// Build PDF options, falling back to ComicInfo.xml or ComicBookInfo found in the archive
+    pub fn to_options(&self, archive_metadata: Option<DocumentMetadata>) -> Result<PdfOutputOptions, String> {
+        let settings = PdfMetadataSettings {
+            title: self.title.clone(),
+            author: self.author.clone(),
+            subject: self.subject.clone(),
+            keywords: self.keywords.clone(),
+            date: self.date.clone(),
+            manga: self.manga,
+            spreads: self.spreads,
+            pdfa: self.pdfa,
+        };
+        let mut options = PdfOutputOptions::from_settings(&settings, archive_metadata)
+            .map_err(|e| format!("{:#}", e))?;
+        options.encryption = self.protection.as_ref().map(PdfProtectionSettings::to_encryption);
+        Ok(options)
+    }
+}
+
+/// Result of a PDF optimisation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfOptimizeResult {
+    pub pages: usize,
+    pub extracted_pages: usize,
+    pub rendered_pages: usize,
+    pub input_size: u64,
+    pub output_size: u64,
+    pub saved_bytes: i64,
+}
+
+impl From<OptimizeReport> for PdfOptimizeResult {
+    fn from(report: OptimizeReport) -> Self {
+        PdfOptimizeResult {
+            pages: report.pages,
+            extracted_pages: report.extracted_pages,
+            rendered_pages: report.rendered_pages,
+            input_size: report.input_size,
+            output_size: report.output_size,
+            saved_bytes: report.saved_bytes(),
+        }
+    }
+}
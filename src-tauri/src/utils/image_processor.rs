@@ -1,6 +1,5 @@
 use anyhow::{Context, Result};
-use image::{DynamicImage, ImageReader, ImageEncoder, GenericImageView};
-use std::io::Cursor;
+use image::{DynamicImage, ImageEncoder, GenericImageView};
 
 use crate::models::ImageFormat;
 
@@ -11,11 +10,7 @@ pub fn convert_image(
     quality: u8,
 ) -> Result<Vec<u8>> {
     // Load input image
-    let img = ImageReader::new(Cursor::new(input))
-        .with_guessed_format()
-        .context("Failed to detect image format")?
-        .decode()
-        .context("Failed to decode image")?;
+    let img = pdf_conversion_lib::decode_image(input)?;
 
     encode_image(&img, format, quality)
 }
@@ -71,11 +66,7 @@ pub fn get_image_dimensions(input: &[u8]) -> Result<(u32, u32)> {
         Ok(size) => Ok((size.width as u32, size.height as u32)),
         Err(_) => {
             // Fallback to full decode if imagesize fails
-            let img = ImageReader::new(Cursor::new(input))
-                .with_guessed_format()
-                .context("Failed to detect image format")?
-                .decode()
-                .context("Failed to decode image")?;
+            let img = pdf_conversion_lib::decode_image(input)?;
             Ok(img.dimensions())
         }
     }
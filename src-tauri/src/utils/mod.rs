@@ -4,9 +4,9 @@ pub mod archive;
 pub mod pdf_creator;
 pub mod pdfium_loader_gui;
 pub mod crash_handler;
+pub mod password;
 
 // Note: The following modules are kept for potential future use but are not currently active:
-// - pdf_extractor: Direct PDF image extraction (currently using pdfium-render instead)
 // - ghostscript_renderer: Ghostscript-based PDF rendering (optional external dependency)
 // - pdf_content_analyzer: PDF structure analysis
 // - imagemagick_converter: ImageMagick-based conversion (optional external dependency)
@@ -16,6 +16,7 @@ pub use image_processor::*;
 pub use archive::*;
 pub use pdf_creator::*;
 pub use crash_handler::*;
+pub use password::*;
 
 // For GUI: use our enhanced version with logging
 pub use pdfium_loader_gui::bind_pdfium;
@@ -0,0 +1,32 @@
+use pdf_conversion_lib::{archive_password_error, limit_error, password_error, unsupported_page_error, ArchivePasswordError, PdfPasswordError};
+
+/// Describe a conversion error for the frontend
+/// Password errors (PDF or CBZ) are reported as their stable code so the GUI can ask for a password and retry.
+/// Safety limit errors are reported by their own message, whatever context they were wrapped in.
+/// Pages the built-in reader refuses (PDFium missing) are reported with the reason.
+pub fn describe_error(context: &str, error: &anyhow::Error) -> String {
+    if let Some(kind) = password_error(error) {
+        return kind.code().to_string();
+    }
+    if let Some(kind) = archive_password_error(error) {
+        return kind.code().to_string();
+    }
+    if let Some(limit) = limit_error(error) {
+        return format!("{}: {}", context, limit);
+    }
+    if let Some(page) = unsupported_page_error(error) {
+        return format!("{}: {} ({})", context, page, error.root_cause());
+    }
+    format!("{}: {}", context, error)
+}
+
+/// Check if a command error is a password error code (see `describe_error`)
+pub fn is_password_error_code(message: &str) -> bool {
+    [
+        PdfPasswordError::Required.code(),
+        PdfPasswordError::Incorrect.code(),
+        ArchivePasswordError::Required.code(),
+        ArchivePasswordError::Incorrect.code(),
+    ]
+    .contains(&message)
+}
@@ -1,4 +1,5 @@
 use anyhow::{Context, Result};
+use pdf_conversion_lib::load_pdf_document;
 use pdfium_render::prelude::*;
 use crate::models::ImageFormat;
 
@@ -9,12 +10,13 @@ pub async fn render_pdf_page(
     dpi: u32,
     format: ImageFormat,
     quality: u8,
+    password: Option<String>,
 ) -> Result<Vec<u8>> {
     let pdf_data = tokio::fs::read(pdf_path)
         .await
         .context("Failed to read PDF file")?;
 
-    render_page_from_bytes(&pdf_data, page_num, dpi, format, quality).await
+    render_page_from_bytes(&pdf_data, page_num, dpi, format, quality, password).await
 }
 
 /// Render a PDF page from bytes - async wrapper
@@ -24,12 +26,13 @@ pub async fn render_page_from_bytes(
     dpi: u32,
     format: ImageFormat,
     quality: u8,
+    password: Option<String>,
 ) -> Result<Vec<u8>> {
     // pdfium-render is synchronous, so we use spawn_blocking
     let pdf_data = pdf_data.to_vec();
 
     tokio::task::spawn_blocking(move || {
-        render_pdf_page_sync(&pdf_data, page_num, dpi, format, quality)
+        render_pdf_page_sync(&pdf_data, page_num, dpi, format, quality, password.as_deref())
     })
     .await
     .context("Task join error")?
@@ -43,12 +46,11 @@ pub fn render_pdf_page_sync(
     dpi: u32,
     format: ImageFormat,
     _quality: u8,
+    password: Option<&str>,
 ) -> Result<Vec<u8>> {
     let pdfium = crate::utils::bind_pdfium(None)?;
 
-    let document = pdfium
-        .load_pdf_from_byte_vec(pdf_data.to_vec(), None)
-        .context("Failed to load PDF document")?;
+    let document = load_pdf_document(&pdfium, pdf_data.to_vec(), password)?;
 
     // Get page (page_num is 1-indexed, but pdfium uses 0-indexed)
     let page = document
@@ -0,0 +1,98 @@
+import { useState, useCallback, useRef, useEffect } from 'react';
+import { useTranslation } from '@/lib/useTranslation';
+
+interface PasswordDialogProps {
+  message: string;
+  onSubmit: (password: string) => void;
+  onCancel: () => void;
+}
+
+/**
+ * Modal asking for the password of an encrypted PDF or archive
+ */
+export default function PasswordDialog({ message, onSubmit, onCancel }: PasswordDialogProps) {
+  const { t } = useTranslation();
+  const [password, setPassword] = useState('');
+  const inputRef = useRef<HTMLInputElement>(null);
+
+  useEffect(() => {
+    inputRef.current?.focus();
+  }, []);
+
+  return (
+    <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/50">
+      <form
+        className="w-full max-w-md bg-white dark:bg-gray-800 rounded-lg shadow-lg p-4"
+        onSubmit={(e) => {
+          e.preventDefault();
+          onSubmit(password);
+        }}
+        onKeyDown={(e) => {
+          if (e.key === 'Escape') onCancel();
+        }}
+      >
+        <label className="block text-sm text-gray-900 dark:text-white mb-2">
+          {message}
+          <input
+            ref={inputRef}
+            type="password"
+            autoComplete="off"
+            value={password}
+            onChange={(e) => setPassword(e.target.value)}
+            className="w-full mt-2 px-2 py-1 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 text-gray-900 dark:text-white text-sm"
+          />
+        </label>
+        <div className="flex justify-end gap-2 mt-3">
+          <button
+            type="button"
+            onClick={onCancel}
+            className="px-4 py-2 bg-gray-500 text-white rounded-lg hover:bg-gray-600 transition-colors"
+          >
+            {t('cancel')}
+          </button>
+          <button
+            type="submit"
+            className="px-4 py-2 bg-indigo-600 text-white rounded-lg hover:bg-indigo-700 transition-colors"
+          >
+            {t('unlock')}
+          </button>
+        </div>
+      </form>
+    </div>
+  );
+}
+
+/**
+ * Ask for passwords from async code: `askPassword(name, incorrect)` shows the dialog and
+ * resolves with the entered password, or rejects with "Password required" when cancelled.
+ * Render `passwordDialog` somewhere in the page.
+ */
+export function usePasswordPrompt() {
+  const { t } = useTranslation();
+  const [request, setRequest] = useState<{
+    message: string;
+    resolve: (password: string) => void;
+    reject: (error: Error) => void;
+  } | null>(null);
+
+  const askPassword = useCallback((name: string, incorrect: boolean) => {
+    const message = t(incorrect ? 'passwordIncorrect' : 'passwordPrompt').replace('{name}', name);
+    return new Promise<string>((resolve, reject) => setRequest({ message, resolve, reject }));
+  }, [t]);
+
+  const passwordDialog = request && (
+    <PasswordDialog
+      message={request.message}
+      onSubmit={(password) => {
+        setRequest(null);
+        request.resolve(password);
+      }}
+      onCancel={() => {
+        setRequest(null);
+        request.reject(new Error(t('passwordRequired')));
+      }}
+    />
+  );
+
+  return { askPassword, passwordDialog };
+}
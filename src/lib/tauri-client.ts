@@ -29,12 +29,45 @@ export interface CbzPageInfo {
   height: number;
   format: string;
   sizeKb: number;
+  pageType?: string; // ComicInfo.xml page type (FrontCover, Deleted, ...)
+  doublePage: boolean;
+}
+
+export interface CbzComicMetadata {
+  title?: string;
+  series?: string;
+  number?: string;
+  summary?: string;
+  writer?: string;
+  genre?: string;
+  tags?: string;
+  year?: number;
+  languageIso?: string;
+  manga: boolean;
 }
 
 export interface CbzAnalysisResult {
-  pageCount: number;
-  pages: CbzPageInfo[];
+  pageCount: number; // Excludes pages marked Deleted
+  pages: CbzPageInfo[]; // Reading order, Deleted pages last
   cbzSizeMb: number;
+  metadata?: CbzComicMetadata; // From ComicInfo.xml or the ComicBookInfo ZIP comment
+}
+
+export interface ArchiveVerifyIssue {
+  severity: 'error' | 'warning';
+  kind: string; // crc_mismatch, read_error, undecodable_image, implausible_dimensions, duplicate_name, ambiguous_name, missing_pages, invalid_comic_info
+  entry?: string;
+  message: string;
+}
+
+export interface ArchiveVerifyReport {
+  format: 'cbz' | 'cbr';
+  valid: boolean; // No errors (warnings allowed)
+  entries: number;
+  pages: number;
+  errors: number;
+  warnings: number;
+  issues: ArchiveVerifyIssue[];
 }
 
 export interface ConversionProgress {
@@ -47,6 +80,58 @@ export interface ConversionProgress {
 
 export type ImageFormat = 'jpeg' | 'png';
 
+/**
+ * PDF metadata and viewer options for CBZ → PDF conversion.
+ * Missing values are read from the archive's ComicInfo.xml.
+ */
+export interface PdfDocumentSettings {
+  title?: string;
+  author?: string;
+  subject?: string;
+  keywords?: string;
+  date?: string; // YYYY-MM-DD
+  manga?: boolean; // Right-to-left reading direction
+  spreads?: boolean; // Two-page layout with the cover alone
+  pdfa?: boolean; // PDF/A-2b archival output
+  protection?: PdfProtectionSettings; // AES-256 password protection
+  archiveOrder?: boolean; // Keep archive entry order instead of natural filename order
+  maxResolution?: number; // Downscale pages whose longest side is larger, in pixels (lossy mode only)
+}
+
+/**
+ * ComicInfo.xml options for PDF → CBZ conversion.
+ * Title, Writer and Summary are read from the PDF metadata.
+ */
+export interface CbzDocumentSettings {
+  manga?: boolean; // Right-to-left reading direction
+  language?: string; // ISO code, e.g. 'en', 'fr', 'ja'
+  skipComicInfo?: boolean; // Do not add ComicInfo.xml
+  comicBookInfo?: boolean; // Also write ComicBookInfo JSON in the ZIP comment
+  splitSpreads?: boolean; // Split double-page spreads into two pages (right page first with manga)
+  keepSpreads?: boolean; // Keep each full spread before its halves
+}
+
+/**
+ * Result of a PDF optimisation (sizes in bytes)
+ */
+export interface PdfOptimizeResult {
+  pages: number;
+  extractedPages: number; // Rebuilt from their embedded image
+  renderedPages: number; // Rendered because no single image covers them
+  inputSize: number;
+  outputSize: number;
+  savedBytes: number; // Negative when the output is larger
+}
+
+export interface PdfProtectionSettings {
+  userPassword?: string; // Required to open the PDF
+  ownerPassword?: string; // Full access (random if not set)
+  allowPrint?: boolean; // Permissions default to allowed
+  allowCopy?: boolean;
+  allowModify?: boolean;
+  allowAnnotate?: boolean;
+}
+
 // ============================================================================
 // File Operations
 // ============================================================================
@@ -179,11 +264,56 @@ export async function openFile(path: string): Promise<void> {
 // PDF Operations
 // ============================================================================
 
+/**
+ * Error codes returned by commands when a PDF or CBZ is encrypted
+ * (the password is missing or wrong). Ask the user for a password and retry.
+ */
+export const PDF_PASSWORD_REQUIRED = 'PDF_PASSWORD_REQUIRED';
+export const PDF_PASSWORD_INCORRECT = 'PDF_PASSWORD_INCORRECT';
+export const ARCHIVE_PASSWORD_REQUIRED = 'ARCHIVE_PASSWORD_REQUIRED';
+export const ARCHIVE_PASSWORD_INCORRECT = 'ARCHIVE_PASSWORD_INCORRECT';
+
+/**
+ * Check if a command error means the PDF or CBZ needs a (different) password
+ */
+export function isPasswordError(error: unknown): boolean {
+  const message = error instanceof Error ? error.message : String(error);
+  return [
+    PDF_PASSWORD_REQUIRED,
+    PDF_PASSWORD_INCORRECT,
+    ARCHIVE_PASSWORD_REQUIRED,
+    ARCHIVE_PASSWORD_INCORRECT,
+  ].includes(message);
+}
+
+/**
+ * Run a command on a possibly encrypted input, asking for the password and retrying on password errors
+ * `password` (e.g. one entered for a previous file) is tried first. `askPassword` is told whether a
+ * password it returned was rejected, and rejects itself when the user gives up.
+ * Returns the result and the password that worked, so it can be reused for the next command.
+ */
+export async function withPasswordRetry<T>(
+  run: (password?: string) => Promise<T>,
+  askPassword: (incorrect: boolean) => Promise<string>,
+  password?: string
+): Promise<{ result: T; password?: string }> {
+  let entered = false;
+  for (;;) {
+    try {
+      return { result: await run(password), password };
+    } catch (err) {
+      if (!isPasswordError(err)) throw err;
+      password = await askPassword(entered);
+      entered = true;
+    }
+  }
+}
+
 /**
  * Analyze a PDF file
  */
-export async function analyzePdf(path: string): Promise<PdfAnalysisResult> {
-  return invoke<PdfAnalysisResult>('analyze_pdf', { path });
+export async function analyzePdf(path: string, password?: string): Promise<PdfAnalysisResult> {
+  return invoke<PdfAnalysisResult>('analyze_pdf', { path, password });
 }
 
 /**
@@ -194,7 +324,8 @@ export async function generatePreview(
   page: number,
   dpi: number,
   format: ImageFormat,
-  quality: number
+  quality: number,
+  password?: string
 ): Promise<Uint8Array> {
   const result = await invoke<number[]>('generate_preview', {
     path,
@@ -202,6 +333,7 @@ export async function generatePreview(
     dpi,
     format,
     quality,
+    password,
   });
   return new Uint8Array(result);
 }
@@ -216,7 +348,10 @@ export async function convertPdfToCbz(
   dpi: number,
   quality: number,
   onProgress?: (progress: ConversionProgress) => void,
-  lossless?: boolean  // New parameter for lossless mode
+  lossless?: boolean,  // New parameter for lossless mode
+  password?: string,  // Password for encrypted PDFs
+  archivePassword?: string,  // Encrypt the CBZ with AES-256
+  settings?: CbzDocumentSettings  // ComicInfo.xml options
 ): Promise<Uint8Array> {
   const callId = ++convertCallId;
   const startTime = new Date().toLocaleTimeString();
@@ -246,6 +381,9 @@ export async function convertPdfToCbz(
       dpi,
       quality,
       lossless: lossless ?? false,  // Default to false
+      password,
+      archivePassword,
+      settings,
     });
 
     const endTime = new Date().toLocaleTimeString();
@@ -276,7 +414,10 @@ export async function convertPdfToCbzDirect(
   dpi: number,
   quality: number,
   onProgress?: (progress: ConversionProgress) => void,
-  lossless?: boolean
+  lossless?: boolean,
+  password?: string,
+  archivePassword?: string,
+  settings?: CbzDocumentSettings
 ): Promise<number> {
   // Prevent re-entry
   if (directConvertInProgress) {
@@ -311,6 +452,9 @@ export async function convertPdfToCbzDirect(
       dpi,
       quality,
       lossless: lossless ?? false,
+      password,
+      archivePassword,
+      settings,
     });
 
     const endTime = new Date().toLocaleTimeString();
@@ -367,8 +511,15 @@ export async function optimizePdf(
 /**
  * Analyze a CBZ file
  */
-export async function analyzeCbz(path: string): Promise<CbzAnalysisResult> {
-  return invoke<CbzAnalysisResult>('analyze_cbz', { path });
+export async function analyzeCbz(path: string, password?: string, archiveOrder?: boolean): Promise<CbzAnalysisResult> {
+  return invoke<CbzAnalysisResult>('analyze_cbz', { path, password, archiveOrder });
+}
+
+/**
+ * Verify a CBZ/CBR file: entry CRCs, image decoding, duplicate names and missing page numbers
+ */
+export async function verifyArchive(path: string, password?: string): Promise<ArchiveVerifyReport> {
+  return invoke<ArchiveVerifyReport>('verify_archive', { path, password });
 }
 
 /**
@@ -378,13 +529,17 @@ export async function generateCbzPreview(
   path: string,
   page: number,
   format: ImageFormat,
-  quality: number
+  quality: number,
+  password?: string,
+  archiveOrder?: boolean  // Keep archive entry order instead of natural filename order
 ): Promise<Uint8Array> {
   const result = await invoke<number[]>('generate_cbz_preview', {
     path,
     page,
     format,
     quality,
+    password,
+    archiveOrder,
   });
   return new Uint8Array(result);
 }
@@ -398,7 +553,9 @@ export async function convertCbzToPdf(
   path: string,
   onProgress?: (progress: ConversionProgress) => void,
   lossless?: boolean,
-  quality?: number
+  quality?: number,
+  settings?: PdfDocumentSettings,
+  password?: string  // Password for encrypted CBZ/CBR archives
 ): Promise<Uint8Array> {
   // Setup progress listener
   let unlisten: (() => void) | undefined;
@@ -419,6 +576,8 @@ export async function convertCbzToPdf(
       path,
       lossless: lossless ?? true,  // Default to lossless
       quality: quality ?? 90,
+      settings: settings ?? null,
+      password,
     });
     console.log('[convertCbzToPdf] Invoke returned, length:', result.length);
 
@@ -436,6 +595,129 @@ export async function convertCbzToPdf(
   }
 }
 
+/**
+ * Convert CBZ/CBR to PDF with DIRECT disk write
+ * Pages are streamed to the output file one at a time, so memory use does not
+ * grow with book size. Returns the file size instead of the file contents.
+ */
+export async function convertCbzToPdfDirect(
+  path: string,
+  outputPath: string,
+  onProgress?: (progress: ConversionProgress) => void,
+  lossless?: boolean,
+  quality?: number,
+  settings?: PdfDocumentSettings,
+  password?: string  // Password for encrypted CBZ/CBR archives
+): Promise<number> {
+  let unlisten: (() => void) | undefined;
+
+  if (onProgress) {
+    unlisten = await listen<ConversionProgress>('conversion-progress', (event) => {
+      if (event.payload.message) {
+        console.log(`[CONVERSION] ${event.payload.message}`);
+      }
+      onProgress(event.payload);
+    });
+  }
+
+  try {
+    return await invoke<number>('convert_cbz_to_pdf_direct', {
+      path,
+      outputPath,
+      lossless: lossless ?? true,
+      quality: quality ?? 90,
+      settings: settings ?? null,
+      password,
+    });
+  } finally {
+    if (unlisten) {
+      unlisten();
+    }
+  }
+}
+
+/**
+ * Merge PDFs and CBZ/CBR archives into one CBZ or PDF (chosen by the output extension), written directly to disk
+ * Each input becomes a chapter (folder in CBZ, bookmark in PDF); returns the file size
+ */
+export async function mergeFiles(
+  paths: string[],
+  outputPath: string,
+  onProgress?: (progress: ConversionProgress) => void,
+  dpi?: number,
+  quality?: number,
+  lossless?: boolean,
+  separator?: string,  // "blank" or the path of an image inserted before each input after the first
+  password?: string,  // Password for encrypted inputs
+  archivePassword?: string,  // Encrypt CBZ output with AES-256
+  settings?: PdfDocumentSettings
+): Promise<number> {
+  let unlisten: (() => void) | undefined;
+
+  if (onProgress) {
+    unlisten = await listen<ConversionProgress>('conversion-progress', (event) => {
+      onProgress(event.payload);
+    });
+  }
+
+  try {
+    return await invoke<number>('merge_files', {
+      paths,
+      outputPath,
+      dpi: dpi ?? 200,
+      quality: quality ?? 85,
+      lossless: lossless ?? false,
+      separator: separator ?? null,
+      password,
+      archivePassword,
+      settings: settings ?? null,
+    });
+  } finally {
+    if (unlisten) {
+      unlisten();
+    }
+  }
+}
+
+/**
+ * Shrink a PDF by downsampling and recompressing its page images to a target DPI
+ * Page sizes are kept; metadata is copied from the source PDF unless set in settings
+ */
+export async function optimizePdf(
+  path: string,
+  outputPath: string,
+  onProgress?: (progress: ConversionProgress) => void,
+  dpi?: number,
+  lossless?: boolean,
+  quality?: number,
+  settings?: PdfDocumentSettings,
+  password?: string  // Password for encrypted PDFs
+): Promise<PdfOptimizeResult> {
+  let unlisten: (() => void) | undefined;
+
+  if (onProgress) {
+    unlisten = await listen<ConversionProgress>('conversion-progress', (event) => {
+      onProgress(event.payload);
+    });
+  }
+
+  try {
+    return await invoke<PdfOptimizeResult>('optimize_pdf', {
+      path,
+      outputPath,
+      dpi: dpi ?? 150,
+      lossless: lossless ?? false,
+      quality: quality ?? 80,
+      settings: settings ?? null,
+      password,
+    });
+  } finally {
+    if (unlisten) {
+      unlisten();
+    }
+  }
+}
+
 // ============================================================================
 // Utility Functions
 // ============================================================================
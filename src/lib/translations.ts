@@ -86,6 +86,10 @@ export const translations = {
     serverConnectionError: 'Server connection error',
     noResponseStream: 'No response stream',
     conversionError: 'Conversion error',
+    passwordPrompt: '"{name}" is password-protected. Enter its password:',
+    passwordIncorrect: 'Incorrect password for "{name}". Try again:',
+    passwordRequired: 'Password required',
+    unlock: 'Open',
 
     // BatchSettings
     conversionSettings: 'Conversion settings',
@@ -207,6 +211,10 @@ export const translations = {
     serverConnectionError: 'Erreur de connexion au serveur',
     noResponseStream: 'Pas de flux de réponse',
     conversionError: 'Erreur de conversion',
+    passwordPrompt: '« {name} » est protégé par un mot de passe. Saisissez-le :',
+    passwordIncorrect: 'Mot de passe incorrect pour « {name} ». Réessayez :',
+    passwordRequired: 'Mot de passe requis',
+    unlock: 'Ouvrir',
 
     // BatchSettings
     conversionSettings: 'Paramètres de conversion',
@@ -327,6 +335,10 @@ export const translations = {
     serverConnectionError: 'Error de conexión al servidor',
     noResponseStream: 'Sin flujo de respuesta',
     conversionError: 'Error de conversión',
+    passwordPrompt: '"{name}" está protegido con contraseña. Introduzca la contraseña:',
+    passwordIncorrect: 'Contraseña incorrecta para "{name}". Inténtelo de nuevo:',
+    passwordRequired: 'Contraseña requerida',
+    unlock: 'Abrir',
 
     // BatchSettings
     conversionSettings: 'Configuración de conversión',
@@ -444,6 +456,10 @@ export const translations = {
     serverConnectionError: '服务器连接错误',
     noResponseStream: '无响应流',
     conversionError: '转换错误',
+    passwordPrompt: '“{name}”受密码保护。请输入密码：',
+    passwordIncorrect: '“{name}”的密码不正确。请重试：',
+    passwordRequired: '需要密码',
+    unlock: '打开',
 
     // BatchSettings
     conversionSettings: '转换设置',
@@ -1,6 +1,7 @@
 import { useState, useCallback } from 'react';
 import { useTranslation } from '@/lib/useTranslation';
 import LanguageSelector from '@/components/LanguageSelector';
+import { usePasswordPrompt } from '@/components/PasswordDialog';
 import TauriBatchUploader from '@/components/TauriBatchUploader';
 import BatchSettings from '@/components/BatchSettings';
 import BatchResults from '@/components/BatchResults';
@@ -24,6 +25,7 @@ interface BatchProps {
 export default function Batch({ onNavigateToHome }: BatchProps) {
   const { lang, setLang, t } = useTranslation();
   const [mode, setMode] = useState<ConversionMode>('pdf-to-cbz');
+  const { askPassword, passwordDialog } = usePasswordPrompt();
   const [files, setFiles] = useState<BatchFileState[]>([]);
   const [isConverting, setIsConverting] = useState(false);
   const [settings, setSettings] = useState<BatchSettings>({
@@ -81,6 +83,9 @@ export default function Batch({ onNavigateToHome }: BatchProps) {
     setIsConverting(true);
     setGlobalProgress({ completedFiles: 0, totalFiles: files.length, currentFileProgress: 0 });
 
+    // Password of the last encrypted file, tried first on the next one
+    let lastPassword: string | undefined;
+
     for (let i = 0; i < files.length; i++) {
       const file = files[i];
       const filePath = file.filePath;
@@ -89,6 +94,8 @@ export default function Batch({ onNavigateToHome }: BatchProps) {
         updateFileStatus(file.id, 'error', 'File path not available');
         continue;
       }
+      const fileName = filePath.split(/[/\\]/).pop() || filePath;
+      const askFilePassword = (incorrect: boolean) => askPassword(fileName, incorrect);
 
       try {
         updateFileStatus(file.id, 'analyzing', undefined, undefined, 0);
@@ -100,42 +107,55 @@ export default function Batch({ onNavigateToHome }: BatchProps) {
           let fileDpi = settings.dpi as number;
 
           // If auto DPI (0), analyze the file to get native DPI
+          // Encrypted PDFs ask for their password here; it is reused for the conversion below
           if (fileDpi === 0) {
             try {
-              const analysis = await TauriClient.analyzePdf(filePath);
-              fileDpi = analysis.nativeDpi;
+              const analysis = await TauriClient.withPasswordRetry(
+                (password) => TauriClient.analyzePdf(filePath, password),
+                askFilePassword,
+                lastPassword
+              );
+              fileDpi = analysis.result.nativeDpi;
+              lastPassword = analysis.password;
             } catch (err) {
-              // If analysis fails, use default 150 DPI
+              // A cancelled password prompt fails the file; other analysis errors use the default 150 DPI
+              if (err instanceof Error && err.message === t('passwordRequired')) throw err;
               fileDpi = 150;
             }
           }
 
           // Convert the file with progress tracking
-          imageData = await TauriClient.convertPdfToCbz(
-            filePath,
-            fileDpi,
-            settings.format,
-            settings.quality,
-            (progress) => {
-              // Update the current file's progress
-              updateFileStatus(
-                file.id,
-                'converting',
-                undefined,
-                undefined,
-                progress.percentage,
-                progress.currentPage,
-                progress.totalPages
-              );
-              // Update global progress
-              setGlobalProgress({
-                completedFiles: i,
-                totalFiles: files.length,
-                currentFileProgress: progress.percentage,
-              });
-            },
-            settings.directExtract
+          const conversion = await TauriClient.withPasswordRetry(
+            (password) => TauriClient.convertPdfToCbz(
+              filePath,
+              fileDpi,
+              settings.quality,
+              (progress) => {
+                // Update the current file's progress
+                updateFileStatus(
+                  file.id,
+                  'converting',
+                  undefined,
+                  undefined,
+                  progress.percentage,
+                  progress.currentPage,
+                  progress.totalPages
+                );
+                // Update global progress
+                setGlobalProgress({
+                  completedFiles: i,
+                  totalFiles: files.length,
+                  currentFileProgress: progress.percentage,
+                });
+              },
+              settings.format === 'png',  // PNG output is the lossless mode
+              password
+            ),
+            askFilePassword,
+            lastPassword
           );
+          imageData = conversion.result;
+          lastPassword = conversion.password;
         } else {
           // TODO: Implement CBZ to PDF conversion
           throw new Error('CBZ to PDF conversion not yet implemented');
@@ -166,7 +186,7 @@ export default function Batch({ onNavigateToHome }: BatchProps) {
     }
 
     setIsConverting(false);
-  }, [files, mode, settings, updateFileStatus]);
+  }, [files, mode, settings, updateFileStatus, askPassword, t]);
 
   // Cancel conversion
   const handleCancel = useCallback(() => {
@@ -401,6 +421,8 @@ export default function Batch({ onNavigateToHome }: BatchProps) {
           {t('footer')} • {t('madeWith')} ❤️
         </p>
       </footer>
+
+      {passwordDialog}
     </div>
   );
 }
@@ -1,6 +1,7 @@
 import { useState, useCallback, useEffect, useRef } from 'react';
 import { useTranslation } from '@/lib/useTranslation';
 import LanguageSelector from '@/components/LanguageSelector';
+import { usePasswordPrompt } from '@/components/PasswordDialog';
 import * as TauriClient from '@/lib/tauri-client';
 import { listen } from '@tauri-apps/api/event';
 
@@ -22,6 +23,7 @@ interface HomeProps {}
 export default function Home({}: HomeProps) {
   const { lang, setLang, t } = useTranslation();
   const [mode, setMode] = useState<ConversionMode>('pdf-to-cbz');
+  const { askPassword, passwordDialog } = usePasswordPrompt();
 
   // Build timestamp - set once on mount (removed verbose logging for production)
   const [buildTime] = useState(() => {
@@ -45,6 +47,8 @@ export default function Home({}: HomeProps) {
   const [dpi, setDpi] = useState<string>('200'); // 200 DPI - Default recommended
   const [quality, setQuality] = useState(85);  // Quality 85 - Balanced
   const [lossless, setLossless] = useState(false);  // Lossless mode disabled by default
+  const [manga, setManga] = useState(false);  // ComicInfo.xml Manga flag (right-to-left)
+  const [language, setLanguage] = useState('');  // ComicInfo.xml LanguageISO
 
   // Status
   const [error, setError] = useState<string | null>(null);
@@ -163,6 +167,9 @@ export default function Home({}: HomeProps) {
       // Reset all files to pending status
       setBatchFiles(filesToProcess.map(f => ({ ...f, status: 'pending', progress: 0, error: undefined, savePath: undefined })));
 
+      // Password of the last encrypted file, tried first on the next one
+      let lastPassword: string | undefined;
+
       // Process each file
       for (let i = 0; i < filesToProcess.length; i++) {
       // Check if conversion was cancelled
@@ -211,39 +218,46 @@ export default function Home({}: HomeProps) {
         console.log(`[FRONTEND] Calling conversion function...`);
         console.log(`[TIMING] Starting conversion at ${new Date().toLocaleTimeString()}`);
 
-        let convertedSize: number;
-
-        if (mode === 'pdf-to-cbz') {
-          // Use DIRECT disk write (no IPC bottleneck for large files!)
-          convertedSize = await TauriClient.convertPdfToCbzDirect(
-            file.path,
-            savePath,  // Write directly to disk
-            effectiveDpi,
-            quality,
-            (progress) => {
-              console.log(`[FRONTEND] Progress: ${progress.percentage}%`);
-              setBatchFiles(prev => prev.map((f) =>
-                f.path === file.path ? { ...f, progress: progress.percentage } : f
-              ));
-            },
-            lossless
-          );
-        } else {
-          // CBZ to PDF still uses old method (typically smaller files)
-          const outputData = await TauriClient.convertCbzToPdf(
-            file.path,
-            (progress) => {
-              console.log(`[FRONTEND] Progress: ${progress.percentage}%`);
-              setBatchFiles(prev => prev.map((f) =>
-                f.path === file.path ? { ...f, progress: progress.percentage } : f
-              ));
-            },
-            lossless,
-            quality
-          );
-          await TauriClient.saveDataToFile(outputData, savePath);
-          convertedSize = outputData.length;
-        }
+        // Encrypted inputs fail with a password error code: ask for the password and retry.
+        // The last password that worked is tried first, so a series of protected files is asked for once.
+        const { result: convertedSize, password } = await TauriClient.withPasswordRetry(
+          (password) => mode === 'pdf-to-cbz'
+            // Use DIRECT disk write (no IPC bottleneck for large files!)
+            ? TauriClient.convertPdfToCbzDirect(
+                file.path,
+                savePath,  // Write directly to disk
+                effectiveDpi,
+                quality,
+                (progress) => {
+                  console.log(`[FRONTEND] Progress: ${progress.percentage}%`);
+                  setBatchFiles(prev => prev.map((f) =>
+                    f.path === file.path ? { ...f, progress: progress.percentage } : f
+                  ));
+                },
+                lossless,
+                password,
+                undefined,
+                { manga, language: language.trim() || undefined }
+              )
+            // Stream PDF pages directly to disk (memory independent of book size)
+            : TauriClient.convertCbzToPdfDirect(
+                file.path,
+                savePath,
+                (progress) => {
+                  console.log(`[FRONTEND] Progress: ${progress.percentage}%`);
+                  setBatchFiles(prev => prev.map((f) =>
+                    f.path === file.path ? { ...f, progress: progress.percentage } : f
+                  ));
+                },
+                lossless,
+                quality,
+                undefined,
+                password
+              ),
+          (incorrect) => askPassword(file.name, incorrect),
+          lastPassword
+        );
+        lastPassword = password;
 
         console.log(`[TIMING] Conversion completed at ${new Date().toLocaleTimeString()}`);
         console.log(`[FRONTEND] Output size: ${convertedSize} bytes (${(convertedSize / 1024 / 1024).toFixed(1)} MB)`);
@@ -300,7 +314,7 @@ export default function Home({}: HomeProps) {
       console.log(`[CONV #${thisConversionId}] CONVERSION FULLY COMPLETE`);
       console.log(`[CONV #${thisConversionId}] ${'='.repeat(60)}\n`);
     }
-  }, [batchFiles, mode, effectiveDpi, quality, lossless]);
+  }, [batchFiles, mode, effectiveDpi, quality, lossless, manga, language, askPassword]);
 
   // Cancel batch conversion
   const handleCancelBatch = useCallback(() => {
@@ -597,6 +611,32 @@ export default function Home({}: HomeProps) {
                     {lossless ? '⚠️ Slower but preserves original quality' : '⚡ Optimized with multi-threading'}
                   </p>
                 </div>
+                {mode === 'pdf-to-cbz' && (
+                  <div>
+                    <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
+                      ComicInfo
+                    </label>
+                    <div className="flex items-center gap-2">
+                      <input
+                        type="checkbox"
+                        checked={manga}
+                        onChange={(e) => setManga(e.target.checked)}
+                        className="rounded border-gray-300 dark:border-gray-600"
+                      />
+                      <span className="text-sm text-gray-700 dark:text-gray-300">
+                        Manga (right-to-left)
+                      </span>
+                    </div>
+                    <input
+                      type="text"
+                      value={language}
+                      onChange={(e) => setLanguage(e.target.value)}
+                      placeholder="Language (en, fr, ja...)"
+                      maxLength={8}
+                      className="w-full mt-1 px-2 py-1 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 text-gray-900 dark:text-white text-sm"
+                    />
+                  </div>
+                )}
                 {!lossless && (
                   <div>
                     <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
@@ -767,6 +807,8 @@ export default function Home({}: HomeProps) {
           {t('footer')} • {t('madeWith')} ❤️
         </p>
       </footer>
+
+      {passwordDialog}
     </div>
   );
 }